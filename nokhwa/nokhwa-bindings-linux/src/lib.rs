@@ -21,18 +21,23 @@ mod internal {
         error::NokhwaError,
         traits::CaptureBackendTrait,
         types::{
-            ApiBackend, CameraControl, CameraFormat, CameraIndex, CameraInfo,
-            ControlValueDescription, ControlValueSetter, FrameFormat, KnownCameraControl,
-            KnownCameraControlFlag, RequestedFormat, RequestedFormatType, Resolution,
+            ApiBackend, BayerPattern, CameraControl, CameraFormat, CameraIndex, CameraInfo,
+            ColorRange, ColorSpace, Colorimetry, ControlValueDescription, ControlValueSetter,
+            FrameFormat, KnownCameraControl, KnownCameraControlFlag, RequestedFormat,
+            RequestedFormatType, Resolution,
         },
     };
     use std::{
         borrow::Cow,
         collections::HashMap,
         io::{self, ErrorKind},
+        mem::MaybeUninit,
+        time::Duration,
     };
     use v4l::{
+        buffer::Flags as BufferFlags,
         control::{Control, Flags, Type, Value},
+        format::{Colorspace, Quantization},
         frameinterval::FrameIntervalEnum,
         framesize::FrameSizeEnum,
         io::traits::{CaptureStream, Stream},
@@ -244,7 +249,8 @@ mod internal {
                     Resolution::new(format.width, format.height),
                     frame_format,
                     fps,
-                ))
+                )
+                .with_colorimetry(format_colorimetry(&format)))
             }
             Err(why) => Err(NokhwaError::GetPropertyError {
                 property: "parameters".to_string(),
@@ -253,15 +259,35 @@ mod internal {
         }
     }
 
+    /// Reads the [`Colorimetry`] of a format from its `colorspace` and `quantization`. Drivers that leave these at their
+    /// defaults get the defaults V4L2 itself implies for YUV formats.
+    fn format_colorimetry(format: &Format) -> Colorimetry {
+        let space = match format.colorspace {
+            Colorspace::Rec709 | Colorspace::SMPTE240M | Colorspace::DCIP3 => ColorSpace::Bt709,
+            Colorspace::BT2020 => ColorSpace::Bt2020,
+            _ => ColorSpace::Bt601,
+        };
+        let range = match format.quantization {
+            Quantization::FullRange => ColorRange::Full,
+            _ => ColorRange::Limited,
+        };
+        Colorimetry::new(space, range)
+    }
+
     /// The backend struct that interfaces with V4L2.
     /// To see what this does, please see [`CaptureBackendTrait`].
     /// # Quirks
     /// - Calling [`set_resolution()`](CaptureBackendTrait::set_resolution), [`set_frame_rate()`](CaptureBackendTrait::set_frame_rate), or [`set_frame_format()`](CaptureBackendTrait::set_frame_format) each internally calls [`set_camera_format()`](CaptureBackendTrait::set_camera_format).
+    /// - Buffer timestamps are the driver's capture times, on the system's monotonic clock (`CLOCK_MONOTONIC`) for most drivers. [`timestamp_clock()`](CaptureBackendTrait::timestamp_clock) reads that clock once the driver has flagged its buffers as monotonically timestamped. Dropped frames are counted from gaps in the driver's sequence numbers.
+    /// - The [`Colorimetry`] of the [`CameraFormat`] is what the driver reports for the current format, the one asked for is ignored.
+    /// - UVC depth cameras expose their depth stream ([`FrameFormat::Z16`]) and color stream as separate devices.
     pub struct V4LCaptureDevice<'a> {
         camera_format: CameraFormat,
         camera_info: CameraInfo,
         device: SharedDevice,
         stream_handle: Option<MmapStream<'a>>,
+        last_sequence: Option<u32>,
+        monotonic_timestamps: bool,
     }
 
     impl<'a> V4LCaptureDevice<'a> {
@@ -418,10 +444,13 @@ mod internal {
                 ),
                 device: shared_device,
                 stream_handle: None,
+                last_sequence: None,
+                monotonic_timestamps: false,
             };
 
             v4l2.force_refresh_camera_format()?;
-            if v4l2.camera_format() != format {
+            // The driver picks the colorimetry, it is not part of what was asked for
+            if v4l2.camera_format().with_colorimetry(format.colorimetry()) != format {
                 return Err(NokhwaError::SetPropertyError {
                     property: "CameraFormat".to_string(),
                     value: String::new(),
@@ -535,13 +564,7 @@ mod internal {
                 }
             };
 
-            let v4l_fcc = match new_fmt.format() {
-                FrameFormat::MJPEG => FourCC::new(b"MJPG"),
-                FrameFormat::YUYV => FourCC::new(b"YUYV"),
-                FrameFormat::GRAY => FourCC::new(b"GRAY"),
-                FrameFormat::RAWRGB => FourCC::new(b"RGB3"),
-                FrameFormat::NV12 => FourCC::new(b"NV12"),
-            };
+            let v4l_fcc = frameformat_to_fourcc(new_fmt.format());
 
             let format = Format::new(new_fmt.width(), new_fmt.height(), v4l_fcc);
             let frame_rate = Parameters::with_fps(new_fmt.frame_rate());
@@ -591,7 +614,7 @@ mod internal {
             self.camera_format = new_fmt;
 
             self.force_refresh_camera_format()?;
-            if self.camera_format != new_fmt {
+            if self.camera_format.with_colorimetry(new_fmt.colorimetry()) != new_fmt {
                 return Err(NokhwaError::SetPropertyError {
                     property: "CameraFormat".to_string(),
                     value: new_fmt.to_string(),
@@ -869,6 +892,7 @@ mod internal {
                 Err(why) => return Err(NokhwaError::OpenStreamError(why.to_string())),
             }
             self.stream_handle = Some(stream);
+            self.last_sequence = None;
             Ok(())
         }
 
@@ -876,20 +900,55 @@ mod internal {
             self.stream_handle.is_some()
         }
 
+        #[allow(clippy::cast_sign_loss)]
+        #[allow(clippy::cast_possible_truncation)]
         fn frame(&mut self) -> Result<Buffer, NokhwaError> {
             let cam_fmt = self.camera_format;
-            let raw_frame = self.frame_raw()?;
-            Ok(Buffer::new(
-                cam_fmt.resolution(),
-                &raw_frame,
-                cam_fmt.format(),
-            ))
+            let (buffer, sequence) = match &mut self.stream_handle {
+                Some(sh) => match sh.next() {
+                    Ok((data, metadata)) => {
+                        let mut buffer = Buffer::new(cam_fmt.resolution(), data, cam_fmt.format())
+                            .with_colorimetry(cam_fmt.colorimetry())
+                            .with_sequence(u64::from(metadata.sequence));
+
+                        // Drivers that do not timestamp buffers leave the timestamp at zero
+                        let timestamp = metadata.timestamp;
+                        self.monotonic_timestamps = metadata.flags & BufferFlags::TIMESTAMP_MASK
+                            == BufferFlags::TIMESTAMP_MONOTONIC;
+                        if timestamp.sec > 0 || timestamp.usec > 0 {
+                            buffer = buffer.with_timestamp(
+                                Duration::from_secs(timestamp.sec.max(0) as u64)
+                                    + Duration::from_micros(timestamp.usec.max(0) as u64),
+                            );
+                        }
+
+                        if let Some(last_sequence) = self.last_sequence {
+                            let gap = metadata.sequence.wrapping_sub(last_sequence);
+                            buffer = buffer.with_dropped_frames(u64::from(gap.saturating_sub(1)));
+                        }
+
+                        (buffer, metadata.sequence)
+                    }
+                    Err(why) => return Err(NokhwaError::ReadFrameError(why.to_string())),
+                },
+                None => {
+                    return Err(NokhwaError::ReadFrameError(
+                        "Stream Not Started".to_string(),
+                    ))
+                }
+            };
+
+            self.last_sequence = Some(sequence);
+            Ok(buffer)
         }
 
         fn frame_raw(&mut self) -> Result<Cow<[u8]>, NokhwaError> {
             match &mut self.stream_handle {
                 Some(sh) => match sh.next() {
-                    Ok((data, _)) => Ok(Cow::Borrowed(data)),
+                    Ok((data, metadata)) => {
+                        self.last_sequence = Some(metadata.sequence);
+                        Ok(Cow::Borrowed(data))
+                    }
                     Err(why) => Err(NokhwaError::ReadFrameError(why.to_string())),
                 },
                 None => Err(NokhwaError::ReadFrameError(
@@ -898,10 +957,30 @@ mod internal {
             }
         }
 
+        #[allow(clippy::cast_sign_loss)]
+        #[allow(clippy::cast_possible_truncation)]
+        fn timestamp_clock(&self) -> Option<Duration> {
+            if !self.monotonic_timestamps {
+                return None;
+            }
+
+            let mut now = MaybeUninit::<libc::timespec>::uninit();
+            // SAFETY: `now` is valid for `clock_gettime` to write a `timespec` to, and only read once it has.
+            let now = unsafe {
+                if libc::clock_gettime(libc::CLOCK_MONOTONIC, now.as_mut_ptr()) != 0 {
+                    return None;
+                }
+                now.assume_init()
+            };
+            Some(Duration::new(now.tv_sec as u64, now.tv_nsec as u32))
+        }
+
         fn stop_stream(&mut self) -> Result<(), NokhwaError> {
             if self.stream_handle.is_some() {
                 self.stream_handle = None;
             }
+            self.last_sequence = None;
+            self.monotonic_timestamps = false;
             Ok(())
         }
     }
@@ -910,9 +989,27 @@ mod internal {
         match fourcc.str().ok()? {
             "YUYV" => Some(FrameFormat::YUYV),
             "MJPG" => Some(FrameFormat::MJPEG),
-            "GRAY" => Some(FrameFormat::GRAY),
+            "GREY" | "GRAY" => Some(FrameFormat::GRAY),
             "RGB3" => Some(FrameFormat::RAWRGB),
             "NV12" => Some(FrameFormat::NV12),
+            "UYVY" => Some(FrameFormat::UYVY),
+            "YVYU" => Some(FrameFormat::YVYU),
+            "NV21" => Some(FrameFormat::NV21),
+            "YU12" => Some(FrameFormat::I420),
+            "BGR3" => Some(FrameFormat::RAWBGR),
+            "RGBP" => Some(FrameFormat::RGB565),
+            "Y10 " => Some(FrameFormat::GRAY10),
+            "Y12 " => Some(FrameFormat::GRAY12),
+            "Y16 " => Some(FrameFormat::GRAY16),
+            "Z16 " => Some(FrameFormat::Z16),
+            "RGGB" => Some(FrameFormat::Bayer8(BayerPattern::RGGB)),
+            "BA81" => Some(FrameFormat::Bayer8(BayerPattern::BGGR)),
+            "GRBG" => Some(FrameFormat::Bayer8(BayerPattern::GRBG)),
+            "GBRG" => Some(FrameFormat::Bayer8(BayerPattern::GBRG)),
+            "RG10" => Some(FrameFormat::Bayer10(BayerPattern::RGGB)),
+            "BG10" => Some(FrameFormat::Bayer10(BayerPattern::BGGR)),
+            "BA10" => Some(FrameFormat::Bayer10(BayerPattern::GRBG)),
+            "GB10" => Some(FrameFormat::Bayer10(BayerPattern::GBRG)),
             _ => None,
         }
     }
@@ -921,9 +1018,27 @@ mod internal {
         match fourcc {
             FrameFormat::MJPEG => FourCC::new(b"MJPG"),
             FrameFormat::YUYV => FourCC::new(b"YUYV"),
-            FrameFormat::GRAY => FourCC::new(b"GRAY"),
+            FrameFormat::GRAY => FourCC::new(b"GREY"),
             FrameFormat::RAWRGB => FourCC::new(b"RGB3"),
             FrameFormat::NV12 => FourCC::new(b"NV12"),
+            FrameFormat::UYVY => FourCC::new(b"UYVY"),
+            FrameFormat::YVYU => FourCC::new(b"YVYU"),
+            FrameFormat::NV21 => FourCC::new(b"NV21"),
+            FrameFormat::I420 => FourCC::new(b"YU12"),
+            FrameFormat::RAWBGR => FourCC::new(b"BGR3"),
+            FrameFormat::RGB565 => FourCC::new(b"RGBP"),
+            FrameFormat::GRAY10 => FourCC::new(b"Y10 "),
+            FrameFormat::GRAY12 => FourCC::new(b"Y12 "),
+            FrameFormat::GRAY16 => FourCC::new(b"Y16 "),
+            FrameFormat::Z16 => FourCC::new(b"Z16 "),
+            FrameFormat::Bayer8(BayerPattern::RGGB) => FourCC::new(b"RGGB"),
+            FrameFormat::Bayer8(BayerPattern::BGGR) => FourCC::new(b"BA81"),
+            FrameFormat::Bayer8(BayerPattern::GRBG) => FourCC::new(b"GRBG"),
+            FrameFormat::Bayer8(BayerPattern::GBRG) => FourCC::new(b"GBRG"),
+            FrameFormat::Bayer10(BayerPattern::RGGB) => FourCC::new(b"RG10"),
+            FrameFormat::Bayer10(BayerPattern::BGGR) => FourCC::new(b"BG10"),
+            FrameFormat::Bayer10(BayerPattern::GRBG) => FourCC::new(b"BA10"),
+            FrameFormat::Bayer10(BayerPattern::GBRG) => FourCC::new(b"GB10"),
         }
     }
 }
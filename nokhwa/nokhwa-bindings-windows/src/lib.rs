@@ -117,6 +117,42 @@ pub mod wmf {
         0x0010,
         [0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71],
     );
+    const MF_VIDEO_FORMAT_UYVY: GUID = GUID::from_values(
+        0x5956_5955,
+        0x0000,
+        0x0010,
+        [0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71],
+    );
+    const MF_VIDEO_FORMAT_YVYU: GUID = GUID::from_values(
+        0x5559_5659,
+        0x0000,
+        0x0010,
+        [0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71],
+    );
+    const MF_VIDEO_FORMAT_NV21: GUID = GUID::from_values(
+        0x3132_564E,
+        0x0000,
+        0x0010,
+        [0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71],
+    );
+    const MF_VIDEO_FORMAT_I420: GUID = GUID::from_values(
+        0x3032_3449,
+        0x0000,
+        0x0010,
+        [0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71],
+    );
+    const MF_VIDEO_FORMAT_RGB565: GUID = GUID::from_values(
+        0x0000_0017,
+        0x0000,
+        0x0010,
+        [0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71],
+    );
+    const MF_VIDEO_FORMAT_Y16: GUID = GUID::from_values(
+        0x2036_3159,
+        0x0000,
+        0x0010,
+        [0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71],
+    );
 
     const MEDIA_FOUNDATION_FIRST_VIDEO_STREAM: u32 = 0xFFFF_FFFC;
     const MF_SOURCE_READER_MEDIASOURCE: u32 = 0xFFFF_FFFF;
@@ -169,17 +205,35 @@ pub mod wmf {
             MF_VIDEO_FORMAT_GRAY => Some(FrameFormat::GRAY),
             MF_VIDEO_FORMAT_YUY2 => Some(FrameFormat::YUYV),
             MF_VIDEO_FORMAT_MJPEG => Some(FrameFormat::MJPEG),
+            MF_VIDEO_FORMAT_UYVY => Some(FrameFormat::UYVY),
+            MF_VIDEO_FORMAT_YVYU => Some(FrameFormat::YVYU),
+            MF_VIDEO_FORMAT_NV21 => Some(FrameFormat::NV21),
+            MF_VIDEO_FORMAT_I420 => Some(FrameFormat::I420),
+            MF_VIDEO_FORMAT_RGB565 => Some(FrameFormat::RGB565),
+            MF_VIDEO_FORMAT_Y16 => Some(FrameFormat::GRAY16),
             _ => None,
         }
     }
 
-    fn frameformat_to_guid(frameformat: FrameFormat) -> GUID {
+    fn frameformat_to_guid(frameformat: FrameFormat) -> Option<GUID> {
         match frameformat {
-            FrameFormat::MJPEG => MF_VIDEO_FORMAT_MJPEG,
-            FrameFormat::YUYV => MF_VIDEO_FORMAT_YUY2,
-            FrameFormat::NV12 => MF_VIDEO_FORMAT_NV12,
-            FrameFormat::GRAY => MF_VIDEO_FORMAT_GRAY,
-            FrameFormat::RAWRGB => MF_VIDEO_FORMAT_RGB24,
+            FrameFormat::MJPEG => Some(MF_VIDEO_FORMAT_MJPEG),
+            FrameFormat::YUYV => Some(MF_VIDEO_FORMAT_YUY2),
+            FrameFormat::NV12 => Some(MF_VIDEO_FORMAT_NV12),
+            FrameFormat::GRAY => Some(MF_VIDEO_FORMAT_GRAY),
+            FrameFormat::RAWRGB => Some(MF_VIDEO_FORMAT_RGB24),
+            FrameFormat::UYVY => Some(MF_VIDEO_FORMAT_UYVY),
+            FrameFormat::YVYU => Some(MF_VIDEO_FORMAT_YVYU),
+            FrameFormat::NV21 => Some(MF_VIDEO_FORMAT_NV21),
+            FrameFormat::I420 => Some(MF_VIDEO_FORMAT_I420),
+            FrameFormat::RGB565 => Some(MF_VIDEO_FORMAT_RGB565),
+            FrameFormat::GRAY16 => Some(MF_VIDEO_FORMAT_Y16),
+            FrameFormat::RAWBGR
+            | FrameFormat::GRAY10
+            | FrameFormat::GRAY12
+            | FrameFormat::Z16
+            | FrameFormat::Bayer8(_)
+            | FrameFormat::Bayer10(_) => None,
         }
     }
 
@@ -1028,7 +1082,13 @@ pub mod wmf {
                 bytes[3] = 0x01;
                 u64::from_le_bytes(bytes)
             };
-            let fourcc = frameformat_to_guid(format.format());
+            let Some(fourcc) = frameformat_to_guid(format.format()) else {
+                return Err(NokhwaError::SetPropertyError {
+                    property: "MF_MT_SUBTYPE".to_string(),
+                    value: format.format().to_string(),
+                    error: "Not supported by Media Foundation".to_string(),
+                });
+            };
             // setting to the new media_type
             if let Err(why) = unsafe { media_type.SetGUID(&MF_MT_MAJOR_TYPE, &MFMediaType_Video) } {
                 return Err(NokhwaError::SetPropertyError {
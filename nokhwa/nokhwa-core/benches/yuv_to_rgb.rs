@@ -0,0 +1,80 @@
+/*
+ * Copyright 2022 l1npengtul <l1npengtul@protonmail.com> / The Nokhwa Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use nokhwa_core::types::{buf_nv12_to_rgb, buf_yuyv422_to_rgb, Colorimetry, Resolution};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+
+/// A deterministic, noisy frame, so that no value range is favoured.
+fn frame(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state.to_le_bytes()[0]
+        })
+        .collect()
+}
+
+fn yuyv(c: &mut Criterion) {
+    let pixels = (WIDTH * HEIGHT) as usize;
+    let data = frame(pixels * 2);
+    let mut group = c.benchmark_group("yuyv422 1080p");
+    group.throughput(Throughput::Elements(pixels as u64));
+
+    for (name, rgba, pixel_size) in [("rgb", false, 3), ("rgba", true, 4)] {
+        let mut dest = vec![0; pixels * pixel_size];
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                buf_yuyv422_to_rgb(black_box(&data), &mut dest, rgba, Colorimetry::default())
+                    .unwrap()
+            });
+        });
+    }
+    group.finish();
+}
+
+fn nv12(c: &mut Criterion) {
+    let resolution = Resolution::new(WIDTH, HEIGHT);
+    let pixels = (WIDTH * HEIGHT) as usize;
+    let data = frame(pixels * 3 / 2);
+    let mut group = c.benchmark_group("nv12 1080p");
+    group.throughput(Throughput::Elements(pixels as u64));
+
+    for (name, rgba, pixel_size) in [("rgb", false, 3), ("rgba", true, 4)] {
+        let mut dest = vec![0; pixels * pixel_size];
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                buf_nv12_to_rgb(
+                    resolution,
+                    black_box(&data),
+                    &mut dest,
+                    rgba,
+                    Colorimetry::default(),
+                )
+                .unwrap()
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, yuyv, nv12);
+criterion_main!(benches);
@@ -17,13 +17,17 @@
 use crate::{
     error::NokhwaError,
     pixel_format::FormatDecoder,
-    types::{FrameFormat, Resolution},
+    types::{Colorimetry, FrameFormat, Resolution},
 };
 use bytes::Bytes;
-use image::ImageBuffer;
+use image::{ImageBuffer, Pixel};
+use std::time::Duration;
 
 /// A buffer returned by a camera to accommodate custom decoding.
-/// Contains information of Resolution, the buffer's [`FrameFormat`], and the buffer.
+/// Contains information of Resolution, the buffer's [`FrameFormat`] and [`Colorimetry`], and the buffer.
+///
+/// Backends that know when and in which order frames were captured also attach a capture timestamp,
+/// a sequence number and the number of frames dropped right before this one.
 ///
 /// Note that decoding on the main thread **will** decrease your performance and lead to dropped frames.
 #[derive(Clone, Debug, Hash, PartialOrd, PartialEq, Eq)]
@@ -31,6 +35,10 @@ pub struct Buffer {
     resolution: Resolution,
     buffer: Bytes,
     source_frame_format: FrameFormat,
+    colorimetry: Colorimetry,
+    timestamp: Option<Duration>,
+    sequence: Option<u64>,
+    dropped_frames: u64,
 }
 
 impl Buffer {
@@ -42,9 +50,41 @@ impl Buffer {
             resolution: res,
             buffer: Bytes::copy_from_slice(buf),
             source_frame_format,
+            colorimetry: Colorimetry::default(),
+            timestamp: None,
+            sequence: None,
+            dropped_frames: 0,
         }
     }
 
+    /// Sets the [`Colorimetry`] of this buffer, which the YUV decoders use. See [`colorimetry()`](Self::colorimetry).
+    #[must_use]
+    pub fn with_colorimetry(mut self, colorimetry: Colorimetry) -> Self {
+        self.colorimetry = colorimetry;
+        self
+    }
+
+    /// Sets the capture timestamp of this buffer. See [`timestamp()`](Self::timestamp).
+    #[must_use]
+    pub fn with_timestamp(mut self, timestamp: Duration) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Sets the sequence number of this buffer. See [`sequence()`](Self::sequence).
+    #[must_use]
+    pub fn with_sequence(mut self, sequence: u64) -> Self {
+        self.sequence = Some(sequence);
+        self
+    }
+
+    /// Sets the number of frames dropped right before this buffer. See [`dropped_frames()`](Self::dropped_frames).
+    #[must_use]
+    pub fn with_dropped_frames(mut self, dropped_frames: u64) -> Self {
+        self.dropped_frames = dropped_frames;
+        self
+    }
+
     /// Get the [`Resolution`] of this buffer.
     #[must_use]
     pub fn resolution(&self) -> Resolution {
@@ -69,14 +109,48 @@ impl Buffer {
         self.source_frame_format
     }
 
+    /// Get the [`Colorimetry`] of this buffer. Backends set it from the camera's [`CameraFormat`](crate::types::CameraFormat).
+    #[must_use]
+    pub fn colorimetry(&self) -> Colorimetry {
+        self.colorimetry
+    }
+
+    /// Get the time this buffer was captured at, if the backend provides it.
+    ///
+    /// Timestamps come from a monotonic clock, so the difference between two timestamps of the same stream is the
+    /// time between their captures. The clock's epoch depends on the backend: `Video4Linux` uses the system's
+    /// monotonic clock, `Replay` the time of recording and the synthetic backends the start of the stream.
+    #[must_use]
+    pub fn timestamp(&self) -> Option<Duration> {
+        self.timestamp
+    }
+
+    /// Get the sequence number the backend gave this buffer, if it provides one. It increases by one for every frame captured, including dropped ones.
+    #[must_use]
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    /// Get the number of frames dropped between the previous buffer and this one, as far as the backend can tell.
+    #[must_use]
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+
     /// Decodes a image with allocation using the provided [`FormatDecoder`].
     /// # Errors
     /// Will error when the decoding fails.
     #[inline]
+    #[allow(clippy::type_complexity)]
     pub fn decode_image<F: FormatDecoder>(
         &self,
-    ) -> Result<ImageBuffer<F::Output, Vec<u8>>, NokhwaError> {
-        let new_data = F::write_output(self.source_frame_format, self.resolution, &self.buffer)?;
+    ) -> Result<ImageBuffer<F::Output, Vec<<F::Output as Pixel>::Subpixel>>, NokhwaError> {
+        let new_data = F::write_output(
+            self.source_frame_format,
+            self.resolution,
+            self.colorimetry,
+            &self.buffer,
+        )?;
         let image =
             ImageBuffer::from_raw(self.resolution.width_x, self.resolution.height_y, new_data)
                 .ok_or(NokhwaError::ProcessFrameError {
@@ -93,11 +167,12 @@ impl Buffer {
     #[inline]
     pub fn decode_image_to_buffer<F: FormatDecoder>(
         &self,
-        buffer: &mut [u8],
+        buffer: &mut [<F::Output as Pixel>::Subpixel],
     ) -> Result<(), NokhwaError> {
         F::write_output_buffer(
             self.source_frame_format,
             self.resolution,
+            self.colorimetry,
             &self.buffer,
             buffer,
         )
@@ -118,7 +193,6 @@ impl Buffer {
     pub fn decode_opencv_mat<F: FormatDecoder>(
         &mut self,
     ) -> Result<opencv::core::Mat, NokhwaError> {
-        use image::Pixel;
         use opencv::core::{Mat, Mat_AUTO_STEP, CV_8UC1, CV_8UC2, CV_8UC3, CV_8UC4};
 
         let array_type = match F::Output::CHANNEL_COUNT {
@@ -165,7 +239,6 @@ impl Buffer {
         &mut self,
         dst: &mut opencv::core::Mat,
     ) -> Result<(), NokhwaError> {
-        use image::Pixel;
         use opencv::core::{
             Mat, MatTraitConst, MatTraitManual, Scalar, CV_8UC1, CV_8UC2, CV_8UC3, CV_8UC4,
         };
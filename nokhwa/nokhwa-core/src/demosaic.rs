@@ -0,0 +1,333 @@
+/*
+ * Copyright 2022 l1npengtul <l1npengtul@protonmail.com> / The Nokhwa Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Bayer demosaicing.
+//!
+//! All interpolation is done on the samples at their native bit depth, the results are only reduced to the output depth
+//! at the very end. Samples outside the frame are mirrored back in around the edge pixel, which keeps the color of every
+//! sample the same as in the real mosaic.
+
+use crate::{
+    error::NokhwaError,
+    types::{widen_sample, BayerPattern, DemosaicMethod, FrameFormat, Resolution},
+};
+
+/// A raw Bayer frame.
+pub(crate) struct Mosaic<'a> {
+    data: &'a [u8],
+    pattern: BayerPattern,
+    width: usize,
+    height: usize,
+    bit_depth: u32,
+}
+
+impl<'a> Mosaic<'a> {
+    /// Checks that `data` is a whole frame of `format` at `resolution`. `destination` is only used to describe errors.
+    pub(crate) fn new(
+        format: FrameFormat,
+        resolution: Resolution,
+        data: &'a [u8],
+        destination: &str,
+    ) -> Result<Self, NokhwaError> {
+        let error = |error: &str| NokhwaError::ProcessFrameError {
+            src: format,
+            destination: destination.to_string(),
+            error: error.to_string(),
+        };
+
+        let (pattern, bit_depth) = match format {
+            FrameFormat::Bayer8(pattern) => (pattern, 8),
+            FrameFormat::Bayer10(pattern) => (pattern, 10),
+            _ => return Err(error("Not a Bayer format")),
+        };
+        if resolution.width() < 2
+            || resolution.height() < 2
+            || !resolution.width().is_multiple_of(2)
+            || !resolution.height().is_multiple_of(2)
+        {
+            return Err(error("bad resolution"));
+        }
+
+        let width = resolution.width() as usize;
+        let height = resolution.height() as usize;
+        let sample_size = if bit_depth > 8 { 2 } else { 1 };
+        if data.len() != width * height * sample_size {
+            return Err(error("bad input buffer size"));
+        }
+
+        Ok(Mosaic {
+            data,
+            pattern,
+            width,
+            height,
+            bit_depth,
+        })
+    }
+
+    /// The number of pixels in the frame.
+    pub(crate) fn pixel_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Clamps an interpolated value to the range of a sample and reduces it to 8 bits.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    pub(crate) fn to_u8(&self, value: i32) -> u8 {
+        let max = (1 << self.bit_depth) - 1;
+        (value.clamp(0, max) >> (self.bit_depth - 8)) as u8
+    }
+
+    /// Clamps an interpolated value to the range of a sample and widens it to 16 bits.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    pub(crate) fn to_u16(&self, value: i32) -> u16 {
+        let max = (1 << self.bit_depth) - 1;
+        widen_sample(value.clamp(0, max) as u16, self.bit_depth)
+    }
+
+    /// Demosaics the frame with `method`, handing every pixel's index and RGB values at the native bit depth to `write`.
+    pub(crate) fn demosaic(&self, method: DemosaicMethod, mut write: impl FnMut(usize, [i32; 3])) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let rgb = match method {
+                    DemosaicMethod::Bilinear => self.bilinear(x, y),
+                    DemosaicMethod::MalvarHeCutler => self.malvar_he_cutler(x, y),
+                };
+                write(y * self.width + x, rgb);
+            }
+        }
+    }
+
+    /// Computes the luminance of every pixel straight from the mosaic, handing its index and luminance at the native
+    /// bit depth to `write`.
+    ///
+    /// Every 2x2 window of a Bayer mosaic holds one red, two green and one blue sample, so weighting the window a pixel
+    /// starts (the one it ends, on the last row and column) with the luma weights of
+    /// [`rgb_to_luma`](crate::types::rgb_to_luma) gives its luminance at full resolution without interpolating any color.
+    pub(crate) fn luma(&self, mut write: impl FnMut(usize, i32)) {
+        const WEIGHTS: [i32; 3] = [77, 75, 29];
+
+        for y in 0..self.height {
+            let top = y.min(self.height - 2);
+            for x in 0..self.width {
+                let left = x.min(self.width - 2);
+                let mut luma = 128;
+                for (wx, wy) in [
+                    (left, top),
+                    (left + 1, top),
+                    (left, top + 1),
+                    (left + 1, top + 1),
+                ] {
+                    luma += WEIGHTS[self.pattern.channel_at(wx, wy)] * self.raw(wx, wy);
+                }
+                write(y * self.width + x, luma >> 8);
+            }
+        }
+    }
+
+    fn raw(&self, x: usize, y: usize) -> i32 {
+        let index = y * self.width + x;
+        if self.bit_depth > 8 {
+            let sample = u16::from_le_bytes([self.data[index * 2], self.data[index * 2 + 1]]);
+            i32::from(sample & ((1 << self.bit_depth) - 1))
+        } else {
+            i32::from(self.data[index])
+        }
+    }
+
+    /// The sample at an offset from `x`, `y`, mirrored back into the frame if it falls outside.
+    #[allow(clippy::cast_possible_wrap)]
+    #[allow(clippy::cast_sign_loss)]
+    fn at(&self, x: usize, y: usize, dx: isize, dy: isize) -> i32 {
+        let mirror = |position: usize, offset: isize, size: usize| {
+            let position = position as isize + offset;
+            let last = size as isize - 1;
+            let mirrored = if position < 0 {
+                -position
+            } else if position > last {
+                2 * last - position
+            } else {
+                position
+            };
+            mirrored.clamp(0, last) as usize
+        };
+        self.raw(mirror(x, dx, self.width), mirror(y, dy, self.height))
+    }
+
+    /// Averages each missing color over the samples of that color in the surrounding 3x3 window.
+    fn bilinear(&self, x: usize, y: usize) -> [i32; 3] {
+        let own = self.pattern.channel_at(x, y);
+        let mut sums = [0; 3];
+        let mut counts = [0; 3];
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let channel = self
+                    .pattern
+                    .channel_at(x.wrapping_add_signed(dx) & 1, y.wrapping_add_signed(dy) & 1);
+                sums[channel] += self.at(x, y, dx, dy);
+                counts[channel] += 1;
+            }
+        }
+
+        let mut rgb = [0; 3];
+        for (channel, value) in rgb.iter_mut().enumerate() {
+            *value = if channel == own {
+                self.at(x, y, 0, 0)
+            } else {
+                (sums[channel] + counts[channel] / 2) / counts[channel]
+            };
+        }
+        rgb
+    }
+
+    /// Interpolates with the gradient-corrected 5x5 filters of Malvar, He and Cutler, "High-quality linear interpolation
+    /// for demosaicing of Bayer-patterned color images" (ICASSP 2004). The filters are scaled by 16 to stay in integers.
+    fn malvar_he_cutler(&self, x: usize, y: usize) -> [i32; 3] {
+        let s = |dx, dy| self.at(x, y, dx, dy);
+        let center = s(0, 0);
+        let cross = s(-1, 0) + s(1, 0) + s(0, -1) + s(0, 1);
+        let diagonal = s(-1, -1) + s(1, -1) + s(-1, 1) + s(1, 1);
+        let far_horizontal = s(-2, 0) + s(2, 0);
+        let far_vertical = s(0, -2) + s(0, 2);
+        let round = |value: i32| (value + 8) >> 4;
+
+        let own = self.pattern.channel_at(x, y);
+        let mut rgb = [center; 3];
+        if own == 1 {
+            // Green: the color beside it horizontally and the color beside it vertically
+            let horizontal = round(
+                10 * center + 8 * (s(-1, 0) + s(1, 0)) - 2 * (far_horizontal + diagonal)
+                    + far_vertical,
+            );
+            let vertical = round(
+                10 * center + 8 * (s(0, -1) + s(0, 1)) - 2 * (far_vertical + diagonal)
+                    + far_horizontal,
+            );
+            let horizontal_channel = self.pattern.channel_at((x + 1) & 1, y & 1);
+            rgb[horizontal_channel] = horizontal;
+            rgb[2 - horizontal_channel] = vertical;
+        } else {
+            // Red or blue: green from the cross, the other color from the diagonals
+            let far = far_horizontal + far_vertical;
+            rgb[1] = round(8 * center + 4 * cross - 2 * far);
+            rgb[2 - own] = round(12 * center + 4 * diagonal - 3 * far);
+        }
+        rgb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{buf_bayer_to_luma, buf_bayer_to_rgb, rgb_to_luma};
+
+    const PATTERNS: [BayerPattern; 4] = [
+        BayerPattern::RGGB,
+        BayerPattern::BGGR,
+        BayerPattern::GRBG,
+        BayerPattern::GBRG,
+    ];
+
+    const METHODS: [DemosaicMethod; 2] = [DemosaicMethod::Bilinear, DemosaicMethod::MalvarHeCutler];
+
+    const RESOLUTION: Resolution = Resolution {
+        width_x: 16,
+        height_y: 8,
+    };
+
+    /// A mosaic of `pattern` whose pixels take their sample from `color_at(x, y)`, 2 bytes per sample above 8 bits.
+    fn mosaic(
+        pattern: BayerPattern,
+        bit_depth: u32,
+        color_at: impl Fn(usize, usize) -> [u16; 3],
+    ) -> Vec<u8> {
+        let mut data = Vec::new();
+        for y in 0..RESOLUTION.height() as usize {
+            for x in 0..RESOLUTION.width() as usize {
+                let sample = color_at(x, y)[pattern.channel_at(x, y)];
+                if bit_depth > 8 {
+                    data.extend_from_slice(&sample.to_le_bytes());
+                } else {
+                    data.push(u8::try_from(sample).unwrap());
+                }
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn flat_colors_demosaic_to_themselves() {
+        let colors = [[200, 100, 30], [0, 255, 0], [17, 17, 17], [255, 0, 255]];
+        for pattern in PATTERNS {
+            for (format, bit_depth, scale) in [
+                (FrameFormat::Bayer8(pattern), 8, 1),
+                (FrameFormat::Bayer10(pattern), 10, 4),
+            ] {
+                for color in colors {
+                    // Scaled up, with the low bits set to tell 10-bit samples from shifted 8-bit ones
+                    let color = color.map(|value: u16| value * scale + scale / 2);
+                    let data = mosaic(pattern, bit_depth, |_, _| color);
+                    let mosaic = Mosaic::new(format, RESOLUTION, &data, "RGB").unwrap();
+                    for method in METHODS {
+                        mosaic.demosaic(method, |index, rgb| {
+                            assert_eq!(
+                                rgb,
+                                color.map(i32::from),
+                                "{format} {method:?} at pixel {index}"
+                            );
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn luma_matches_demosaiced_luma_on_flat_regions() {
+        // Two flat halves, the demosaicing filters reach 2 pixels and the luma window 1 pixel across the edge
+        let half = RESOLUTION.width() as usize / 2;
+        let color_at = |x: usize, _| {
+            if x < half {
+                [180, 60, 20]
+            } else {
+                [10, 90, 240]
+            }
+        };
+        let flat = |x: usize| x + 2 < half || x >= half + 2;
+
+        for pattern in PATTERNS {
+            let format = FrameFormat::Bayer8(pattern);
+            let data = mosaic(pattern, 8, color_at);
+            let pixels = (RESOLUTION.width() * RESOLUTION.height()) as usize;
+            let mut luma = vec![0; pixels];
+            buf_bayer_to_luma(format, RESOLUTION, &data, &mut luma, false).unwrap();
+
+            for method in METHODS {
+                let mut rgb = vec![0; pixels * 3];
+                buf_bayer_to_rgb(format, RESOLUTION, &data, &mut rgb, false, method).unwrap();
+                for (index, (luma, rgb)) in luma.iter().zip(rgb.chunks_exact(3)).enumerate() {
+                    if flat(index % RESOLUTION.width() as usize) {
+                        assert_eq!(
+                            *luma,
+                            rgb_to_luma(rgb[0], rgb[1], rgb[2]),
+                            "{format} {method:?} at pixel {index}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -20,7 +20,9 @@
 
 //! Core type definitions for `nokhwa`
 pub mod buffer;
+mod demosaic;
 pub mod error;
 pub mod pixel_format;
+mod simd;
 pub mod traits;
 pub mod types;
@@ -15,15 +15,25 @@
  */
 use crate::error::NokhwaError;
 use crate::types::{
-    buf_mjpeg_to_rgb, buf_nv12_to_rgb, buf_yuyv422_to_rgb, color_frame_formats, frame_formats,
-    mjpeg_to_rgb, nv12_to_rgb, yuyv422_to_rgb, FrameFormat, Resolution,
+    buf_bayer_to_luma, buf_bayer_to_luma16, buf_bayer_to_rgb, buf_bgr_to_luma, buf_bgr_to_rgb,
+    buf_gray16_to_luma, buf_gray16_to_luma16, buf_gray16_to_rgb, buf_mjpeg_to_luma,
+    buf_mjpeg_to_rgb, buf_nv12_to_luma, buf_nv12_to_rgb, buf_packed_yuv422_to_luma,
+    buf_packed_yuv422_to_rgb, buf_rgb565_to_luma, buf_rgb565_to_rgb, buf_rgb_to_luma,
+    buf_yuv420_to_luma, buf_yuv420_to_rgb, buf_yuyv422_to_luma, buf_yuyv422_to_rgb,
+    color_frame_formats, frame_formats, mjpeg_to_luma, mjpeg_to_rgb, nv12_to_luma, nv12_to_rgb,
+    widen_sample, yuyv422_to_luma, yuyv422_to_rgb, Colorimetry, DemosaicMethod, FrameFormat,
+    Resolution,
 };
 use image::{Luma, LumaA, Pixel, Rgb, Rgba};
 use std::fmt::Debug;
 
 /// Trait that has methods to convert raw data from the webcam to a proper raw image.
+///
+/// YUV sources are decoded according to the [`Colorimetry`] passed in, which is ignored for other formats.
+///
+/// Most decoders output 8-bit pixels, but the subpixel type is up to the decoder, e.g. [`Luma16Format`] decodes to `u16`.
 pub trait FormatDecoder: Clone + Sized + Send + Sync {
-    type Output: Pixel<Subpixel = u8>;
+    type Output: Pixel;
     const FORMATS: &'static [FrameFormat];
 
     /// Allocates and returns a `Vec`
@@ -32,8 +42,9 @@ pub trait FormatDecoder: Clone + Sized + Send + Sync {
     fn write_output(
         fcc: FrameFormat,
         resolution: Resolution,
+        colorimetry: Colorimetry,
         data: &[u8],
-    ) -> Result<Vec<u8>, NokhwaError>;
+    ) -> Result<Vec<<Self::Output as Pixel>::Subpixel>, NokhwaError>;
 
     /// Writes to a user provided buffer.
     /// # Errors
@@ -41,8 +52,9 @@ pub trait FormatDecoder: Clone + Sized + Send + Sync {
     fn write_output_buffer(
         fcc: FrameFormat,
         resolution: Resolution,
+        colorimetry: Colorimetry,
         data: &[u8],
-        dest: &mut [u8],
+        dest: &mut [<Self::Output as Pixel>::Subpixel],
     ) -> Result<(), NokhwaError>;
 }
 
@@ -52,6 +64,8 @@ pub trait FormatDecoder: Clone + Sized + Send + Sync {
 /// use image::{ImageBuffer, Rgb};
 /// let image: ImageBuffer<Rgb<u8>, Vec<u8>> = buffer.to_image::<RgbFormat>();
 /// ```
+///
+/// Bayer frames are demosaiced with [`DemosaicMethod::Bilinear`]. Use [`MalvarRgbFormat`] for sharper edges.
 #[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
 pub struct RgbFormat;
 
@@ -63,11 +77,12 @@ impl FormatDecoder for RgbFormat {
     fn write_output(
         fcc: FrameFormat,
         resolution: Resolution,
+        colorimetry: Colorimetry,
         data: &[u8],
     ) -> Result<Vec<u8>, NokhwaError> {
         match fcc {
             FrameFormat::MJPEG => mjpeg_to_rgb(data, false),
-            FrameFormat::YUYV => yuyv422_to_rgb(data, false),
+            FrameFormat::YUYV => yuyv422_to_rgb(data, false, colorimetry),
             FrameFormat::GRAY => Ok(data
                 .iter()
                 .flat_map(|x| {
@@ -76,7 +91,23 @@ impl FormatDecoder for RgbFormat {
                 })
                 .collect()),
             FrameFormat::RAWRGB => Ok(data.to_vec()),
-            FrameFormat::NV12 => nv12_to_rgb(resolution, data, false),
+            FrameFormat::NV12 => nv12_to_rgb(resolution, data, false, colorimetry),
+            FrameFormat::UYVY
+            | FrameFormat::YVYU
+            | FrameFormat::NV21
+            | FrameFormat::I420
+            | FrameFormat::RAWBGR
+            | FrameFormat::RGB565
+            | FrameFormat::GRAY10
+            | FrameFormat::GRAY12
+            | FrameFormat::GRAY16
+            | FrameFormat::Z16
+            | FrameFormat::Bayer8(_)
+            | FrameFormat::Bayer10(_) => {
+                let mut dest = vec![0; (resolution.width() * resolution.height() * 3) as usize];
+                Self::write_output_buffer(fcc, resolution, colorimetry, data, &mut dest)?;
+                Ok(dest)
+            }
         }
     }
 
@@ -84,12 +115,13 @@ impl FormatDecoder for RgbFormat {
     fn write_output_buffer(
         fcc: FrameFormat,
         resolution: Resolution,
+        colorimetry: Colorimetry,
         data: &[u8],
         dest: &mut [u8],
     ) -> Result<(), NokhwaError> {
         match fcc {
             FrameFormat::MJPEG => buf_mjpeg_to_rgb(data, dest, false),
-            FrameFormat::YUYV => buf_yuyv422_to_rgb(data, dest, false),
+            FrameFormat::YUYV => buf_yuyv422_to_rgb(data, dest, false, colorimetry),
             FrameFormat::GRAY => {
                 if dest.len() != data.len() * 3 {
                     return Err(NokhwaError::ProcessFrameError {
@@ -111,7 +143,21 @@ impl FormatDecoder for RgbFormat {
                 dest.copy_from_slice(data);
                 Ok(())
             }
-            FrameFormat::NV12 => buf_nv12_to_rgb(resolution, data, dest, false),
+            FrameFormat::NV12 => buf_nv12_to_rgb(resolution, data, dest, false, colorimetry),
+            FrameFormat::UYVY | FrameFormat::YVYU => {
+                buf_packed_yuv422_to_rgb(fcc, data, dest, false, colorimetry)
+            }
+            FrameFormat::NV21 | FrameFormat::I420 => {
+                buf_yuv420_to_rgb(fcc, resolution, data, dest, false, colorimetry)
+            }
+            FrameFormat::RAWBGR => buf_bgr_to_rgb(data, dest, false),
+            FrameFormat::RGB565 => buf_rgb565_to_rgb(data, dest, false),
+            FrameFormat::GRAY10 | FrameFormat::GRAY12 | FrameFormat::GRAY16 | FrameFormat::Z16 => {
+                buf_gray16_to_rgb(fcc, data, dest, false)
+            }
+            FrameFormat::Bayer8(_) | FrameFormat::Bayer10(_) => {
+                buf_bayer_to_rgb(fcc, resolution, data, dest, false, DemosaicMethod::Bilinear)
+            }
         }
     }
 }
@@ -122,6 +168,8 @@ impl FormatDecoder for RgbFormat {
 /// use image::{ImageBuffer, Rgba};
 /// let image: ImageBuffer<Rgba<u8>, Vec<u8>> = buffer.to_image::<RgbAFormat>();
 /// ```
+///
+/// Bayer frames are demosaiced with [`DemosaicMethod::Bilinear`]. Use [`MalvarRgbAFormat`] for sharper edges.
 #[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
 pub struct RgbAFormat;
 
@@ -134,11 +182,12 @@ impl FormatDecoder for RgbAFormat {
     fn write_output(
         fcc: FrameFormat,
         resolution: Resolution,
+        colorimetry: Colorimetry,
         data: &[u8],
     ) -> Result<Vec<u8>, NokhwaError> {
         match fcc {
             FrameFormat::MJPEG => mjpeg_to_rgb(data, true),
-            FrameFormat::YUYV => yuyv422_to_rgb(data, true),
+            FrameFormat::YUYV => yuyv422_to_rgb(data, true, colorimetry),
             FrameFormat::GRAY => Ok(data
                 .iter()
                 .flat_map(|x| {
@@ -150,7 +199,23 @@ impl FormatDecoder for RgbAFormat {
                 .chunks_exact(3)
                 .flat_map(|x| [x[0], x[1], x[2], 255])
                 .collect()),
-            FrameFormat::NV12 => nv12_to_rgb(resolution, data, true),
+            FrameFormat::NV12 => nv12_to_rgb(resolution, data, true, colorimetry),
+            FrameFormat::UYVY
+            | FrameFormat::YVYU
+            | FrameFormat::NV21
+            | FrameFormat::I420
+            | FrameFormat::RAWBGR
+            | FrameFormat::RGB565
+            | FrameFormat::GRAY10
+            | FrameFormat::GRAY12
+            | FrameFormat::GRAY16
+            | FrameFormat::Z16
+            | FrameFormat::Bayer8(_)
+            | FrameFormat::Bayer10(_) => {
+                let mut dest = vec![0; (resolution.width() * resolution.height() * 4) as usize];
+                Self::write_output_buffer(fcc, resolution, colorimetry, data, &mut dest)?;
+                Ok(dest)
+            }
         }
     }
 
@@ -158,13 +223,13 @@ impl FormatDecoder for RgbAFormat {
     fn write_output_buffer(
         fcc: FrameFormat,
         resolution: Resolution,
-
+        colorimetry: Colorimetry,
         data: &[u8],
         dest: &mut [u8],
     ) -> Result<(), NokhwaError> {
         match fcc {
             FrameFormat::MJPEG => buf_mjpeg_to_rgb(data, dest, true),
-            FrameFormat::YUYV => buf_yuyv422_to_rgb(data, dest, true),
+            FrameFormat::YUYV => buf_yuyv422_to_rgb(data, dest, true, colorimetry),
             FrameFormat::GRAY => {
                 if dest.len() != data.len() * 4 {
                     return Err(NokhwaError::ProcessFrameError {
@@ -193,7 +258,127 @@ impl FormatDecoder for RgbAFormat {
                 });
                 Ok(())
             }
-            FrameFormat::NV12 => buf_nv12_to_rgb(resolution, data, dest, true),
+            FrameFormat::NV12 => buf_nv12_to_rgb(resolution, data, dest, true, colorimetry),
+            FrameFormat::UYVY | FrameFormat::YVYU => {
+                buf_packed_yuv422_to_rgb(fcc, data, dest, true, colorimetry)
+            }
+            FrameFormat::NV21 | FrameFormat::I420 => {
+                buf_yuv420_to_rgb(fcc, resolution, data, dest, true, colorimetry)
+            }
+            FrameFormat::RAWBGR => buf_bgr_to_rgb(data, dest, true),
+            FrameFormat::RGB565 => buf_rgb565_to_rgb(data, dest, true),
+            FrameFormat::GRAY10 | FrameFormat::GRAY12 | FrameFormat::GRAY16 | FrameFormat::Z16 => {
+                buf_gray16_to_rgb(fcc, data, dest, true)
+            }
+            FrameFormat::Bayer8(_) | FrameFormat::Bayer10(_) => {
+                buf_bayer_to_rgb(fcc, resolution, data, dest, true, DemosaicMethod::Bilinear)
+            }
+        }
+    }
+}
+
+/// Same as [`RgbFormat`], except that Bayer frames are demosaiced with [`DemosaicMethod::MalvarHeCutler`], which keeps edges
+/// sharper and adds fewer color fringes at about twice the cost.
+///
+/// ```.ignore
+/// use image::{ImageBuffer, Rgb};
+/// let image: ImageBuffer<Rgb<u8>, Vec<u8>> = buffer.to_image::<MalvarRgbFormat>();
+/// ```
+#[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct MalvarRgbFormat;
+
+impl FormatDecoder for MalvarRgbFormat {
+    type Output = Rgb<u8>;
+    const FORMATS: &'static [FrameFormat] = color_frame_formats();
+
+    #[inline]
+    fn write_output(
+        fcc: FrameFormat,
+        resolution: Resolution,
+        colorimetry: Colorimetry,
+        data: &[u8],
+    ) -> Result<Vec<u8>, NokhwaError> {
+        match fcc {
+            FrameFormat::Bayer8(_) | FrameFormat::Bayer10(_) => {
+                let mut dest = vec![0; (resolution.width() * resolution.height() * 3) as usize];
+                Self::write_output_buffer(fcc, resolution, colorimetry, data, &mut dest)?;
+                Ok(dest)
+            }
+            _ => RgbFormat::write_output(fcc, resolution, colorimetry, data),
+        }
+    }
+
+    #[inline]
+    fn write_output_buffer(
+        fcc: FrameFormat,
+        resolution: Resolution,
+        colorimetry: Colorimetry,
+        data: &[u8],
+        dest: &mut [u8],
+    ) -> Result<(), NokhwaError> {
+        match fcc {
+            FrameFormat::Bayer8(_) | FrameFormat::Bayer10(_) => buf_bayer_to_rgb(
+                fcc,
+                resolution,
+                data,
+                dest,
+                false,
+                DemosaicMethod::MalvarHeCutler,
+            ),
+            _ => RgbFormat::write_output_buffer(fcc, resolution, colorimetry, data, dest),
+        }
+    }
+}
+
+/// Same as [`RgbAFormat`], except that Bayer frames are demosaiced with [`DemosaicMethod::MalvarHeCutler`], which keeps edges
+/// sharper and adds fewer color fringes at about twice the cost.
+///
+/// ```.ignore
+/// use image::{ImageBuffer, Rgba};
+/// let image: ImageBuffer<Rgba<u8>, Vec<u8>> = buffer.to_image::<MalvarRgbAFormat>();
+/// ```
+#[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct MalvarRgbAFormat;
+
+impl FormatDecoder for MalvarRgbAFormat {
+    type Output = Rgba<u8>;
+    const FORMATS: &'static [FrameFormat] = color_frame_formats();
+
+    #[inline]
+    fn write_output(
+        fcc: FrameFormat,
+        resolution: Resolution,
+        colorimetry: Colorimetry,
+        data: &[u8],
+    ) -> Result<Vec<u8>, NokhwaError> {
+        match fcc {
+            FrameFormat::Bayer8(_) | FrameFormat::Bayer10(_) => {
+                let mut dest = vec![0; (resolution.width() * resolution.height() * 4) as usize];
+                Self::write_output_buffer(fcc, resolution, colorimetry, data, &mut dest)?;
+                Ok(dest)
+            }
+            _ => RgbAFormat::write_output(fcc, resolution, colorimetry, data),
+        }
+    }
+
+    #[inline]
+    fn write_output_buffer(
+        fcc: FrameFormat,
+        resolution: Resolution,
+        colorimetry: Colorimetry,
+        data: &[u8],
+        dest: &mut [u8],
+    ) -> Result<(), NokhwaError> {
+        match fcc {
+            FrameFormat::Bayer8(_) | FrameFormat::Bayer10(_) => buf_bayer_to_rgb(
+                fcc,
+                resolution,
+                data,
+                dest,
+                true,
+                DemosaicMethod::MalvarHeCutler,
+            ),
+            _ => RgbAFormat::write_output_buffer(fcc, resolution, colorimetry, data, dest),
         }
     }
 }
@@ -212,78 +397,81 @@ impl FormatDecoder for LumaFormat {
 
     const FORMATS: &'static [FrameFormat] = frame_formats();
 
-    #[allow(clippy::cast_possible_truncation)]
-    #[allow(clippy::cast_sign_loss)]
     #[inline]
     fn write_output(
         fcc: FrameFormat,
         resolution: Resolution,
+        colorimetry: Colorimetry,
         data: &[u8],
     ) -> Result<Vec<u8>, NokhwaError> {
         match fcc {
-            FrameFormat::MJPEG => Ok(mjpeg_to_rgb(data, false)?
-                .as_slice()
-                .chunks_exact(3)
-                .map(|x| {
-                    let mut avg = 0;
-                    x.iter().for_each(|v| avg += u16::from(*v));
-                    (avg / 3) as u8
-                })
-                .collect()),
-            FrameFormat::YUYV => Ok(yuyv422_to_rgb(data, false)?
-                .as_slice()
-                .chunks_exact(3)
-                .map(|x| {
-                    let mut avg = 0;
-                    x.iter().for_each(|v| avg += u16::from(*v));
-                    (avg / 3) as u8
-                })
-                .collect()),
-            FrameFormat::NV12 => Ok(nv12_to_rgb(resolution, data, false)?
-                .as_slice()
-                .chunks_exact(3)
-                .map(|x| {
-                    let mut avg = 0;
-                    x.iter().for_each(|v| avg += u16::from(*v));
-                    (avg / 3) as u8
-                })
-                .collect()),
+            FrameFormat::MJPEG => mjpeg_to_luma(data),
+            FrameFormat::YUYV => yuyv422_to_luma(data, colorimetry),
+            FrameFormat::NV12 => nv12_to_luma(resolution, data, colorimetry),
             FrameFormat::GRAY => Ok(data.to_vec()),
-            FrameFormat::RAWRGB => Ok(data
-                .chunks(3)
-                .map(|px| ((i32::from(px[0]) + i32::from(px[1]) + i32::from(px[2])) / 3) as u8)
-                .collect()),
+            FrameFormat::RAWRGB => {
+                let mut dest = vec![0; data.len() / 3];
+                buf_rgb_to_luma(data, &mut dest, false)?;
+                Ok(dest)
+            }
+            FrameFormat::UYVY
+            | FrameFormat::YVYU
+            | FrameFormat::NV21
+            | FrameFormat::I420
+            | FrameFormat::RAWBGR
+            | FrameFormat::RGB565
+            | FrameFormat::GRAY10
+            | FrameFormat::GRAY12
+            | FrameFormat::GRAY16
+            | FrameFormat::Z16
+            | FrameFormat::Bayer8(_)
+            | FrameFormat::Bayer10(_) => {
+                let mut dest = vec![0; (resolution.width() * resolution.height()) as usize];
+                Self::write_output_buffer(fcc, resolution, colorimetry, data, &mut dest)?;
+                Ok(dest)
+            }
         }
     }
 
     #[inline]
     fn write_output_buffer(
         fcc: FrameFormat,
-        _resolution: Resolution,
+        resolution: Resolution,
+        colorimetry: Colorimetry,
         data: &[u8],
         dest: &mut [u8],
     ) -> Result<(), NokhwaError> {
         match fcc {
-            // TODO: implement!
-            FrameFormat::MJPEG | FrameFormat::YUYV | FrameFormat::NV12 => {
-                Err(NokhwaError::ProcessFrameError {
-                    src: fcc,
-                    destination: "Luma => RGB".to_string(),
-                    error: "Conversion Error".to_string(),
-                })
-            }
-
+            FrameFormat::MJPEG => buf_mjpeg_to_luma(data, dest, false),
+            FrameFormat::YUYV => buf_yuyv422_to_luma(data, dest, false, colorimetry),
+            FrameFormat::NV12 => buf_nv12_to_luma(resolution, data, dest, false, colorimetry),
             FrameFormat::GRAY => {
-                data.iter().zip(dest.iter_mut()).for_each(|(pxv, d)| {
-                    *d = *pxv;
-                });
+                if dest.len() != data.len() {
+                    return Err(NokhwaError::ProcessFrameError {
+                        src: fcc,
+                        destination: "GRAY8 => Luma".to_string(),
+                        error: "Bad buffer size".to_string(),
+                    });
+                }
+
+                dest.copy_from_slice(data);
                 Ok(())
             }
-            FrameFormat::RAWRGB => Err(NokhwaError::ProcessFrameError {
-                src: fcc,
-                destination: "RGB => RGB".to_string(),
-                error: "Conversion Error".to_string(),
-            }),
+            FrameFormat::RAWRGB => buf_rgb_to_luma(data, dest, false),
+            FrameFormat::UYVY | FrameFormat::YVYU => {
+                buf_packed_yuv422_to_luma(fcc, data, dest, false, colorimetry)
+            }
+            FrameFormat::NV21 | FrameFormat::I420 => {
+                buf_yuv420_to_luma(fcc, resolution, data, dest, false, colorimetry)
+            }
+            FrameFormat::RAWBGR => buf_bgr_to_luma(data, dest, false),
+            FrameFormat::RGB565 => buf_rgb565_to_luma(data, dest, false),
+            FrameFormat::GRAY10 | FrameFormat::GRAY12 | FrameFormat::GRAY16 | FrameFormat::Z16 => {
+                buf_gray16_to_luma(fcc, data, dest, false)
+            }
+            FrameFormat::Bayer8(_) | FrameFormat::Bayer10(_) => {
+                buf_bayer_to_luma(fcc, resolution, data, dest, false)
+            }
         }
     }
 }
@@ -302,76 +490,56 @@ impl FormatDecoder for LumaAFormat {
 
     const FORMATS: &'static [FrameFormat] = frame_formats();
 
-    #[allow(clippy::cast_possible_truncation)]
     #[inline]
     fn write_output(
         fcc: FrameFormat,
         resolution: Resolution,
+        colorimetry: Colorimetry,
         data: &[u8],
     ) -> Result<Vec<u8>, NokhwaError> {
-        match fcc {
-            FrameFormat::MJPEG => Ok(mjpeg_to_rgb(data, false)?
-                .as_slice()
-                .chunks_exact(3)
-                .flat_map(|x| {
-                    let mut avg = 0;
-                    x.iter().for_each(|v| avg += u16::from(*v));
-                    [(avg / 3) as u8, 255]
-                })
-                .collect()),
-            FrameFormat::YUYV => Ok(yuyv422_to_rgb(data, false)?
-                .as_slice()
-                .chunks_exact(3)
-                .flat_map(|x| {
-                    let mut avg = 0;
-                    x.iter().for_each(|v| avg += u16::from(*v));
-                    [(avg / 3) as u8, 255]
-                })
-                .collect()),
-            FrameFormat::NV12 => Ok(nv12_to_rgb(resolution, data, false)?
-                .as_slice()
-                .chunks_exact(3)
-                .flat_map(|x| {
-                    let mut avg = 0;
-                    x.iter().for_each(|v| avg += u16::from(*v));
-                    [(avg / 3) as u8, 255]
-                })
-                .collect()),
-            FrameFormat::GRAY => Ok(data.iter().flat_map(|x| [*x, 255]).collect()),
-            FrameFormat::RAWRGB => Err(NokhwaError::ProcessFrameError {
-                src: fcc,
-                destination: "RGB => RGB".to_string(),
-                error: "Conversion Error".to_string(),
-            }),
-        }
+        let pixel_count = match fcc {
+            // The resolution of a JPEG is in its header
+            FrameFormat::MJPEG => {
+                return Ok(mjpeg_to_luma(data)?
+                    .into_iter()
+                    .flat_map(|y| [y, 255])
+                    .collect())
+            }
+            FrameFormat::YUYV => data.len() / 2,
+            FrameFormat::NV12
+            | FrameFormat::UYVY
+            | FrameFormat::YVYU
+            | FrameFormat::NV21
+            | FrameFormat::I420
+            | FrameFormat::RAWBGR
+            | FrameFormat::RGB565
+            | FrameFormat::GRAY10
+            | FrameFormat::GRAY12
+            | FrameFormat::GRAY16
+            | FrameFormat::Z16
+            | FrameFormat::Bayer8(_)
+            | FrameFormat::Bayer10(_) => (resolution.width() * resolution.height()) as usize,
+            FrameFormat::GRAY => data.len(),
+            FrameFormat::RAWRGB => data.len() / 3,
+        };
+
+        let mut dest = vec![0; pixel_count * 2];
+        Self::write_output_buffer(fcc, resolution, colorimetry, data, &mut dest)?;
+        Ok(dest)
     }
 
     #[inline]
     fn write_output_buffer(
         fcc: FrameFormat,
-        _resolution: Resolution,
+        resolution: Resolution,
+        colorimetry: Colorimetry,
         data: &[u8],
         dest: &mut [u8],
     ) -> Result<(), NokhwaError> {
         match fcc {
-            FrameFormat::MJPEG => {
-                // FIXME: implement!
-                Err(NokhwaError::ProcessFrameError {
-                    src: fcc,
-                    destination: "MJPEG => LumaA".to_string(),
-                    error: "Conversion Error".to_string(),
-                })
-            }
-            FrameFormat::YUYV => Err(NokhwaError::ProcessFrameError {
-                src: fcc,
-                destination: "YUYV => LumaA".to_string(),
-                error: "Conversion Error".to_string(),
-            }),
-            FrameFormat::NV12 => Err(NokhwaError::ProcessFrameError {
-                src: fcc,
-                destination: "NV12 => LumaA".to_string(),
-                error: "Conversion Error".to_string(),
-            }),
+            FrameFormat::MJPEG => buf_mjpeg_to_luma(data, dest, true),
+            FrameFormat::YUYV => buf_yuyv422_to_luma(data, dest, true, colorimetry),
+            FrameFormat::NV12 => buf_nv12_to_luma(resolution, data, dest, true, colorimetry),
             FrameFormat::GRAY => {
                 if dest.len() != data.len() * 2 {
                     return Err(NokhwaError::ProcessFrameError {
@@ -383,19 +551,420 @@ impl FormatDecoder for LumaAFormat {
 
                 data.iter()
                     .zip(dest.chunks_exact_mut(2))
-                    .enumerate()
-                    .for_each(|(idx, (pxv, d))| {
-                        let index = idx * 2;
-                        d[index] = *pxv;
-                        d[index + 1] = 255;
+                    .for_each(|(pxv, d)| {
+                        d[0] = *pxv;
+                        d[1] = 255;
                     });
                 Ok(())
             }
-            FrameFormat::RAWRGB => Err(NokhwaError::ProcessFrameError {
-                src: fcc,
-                destination: "RGB => RGB".to_string(),
-                error: "Conversion Error".to_string(),
-            }),
+            FrameFormat::RAWRGB => buf_rgb_to_luma(data, dest, true),
+            FrameFormat::UYVY | FrameFormat::YVYU => {
+                buf_packed_yuv422_to_luma(fcc, data, dest, true, colorimetry)
+            }
+            FrameFormat::NV21 | FrameFormat::I420 => {
+                buf_yuv420_to_luma(fcc, resolution, data, dest, true, colorimetry)
+            }
+            FrameFormat::RAWBGR => buf_bgr_to_luma(data, dest, true),
+            FrameFormat::RGB565 => buf_rgb565_to_luma(data, dest, true),
+            FrameFormat::GRAY10 | FrameFormat::GRAY12 | FrameFormat::GRAY16 | FrameFormat::Z16 => {
+                buf_gray16_to_luma(fcc, data, dest, true)
+            }
+            FrameFormat::Bayer8(_) | FrameFormat::Bayer10(_) => {
+                buf_bayer_to_luma(fcc, resolution, data, dest, true)
+            }
+        }
+    }
+}
+
+/// A Zero-Size-Type that contains the definition to convert a given image stream to an Luma16(Grayscale 16-bit) in the [`Buffer`](crate::buffer::Buffer)'s [`.decode_image()`](crate::buffer::Buffer::decode_image)
+///
+/// High bit depth formats ([`FrameFormat::GRAY10`], [`FrameFormat::GRAY12`], [`FrameFormat::GRAY16`], [`FrameFormat::Z16`] and
+/// [`FrameFormat::Bayer10`]) keep all of their bits, every format is widened to the full 16-bit range with [`widen_sample`]. See [`ToneMap`](crate::types::ToneMap)
+/// to bring the result back down to 8 bits.
+///
+/// ```.ignore
+/// use image::{ImageBuffer, Luma};
+/// let image: ImageBuffer<Luma<u16>, Vec<u16>> = buffer.to_image::<Luma16Format>();
+/// ```
+#[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Luma16Format;
+
+impl FormatDecoder for Luma16Format {
+    type Output = Luma<u16>;
+
+    const FORMATS: &'static [FrameFormat] = frame_formats();
+
+    #[inline]
+    fn write_output(
+        fcc: FrameFormat,
+        resolution: Resolution,
+        colorimetry: Colorimetry,
+        data: &[u8],
+    ) -> Result<Vec<u16>, NokhwaError> {
+        match fcc {
+            FrameFormat::GRAY10
+            | FrameFormat::GRAY12
+            | FrameFormat::GRAY16
+            | FrameFormat::Z16
+            | FrameFormat::Bayer10(_) => {
+                let mut dest = vec![0; (resolution.width() * resolution.height()) as usize];
+                Self::write_output_buffer(fcc, resolution, colorimetry, data, &mut dest)?;
+                Ok(dest)
+            }
+            _ => Ok(
+                LumaFormat::write_output(fcc, resolution, colorimetry, data)?
+                    .into_iter()
+                    .map(|luma| widen_sample(u16::from(luma), 8))
+                    .collect(),
+            ),
+        }
+    }
+
+    #[inline]
+    fn write_output_buffer(
+        fcc: FrameFormat,
+        resolution: Resolution,
+        colorimetry: Colorimetry,
+        data: &[u8],
+        dest: &mut [u16],
+    ) -> Result<(), NokhwaError> {
+        match fcc {
+            FrameFormat::GRAY10 | FrameFormat::GRAY12 | FrameFormat::GRAY16 | FrameFormat::Z16 => {
+                buf_gray16_to_luma16(fcc, data, dest)
+            }
+            FrameFormat::Bayer10(_) => buf_bayer_to_luma16(fcc, resolution, data, dest),
+            _ => {
+                let luma = LumaFormat::write_output(fcc, resolution, colorimetry, data)?;
+                if dest.len() != luma.len() {
+                    return Err(NokhwaError::ProcessFrameError {
+                        src: fcc,
+                        destination: "Luma16".to_string(),
+                        error: "Bad buffer size".to_string(),
+                    });
+                }
+
+                for (luma, px) in luma.into_iter().zip(dest.iter_mut()) {
+                    *px = widen_sample(u16::from(luma), 8);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::Buffer;
+    use crate::types::{rgb_to_luma, BayerPattern, ColorRange, ColorSpace};
+
+    const RESOLUTION: Resolution = Resolution {
+        width_x: 8,
+        height_y: 4,
+    };
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 37 + 11) as u8).collect()
+    }
+
+    fn colorimetries() -> Vec<Colorimetry> {
+        let mut colorimetries = Vec::new();
+        for space in [ColorSpace::Bt601, ColorSpace::Bt709, ColorSpace::Bt2020] {
+            for range in [ColorRange::Limited, ColorRange::Full] {
+                colorimetries.push(Colorimetry::new(space, range));
+            }
+        }
+        colorimetries
+    }
+
+    /// Decodes `buffer` to [`LumaFormat`] and [`LumaAFormat`], both allocating and into a buffer, and checks every
+    /// result against `expected`.
+    fn assert_luma(buffer: &Buffer, expected: &[u8]) {
+        let luma = buffer.decode_image::<LumaFormat>().unwrap();
+        assert_eq!(luma.dimensions(), (RESOLUTION.width(), RESOLUTION.height()));
+        assert_eq!(luma.as_raw().as_slice(), expected);
+
+        let mut dest = vec![0; expected.len()];
+        buffer
+            .decode_image_to_buffer::<LumaFormat>(&mut dest)
+            .unwrap();
+        assert_eq!(dest, expected);
+
+        let expected_alpha = expected
+            .iter()
+            .flat_map(|luma| [*luma, 255])
+            .collect::<Vec<u8>>();
+        let luma_alpha = buffer.decode_image::<LumaAFormat>().unwrap();
+        assert_eq!(luma_alpha.as_raw(), &expected_alpha);
+
+        let mut dest = vec![0; expected_alpha.len()];
+        buffer
+            .decode_image_to_buffer::<LumaAFormat>(&mut dest)
+            .unwrap();
+        assert_eq!(dest, expected_alpha);
+
+        let mut short = vec![0; expected.len() - 1];
+        assert!(buffer
+            .decode_image_to_buffer::<LumaFormat>(&mut short)
+            .is_err());
+    }
+
+    #[test]
+    fn yuyv_to_luma() {
+        let data = pattern((RESOLUTION.width() * RESOLUTION.height() * 2) as usize);
+        for colorimetry in colorimetries() {
+            let expected = data
+                .iter()
+                .step_by(2)
+                .map(|y| colorimetry.y_to_luma(*y))
+                .collect::<Vec<u8>>();
+            let buffer =
+                Buffer::new(RESOLUTION, &data, FrameFormat::YUYV).with_colorimetry(colorimetry);
+            assert_luma(&buffer, &expected);
+        }
+    }
+
+    #[test]
+    fn nv12_to_luma() {
+        let pixels = (RESOLUTION.width() * RESOLUTION.height()) as usize;
+        let data = pattern(pixels * 3 / 2);
+        for colorimetry in colorimetries() {
+            let expected = data[..pixels]
+                .iter()
+                .map(|y| colorimetry.y_to_luma(*y))
+                .collect::<Vec<u8>>();
+            let buffer =
+                Buffer::new(RESOLUTION, &data, FrameFormat::NV12).with_colorimetry(colorimetry);
+            assert_luma(&buffer, &expected);
+        }
+    }
+
+    #[test]
+    fn rawrgb_to_luma() {
+        let data = pattern((RESOLUTION.width() * RESOLUTION.height() * 3) as usize);
+        let expected = data
+            .chunks_exact(3)
+            .map(|rgb| rgb_to_luma(rgb[0], rgb[1], rgb[2]))
+            .collect::<Vec<u8>>();
+        let buffer = Buffer::new(RESOLUTION, &data, FrameFormat::RAWRGB);
+        assert_luma(&buffer, &expected);
+    }
+
+    /// Decodes `buffer` with `F`, both allocating and into a buffer, and checks both results against `expected`.
+    fn assert_decoded<F: FormatDecoder<Output = P>, P: Pixel<Subpixel = u8>>(
+        buffer: &Buffer,
+        expected: &[u8],
+    ) {
+        let image = buffer.decode_image::<F>().unwrap();
+        assert_eq!(image.as_raw().as_slice(), expected);
+
+        let mut dest = vec![0; expected.len()];
+        buffer.decode_image_to_buffer::<F>(&mut dest).unwrap();
+        assert_eq!(dest, expected);
+    }
+
+    /// Checks that `buffer` decodes to the pixels `rgb` with [`RgbFormat`] and [`RgbAFormat`], and to `luma` with
+    /// [`LumaFormat`] and [`LumaAFormat`].
+    fn assert_pixels(buffer: &Buffer, rgb: &[[u8; 3]], luma: &[u8]) {
+        let expected = rgb.iter().flatten().copied().collect::<Vec<u8>>();
+        assert_decoded::<RgbFormat, _>(buffer, &expected);
+        let expected = rgb
+            .iter()
+            .flat_map(|[r, g, b]| [*r, *g, *b, 255])
+            .collect::<Vec<u8>>();
+        assert_decoded::<RgbAFormat, _>(buffer, &expected);
+        assert_decoded::<LumaFormat, _>(buffer, luma);
+        let expected = luma
+            .iter()
+            .flat_map(|luma| [*luma, 255])
+            .collect::<Vec<u8>>();
+        assert_decoded::<LumaAFormat, _>(buffer, &expected);
+    }
+
+    /// The luma of a 4x2 YUV test frame, row by row.
+    const YUV_LUMA: [u8; 8] = [16, 60, 110, 160, 200, 235, 90, 40];
+    /// The U and V of the left and right halves of the YUV test frame.
+    const YUV_CHROMA: [[u8; 2]; 2] = [[90, 240], [200, 50]];
+
+    /// Decodes `data`, the YUV test frame in `format`, with every colorimetry and checks the pixels.
+    fn assert_yuv_pixels(format: FrameFormat, data: &[u8]) {
+        for colorimetry in colorimetries() {
+            let rgb = YUV_LUMA
+                .iter()
+                .enumerate()
+                .map(|(index, y)| {
+                    let [u, v] = YUV_CHROMA[index % 4 / 2];
+                    colorimetry.yuv_to_rgb(i32::from(*y), i32::from(u), i32::from(v))
+                })
+                .collect::<Vec<[u8; 3]>>();
+            let luma = YUV_LUMA
+                .iter()
+                .map(|y| colorimetry.y_to_luma(*y))
+                .collect::<Vec<u8>>();
+            let buffer =
+                Buffer::new(Resolution::new(4, 2), data, format).with_colorimetry(colorimetry);
+            assert_pixels(&buffer, &rgb, &luma);
+        }
+    }
+
+    #[test]
+    fn uyvy_pixels() {
+        let [y0, y1, y2, y3, y4, y5, y6, y7] = YUV_LUMA;
+        let [[u0, v0], [u1, v1]] = YUV_CHROMA;
+        assert_yuv_pixels(
+            FrameFormat::UYVY,
+            &[
+                u0, y0, v0, y1, u1, y2, v1, y3, u0, y4, v0, y5, u1, y6, v1, y7,
+            ],
+        );
+    }
+
+    #[test]
+    fn yvyu_pixels() {
+        let [y0, y1, y2, y3, y4, y5, y6, y7] = YUV_LUMA;
+        let [[u0, v0], [u1, v1]] = YUV_CHROMA;
+        assert_yuv_pixels(
+            FrameFormat::YVYU,
+            &[
+                y0, v0, y1, u0, y2, v1, y3, u1, y4, v0, y5, u0, y6, v1, y7, u1,
+            ],
+        );
+    }
+
+    #[test]
+    fn nv21_pixels() {
+        let [[u0, v0], [u1, v1]] = YUV_CHROMA;
+        let mut data = YUV_LUMA.to_vec();
+        data.extend_from_slice(&[v0, u0, v1, u1]);
+        assert_yuv_pixels(FrameFormat::NV21, &data);
+    }
+
+    #[test]
+    fn i420_pixels() {
+        let [[u0, v0], [u1, v1]] = YUV_CHROMA;
+        let mut data = YUV_LUMA.to_vec();
+        data.extend_from_slice(&[u0, u1, v0, v1]);
+        assert_yuv_pixels(FrameFormat::I420, &data);
+    }
+
+    #[test]
+    fn bgr_pixels() {
+        let data = [10, 20, 30, 255, 0, 128, 0, 0, 0, 255, 255, 255];
+        let buffer = Buffer::new(Resolution::new(2, 2), &data, FrameFormat::RAWBGR);
+        assert_pixels(
+            &buffer,
+            &[[30, 20, 10], [128, 0, 255], [0, 0, 0], [255, 255, 255]],
+            &[22, 67, 0, 255],
+        );
+    }
+
+    #[test]
+    fn rgb565_pixels() {
+        // Red, green, blue and the middle of every channel, little-endian
+        let data = [0x00, 0xF8, 0xE0, 0x07, 0x1F, 0x00, 0x10, 0x84];
+        let buffer = Buffer::new(Resolution::new(2, 2), &data, FrameFormat::RGB565);
+        assert_pixels(
+            &buffer,
+            &[[255, 0, 0], [0, 255, 0], [0, 0, 255], [132, 130, 132]],
+            &[77, 149, 29, 131],
+        );
+    }
+
+    #[test]
+    fn gray16_pixels() {
+        let data = [0x34, 0x12, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x80];
+        let buffer = Buffer::new(Resolution::new(2, 2), &data, FrameFormat::GRAY16);
+        assert_pixels(
+            &buffer,
+            &[[0x12; 3], [255; 3], [0; 3], [128; 3]],
+            &[0x12, 255, 0, 128],
+        );
+    }
+
+    /// Decodes `data` in `format` with [`Luma16Format`], both allocating and into a buffer, and checks both results.
+    fn assert_luma16(format: FrameFormat, data: &[u8], expected: &[u16]) {
+        let buffer = Buffer::new(Resolution::new(2, 2), data, format);
+        let luma = buffer.decode_image::<Luma16Format>().unwrap();
+        assert_eq!(luma.as_raw().as_slice(), expected, "{format}");
+
+        let mut dest = vec![0; expected.len()];
+        buffer
+            .decode_image_to_buffer::<Luma16Format>(&mut dest)
+            .unwrap();
+        assert_eq!(dest, expected, "{format}");
+    }
+
+    #[test]
+    fn high_bit_depth_to_luma16() {
+        let samples = |samples: [u16; 4]| {
+            samples
+                .iter()
+                .flat_map(|sample| sample.to_le_bytes())
+                .collect::<Vec<u8>>()
+        };
+
+        // The unused high bits are ignored, full white stays full white
+        assert_luma16(
+            FrameFormat::GRAY10,
+            &samples([0, 0x3FF, 0x200, 0xFC00 | 0x155]),
+            &[0, 0xFFFF, 0x8020, 0x5555],
+        );
+        assert_luma16(
+            FrameFormat::GRAY12,
+            &samples([0, 0xFFF, 0x800, 0xF123]),
+            &[0, 0xFFFF, 0x8008, 0x1231],
+        );
+        assert_luma16(
+            FrameFormat::GRAY16,
+            &samples([0, 0xFFFF, 0x8000, 0x1234]),
+            &[0, 0xFFFF, 0x8000, 0x1234],
+        );
+        // A flat gray Bayer frame, all four colors at the same level
+        assert_luma16(
+            FrameFormat::Bayer10(BayerPattern::RGGB),
+            &samples([0x3FF; 4]),
+            &[0xFFFF; 4],
+        );
+        // 8-bit sources are widened too
+        assert_luma16(
+            FrameFormat::GRAY,
+            &[0, 255, 0x80, 0x12],
+            &[0, 0xFFFF, 0x8080, 0x1212],
+        );
+    }
+
+    #[cfg(all(feature = "mjpeg", not(target_arch = "wasm")))]
+    #[test]
+    fn mjpeg_to_luma() {
+        use crate::types::rgb_to_mjpeg;
+
+        // Flat colors survive compression, up to rounding
+        let rgb = [[200, 40, 40], [40, 200, 40], [40, 40, 200], [128, 128, 128]];
+        let resolution = Resolution::new(16, 16);
+        let data = (0..resolution.height())
+            .flat_map(|y| (0..resolution.width()).map(move |x| (x / 8 + 2 * (y / 8)) as usize))
+            .flat_map(|i| rgb[i])
+            .collect::<Vec<u8>>();
+        let mjpeg = rgb_to_mjpeg(resolution, &data, 100).unwrap();
+        let buffer = Buffer::new(resolution, &mjpeg, FrameFormat::MJPEG);
+
+        let expected = data
+            .chunks_exact(3)
+            .map(|rgb| rgb_to_luma(rgb[0], rgb[1], rgb[2]))
+            .collect::<Vec<u8>>();
+        let luma = buffer.decode_image::<LumaFormat>().unwrap();
+        for (luma, expected) in luma.as_raw().iter().zip(&expected) {
+            assert!(luma.abs_diff(*expected) <= 3, "{luma} != {expected}");
+        }
+
+        let mut dest = vec![0; expected.len() * 2];
+        buffer
+            .decode_image_to_buffer::<LumaAFormat>(&mut dest)
+            .unwrap();
+        for (luma_alpha, expected) in dest.chunks_exact(2).zip(&expected) {
+            assert!(luma_alpha[0].abs_diff(*expected) <= 3);
+            assert_eq!(luma_alpha[1], 255);
         }
     }
 }
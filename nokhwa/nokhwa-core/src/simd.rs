@@ -0,0 +1,786 @@
+/*
+ * Copyright 2022 l1npengtul <l1npengtul@protonmail.com> / The Nokhwa Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Vectorized YUV to RGB conversion.
+//!
+//! These compute the exact same integer equation as [`Colorimetry::yuv_to_rgb`](crate::types::Colorimetry::yuv_to_rgb) in 32-bit
+//! lanes, with saturating packs standing in for the clamp, so their output is bit-exact with the scalar path.
+//!
+//! Each function converts as many whole blocks of 16 pixels as it can, with the best instruction set the CPU supports
+//! (AVX2 or SSE4.1 on x86, NEON on `AArch64`), and reports how much it converted. The caller converts the rest with the
+//! scalar code. On other architectures nothing is converted here.
+
+use crate::types::YuvCoefficients;
+
+/// Number of pixels converted per block.
+const BLOCK_PIXELS: usize = 16;
+
+/// Converts the leading whole blocks of a YUYV 4:2:2 stream to RGB888 (or RGBA8888 if `rgba`), returning the number of bytes of `data`
+/// that were converted. `dest` must be sized for all of `data`.
+#[allow(unreachable_code)]
+pub(crate) fn yuyv422_to_rgb(
+    data: &[u8],
+    dest: &mut [u8],
+    rgba: bool,
+    coefficients: YuvCoefficients,
+) -> usize {
+    let blocks = data.len() / (BLOCK_PIXELS * 2);
+    if blocks == 0 {
+        return 0;
+    }
+    debug_assert!(dest.len() >= blocks * BLOCK_PIXELS * pixel_size(rgba));
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: the CPU supports AVX2, and the block count is bounded by both buffers
+            unsafe { x86::yuyv_avx2(data, dest, blocks, rgba, &coefficients) };
+            return blocks * BLOCK_PIXELS * 2;
+        }
+        if is_x86_feature_detected!("sse4.1") {
+            // SAFETY: the CPU supports SSE4.1, and the block count is bounded by both buffers
+            unsafe { x86::yuyv_sse41(data, dest, blocks, rgba, &coefficients) };
+            return blocks * BLOCK_PIXELS * 2;
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            // SAFETY: the CPU supports NEON, and the block count is bounded by both buffers
+            unsafe { aarch64::yuyv_neon(data, dest, blocks, rgba, &coefficients) };
+            return blocks * BLOCK_PIXELS * 2;
+        }
+    }
+
+    0
+}
+
+/// Converts the leading whole blocks of one row of a NV12 image to RGB888 (or RGBA8888 if `rgba`), returning the number of pixels
+/// that were converted. `uv` is the interleaved chroma row shared by this row, and `dest` must be sized for the whole row.
+#[allow(unreachable_code)]
+pub(crate) fn nv12_row_to_rgb(
+    y: &[u8],
+    uv: &[u8],
+    dest: &mut [u8],
+    rgba: bool,
+    coefficients: YuvCoefficients,
+) -> usize {
+    let blocks = y.len().min(uv.len()) / BLOCK_PIXELS;
+    if blocks == 0 {
+        return 0;
+    }
+    debug_assert!(dest.len() >= blocks * BLOCK_PIXELS * pixel_size(rgba));
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: the CPU supports AVX2, and the block count is bounded by all buffers
+            unsafe { x86::nv12_avx2(y, uv, dest, blocks, rgba, &coefficients) };
+            return blocks * BLOCK_PIXELS;
+        }
+        if is_x86_feature_detected!("sse4.1") {
+            // SAFETY: the CPU supports SSE4.1, and the block count is bounded by all buffers
+            unsafe { x86::nv12_sse41(y, uv, dest, blocks, rgba, &coefficients) };
+            return blocks * BLOCK_PIXELS;
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            // SAFETY: the CPU supports NEON, and the block count is bounded by all buffers
+            unsafe { aarch64::nv12_neon(y, uv, dest, blocks, rgba, &coefficients) };
+            return blocks * BLOCK_PIXELS;
+        }
+    }
+
+    0
+}
+
+#[allow(dead_code)]
+const fn pixel_size(rgba: bool) -> usize {
+    if rgba {
+        4
+    } else {
+        3
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[allow(clippy::cast_possible_wrap)]
+#[allow(clippy::many_single_char_names)]
+mod x86 {
+    use super::{pixel_size, YuvCoefficients, BLOCK_PIXELS};
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::{
+        __m128i, __m256i, _mm256_add_epi32, _mm256_castsi256_si128, _mm256_cvtepu8_epi32,
+        _mm256_extracti128_si256, _mm256_mullo_epi32, _mm256_set1_epi32, _mm256_srai_epi32,
+        _mm256_sub_epi32, _mm_add_epi32, _mm_cvtepu8_epi32, _mm_loadu_si128, _mm_mullo_epi32,
+        _mm_or_si128, _mm_packs_epi32, _mm_packus_epi16, _mm_set1_epi32, _mm_set1_epi8,
+        _mm_setr_epi8, _mm_shuffle_epi8, _mm_slli_si128, _mm_srai_epi32, _mm_srli_si128,
+        _mm_storeu_si128, _mm_sub_epi32, _mm_unpackhi_epi16, _mm_unpackhi_epi8, _mm_unpacklo_epi16,
+        _mm_unpacklo_epi64, _mm_unpacklo_epi8,
+    };
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::{
+        __m128i, __m256i, _mm256_add_epi32, _mm256_castsi256_si128, _mm256_cvtepu8_epi32,
+        _mm256_extracti128_si256, _mm256_mullo_epi32, _mm256_set1_epi32, _mm256_srai_epi32,
+        _mm256_sub_epi32, _mm_add_epi32, _mm_cvtepu8_epi32, _mm_loadu_si128, _mm_mullo_epi32,
+        _mm_or_si128, _mm_packs_epi32, _mm_packus_epi16, _mm_set1_epi32, _mm_set1_epi8,
+        _mm_setr_epi8, _mm_shuffle_epi8, _mm_slli_si128, _mm_srai_epi32, _mm_srli_si128,
+        _mm_storeu_si128, _mm_sub_epi32, _mm_unpackhi_epi16, _mm_unpackhi_epi8, _mm_unpacklo_epi16,
+        _mm_unpacklo_epi64, _mm_unpacklo_epi8,
+    };
+
+    /// Splits 16 YUYV pixels (32 bytes) into their Y, U and V, with the chroma repeated for both pixels of a pair.
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn load_yuyv(src: *const u8) -> (__m128i, __m128i, __m128i) {
+        let first = _mm_loadu_si128(src.cast());
+        let second = _mm_loadu_si128(src.add(16).cast());
+
+        let y = _mm_setr_epi8(0, 2, 4, 6, 8, 10, 12, 14, -1, -1, -1, -1, -1, -1, -1, -1);
+        let u = _mm_setr_epi8(1, 1, 5, 5, 9, 9, 13, 13, -1, -1, -1, -1, -1, -1, -1, -1);
+        let v = _mm_setr_epi8(3, 3, 7, 7, 11, 11, 15, 15, -1, -1, -1, -1, -1, -1, -1, -1);
+
+        let gather = |mask| {
+            _mm_unpacklo_epi64(
+                _mm_shuffle_epi8(first, mask),
+                _mm_shuffle_epi8(second, mask),
+            )
+        };
+        (gather(y), gather(u), gather(v))
+    }
+
+    /// Loads 16 NV12 pixels from a luma row and its chroma row, with the chroma repeated for both pixels of a pair.
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn load_nv12(y: *const u8, uv: *const u8) -> (__m128i, __m128i, __m128i) {
+        let luma = _mm_loadu_si128(y.cast());
+        let chroma = _mm_loadu_si128(uv.cast());
+
+        let u = _mm_setr_epi8(0, 0, 2, 2, 4, 4, 6, 6, 8, 8, 10, 10, 12, 12, 14, 14);
+        let v = _mm_setr_epi8(1, 1, 3, 3, 5, 5, 7, 7, 9, 9, 11, 11, 13, 13, 15, 15);
+
+        (
+            luma,
+            _mm_shuffle_epi8(chroma, u),
+            _mm_shuffle_epi8(chroma, v),
+        )
+    }
+
+    /// Converts 16 pixels of Y, U and V to R, G and B, 4 pixels at a time.
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn convert_sse41(
+        y: __m128i,
+        u: __m128i,
+        v: __m128i,
+        k: &YuvCoefficients,
+    ) -> (__m128i, __m128i, __m128i) {
+        let mut r = [_mm_set1_epi32(0); 4];
+        let mut g = [_mm_set1_epi32(0); 4];
+        let mut b = [_mm_set1_epi32(0); 4];
+
+        let offset = _mm_set1_epi32(k.y_offset);
+        let center = _mm_set1_epi32(128);
+        let round = _mm_set1_epi32(128);
+
+        for quarter in 0..4 {
+            let (y, u, v) = match quarter {
+                0 => (y, u, v),
+                1 => (
+                    _mm_srli_si128::<4>(y),
+                    _mm_srli_si128::<4>(u),
+                    _mm_srli_si128::<4>(v),
+                ),
+                2 => (
+                    _mm_srli_si128::<8>(y),
+                    _mm_srli_si128::<8>(u),
+                    _mm_srli_si128::<8>(v),
+                ),
+                _ => (
+                    _mm_srli_si128::<12>(y),
+                    _mm_srli_si128::<12>(u),
+                    _mm_srli_si128::<12>(v),
+                ),
+            };
+
+            let c = _mm_add_epi32(
+                _mm_mullo_epi32(
+                    _mm_sub_epi32(_mm_cvtepu8_epi32(y), offset),
+                    _mm_set1_epi32(k.y),
+                ),
+                round,
+            );
+            let d = _mm_sub_epi32(_mm_cvtepu8_epi32(u), center);
+            let e = _mm_sub_epi32(_mm_cvtepu8_epi32(v), center);
+
+            r[quarter] =
+                _mm_srai_epi32::<8>(_mm_add_epi32(c, _mm_mullo_epi32(e, _mm_set1_epi32(k.r_v))));
+            g[quarter] = _mm_srai_epi32::<8>(_mm_sub_epi32(
+                c,
+                _mm_add_epi32(
+                    _mm_mullo_epi32(d, _mm_set1_epi32(k.g_u)),
+                    _mm_mullo_epi32(e, _mm_set1_epi32(k.g_v)),
+                ),
+            ));
+            b[quarter] =
+                _mm_srai_epi32::<8>(_mm_add_epi32(c, _mm_mullo_epi32(d, _mm_set1_epi32(k.b_u))));
+        }
+
+        (pack(r), pack(g), pack(b))
+    }
+
+    /// Converts 16 pixels of Y, U and V to R, G and B, 8 pixels at a time.
+    #[target_feature(enable = "avx2")]
+    unsafe fn convert_avx2(
+        y: __m128i,
+        u: __m128i,
+        v: __m128i,
+        k: &YuvCoefficients,
+    ) -> (__m128i, __m128i, __m128i) {
+        let mut r = [_mm_set1_epi32(0); 4];
+        let mut g = [_mm_set1_epi32(0); 4];
+        let mut b = [_mm_set1_epi32(0); 4];
+
+        let offset = _mm256_set1_epi32(k.y_offset);
+        let center = _mm256_set1_epi32(128);
+        let round = _mm256_set1_epi32(128);
+
+        for half in 0..2 {
+            let (y, u, v) = if half == 0 {
+                (y, u, v)
+            } else {
+                (
+                    _mm_srli_si128::<8>(y),
+                    _mm_srli_si128::<8>(u),
+                    _mm_srli_si128::<8>(v),
+                )
+            };
+
+            let c = _mm256_add_epi32(
+                _mm256_mullo_epi32(
+                    _mm256_sub_epi32(_mm256_cvtepu8_epi32(y), offset),
+                    _mm256_set1_epi32(k.y),
+                ),
+                round,
+            );
+            let d = _mm256_sub_epi32(_mm256_cvtepu8_epi32(u), center);
+            let e = _mm256_sub_epi32(_mm256_cvtepu8_epi32(v), center);
+
+            let split = |x: __m256i| (_mm256_castsi256_si128(x), _mm256_extracti128_si256::<1>(x));
+
+            (r[half * 2], r[half * 2 + 1]) = split(_mm256_srai_epi32::<8>(_mm256_add_epi32(
+                c,
+                _mm256_mullo_epi32(e, _mm256_set1_epi32(k.r_v)),
+            )));
+            (g[half * 2], g[half * 2 + 1]) = split(_mm256_srai_epi32::<8>(_mm256_sub_epi32(
+                c,
+                _mm256_add_epi32(
+                    _mm256_mullo_epi32(d, _mm256_set1_epi32(k.g_u)),
+                    _mm256_mullo_epi32(e, _mm256_set1_epi32(k.g_v)),
+                ),
+            )));
+            (b[half * 2], b[half * 2 + 1]) = split(_mm256_srai_epi32::<8>(_mm256_add_epi32(
+                c,
+                _mm256_mullo_epi32(d, _mm256_set1_epi32(k.b_u)),
+            )));
+        }
+
+        (pack(r), pack(g), pack(b))
+    }
+
+    /// Packs 16 32-bit channel values to bytes. The saturation is the clamp to 0-255.
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn pack(quarters: [__m128i; 4]) -> __m128i {
+        _mm_packus_epi16(
+            _mm_packs_epi32(quarters[0], quarters[1]),
+            _mm_packs_epi32(quarters[2], quarters[3]),
+        )
+    }
+
+    /// Interleaves 16 pixels of R, G and B and writes them to `dest` as RGB888 or RGBA8888.
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn store(r: __m128i, g: __m128i, b: __m128i, dest: *mut u8, rgba: bool) {
+        let alpha = _mm_set1_epi8(-1);
+        let rg_low = _mm_unpacklo_epi8(r, g);
+        let rg_high = _mm_unpackhi_epi8(r, g);
+        let ba_low = _mm_unpacklo_epi8(b, alpha);
+        let ba_high = _mm_unpackhi_epi8(b, alpha);
+
+        let pixels = [
+            _mm_unpacklo_epi16(rg_low, ba_low),
+            _mm_unpackhi_epi16(rg_low, ba_low),
+            _mm_unpacklo_epi16(rg_high, ba_high),
+            _mm_unpackhi_epi16(rg_high, ba_high),
+        ];
+
+        if rgba {
+            for (idx, quad) in pixels.iter().enumerate() {
+                _mm_storeu_si128(dest.add(idx * 16).cast(), *quad);
+            }
+            return;
+        }
+
+        // Drop the alpha bytes, then stitch the four 12 byte runs into three full vectors
+        let drop_alpha = _mm_setr_epi8(0, 1, 2, 4, 5, 6, 8, 9, 10, 12, 13, 14, -1, -1, -1, -1);
+        let packed = pixels.map(|quad| _mm_shuffle_epi8(quad, drop_alpha));
+
+        _mm_storeu_si128(
+            dest.cast(),
+            _mm_or_si128(packed[0], _mm_slli_si128::<12>(packed[1])),
+        );
+        _mm_storeu_si128(
+            dest.add(16).cast(),
+            _mm_or_si128(
+                _mm_srli_si128::<4>(packed[1]),
+                _mm_slli_si128::<8>(packed[2]),
+            ),
+        );
+        _mm_storeu_si128(
+            dest.add(32).cast(),
+            _mm_or_si128(
+                _mm_srli_si128::<8>(packed[2]),
+                _mm_slli_si128::<4>(packed[3]),
+            ),
+        );
+    }
+
+    macro_rules! yuyv_kernel {
+        ($name:ident, $feature:literal, $convert:ident) => {
+            /// Converts `blocks` blocks of 16 YUYV pixels.
+            #[target_feature(enable = $feature)]
+            pub(super) unsafe fn $name(
+                data: &[u8],
+                dest: &mut [u8],
+                blocks: usize,
+                rgba: bool,
+                coefficients: &YuvCoefficients,
+            ) {
+                let block_size = BLOCK_PIXELS * pixel_size(rgba);
+                for block in 0..blocks {
+                    let (y, u, v) = load_yuyv(data.as_ptr().add(block * BLOCK_PIXELS * 2));
+                    let (r, g, b) = $convert(y, u, v, coefficients);
+                    store(r, g, b, dest.as_mut_ptr().add(block * block_size), rgba);
+                }
+            }
+        };
+    }
+
+    macro_rules! nv12_kernel {
+        ($name:ident, $feature:literal, $convert:ident) => {
+            /// Converts `blocks` blocks of 16 pixels of a NV12 row.
+            #[target_feature(enable = $feature)]
+            pub(super) unsafe fn $name(
+                y: &[u8],
+                uv: &[u8],
+                dest: &mut [u8],
+                blocks: usize,
+                rgba: bool,
+                coefficients: &YuvCoefficients,
+            ) {
+                let block_size = BLOCK_PIXELS * pixel_size(rgba);
+                for block in 0..blocks {
+                    let (y, u, v) = load_nv12(
+                        y.as_ptr().add(block * BLOCK_PIXELS),
+                        uv.as_ptr().add(block * BLOCK_PIXELS),
+                    );
+                    let (r, g, b) = $convert(y, u, v, coefficients);
+                    store(r, g, b, dest.as_mut_ptr().add(block * block_size), rgba);
+                }
+            }
+        };
+    }
+
+    yuyv_kernel!(yuyv_sse41, "sse4.1", convert_sse41);
+    yuyv_kernel!(yuyv_avx2, "avx2", convert_avx2);
+    nv12_kernel!(nv12_sse41, "sse4.1", convert_sse41);
+    nv12_kernel!(nv12_avx2, "avx2", convert_avx2);
+}
+
+#[cfg(target_arch = "aarch64")]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::many_single_char_names)]
+mod aarch64 {
+    use super::{pixel_size, YuvCoefficients, BLOCK_PIXELS};
+    use std::arch::aarch64::{
+        int16x8_t, uint8x16x3_t, uint8x16x4_t, uint8x8_t, vaddq_s32, vcombine_s16, vcombine_u8,
+        vdupq_n_s16, vdupq_n_s32, vdupq_n_u8, vget_low_s16, vld2_u8, vld4_u8, vmlaq_n_s32,
+        vmovl_high_s16, vmovl_s16, vmovl_u8, vmulq_n_s32, vqmovn_s32, vqmovun_s16,
+        vreinterpretq_s16_u16, vshrq_n_s32, vst3q_u8, vst4q_u8, vsubq_s16, vzip1_u8, vzip2_u8,
+    };
+
+    /// Computes one channel of 8 pixels as `(ky * c + kd * d + ke * e + 128) >> 8`. The saturating narrows are the clamp to 0-255.
+    #[target_feature(enable = "neon")]
+    unsafe fn channel(
+        c: int16x8_t,
+        d: int16x8_t,
+        e: int16x8_t,
+        ky: i32,
+        kd: i32,
+        ke: i32,
+    ) -> uint8x8_t {
+        let round = vdupq_n_s32(128);
+        let low = vshrq_n_s32::<8>(vaddq_s32(
+            vmlaq_n_s32(
+                vmlaq_n_s32(
+                    vmulq_n_s32(vmovl_s16(vget_low_s16(c)), ky),
+                    vmovl_s16(vget_low_s16(d)),
+                    kd,
+                ),
+                vmovl_s16(vget_low_s16(e)),
+                ke,
+            ),
+            round,
+        ));
+        let high = vshrq_n_s32::<8>(vaddq_s32(
+            vmlaq_n_s32(
+                vmlaq_n_s32(vmulq_n_s32(vmovl_high_s16(c), ky), vmovl_high_s16(d), kd),
+                vmovl_high_s16(e),
+                ke,
+            ),
+            round,
+        ));
+        vqmovun_s16(vcombine_s16(vqmovn_s32(low), vqmovn_s32(high)))
+    }
+
+    /// Converts 8 pixels sharing 8 chroma samples to R, G and B.
+    #[target_feature(enable = "neon")]
+    unsafe fn convert(
+        y: uint8x8_t,
+        u: uint8x8_t,
+        v: uint8x8_t,
+        k: &YuvCoefficients,
+    ) -> [uint8x8_t; 3] {
+        let widen = |x: uint8x8_t, offset: i16| {
+            vsubq_s16(vreinterpretq_s16_u16(vmovl_u8(x)), vdupq_n_s16(offset))
+        };
+        // The offset is 0 or 16
+        let c = widen(y, k.y_offset as i16);
+        let d = widen(u, 128);
+        let e = widen(v, 128);
+        [
+            channel(c, d, e, k.y, 0, k.r_v),
+            channel(c, d, e, k.y, -k.g_u, -k.g_v),
+            channel(c, d, e, k.y, k.b_u, 0),
+        ]
+    }
+
+    /// Converts the even and odd pixels of 8 pairs sharing chroma, and writes the 16 pixels to `dest` in order.
+    #[target_feature(enable = "neon")]
+    unsafe fn convert_pairs(
+        even: uint8x8_t,
+        odd: uint8x8_t,
+        u: uint8x8_t,
+        v: uint8x8_t,
+        dest: *mut u8,
+        rgba: bool,
+        k: &YuvCoefficients,
+    ) {
+        let even = convert(even, u, v, k);
+        let odd = convert(odd, u, v, k);
+        let [r, g, b] = [0, 1, 2].map(|channel| {
+            vcombine_u8(
+                vzip1_u8(even[channel], odd[channel]),
+                vzip2_u8(even[channel], odd[channel]),
+            )
+        });
+
+        if rgba {
+            vst4q_u8(dest, uint8x16x4_t(r, g, b, vdupq_n_u8(255)));
+        } else {
+            vst3q_u8(dest, uint8x16x3_t(r, g, b));
+        }
+    }
+
+    /// Converts `blocks` blocks of 16 YUYV pixels.
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn yuyv_neon(
+        data: &[u8],
+        dest: &mut [u8],
+        blocks: usize,
+        rgba: bool,
+        coefficients: &YuvCoefficients,
+    ) {
+        let block_size = BLOCK_PIXELS * pixel_size(rgba);
+        for block in 0..blocks {
+            // Y0 U Y1 V deinterleaves into the even luma, chroma, odd luma and chroma
+            let yuyv = vld4_u8(data.as_ptr().add(block * BLOCK_PIXELS * 2));
+            convert_pairs(
+                yuyv.0,
+                yuyv.2,
+                yuyv.1,
+                yuyv.3,
+                dest.as_mut_ptr().add(block * block_size),
+                rgba,
+                coefficients,
+            );
+        }
+    }
+
+    /// Converts `blocks` blocks of 16 pixels of a NV12 row.
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn nv12_neon(
+        y: &[u8],
+        uv: &[u8],
+        dest: &mut [u8],
+        blocks: usize,
+        rgba: bool,
+        coefficients: &YuvCoefficients,
+    ) {
+        let block_size = BLOCK_PIXELS * pixel_size(rgba);
+        for block in 0..blocks {
+            let luma = vld2_u8(y.as_ptr().add(block * BLOCK_PIXELS));
+            let chroma = vld2_u8(uv.as_ptr().add(block * BLOCK_PIXELS));
+            convert_pairs(
+                luma.0,
+                luma.1,
+                chroma.0,
+                chroma.1,
+                dest.as_mut_ptr().add(block * block_size),
+                rgba,
+                coefficients,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ColorRange, ColorSpace, Colorimetry};
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    /// Pixel counts covering no blocks, partial blocks and several whole blocks with a remainder.
+    const LENGTHS: [usize; 9] = [0, 2, 14, 16, 18, 32, 46, 64, 254];
+
+    const CASES: usize = 64;
+
+    fn colorimetries() -> impl Iterator<Item = Colorimetry> {
+        [ColorSpace::Bt601, ColorSpace::Bt709, ColorSpace::Bt2020]
+            .into_iter()
+            .flat_map(|space| {
+                [ColorRange::Limited, ColorRange::Full]
+                    .into_iter()
+                    .map(move |range| Colorimetry::new(space, range))
+            })
+    }
+
+    fn random_bytes(rng: &mut SmallRng, len: usize) -> Vec<u8> {
+        (0..len).map(|_| rng.gen()).collect()
+    }
+
+    fn simd_available() -> bool {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            is_x86_feature_detected!("sse4.1")
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            std::arch::is_aarch64_feature_detected!("neon")
+        }
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            false
+        }
+    }
+
+    fn scalar_pixel(colorimetry: Colorimetry, y: u8, u: u8, v: u8, rgba: bool) -> Vec<u8> {
+        let rgb = colorimetry.yuv_to_rgb(i32::from(y), i32::from(u), i32::from(v));
+        if rgba {
+            vec![rgb[0], rgb[1], rgb[2], 255]
+        } else {
+            rgb.to_vec()
+        }
+    }
+
+    fn scalar_yuyv(colorimetry: Colorimetry, data: &[u8], rgba: bool) -> Vec<u8> {
+        data.chunks_exact(4)
+            .flat_map(|yuyv| {
+                let mut pair = scalar_pixel(colorimetry, yuyv[0], yuyv[1], yuyv[3], rgba);
+                pair.extend(scalar_pixel(colorimetry, yuyv[2], yuyv[1], yuyv[3], rgba));
+                pair
+            })
+            .collect()
+    }
+
+    fn scalar_nv12(colorimetry: Colorimetry, y: &[u8], uv: &[u8], rgba: bool) -> Vec<u8> {
+        y.iter()
+            .enumerate()
+            .flat_map(|(i, y)| {
+                let chroma = i / 2 * 2;
+                scalar_pixel(colorimetry, *y, uv[chroma], uv[chroma + 1], rgba)
+            })
+            .collect()
+    }
+
+    type YuyvKernel = unsafe fn(&[u8], &mut [u8], usize, bool, &YuvCoefficients);
+    type Nv12Kernel = unsafe fn(&[u8], &[u8], &mut [u8], usize, bool, &YuvCoefficients);
+
+    /// Every kernel this CPU supports, including those the dispatchers pass over for a wider one.
+    fn kernels() -> Vec<(&'static str, YuyvKernel, Nv12Kernel)> {
+        #[allow(unused_mut)]
+        let mut kernels: Vec<(&'static str, YuyvKernel, Nv12Kernel)> = Vec::new();
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("sse4.1") {
+                kernels.push(("sse4.1", x86::yuyv_sse41, x86::nv12_sse41));
+            }
+            if is_x86_feature_detected!("avx2") {
+                kernels.push(("avx2", x86::yuyv_avx2, x86::nv12_avx2));
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                kernels.push(("neon", aarch64::yuyv_neon, aarch64::nv12_neon));
+            }
+        }
+        kernels
+    }
+
+    /// Checks that the first `pixels` pixels of `dest` are `expected`, and that everything past them was not written.
+    fn assert_converted(dest: &[u8], expected: &[u8], pixels: usize, rgba: bool, what: &str) {
+        let converted = pixels * pixel_size(rgba);
+        assert_eq!(dest[..converted], expected[..converted], "{what}");
+        assert!(dest[converted..].iter().all(|byte| *byte == 0), "{what}");
+    }
+
+    #[test]
+    fn yuyv_matches_scalar() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        for colorimetry in colorimetries() {
+            for rgba in [false, true] {
+                for pixels in LENGTHS {
+                    for _ in 0..CASES {
+                        let data = random_bytes(&mut rng, pixels * 2);
+                        let expected = scalar_yuyv(colorimetry, &data, rgba);
+
+                        let mut dest = vec![0; pixels * pixel_size(rgba)];
+                        let converted =
+                            yuyv422_to_rgb(&data, &mut dest, rgba, colorimetry.coefficients());
+                        assert_eq!(converted % (BLOCK_PIXELS * 2), 0);
+                        if simd_available() {
+                            assert_eq!(converted / 2, pixels - pixels % BLOCK_PIXELS);
+                        }
+
+                        let what =
+                            format!("{colorimetry:?} rgba {rgba}, {pixels} pixels: {data:?}");
+                        assert_converted(&dest, &expected, converted / 2, rgba, &what);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nv12_matches_scalar() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        for colorimetry in colorimetries() {
+            for rgba in [false, true] {
+                for pixels in LENGTHS {
+                    for _ in 0..CASES {
+                        let y = random_bytes(&mut rng, pixels);
+                        let uv = random_bytes(&mut rng, pixels);
+                        let expected = scalar_nv12(colorimetry, &y, &uv, rgba);
+
+                        let mut dest = vec![0; pixels * pixel_size(rgba)];
+                        let converted =
+                            nv12_row_to_rgb(&y, &uv, &mut dest, rgba, colorimetry.coefficients());
+                        assert_eq!(converted % BLOCK_PIXELS, 0);
+                        if simd_available() {
+                            assert_eq!(converted, pixels - pixels % BLOCK_PIXELS);
+                        }
+
+                        let what =
+                            format!("{colorimetry:?} rgba {rgba}, {pixels} pixels: {y:?} {uv:?}");
+                        assert_converted(&dest, &expected, converted, rgba, &what);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn extremes_match_scalar() {
+        // Every combination of the values at and just past the ends of both ranges, where the clamps matter
+        let values = [
+            0, 1, 15, 16, 17, 127, 128, 129, 235, 236, 239, 240, 241, 254, 255,
+        ];
+        let mut triples = Vec::new();
+        for y in values {
+            for u in values {
+                for v in values {
+                    triples.push((y, u, v));
+                }
+            }
+        }
+        for colorimetry in colorimetries() {
+            for rgba in [false, true] {
+                let data = triples
+                    .iter()
+                    .flat_map(|(y, u, v)| [*y, *u, *y, *v])
+                    .collect::<Vec<u8>>();
+                let expected = scalar_yuyv(colorimetry, &data, rgba);
+                let mut dest = vec![0; expected.len()];
+                let converted = yuyv422_to_rgb(&data, &mut dest, rgba, colorimetry.coefficients());
+                let what = format!("{colorimetry:?} rgba {rgba}");
+                assert_converted(&dest, &expected, converted / 2, rgba, &what);
+
+                let blocks = triples.len() * 2 / BLOCK_PIXELS;
+                for (name, yuyv, _) in kernels() {
+                    let mut dest = vec![0; expected.len()];
+                    // SAFETY: the CPU supports the kernel, and the block count is bounded by both buffers
+                    unsafe { yuyv(&data, &mut dest, blocks, rgba, &colorimetry.coefficients()) };
+                    let what = format!("{name}, {colorimetry:?} rgba {rgba}");
+                    assert_converted(&dest, &expected, blocks * BLOCK_PIXELS, rgba, &what);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_kernel_matches_scalar() {
+        let mut rng = SmallRng::seed_from_u64(3);
+        for (name, yuyv, nv12) in kernels() {
+            for colorimetry in colorimetries() {
+                let coefficients = colorimetry.coefficients();
+                for rgba in [false, true] {
+                    for blocks in [1, 2, 5] {
+                        let pixels = blocks * BLOCK_PIXELS;
+                        for _ in 0..CASES {
+                            let data = random_bytes(&mut rng, pixels * 2);
+                            let mut dest = vec![0; pixels * pixel_size(rgba)];
+                            // SAFETY: the CPU supports the kernel, and the buffers hold exactly `blocks` blocks
+                            unsafe { yuyv(&data, &mut dest, blocks, rgba, &coefficients) };
+                            let what =
+                                format!("{name} YUYV, {colorimetry:?} rgba {rgba}: {data:?}");
+                            assert_eq!(dest, scalar_yuyv(colorimetry, &data, rgba), "{what}");
+
+                            let y = random_bytes(&mut rng, pixels);
+                            let uv = random_bytes(&mut rng, pixels);
+                            let mut dest = vec![0; pixels * pixel_size(rgba)];
+                            // SAFETY: the CPU supports the kernel, and the buffers hold exactly `blocks` blocks
+                            unsafe { nv12(&y, &uv, &mut dest, blocks, rgba, &coefficients) };
+                            let what =
+                                format!("{name} NV12, {colorimetry:?} rgba {rgba}: {y:?} {uv:?}");
+                            assert_eq!(dest, scalar_nv12(colorimetry, &y, &uv, rgba), "{what}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
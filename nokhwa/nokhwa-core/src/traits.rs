@@ -22,7 +22,7 @@ use crate::{
         KnownCameraControl, Resolution,
     },
 };
-use std::{borrow::Cow, collections::HashMap};
+use std::{borrow::Cow, collections::HashMap, time::Duration};
 #[cfg(feature = "wgpu-types")]
 use wgpu::{
     Device as WgpuDevice, Extent3d, ImageCopyTexture, ImageDataLayout, Queue as WgpuQueue,
@@ -165,15 +165,27 @@ pub trait CaptureBackendTrait {
     /// If the backend fails to get the frame (e.g. already taken, busy, doesn't exist anymore), or [`open_stream()`](CaptureBackendTrait::open_stream()) has not been called yet, this will error.
     fn frame_raw(&mut self) -> Result<Cow<[u8]>, NokhwaError>;
 
+    /// The current time on the clock the [`Buffer::timestamp()`]s of this backend are taken on, or `None` if that clock is
+    /// unknown or cannot be read. Subtracting a frame's timestamp from it right after the frame was returned gives its
+    /// capture-to-delivery latency.
+    #[must_use]
+    fn timestamp_clock(&self) -> Option<Duration> {
+        None
+    }
+
     /// The minimum buffer size needed to write the current frame. If `alpha` is true, it will instead return the minimum size of the buffer with an alpha channel as well.
-    /// This assumes that you are decoding to RGB/RGBA for [`FrameFormat::MJPEG`] or [`FrameFormat::YUYV`] and Luma8/LumaA8 for [`FrameFormat::GRAY`]
+    /// This assumes that you are decoding to RGB/RGBA for color formats such as [`FrameFormat::MJPEG`] or [`FrameFormat::YUYV`] and Luma8/LumaA8 for grayscale and depth formats such as [`FrameFormat::GRAY`]
     #[must_use]
     fn decoded_buffer_size(&self, alpha: bool) -> usize {
         let cfmt = self.camera_format();
         let resolution = cfmt.resolution();
         let pxwidth = match cfmt.format() {
-            FrameFormat::MJPEG | FrameFormat::YUYV | FrameFormat::RAWRGB | FrameFormat::NV12 => 3,
-            FrameFormat::GRAY => 1,
+            FrameFormat::GRAY
+            | FrameFormat::GRAY10
+            | FrameFormat::GRAY12
+            | FrameFormat::GRAY16
+            | FrameFormat::Z16 => 1,
+            _ => 3,
         };
         if alpha {
             return (resolution.width() * resolution.height() * (pxwidth + 1)) as usize;
@@ -250,4 +262,20 @@ where
     }
 }
 
-pub trait VirtualBackendTrait {}
+/// A source of generated frames, which a virtual camera serves in place of captured ones.
+///
+/// Implementations should be deterministic: the same `sequence` and `timestamp` must always render the same frame,
+/// so that anything computed from the frames can be reproduced and compared against ground truth.
+pub trait VirtualBackendTrait {
+    /// A short human readable name of the source, used as the name of the virtual camera.
+    fn name(&self) -> String;
+
+    /// Renders the frame with the sequence number `sequence`, shown `timestamp` after the stream started.
+    /// The frame is RGB888 (R,G,B,R,G,B,...) and must be exactly `resolution` in size.
+    fn render_frame(
+        &mut self,
+        sequence: u64,
+        timestamp: Duration,
+        resolution: Resolution,
+    ) -> Vec<u8>;
+}
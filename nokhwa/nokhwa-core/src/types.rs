@@ -1,4 +1,4 @@
-use crate::{error::NokhwaError, pixel_format::FormatDecoder};
+use crate::{demosaic::Mosaic, error::NokhwaError, pixel_format::FormatDecoder, simd};
 #[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
 use std::{
@@ -287,10 +287,19 @@ impl TryFrom<CameraIndex> for usize {
 
 /// Describes a frame format (i.e. how the bytes themselves are encoded). Often called `FourCC`.
 /// - YUYV is a mathematical color space. You can read more [here.](https://en.wikipedia.org/wiki/YCbCr)
-/// - NV12 is same as above. Note that a partial compression (e.g. [16, 235] may be coerced to [0, 255].
+/// - UYVY and YVYU are YUYV with the bytes of each macropixel in a different order.
+/// - NV12 is same as above. How its values map to RGB is described by the [`Colorimetry`] of the [`CameraFormat`].
+/// - NV21 is NV12 with the U and V samples swapped. I420 (`YU12`) is planar, with a full U plane followed by a full V plane.
 /// - MJPEG is a motion-jpeg compressed frame, it allows for high frame rates.
 /// - GRAY is a grayscale image format, usually for specialized cameras such as IR Cameras.
-/// - RAWRGB is a Raw RGB888 format.
+/// - GRAY10, GRAY12 and GRAY16 are 10, 12 and 16-bit grayscale formats (`Y10`, `Y12` and `Y16`), stored little-endian in the
+///   low bits of 16-bit words. Decode them with [`Luma16Format`](crate::pixel_format::Luma16Format) to keep every bit.
+/// - Z16 is a 16-bit little-endian depth map (`Z16`), where 0 means no measurement. The unit of the samples depends on the
+///   camera, see [`buf_z16_to_depth`]. It decodes like GRAY16.
+/// - RAWRGB is a Raw RGB888 format. RAWBGR is the same, with the red and blue bytes swapped (`BGR3`).
+/// - RGB565 is a 16-bit little-endian RGB format (`RGBP`), with 5 bits of red, 6 bits of green and 5 bits of blue.
+/// - Bayer8 and Bayer10 are the raw output of a color sensor, one sample per pixel with the colors arranged in a [`BayerPattern`].
+///   10-bit samples are stored little-endian in the low bits of 16-bit words.
 #[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum FrameFormat {
@@ -299,6 +308,41 @@ pub enum FrameFormat {
     NV12,
     GRAY,
     RAWRGB,
+    UYVY,
+    YVYU,
+    NV21,
+    I420,
+    RAWBGR,
+    RGB565,
+    GRAY10,
+    GRAY12,
+    GRAY16,
+    Z16,
+    Bayer8(BayerPattern),
+    Bayer10(BayerPattern),
+}
+
+impl FrameFormat {
+    /// The number of significant bits of each sample: 10, 12 or 16 for [`FrameFormat::GRAY10`], [`FrameFormat::GRAY12`],
+    /// [`FrameFormat::GRAY16`], [`FrameFormat::Z16`] and [`FrameFormat::Bayer10`], 8 for every other format.
+    #[must_use]
+    pub const fn bit_depth(&self) -> u32 {
+        match self {
+            FrameFormat::GRAY10 | FrameFormat::Bayer10(_) => 10,
+            FrameFormat::GRAY12 => 12,
+            FrameFormat::GRAY16 | FrameFormat::Z16 => 16,
+            _ => 8,
+        }
+    }
+
+    /// The [`BayerPattern`] of a Bayer format, or `None` for every other format.
+    #[must_use]
+    pub const fn bayer_pattern(&self) -> Option<BayerPattern> {
+        match self {
+            FrameFormat::Bayer8(pattern) | FrameFormat::Bayer10(pattern) => Some(*pattern),
+            _ => None,
+        }
+    }
 }
 
 impl Display for FrameFormat {
@@ -319,6 +363,42 @@ impl Display for FrameFormat {
             FrameFormat::NV12 => {
                 write!(f, "NV12")
             }
+            FrameFormat::UYVY => {
+                write!(f, "UYVY")
+            }
+            FrameFormat::YVYU => {
+                write!(f, "YVYU")
+            }
+            FrameFormat::NV21 => {
+                write!(f, "NV21")
+            }
+            FrameFormat::I420 => {
+                write!(f, "I420")
+            }
+            FrameFormat::RAWBGR => {
+                write!(f, "RAWBGR")
+            }
+            FrameFormat::RGB565 => {
+                write!(f, "RGB565")
+            }
+            FrameFormat::GRAY10 => {
+                write!(f, "GRAY10")
+            }
+            FrameFormat::GRAY12 => {
+                write!(f, "GRAY12")
+            }
+            FrameFormat::GRAY16 => {
+                write!(f, "GRAY16")
+            }
+            FrameFormat::Z16 => {
+                write!(f, "Z16")
+            }
+            FrameFormat::Bayer8(pattern) => {
+                write!(f, "{pattern}8")
+            }
+            FrameFormat::Bayer10(pattern) => {
+                write!(f, "{pattern}10")
+            }
         }
     }
 }
@@ -332,10 +412,28 @@ impl FromStr for FrameFormat {
             "GRAY" => Ok(FrameFormat::GRAY),
             "RAWRGB" => Ok(FrameFormat::RAWRGB),
             "NV12" => Ok(FrameFormat::NV12),
-            _ => Err(NokhwaError::StructureError {
-                structure: "FrameFormat".to_string(),
-                error: format!("No match for {s}"),
-            }),
+            "UYVY" => Ok(FrameFormat::UYVY),
+            "YVYU" => Ok(FrameFormat::YVYU),
+            "NV21" => Ok(FrameFormat::NV21),
+            "I420" => Ok(FrameFormat::I420),
+            "RAWBGR" => Ok(FrameFormat::RAWBGR),
+            "RGB565" => Ok(FrameFormat::RGB565),
+            "GRAY10" => Ok(FrameFormat::GRAY10),
+            "GRAY12" => Ok(FrameFormat::GRAY12),
+            "GRAY16" => Ok(FrameFormat::GRAY16),
+            "Z16" => Ok(FrameFormat::Z16),
+            _ => {
+                if let Some(Ok(pattern)) = s.strip_suffix("10").map(str::parse) {
+                    return Ok(FrameFormat::Bayer10(pattern));
+                }
+                if let Some(Ok(pattern)) = s.strip_suffix('8').map(str::parse) {
+                    return Ok(FrameFormat::Bayer8(pattern));
+                }
+                Err(NokhwaError::StructureError {
+                    structure: "FrameFormat".to_string(),
+                    error: format!("No match for {s}"),
+                })
+            }
         }
     }
 }
@@ -349,6 +447,24 @@ pub const fn frame_formats() -> &'static [FrameFormat] {
         FrameFormat::NV12,
         FrameFormat::GRAY,
         FrameFormat::RAWRGB,
+        FrameFormat::UYVY,
+        FrameFormat::YVYU,
+        FrameFormat::NV21,
+        FrameFormat::I420,
+        FrameFormat::RAWBGR,
+        FrameFormat::RGB565,
+        FrameFormat::GRAY10,
+        FrameFormat::GRAY12,
+        FrameFormat::GRAY16,
+        FrameFormat::Z16,
+        FrameFormat::Bayer8(BayerPattern::RGGB),
+        FrameFormat::Bayer8(BayerPattern::BGGR),
+        FrameFormat::Bayer8(BayerPattern::GRBG),
+        FrameFormat::Bayer8(BayerPattern::GBRG),
+        FrameFormat::Bayer10(BayerPattern::RGGB),
+        FrameFormat::Bayer10(BayerPattern::BGGR),
+        FrameFormat::Bayer10(BayerPattern::GRBG),
+        FrameFormat::Bayer10(BayerPattern::GBRG),
     ]
 }
 
@@ -360,9 +476,411 @@ pub const fn color_frame_formats() -> &'static [FrameFormat] {
         FrameFormat::YUYV,
         FrameFormat::NV12,
         FrameFormat::RAWRGB,
+        FrameFormat::UYVY,
+        FrameFormat::YVYU,
+        FrameFormat::NV21,
+        FrameFormat::I420,
+        FrameFormat::RAWBGR,
+        FrameFormat::RGB565,
+        FrameFormat::Bayer8(BayerPattern::RGGB),
+        FrameFormat::Bayer8(BayerPattern::BGGR),
+        FrameFormat::Bayer8(BayerPattern::GRBG),
+        FrameFormat::Bayer8(BayerPattern::GBRG),
+        FrameFormat::Bayer10(BayerPattern::RGGB),
+        FrameFormat::Bayer10(BayerPattern::BGGR),
+        FrameFormat::Bayer10(BayerPattern::GRBG),
+        FrameFormat::Bayer10(BayerPattern::GBRG),
     ]
 }
 
+/// The arrangement of the color filters over a Bayer sensor, named after the colors of its top-left 2x2 block, left to right and top to bottom.
+#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum BayerPattern {
+    RGGB,
+    BGGR,
+    GRBG,
+    GBRG,
+}
+
+impl BayerPattern {
+    /// The RGB channel (0 for red, 1 for green, 2 for blue) of the pixel at `x`, `y`.
+    #[must_use]
+    pub const fn channel_at(self, x: usize, y: usize) -> usize {
+        let (red_x, red_y) = match self {
+            BayerPattern::RGGB => (0, 0),
+            BayerPattern::BGGR => (1, 1),
+            BayerPattern::GRBG => (1, 0),
+            BayerPattern::GBRG => (0, 1),
+        };
+        match (x % 2 == red_x, y % 2 == red_y) {
+            (true, true) => 0,
+            (false, false) => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// How the two missing colors of every pixel of a Bayer frame are interpolated.
+/// - `Bilinear` averages the nearest samples of each color. It is fast, but blurs and adds color fringes along edges.
+/// - `MalvarHeCutler` corrects the bilinear estimate with the gradient of the pixel's own color, which keeps edges much
+///   sharper at about twice the cost.
+#[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum DemosaicMethod {
+    #[default]
+    Bilinear,
+    MalvarHeCutler,
+}
+
+/// How [`ToneMap::apply`] reduces 16-bit luminance, e.g. from [`Luma16Format`](crate::pixel_format::Luma16Format), to 8 bits.
+/// - `Linear` maps `black..=white` linearly to `0..=255`, clipping everything outside. The default keeps the high 8 bits.
+/// - `Percentile` is `Linear` with the black and white points at the `low` and `high` percentiles (0-100) of each frame,
+///   which follows the brightness of the scene at the cost of flicker when it changes.
+/// - `Gamma` brightens the shadows of the full range with the exponent `1 / gamma`, like the transfer curve of most cameras.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ToneMap {
+    Linear { black: u16, white: u16 },
+    Percentile { low: f32, high: f32 },
+    Gamma(f32),
+}
+
+impl ToneMap {
+    /// Reduces the 16-bit `samples` to 8 bits into `dest`.
+    /// # Errors
+    /// If `dest` is not the same length as `samples`, this will error.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn apply(self, samples: &[u16], dest: &mut [u8]) -> Result<(), NokhwaError> {
+        if samples.len() != dest.len() {
+            return Err(NokhwaError::ProcessFrameError {
+                src: FrameFormat::GRAY16,
+                destination: "Luma".to_string(),
+                error: "bad buffer size".to_string(),
+            });
+        }
+
+        let linear = |black: u16, white: u16| {
+            let white = white.max(black);
+            let range = u32::from(white.saturating_sub(black)).max(1);
+            move |sample: u16| {
+                let sample = u32::from(sample.clamp(black, white) - black);
+                ((sample * 255 + range / 2) / range) as u8
+            }
+        };
+
+        match self {
+            ToneMap::Linear { black, white } => {
+                let map = linear(black, white);
+                for (sample, px) in samples.iter().zip(dest.iter_mut()) {
+                    *px = map(*sample);
+                }
+            }
+            ToneMap::Percentile { low, high } => {
+                // The percentiles are found in a histogram of the high 10 bits, the mapping itself uses all 16
+                let mut histogram = [0_usize; 1024];
+                for sample in samples {
+                    histogram[usize::from(*sample >> 6)] += 1;
+                }
+                let bin_at = |percentile: f32| {
+                    let rank =
+                        ((percentile.clamp(0.0, 100.0) / 100.0) * samples.len() as f32) as usize;
+                    let mut seen = 0;
+                    histogram
+                        .iter()
+                        .position(|count| {
+                            seen += count;
+                            seen > rank
+                        })
+                        .unwrap_or(histogram.len() - 1) as u16
+                };
+                let map = linear(bin_at(low) << 6, (bin_at(high) << 6) | 0x3F);
+                for (sample, px) in samples.iter().zip(dest.iter_mut()) {
+                    *px = map(*sample);
+                }
+            }
+            ToneMap::Gamma(gamma) => {
+                // A curve over the high 12 bits is plenty for 8 bits of output
+                let exponent = 1.0 / gamma.max(f32::EPSILON);
+                let curve: Vec<u8> = (0..4096_u16)
+                    .map(|index| {
+                        (f32::from(index) / 4095.0)
+                            .powf(exponent)
+                            .mul_add(255.0, 0.5) as u8
+                    })
+                    .collect();
+                for (sample, px) in samples.iter().zip(dest.iter_mut()) {
+                    *px = curve[usize::from(*sample >> 4)];
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ToneMap {
+    fn default() -> Self {
+        ToneMap::Linear {
+            black: 0,
+            white: u16::MAX,
+        }
+    }
+}
+
+impl Display for ToneMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToneMap::Linear { black, white } => write!(f, "linear:{black}:{white}"),
+            ToneMap::Percentile { low, high } => write!(f, "percentile:{low}:{high}"),
+            ToneMap::Gamma(gamma) => write!(f, "gamma:{gamma}"),
+        }
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = NokhwaError;
+
+    /// Parses the [`Display`] form, e.g. `linear:0:4095`, `percentile:1:99` or `gamma:2.2`. `linear`, `percentile` and
+    /// `gamma` alone are the full range, the 1st to 99th percentile and a gamma of 2.2.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || NokhwaError::StructureError {
+            structure: "ToneMap".to_string(),
+            error: format!("No match for {s}"),
+        };
+
+        let mut parts = s.split(':');
+        let kind = parts.next().unwrap_or_default();
+        let values = parts.collect::<Vec<&str>>();
+        match (kind, values.as_slice()) {
+            ("linear", []) => Ok(ToneMap::default()),
+            ("linear", [black, white]) => Ok(ToneMap::Linear {
+                black: black.parse().map_err(|_| error())?,
+                white: white.parse().map_err(|_| error())?,
+            }),
+            ("percentile", []) => Ok(ToneMap::Percentile {
+                low: 1.0,
+                high: 99.0,
+            }),
+            ("percentile", [low, high]) => Ok(ToneMap::Percentile {
+                low: low.parse().map_err(|_| error())?,
+                high: high.parse().map_err(|_| error())?,
+            }),
+            ("gamma", []) => Ok(ToneMap::Gamma(2.2)),
+            ("gamma", [gamma]) => Ok(ToneMap::Gamma(gamma.parse().map_err(|_| error())?)),
+            _ => Err(error()),
+        }
+    }
+}
+
+impl Display for BayerPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BayerPattern::RGGB => write!(f, "RGGB"),
+            BayerPattern::BGGR => write!(f, "BGGR"),
+            BayerPattern::GRBG => write!(f, "GRBG"),
+            BayerPattern::GBRG => write!(f, "GBRG"),
+        }
+    }
+}
+
+impl FromStr for BayerPattern {
+    type Err = NokhwaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "RGGB" => Ok(BayerPattern::RGGB),
+            "BGGR" => Ok(BayerPattern::BGGR),
+            "GRBG" => Ok(BayerPattern::GRBG),
+            "GBRG" => Ok(BayerPattern::GBRG),
+            _ => Err(NokhwaError::StructureError {
+                structure: "BayerPattern".to_string(),
+                error: format!("No match for {s}"),
+            }),
+        }
+    }
+}
+
+/// The `YCbCr` encoding of a YUV frame, i.e. which standard's luma weights its luma and chroma were derived with.
+/// - `Bt601` is used by SD video, JPEG and most webcams.
+/// - `Bt709` is used by HD video.
+/// - `Bt2020` is used by UHD video.
+#[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ColorSpace {
+    #[default]
+    Bt601,
+    Bt709,
+    Bt2020,
+}
+
+impl Display for ColorSpace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorSpace::Bt601 => write!(f, "BT.601"),
+            ColorSpace::Bt709 => write!(f, "BT.709"),
+            ColorSpace::Bt2020 => write!(f, "BT.2020"),
+        }
+    }
+}
+
+impl FromStr for ColorSpace {
+    type Err = NokhwaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "BT.601" => Ok(ColorSpace::Bt601),
+            "BT.709" => Ok(ColorSpace::Bt709),
+            "BT.2020" => Ok(ColorSpace::Bt2020),
+            _ => Err(NokhwaError::StructureError {
+                structure: "ColorSpace".to_string(),
+                error: format!("No match for {s}"),
+            }),
+        }
+    }
+}
+
+/// The quantization range of a YUV frame.
+/// - `Limited` puts black at 16 and white at 235 (240 for chroma), leaving headroom. Most video uses this.
+/// - `Full` uses all of [0, 255], like JPEG does.
+#[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ColorRange {
+    #[default]
+    Limited,
+    Full,
+}
+
+impl Display for ColorRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorRange::Limited => write!(f, "Limited"),
+            ColorRange::Full => write!(f, "Full"),
+        }
+    }
+}
+
+impl FromStr for ColorRange {
+    type Err = NokhwaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Limited" => Ok(ColorRange::Limited),
+            "Full" => Ok(ColorRange::Full),
+            _ => Err(NokhwaError::StructureError {
+                structure: "ColorRange".to_string(),
+                error: format!("No match for {s}"),
+            }),
+        }
+    }
+}
+
+/// Describes how the values of a YUV frame ([`FrameFormat::YUYV`], [`FrameFormat::NV12`]) map to RGB: its [`ColorSpace`] and [`ColorRange`].
+///
+/// The default is limited range BT.601, which is what webcams that do not say otherwise send. Using the wrong one
+/// shifts the brightness and saturation of the decoded image. Other frame formats ignore this.
+#[derive(Copy, Clone, Debug, Default, Hash, Ord, PartialOrd, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Colorimetry {
+    space: ColorSpace,
+    range: ColorRange,
+}
+
+impl Colorimetry {
+    /// Construct a new [`Colorimetry`]
+    #[must_use]
+    pub const fn new(space: ColorSpace, range: ColorRange) -> Self {
+        Colorimetry { space, range }
+    }
+
+    /// Get the [`ColorSpace`].
+    #[must_use]
+    pub fn space(self) -> ColorSpace {
+        self.space
+    }
+
+    /// Get the [`ColorRange`].
+    #[must_use]
+    pub fn range(self) -> ColorRange {
+        self.range
+    }
+
+    /// Convert a `YCbCr` 4:4:4 pixel of this colorimetry to RGB888.
+    ///
+    /// For the default colorimetry this is the same as [`yuyv444_to_rgb`].
+    #[allow(clippy::many_single_char_names)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[must_use]
+    #[inline]
+    pub fn yuv_to_rgb(self, y: i32, u: i32, v: i32) -> [u8; 3] {
+        let k = self.coefficients();
+        let c = (y - k.y_offset) * k.y;
+        let d = u - 128;
+        let e = v - 128;
+        let r = ((c + k.r_v * e + 128) >> 8).clamp(0, 255) as u8;
+        let g = ((c - k.g_u * d - k.g_v * e + 128) >> 8).clamp(0, 255) as u8;
+        let b = ((c + k.b_u * d + 128) >> 8).clamp(0, 255) as u8;
+        [r, g, b]
+    }
+
+    /// Expands a Y sample of this colorimetry to the full range luminance of [`FrameFormat::GRAY`], the same way
+    /// [`yuv_to_rgb`](Self::yuv_to_rgb) does for a neutral pixel.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[must_use]
+    #[inline]
+    pub fn y_to_luma(self, y: u8) -> u8 {
+        let k = self.coefficients();
+        ((k.y * (i32::from(y) - k.y_offset) + 128) >> 8).clamp(0, 255) as u8
+    }
+
+    /// The fixed point coefficients of the `YCbCr` to RGB equation.
+    pub(crate) fn coefficients(self) -> YuvCoefficients {
+        // Derived from each standard's luma weights, scaled by 256 and by 255/219 (luma) and 255/224 (chroma) for limited range
+        let (r_v, g_u, g_v, b_u) = match (self.space, self.range) {
+            (ColorSpace::Bt601, ColorRange::Limited) => (409, 100, 208, 516),
+            (ColorSpace::Bt601, ColorRange::Full) => (359, 88, 183, 454),
+            (ColorSpace::Bt709, ColorRange::Limited) => (459, 55, 136, 541),
+            (ColorSpace::Bt709, ColorRange::Full) => (403, 48, 120, 475),
+            (ColorSpace::Bt2020, ColorRange::Limited) => (430, 48, 167, 548),
+            (ColorSpace::Bt2020, ColorRange::Full) => (377, 42, 146, 482),
+        };
+        let (y_offset, y) = match self.range {
+            ColorRange::Limited => (16, 298),
+            ColorRange::Full => (0, 256),
+        };
+
+        YuvCoefficients {
+            y_offset,
+            y,
+            r_v,
+            g_u,
+            g_v,
+            b_u,
+        }
+    }
+}
+
+impl Display for Colorimetry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} Range", self.space, self.range)
+    }
+}
+
+/// The `YCbCr` to RGB equation of a [`Colorimetry`], with 8 fractional bits:
+/// `R = (y * (Y - y_offset) + r_v * (V - 128) + 128) >> 8` and so on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct YuvCoefficients {
+    pub(crate) y_offset: i32,
+    pub(crate) y: i32,
+    pub(crate) r_v: i32,
+    pub(crate) g_u: i32,
+    pub(crate) g_v: i32,
+    pub(crate) b_u: i32,
+}
+
 /// Describes a Resolution.
 /// This struct consists of a Width and a Height value (x,y). <br>
 /// Note: the [`Ord`] implementation of this struct is flipped from highest to lowest.
@@ -450,13 +968,17 @@ impl Ord for Resolution {
 }
 
 /// This is a convenience struct that holds all information about the format of a webcam stream.
-/// It consists of a [`Resolution`], [`FrameFormat`], and a frame rate(u8).
+/// It consists of a [`Resolution`], [`FrameFormat`], a frame rate(u8), and the [`Colorimetry`] of YUV formats.
+///
+/// Backends that can tell fill in the [`Colorimetry`] of the current format, otherwise it is the default.
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct CameraFormat {
     resolution: Resolution,
     format: FrameFormat,
     frame_rate: u32,
+    #[cfg_attr(feature = "serialize", serde(default))]
+    colorimetry: Colorimetry,
 }
 
 impl CameraFormat {
@@ -467,6 +989,7 @@ impl CameraFormat {
             resolution,
             format,
             frame_rate,
+            colorimetry: Colorimetry::default(),
         }
     }
 
@@ -480,9 +1003,17 @@ impl CameraFormat {
             },
             format,
             frame_rate: fps,
+            colorimetry: Colorimetry::default(),
         }
     }
 
+    /// Sets the [`Colorimetry`] of this [`CameraFormat`].
+    #[must_use]
+    pub fn with_colorimetry(mut self, colorimetry: Colorimetry) -> Self {
+        self.colorimetry = colorimetry;
+        self
+    }
+
     /// Get the resolution of the current [`CameraFormat`]
     #[must_use]
     pub fn resolution(&self) -> Resolution {
@@ -527,6 +1058,17 @@ impl CameraFormat {
     pub fn set_format(&mut self, format: FrameFormat) {
         self.format = format;
     }
+
+    /// Get the [`CameraFormat`]'s [`Colorimetry`].
+    #[must_use]
+    pub fn colorimetry(&self) -> Colorimetry {
+        self.colorimetry
+    }
+
+    /// Set the [`CameraFormat`]'s [`Colorimetry`].
+    pub fn set_colorimetry(&mut self, colorimetry: Colorimetry) {
+        self.colorimetry = colorimetry;
+    }
 }
 
 impl Default for CameraFormat {
@@ -535,6 +1077,7 @@ impl Default for CameraFormat {
             resolution: Resolution::new(640, 480),
             format: FrameFormat::MJPEG,
             frame_rate: 30,
+            colorimetry: Colorimetry::default(),
         }
     }
 }
@@ -1377,6 +1920,9 @@ impl Display for ControlValueSetter {
 /// - `GStreamer` - ***DEPRECATED*** Uses `GStreamer` RTP to capture. Platform agnostic.
 /// - `Network` - Uses `OpenCV` to capture from an IP.
 /// - `Browser` - Uses browser APIs to capture from a webcam.
+/// - `Replay` - Plays back a session recorded to disk. Platform agnostic.
+/// - `MediaFile` - Reads from a directory of images or a Motion-JPEG AVI file. Platform agnostic.
+/// - `Virtual` - Generates synthetic test patterns. Platform agnostic.
 #[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum ApiBackend {
@@ -1389,6 +1935,9 @@ pub enum ApiBackend {
     GStreamer,
     Network,
     Browser,
+    Replay,
+    MediaFile,
+    Virtual,
 }
 
 impl Display for ApiBackend {
@@ -1606,17 +2155,21 @@ pub fn yuyv422_predicted_size(size: usize, rgba: bool) -> usize {
 // The YUY2(YUYV) format is a 16 bit format. We read 4 bytes at a time to get 6 bytes of RGB888.
 // First, the YUY2 is converted to YCbCr 4:4:4 (4:2:2 -> 4:4:4)
 // then it is converted to 6 bytes (2 pixels) of RGB888
-/// Converts a YUYV 4:2:2 datastream to a RGB888 Stream. [For further reading](https://en.wikipedia.org/wiki/YUV#Converting_between_Y%E2%80%B2UV_and_RGB)
+/// Converts a YUYV 4:2:2 datastream of the given [`Colorimetry`] to a RGB888 Stream. [For further reading](https://en.wikipedia.org/wiki/YUV#Converting_between_Y%E2%80%B2UV_and_RGB)
 /// # Errors
 /// This may error when the data stream size is not divisible by 4, a i32 -> u8 conversion fails, or it fails to read from a certain index.
 #[inline]
-pub fn yuyv422_to_rgb(data: &[u8], rgba: bool) -> Result<Vec<u8>, NokhwaError> {
+pub fn yuyv422_to_rgb(
+    data: &[u8],
+    rgba: bool,
+    colorimetry: Colorimetry,
+) -> Result<Vec<u8>, NokhwaError> {
     let pixel_size = if rgba { 4 } else { 3 };
     // yuyv yields 2 3-byte pixels per yuyv chunk
     let rgb_buf_size = (data.len() / 4) * (2 * pixel_size);
 
     let mut dest = vec![0; rgb_buf_size];
-    buf_yuyv422_to_rgb(data, &mut dest, rgba)?;
+    buf_yuyv422_to_rgb(data, &mut dest, rgba, colorimetry)?;
 
     Ok(dest)
 }
@@ -1625,7 +2178,12 @@ pub fn yuyv422_to_rgb(data: &[u8], rgba: bool) -> Result<Vec<u8>, NokhwaError> {
 /// # Errors
 /// If the stream is invalid YUYV, or the destination buffer is not large enough, this will error.
 #[inline]
-pub fn buf_yuyv422_to_rgb(data: &[u8], dest: &mut [u8], rgba: bool) -> Result<(), NokhwaError> {
+pub fn buf_yuyv422_to_rgb(
+    data: &[u8],
+    dest: &mut [u8],
+    rgba: bool,
+    colorimetry: Colorimetry,
+) -> Result<(), NokhwaError> {
     if data.len() % 4 != 0 {
         return Err(NokhwaError::ProcessFrameError {
             src: FrameFormat::YUYV,
@@ -1647,6 +2205,13 @@ pub fn buf_yuyv422_to_rgb(data: &[u8], dest: &mut [u8], rgba: bool) -> Result<()
         });
     }
 
+    // Convert the bulk of the stream with SIMD, the scalar code below handles what is left
+    let coefficients = colorimetry.coefficients();
+    let converted = simd::yuyv422_to_rgb(data, dest, rgba, coefficients);
+    let data = &data[converted..];
+    let dest = &mut dest[(converted / 4) * (2 * pixel_size)..];
+    let rgb_buf_size = dest.len();
+
     let iter = data.chunks_exact(4);
 
     if rgba {
@@ -1656,9 +2221,9 @@ pub fn buf_yuyv422_to_rgb(data: &[u8], dest: &mut [u8], rgba: bool) -> Result<()
                 let u = i32::from(yuyv[1]);
                 let y2 = i32::from(yuyv[2]);
                 let v = i32::from(yuyv[3]);
-                let pixel1 = yuyv444_to_rgba(y1, u, v);
-                let pixel2 = yuyv444_to_rgba(y2, u, v);
-                [pixel1, pixel2]
+                let [r1, g1, b1] = colorimetry.yuv_to_rgb(y1, u, v);
+                let [r2, g2, b2] = colorimetry.yuv_to_rgb(y2, u, v);
+                [[r1, g1, b1, 255], [r2, g2, b2, 255]]
             })
             .flatten();
         for i in dest.iter_mut().take(rgb_buf_size) {
@@ -1680,8 +2245,8 @@ pub fn buf_yuyv422_to_rgb(data: &[u8], dest: &mut [u8], rgba: bool) -> Result<()
                 let u = i32::from(yuyv[1]);
                 let y2 = i32::from(yuyv[2]);
                 let v = i32::from(yuyv[3]);
-                let pixel1 = yuyv444_to_rgb(y1, u, v);
-                let pixel2 = yuyv444_to_rgb(y2, u, v);
+                let pixel1 = colorimetry.yuv_to_rgb(y1, u, v);
+                let pixel2 = colorimetry.yuv_to_rgb(y2, u, v);
                 [pixel1, pixel2]
             })
             .flatten();
@@ -1704,20 +2269,14 @@ pub fn buf_yuyv422_to_rgb(data: &[u8], dest: &mut [u8], rgba: bool) -> Result<()
 }
 
 // equation from https://en.wikipedia.org/wiki/YUV#Converting_between_Y%E2%80%B2UV_and_RGB
-/// Convert `YCbCr` 4:4:4 to a RGB888. [For further reading](https://en.wikipedia.org/wiki/YUV#Converting_between_Y%E2%80%B2UV_and_RGB)
+/// Convert limited range BT.601 `YCbCr` 4:4:4 to a RGB888. [For further reading](https://en.wikipedia.org/wiki/YUV#Converting_between_Y%E2%80%B2UV_and_RGB)
+///
+/// See [`Colorimetry::yuv_to_rgb`] for other colorimetries.
 #[allow(clippy::many_single_char_names)]
-#[allow(clippy::cast_possible_truncation)]
-#[allow(clippy::cast_sign_loss)]
 #[must_use]
 #[inline]
 pub fn yuyv444_to_rgb(y: i32, u: i32, v: i32) -> [u8; 3] {
-    let c298 = (y - 16) * 298;
-    let d = u - 128;
-    let e = v - 128;
-    let r = ((c298 + 409 * e + 128) >> 8) as u8;
-    let g = ((c298 - 100 * d - 208 * e + 128) >> 8) as u8;
-    let b = ((c298 + 516 * d + 128) >> 8) as u8;
-    [r, g, b]
+    Colorimetry::default().yuv_to_rgb(y, u, v)
 }
 
 // equation from https://en.wikipedia.org/wiki/YUV#Converting_between_Y%E2%80%B2UV_and_RGB
@@ -1740,10 +2299,11 @@ pub fn nv12_to_rgb(
     resolution: Resolution,
     data: &[u8],
     rgba: bool,
+    colorimetry: Colorimetry,
 ) -> Result<Vec<u8>, NokhwaError> {
     let pxsize = if rgba { 4 } else { 3 };
     let mut dest = vec![0; (pxsize * resolution.width() * resolution.height()) as usize];
-    buf_nv12_to_rgb(resolution, data, &mut dest, rgba)?;
+    buf_nv12_to_rgb(resolution, data, &mut dest, rgba, colorimetry)?;
     Ok(dest)
 }
 
@@ -1760,6 +2320,7 @@ pub fn buf_nv12_to_rgb(
     data: &[u8],
     out: &mut [u8],
     rgba: bool,
+    colorimetry: Colorimetry,
 ) -> Result<(), NokhwaError> {
     if resolution.width() % 2 != 0 || resolution.height() % 2 != 0 {
         return Err(NokhwaError::ProcessFrameError {
@@ -1793,9 +2354,25 @@ pub fn buf_nv12_to_rgb(
 
     let width_usize = resolution.width() as usize;
     // let height_usize = resolution.height() as usize;
+    let coefficients = colorimetry.coefficients();
 
     for (hidx, horizontal_row) in data[0..y_section].chunks_exact(width_usize).enumerate() {
-        for (cidx, column) in horizontal_row.chunks_exact(2).enumerate() {
+        // Convert the bulk of the row with SIMD, the scalar code below handles what is left
+        let uv_start = y_section + ((hidx / 2) * width_usize);
+        let row_start = hidx * width_usize * rgba_size;
+        let converted = simd::nv12_row_to_rgb(
+            horizontal_row,
+            &data[uv_start..uv_start + width_usize],
+            &mut out[row_start..row_start + width_usize * rgba_size],
+            rgba,
+            coefficients,
+        );
+
+        for (cidx, column) in horizontal_row
+            .chunks_exact(2)
+            .enumerate()
+            .skip(converted / 2)
+        {
             let u = data[(y_section) + ((hidx / 2) * width_usize) + (cidx * 2)];
             let v = data[(y_section) + ((hidx / 2) * width_usize) + (cidx * 2) + 1];
 
@@ -1804,8 +2381,12 @@ pub fn buf_nv12_to_rgb(
             let base_index = (hidx * width_usize * rgba_size) + cidx * rgba_size * 2;
 
             if rgba {
-                let px0 = yuyv444_to_rgba(y0 as i32, u as i32, v as i32);
-                let px1 = yuyv444_to_rgba(y1 as i32, u as i32, v as i32);
+                let [r0, g0, b0] =
+                    colorimetry.yuv_to_rgb(i32::from(y0), i32::from(u), i32::from(v));
+                let [r1, g1, b1] =
+                    colorimetry.yuv_to_rgb(i32::from(y1), i32::from(u), i32::from(v));
+                let px0 = [r0, g0, b0, 255];
+                let px1 = [r1, g1, b1, 255];
 
                 out[base_index] = px0[0];
                 out[base_index + 1] = px0[1];
@@ -1816,8 +2397,8 @@ pub fn buf_nv12_to_rgb(
                 out[base_index + 6] = px1[2];
                 out[base_index + 7] = px1[3];
             } else {
-                let px0 = yuyv444_to_rgb(y0 as i32, u as i32, v as i32);
-                let px1 = yuyv444_to_rgb(y1 as i32, u as i32, v as i32);
+                let px0 = colorimetry.yuv_to_rgb(i32::from(y0), i32::from(u), i32::from(v));
+                let px1 = colorimetry.yuv_to_rgb(i32::from(y1), i32::from(u), i32::from(v));
 
                 out[base_index] = px0[0];
                 out[base_index + 1] = px0[1];
@@ -1831,3 +2412,1085 @@ pub fn buf_nv12_to_rgb(
 
     Ok(())
 }
+
+/// Byte offsets of the first Y, U, second Y and V sample within a macropixel of a packed YUV 4:2:2 format.
+fn packed_yuv422_layout(format: FrameFormat) -> Option<[usize; 4]> {
+    match format {
+        FrameFormat::YUYV => Some([0, 1, 2, 3]),
+        FrameFormat::UYVY => Some([1, 0, 3, 2]),
+        FrameFormat::YVYU => Some([0, 3, 2, 1]),
+        _ => None,
+    }
+}
+
+/// Converts a packed YUV 4:2:2 datastream ([`FrameFormat::YUYV`], [`FrameFormat::UYVY`] or [`FrameFormat::YVYU`]) of the given
+/// [`Colorimetry`] to a RGB888 stream and outputs it into a destination buffer. See [`buf_yuyv422_to_rgb`].
+/// # Errors
+/// If `format` is not packed YUV 4:2:2, the stream is invalid, or the destination buffer is not the right size, this will error.
+#[inline]
+pub fn buf_packed_yuv422_to_rgb(
+    format: FrameFormat,
+    data: &[u8],
+    dest: &mut [u8],
+    rgba: bool,
+    colorimetry: Colorimetry,
+) -> Result<(), NokhwaError> {
+    let Some([y0, u, y1, v]) = packed_yuv422_layout(format) else {
+        return Err(NokhwaError::ProcessFrameError {
+            src: format,
+            destination: "RGB888".to_string(),
+            error: "Not a packed YUV 4:2:2 format".to_string(),
+        });
+    };
+    if format == FrameFormat::YUYV {
+        return buf_yuyv422_to_rgb(data, dest, rgba, colorimetry);
+    }
+
+    let pixel_size = if rgba { 4 } else { 3 };
+    if !data.len().is_multiple_of(4) || dest.len() != (data.len() / 4) * (2 * pixel_size) {
+        return Err(NokhwaError::ProcessFrameError {
+            src: format,
+            destination: "RGB888".to_string(),
+            error: "bad buffer size".to_string(),
+        });
+    }
+
+    for (macropixel, out) in data
+        .chunks_exact(4)
+        .zip(dest.chunks_exact_mut(2 * pixel_size))
+    {
+        let (u, v) = (i32::from(macropixel[u]), i32::from(macropixel[v]));
+        for (y, px) in [macropixel[y0], macropixel[y1]]
+            .into_iter()
+            .zip(out.chunks_exact_mut(pixel_size))
+        {
+            px[..3].copy_from_slice(&colorimetry.yuv_to_rgb(i32::from(y), u, v));
+            if rgba {
+                px[3] = 255;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the resolution and buffer sizes of a YUV 4:2:0 ([`FrameFormat::NV12`], [`FrameFormat::NV21`] or [`FrameFormat::I420`])
+/// conversion, returning the size of the Y plane.
+fn check_yuv420(
+    format: FrameFormat,
+    resolution: Resolution,
+    data: &[u8],
+    dest: &[u8],
+    pixel_size: usize,
+    destination: &str,
+) -> Result<usize, NokhwaError> {
+    let error = |error: &str| NokhwaError::ProcessFrameError {
+        src: format,
+        destination: destination.to_string(),
+        error: error.to_string(),
+    };
+
+    if !matches!(
+        format,
+        FrameFormat::NV12 | FrameFormat::NV21 | FrameFormat::I420
+    ) {
+        return Err(error("Not a YUV 4:2:0 format"));
+    }
+    if !resolution.width().is_multiple_of(2) || !resolution.height().is_multiple_of(2) {
+        return Err(error("bad resolution"));
+    }
+
+    let y_section = (resolution.width() * resolution.height()) as usize;
+    if data.len() != (y_section * 3) / 2 {
+        return Err(error("bad input buffer size"));
+    }
+    if dest.len() != y_section * pixel_size {
+        return Err(error("bad output buffer size"));
+    }
+    Ok(y_section)
+}
+
+/// Converts a YUV 4:2:0 datastream ([`FrameFormat::NV12`], [`FrameFormat::NV21`] or [`FrameFormat::I420`]) of the given
+/// [`Colorimetry`] to a RGB888 stream and outputs it into a destination buffer. See [`buf_nv12_to_rgb`].
+/// # Errors
+/// If `format` is not YUV 4:2:0, or the resolution, stream or destination buffer size is wrong, this will error.
+#[inline]
+pub fn buf_yuv420_to_rgb(
+    format: FrameFormat,
+    resolution: Resolution,
+    data: &[u8],
+    dest: &mut [u8],
+    rgba: bool,
+    colorimetry: Colorimetry,
+) -> Result<(), NokhwaError> {
+    let pixel_size = if rgba { 4 } else { 3 };
+    let y_section = check_yuv420(format, resolution, data, dest, pixel_size, "RGB")?;
+    if format == FrameFormat::NV12 {
+        return buf_nv12_to_rgb(resolution, data, dest, rgba, colorimetry);
+    }
+
+    let width = resolution.width() as usize;
+    let (luma, chroma) = data.split_at(y_section);
+    for (row, (luma_row, dest_row)) in luma
+        .chunks_exact(width)
+        .zip(dest.chunks_exact_mut(width * pixel_size))
+        .enumerate()
+    {
+        for (column, (y, px)) in luma_row
+            .iter()
+            .zip(dest_row.chunks_exact_mut(pixel_size))
+            .enumerate()
+        {
+            let (u, v) = if format == FrameFormat::NV21 {
+                let index = (row / 2) * width + (column / 2) * 2;
+                (chroma[index + 1], chroma[index])
+            } else {
+                let index = (row / 2) * (width / 2) + column / 2;
+                (chroma[index], chroma[y_section / 4 + index])
+            };
+            px[..3].copy_from_slice(&colorimetry.yuv_to_rgb(
+                i32::from(*y),
+                i32::from(u),
+                i32::from(v),
+            ));
+            if rgba {
+                px[3] = 255;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Converts a RAWBGR (BGR888) stream to a RGB888 stream and outputs it into a destination buffer.
+/// # Errors
+/// This will error when the data stream or destination buffer size is wrong.
+#[inline]
+pub fn buf_bgr_to_rgb(data: &[u8], dest: &mut [u8], rgba: bool) -> Result<(), NokhwaError> {
+    let pixel_size = if rgba { 4 } else { 3 };
+    if !data.len().is_multiple_of(3) || dest.len() != (data.len() / 3) * pixel_size {
+        return Err(NokhwaError::ProcessFrameError {
+            src: FrameFormat::RAWBGR,
+            destination: "RGB888".to_string(),
+            error: "bad buffer size".to_string(),
+        });
+    }
+
+    for (bgr, px) in data.chunks_exact(3).zip(dest.chunks_exact_mut(pixel_size)) {
+        px[..3].copy_from_slice(&[bgr[2], bgr[1], bgr[0]]);
+        if rgba {
+            px[3] = 255;
+        }
+    }
+    Ok(())
+}
+
+/// Expands a little-endian RGB565 pixel to RGB888, replicating the high bits of each channel into its low bits.
+#[allow(clippy::cast_possible_truncation)]
+#[must_use]
+#[inline]
+pub fn rgb565_to_rgb(pixel: [u8; 2]) -> [u8; 3] {
+    let pixel = u16::from_le_bytes(pixel);
+    let r = (pixel >> 11) as u8;
+    let g = ((pixel >> 5) & 0x3F) as u8;
+    let b = (pixel & 0x1F) as u8;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// Converts a RGB565 stream to a RGB888 stream and outputs it into a destination buffer. See [`rgb565_to_rgb`].
+/// # Errors
+/// This will error when the data stream or destination buffer size is wrong.
+#[inline]
+pub fn buf_rgb565_to_rgb(data: &[u8], dest: &mut [u8], rgba: bool) -> Result<(), NokhwaError> {
+    let pixel_size = if rgba { 4 } else { 3 };
+    if !data.len().is_multiple_of(2) || dest.len() != (data.len() / 2) * pixel_size {
+        return Err(NokhwaError::ProcessFrameError {
+            src: FrameFormat::RGB565,
+            destination: "RGB888".to_string(),
+            error: "bad buffer size".to_string(),
+        });
+    }
+
+    for (pixel, px) in data.chunks_exact(2).zip(dest.chunks_exact_mut(pixel_size)) {
+        px[..3].copy_from_slice(&rgb565_to_rgb([pixel[0], pixel[1]]));
+        if rgba {
+            px[3] = 255;
+        }
+    }
+    Ok(())
+}
+
+/// Widens a sample of `bit_depth` (8 to 16) bits to 16 bits, repeating its high bits in the new low bits so that the
+/// brightest sample stays the brightest.
+#[allow(clippy::cast_possible_truncation)]
+#[must_use]
+#[inline]
+pub fn widen_sample(sample: u16, bit_depth: u32) -> u16 {
+    let sample = u32::from(sample) & ((1 << bit_depth) - 1);
+    ((sample << (16 - bit_depth)) | (sample >> (2 * bit_depth - 16))) as u16
+}
+
+/// Reads the samples of a [`FrameFormat::GRAY10`], [`FrameFormat::GRAY12`], [`FrameFormat::GRAY16`] or [`FrameFormat::Z16`] stream, widened to 16 bits
+/// with [`widen_sample`].
+fn wide_gray_samples<'a>(
+    format: FrameFormat,
+    data: &'a [u8],
+    destination: &str,
+) -> Result<impl Iterator<Item = u16> + 'a, NokhwaError> {
+    if !matches!(
+        format,
+        FrameFormat::GRAY10 | FrameFormat::GRAY12 | FrameFormat::GRAY16 | FrameFormat::Z16
+    ) || !data.len().is_multiple_of(2)
+    {
+        return Err(NokhwaError::ProcessFrameError {
+            src: format,
+            destination: destination.to_string(),
+            error: "Not a whole high bit depth grayscale stream".to_string(),
+        });
+    }
+
+    let bit_depth = format.bit_depth();
+    Ok(data
+        .chunks_exact(2)
+        .map(move |sample| widen_sample(u16::from_le_bytes([sample[0], sample[1]]), bit_depth)))
+}
+
+/// Converts a [`FrameFormat::GRAY10`], [`FrameFormat::GRAY12`], [`FrameFormat::GRAY16`] or [`FrameFormat::Z16`] stream to a RGB888 stream, with the
+/// high 8 bits of each sample in every channel, and outputs it into a destination buffer.
+/// # Errors
+/// If `format` is not a high bit depth grayscale format, or the data stream or destination buffer size is wrong, this will error.
+#[allow(clippy::cast_possible_truncation)]
+#[inline]
+pub fn buf_gray16_to_rgb(
+    format: FrameFormat,
+    data: &[u8],
+    dest: &mut [u8],
+    rgba: bool,
+) -> Result<(), NokhwaError> {
+    let samples = wide_gray_samples(format, data, "RGB888")?;
+    let pixel_size = if rgba { 4 } else { 3 };
+    if dest.len() != (data.len() / 2) * pixel_size {
+        return Err(NokhwaError::ProcessFrameError {
+            src: format,
+            destination: "RGB888".to_string(),
+            error: "bad buffer size".to_string(),
+        });
+    }
+
+    for (sample, px) in samples.zip(dest.chunks_exact_mut(pixel_size)) {
+        let luma = (sample >> 8) as u8;
+        px[..3].copy_from_slice(&[luma, luma, luma]);
+        if rgba {
+            px[3] = 255;
+        }
+    }
+    Ok(())
+}
+
+/// Demosaics a Bayer ([`FrameFormat::Bayer8`] or [`FrameFormat::Bayer10`]) frame to a RGB888 stream with the given
+/// [`DemosaicMethod`] and outputs it into a destination buffer.
+/// # Errors
+/// If `format` is not a Bayer format, or the resolution, stream or destination buffer size is wrong, this will error.
+#[inline]
+pub fn buf_bayer_to_rgb(
+    format: FrameFormat,
+    resolution: Resolution,
+    data: &[u8],
+    dest: &mut [u8],
+    rgba: bool,
+    method: DemosaicMethod,
+) -> Result<(), NokhwaError> {
+    let mosaic = Mosaic::new(format, resolution, data, "RGB")?;
+    let pixel_size = if rgba { 4 } else { 3 };
+    if dest.len() != mosaic.pixel_count() * pixel_size {
+        return Err(NokhwaError::ProcessFrameError {
+            src: format,
+            destination: "RGB".to_string(),
+            error: "bad output buffer size".to_string(),
+        });
+    }
+
+    mosaic.demosaic(method, |index, [r, g, b]| {
+        let px = &mut dest[index * pixel_size..(index + 1) * pixel_size];
+        px[..3].copy_from_slice(&[mosaic.to_u8(r), mosaic.to_u8(g), mosaic.to_u8(b)]);
+        if rgba {
+            px[3] = 255;
+        }
+    });
+    Ok(())
+}
+
+/// Converts a MJPEG stream of `&[u8]` into a `Vec<u8>` of full range luminance (Y,Y,Y,...), without decoding the color.
+/// # Errors
+/// If `mozjpeg` fails to read scanlines or setup the decompressor, this will error.
+#[cfg(all(feature = "mjpeg", not(target_arch = "wasm")))]
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "mjpeg")))]
+#[inline]
+pub fn mjpeg_to_luma(data: &[u8]) -> Result<Vec<u8>, NokhwaError> {
+    let mut dest = vec![0; mjpeg_dimensions(data)?];
+    buf_mjpeg_to_luma(data, &mut dest, false)?;
+    Ok(dest)
+}
+
+/// Converts a MJPEG stream into luminance. Needs the `mjpeg` feature.
+/// # Errors
+/// Always errors, as the `mjpeg` feature is disabled or you are doing this on `WebAssembly`.
+#[cfg(not(all(feature = "mjpeg", not(target_arch = "wasm"))))]
+pub fn mjpeg_to_luma(_data: &[u8]) -> Result<Vec<u8>, NokhwaError> {
+    Err(NokhwaError::NotImplementedError(
+        "Not available on WASM".to_string(),
+    ))
+}
+
+#[cfg(all(feature = "mjpeg", not(target_arch = "wasm")))]
+fn mjpeg_dimensions(data: &[u8]) -> Result<usize, NokhwaError> {
+    use mozjpeg::Decompress;
+
+    match Decompress::new_mem(data) {
+        Ok(decompress) => Ok(decompress.width() * decompress.height()),
+        Err(why) => Err(NokhwaError::ProcessFrameError {
+            src: FrameFormat::MJPEG,
+            destination: "Luma".to_string(),
+            error: why.to_string(),
+        }),
+    }
+}
+
+/// Equivalent to [`mjpeg_to_luma`] except with a destination buffer. If `alpha` is set, every pixel is followed by an opaque alpha byte.
+/// # Errors
+/// If the decoding fails (e.g. invalid MJPEG stream), the buffer is not large enough, or you are doing this on `WebAssembly`, this will error.
+#[cfg(all(feature = "mjpeg", not(target_arch = "wasm")))]
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "mjpeg")))]
+#[inline]
+pub fn buf_mjpeg_to_luma(data: &[u8], dest: &mut [u8], alpha: bool) -> Result<(), NokhwaError> {
+    use mozjpeg::Decompress;
+
+    let mut jpeg_decompress = match Decompress::new_mem(data) {
+        Ok(decompress) => match decompress.grayscale() {
+            Ok(decompressor) => decompressor,
+            Err(why) => {
+                return Err(NokhwaError::ProcessFrameError {
+                    src: FrameFormat::MJPEG,
+                    destination: "Luma".to_string(),
+                    error: why.to_string(),
+                })
+            }
+        },
+        Err(why) => {
+            return Err(NokhwaError::ProcessFrameError {
+                src: FrameFormat::MJPEG,
+                destination: "Luma".to_string(),
+                error: why.to_string(),
+            })
+        }
+    };
+
+    let pixel_count = jpeg_decompress.min_flat_buffer_size();
+    let pixel_size = if alpha { 2 } else { 1 };
+    if dest.len() != pixel_count * pixel_size {
+        return Err(NokhwaError::ProcessFrameError {
+            src: FrameFormat::MJPEG,
+            destination: "Luma".to_string(),
+            error: "Bad decoded buffer size".to_string(),
+        });
+    }
+
+    if alpha {
+        // Decode into the first half, then spread it out back to front so nothing is overwritten before it is read
+        jpeg_decompress.read_scanlines_flat_into(&mut dest[..pixel_count]);
+        for idx in (0..pixel_count).rev() {
+            dest[idx * 2] = dest[idx];
+            dest[idx * 2 + 1] = 255;
+        }
+    } else {
+        jpeg_decompress.read_scanlines_flat_into(dest);
+    }
+
+    if !jpeg_decompress.finish_decompress() {
+        return Err(NokhwaError::ProcessFrameError {
+            src: FrameFormat::MJPEG,
+            destination: "Luma".to_string(),
+            error: "JPEG Decompressor did not finish.".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Equivalent to [`mjpeg_to_luma`] except with a destination buffer. Needs the `mjpeg` feature.
+/// # Errors
+/// Always errors, as the `mjpeg` feature is disabled or you are doing this on `WebAssembly`.
+#[cfg(not(all(feature = "mjpeg", not(target_arch = "wasm"))))]
+pub fn buf_mjpeg_to_luma(_data: &[u8], _dest: &mut [u8], _alpha: bool) -> Result<(), NokhwaError> {
+    Err(NokhwaError::NotImplementedError(
+        "Not available on WASM".to_string(),
+    ))
+}
+
+/// Expands a limited range (16-235) Y sample to the full range luminance of [`FrameFormat::GRAY`], the same way [`yuyv444_to_rgb`] does for a neutral pixel.
+///
+/// See [`Colorimetry::y_to_luma`] for other colorimetries.
+#[must_use]
+#[inline]
+pub fn y_to_luma(y: u8) -> u8 {
+    Colorimetry::default().y_to_luma(y)
+}
+
+/// Writes `luma` to `dest`, followed by an opaque alpha byte for every pixel if `alpha` is set.
+fn write_luma(luma: impl Iterator<Item = u8>, dest: &mut [u8], alpha: bool) {
+    if alpha {
+        for (y, px) in luma.zip(dest.chunks_exact_mut(2)) {
+            px[0] = y;
+            px[1] = 255;
+        }
+    } else {
+        for (y, px) in luma.zip(dest.iter_mut()) {
+            *px = y;
+        }
+    }
+}
+
+/// Extracts the luminance of a YUYV 4:2:2 datastream of the given [`Colorimetry`], skipping the chroma. See [`Colorimetry::y_to_luma`].
+/// # Errors
+/// This will error when the data stream size is not divisible by 4.
+#[inline]
+pub fn yuyv422_to_luma(data: &[u8], colorimetry: Colorimetry) -> Result<Vec<u8>, NokhwaError> {
+    let mut dest = vec![0; data.len() / 2];
+    buf_yuyv422_to_luma(data, &mut dest, false, colorimetry)?;
+    Ok(dest)
+}
+
+/// Same as [`yuyv422_to_luma`] but with a destination buffer. If `alpha` is set, every pixel is followed by an opaque alpha byte.
+/// # Errors
+/// If the stream is invalid YUYV, or the destination buffer is not the right size, this will error.
+#[inline]
+pub fn buf_yuyv422_to_luma(
+    data: &[u8],
+    dest: &mut [u8],
+    alpha: bool,
+    colorimetry: Colorimetry,
+) -> Result<(), NokhwaError> {
+    buf_packed_yuv422_to_luma(FrameFormat::YUYV, data, dest, alpha, colorimetry)
+}
+
+/// Extracts the luminance of a YUYV 4:2:0 bi-planar (NV12) datastream of the given [`Colorimetry`], which is its first plane. See [`Colorimetry::y_to_luma`].
+/// # Errors
+/// This will error when the data stream size is wrong.
+#[inline]
+pub fn nv12_to_luma(
+    resolution: Resolution,
+    data: &[u8],
+    colorimetry: Colorimetry,
+) -> Result<Vec<u8>, NokhwaError> {
+    let mut dest = vec![0; (resolution.width() * resolution.height()) as usize];
+    buf_nv12_to_luma(resolution, data, &mut dest, false, colorimetry)?;
+    Ok(dest)
+}
+
+/// Same as [`nv12_to_luma`] but with a destination buffer. If `alpha` is set, every pixel is followed by an opaque alpha byte.
+/// # Errors
+/// This will error when the data stream or destination buffer size is wrong.
+#[inline]
+pub fn buf_nv12_to_luma(
+    resolution: Resolution,
+    data: &[u8],
+    dest: &mut [u8],
+    alpha: bool,
+    colorimetry: Colorimetry,
+) -> Result<(), NokhwaError> {
+    buf_yuv420_to_luma(
+        FrameFormat::NV12,
+        resolution,
+        data,
+        dest,
+        alpha,
+        colorimetry,
+    )
+}
+
+/// Converts a RGB888 stream to its luminance and outputs it into a destination buffer. See [`rgb_to_luma`].
+/// If `alpha` is set, every pixel is followed by an opaque alpha byte.
+/// # Errors
+/// This will error when the data stream or destination buffer size is wrong.
+#[inline]
+pub fn buf_rgb_to_luma(data: &[u8], dest: &mut [u8], alpha: bool) -> Result<(), NokhwaError> {
+    let pixel_size = if alpha { 2 } else { 1 };
+    if !data.len().is_multiple_of(3) || dest.len() != (data.len() / 3) * pixel_size {
+        return Err(NokhwaError::ProcessFrameError {
+            src: FrameFormat::RAWRGB,
+            destination: "Luma".to_string(),
+            error: "bad buffer size".to_string(),
+        });
+    }
+
+    write_luma(
+        data.chunks_exact(3)
+            .map(|rgb| rgb_to_luma(rgb[0], rgb[1], rgb[2])),
+        dest,
+        alpha,
+    );
+    Ok(())
+}
+
+/// Extracts the luminance of a packed YUV 4:2:2 datastream ([`FrameFormat::YUYV`], [`FrameFormat::UYVY`] or [`FrameFormat::YVYU`])
+/// of the given [`Colorimetry`], skipping the chroma. If `alpha` is set, every pixel is followed by an opaque alpha byte.
+/// # Errors
+/// If `format` is not packed YUV 4:2:2, the stream is invalid, or the destination buffer is not the right size, this will error.
+#[inline]
+pub fn buf_packed_yuv422_to_luma(
+    format: FrameFormat,
+    data: &[u8],
+    dest: &mut [u8],
+    alpha: bool,
+    colorimetry: Colorimetry,
+) -> Result<(), NokhwaError> {
+    let Some([y0, ..]) = packed_yuv422_layout(format) else {
+        return Err(NokhwaError::ProcessFrameError {
+            src: format,
+            destination: "Luma".to_string(),
+            error: "Not a packed YUV 4:2:2 format".to_string(),
+        });
+    };
+    if !data.len().is_multiple_of(4) {
+        return Err(NokhwaError::ProcessFrameError {
+            src: format,
+            destination: "Luma".to_string(),
+            error: "Assertion failure, the YUV stream isn't 4:2:2! (wrong number of bytes)"
+                .to_string(),
+        });
+    }
+
+    let pixel_size = if alpha { 2 } else { 1 };
+    let luma_buf_size = (data.len() / 2) * pixel_size;
+    if dest.len() != luma_buf_size {
+        return Err(NokhwaError::ProcessFrameError {
+            src: format,
+            destination: "Luma".to_string(),
+            error: format!("Assertion failure, the destination Luma buffer is of the wrong size! [expected: {luma_buf_size}, actual: {}]", dest.len()),
+        });
+    }
+
+    // The luma is every other byte, starting at the first Y
+    write_luma(
+        data[y0..]
+            .iter()
+            .step_by(2)
+            .map(|y| colorimetry.y_to_luma(*y)),
+        dest,
+        alpha,
+    );
+    Ok(())
+}
+
+/// Extracts the luminance of a YUV 4:2:0 datastream ([`FrameFormat::NV12`], [`FrameFormat::NV21`] or [`FrameFormat::I420`])
+/// of the given [`Colorimetry`], which is its first plane. If `alpha` is set, every pixel is followed by an opaque alpha byte.
+/// # Errors
+/// If `format` is not YUV 4:2:0, or the resolution, stream or destination buffer size is wrong, this will error.
+#[inline]
+pub fn buf_yuv420_to_luma(
+    format: FrameFormat,
+    resolution: Resolution,
+    data: &[u8],
+    dest: &mut [u8],
+    alpha: bool,
+    colorimetry: Colorimetry,
+) -> Result<(), NokhwaError> {
+    let pixel_size = if alpha { 2 } else { 1 };
+    let y_section = check_yuv420(format, resolution, data, dest, pixel_size, "Luma")?;
+
+    write_luma(
+        data[..y_section].iter().map(|y| colorimetry.y_to_luma(*y)),
+        dest,
+        alpha,
+    );
+    Ok(())
+}
+
+/// Converts a RAWBGR (BGR888) stream to its luminance and outputs it into a destination buffer. See [`rgb_to_luma`].
+/// If `alpha` is set, every pixel is followed by an opaque alpha byte.
+/// # Errors
+/// This will error when the data stream or destination buffer size is wrong.
+#[inline]
+pub fn buf_bgr_to_luma(data: &[u8], dest: &mut [u8], alpha: bool) -> Result<(), NokhwaError> {
+    let pixel_size = if alpha { 2 } else { 1 };
+    if !data.len().is_multiple_of(3) || dest.len() != (data.len() / 3) * pixel_size {
+        return Err(NokhwaError::ProcessFrameError {
+            src: FrameFormat::RAWBGR,
+            destination: "Luma".to_string(),
+            error: "bad buffer size".to_string(),
+        });
+    }
+
+    write_luma(
+        data.chunks_exact(3)
+            .map(|bgr| rgb_to_luma(bgr[2], bgr[1], bgr[0])),
+        dest,
+        alpha,
+    );
+    Ok(())
+}
+
+/// Converts a RGB565 stream to its luminance and outputs it into a destination buffer. See [`rgb565_to_rgb`] and [`rgb_to_luma`].
+/// If `alpha` is set, every pixel is followed by an opaque alpha byte.
+/// # Errors
+/// This will error when the data stream or destination buffer size is wrong.
+#[inline]
+pub fn buf_rgb565_to_luma(data: &[u8], dest: &mut [u8], alpha: bool) -> Result<(), NokhwaError> {
+    let pixel_size = if alpha { 2 } else { 1 };
+    if !data.len().is_multiple_of(2) || dest.len() != (data.len() / 2) * pixel_size {
+        return Err(NokhwaError::ProcessFrameError {
+            src: FrameFormat::RGB565,
+            destination: "Luma".to_string(),
+            error: "bad buffer size".to_string(),
+        });
+    }
+
+    write_luma(
+        data.chunks_exact(2).map(|pixel| {
+            let [r, g, b] = rgb565_to_rgb([pixel[0], pixel[1]]);
+            rgb_to_luma(r, g, b)
+        }),
+        dest,
+        alpha,
+    );
+    Ok(())
+}
+
+/// Converts a [`FrameFormat::GRAY10`], [`FrameFormat::GRAY12`], [`FrameFormat::GRAY16`] or [`FrameFormat::Z16`] stream to its high 8 bits and outputs
+/// it into a destination buffer. If `alpha` is set, every pixel is followed by an opaque alpha byte.
+/// # Errors
+/// If `format` is not a high bit depth grayscale format, or the data stream or destination buffer size is wrong, this will error.
+#[allow(clippy::cast_possible_truncation)]
+#[inline]
+pub fn buf_gray16_to_luma(
+    format: FrameFormat,
+    data: &[u8],
+    dest: &mut [u8],
+    alpha: bool,
+) -> Result<(), NokhwaError> {
+    let samples = wide_gray_samples(format, data, "Luma")?;
+    let pixel_size = if alpha { 2 } else { 1 };
+    if dest.len() != (data.len() / 2) * pixel_size {
+        return Err(NokhwaError::ProcessFrameError {
+            src: format,
+            destination: "Luma".to_string(),
+            error: "bad buffer size".to_string(),
+        });
+    }
+
+    write_luma(samples.map(|sample| (sample >> 8) as u8), dest, alpha);
+    Ok(())
+}
+
+/// Converts a [`FrameFormat::GRAY10`], [`FrameFormat::GRAY12`], [`FrameFormat::GRAY16`] or [`FrameFormat::Z16`] stream to 16-bit luminance, widening
+/// each sample with [`widen_sample`], and outputs it into a destination buffer.
+/// # Errors
+/// If `format` is not a high bit depth grayscale format, or the data stream or destination buffer size is wrong, this will error.
+#[inline]
+pub fn buf_gray16_to_luma16(
+    format: FrameFormat,
+    data: &[u8],
+    dest: &mut [u16],
+) -> Result<(), NokhwaError> {
+    let samples = wide_gray_samples(format, data, "Luma16")?;
+    if dest.len() != data.len() / 2 {
+        return Err(NokhwaError::ProcessFrameError {
+            src: format,
+            destination: "Luma16".to_string(),
+            error: "bad buffer size".to_string(),
+        });
+    }
+
+    for (sample, px) in samples.zip(dest.iter_mut()) {
+        *px = sample;
+    }
+    Ok(())
+}
+
+/// Computes the luminance of a Bayer ([`FrameFormat::Bayer8`] or [`FrameFormat::Bayer10`]) frame at full resolution straight from
+/// the mosaic, without demosaicing it, and outputs it into a destination buffer. Each pixel is weighted from the 2x2 block
+/// it starts, which always holds one red, two green and one blue sample, with the weights of [`rgb_to_luma`].
+/// If `alpha` is set, every pixel is followed by an opaque alpha byte.
+/// # Errors
+/// If `format` is not a Bayer format, or the resolution, stream or destination buffer size is wrong, this will error.
+#[inline]
+pub fn buf_bayer_to_luma(
+    format: FrameFormat,
+    resolution: Resolution,
+    data: &[u8],
+    dest: &mut [u8],
+    alpha: bool,
+) -> Result<(), NokhwaError> {
+    let mosaic = Mosaic::new(format, resolution, data, "Luma")?;
+    let pixel_size = if alpha { 2 } else { 1 };
+    if dest.len() != mosaic.pixel_count() * pixel_size {
+        return Err(NokhwaError::ProcessFrameError {
+            src: format,
+            destination: "Luma".to_string(),
+            error: "bad output buffer size".to_string(),
+        });
+    }
+
+    mosaic.luma(|index, luma| {
+        dest[index * pixel_size] = mosaic.to_u8(luma);
+        if alpha {
+            dest[index * pixel_size + 1] = 255;
+        }
+    });
+    Ok(())
+}
+
+/// Computes the 16-bit luminance of a Bayer ([`FrameFormat::Bayer8`] or [`FrameFormat::Bayer10`]) frame the same way as
+/// [`buf_bayer_to_luma`], widened with [`widen_sample`], and outputs it into a destination buffer.
+/// # Errors
+/// If `format` is not a Bayer format, or the resolution, stream or destination buffer size is wrong, this will error.
+#[inline]
+pub fn buf_bayer_to_luma16(
+    format: FrameFormat,
+    resolution: Resolution,
+    data: &[u8],
+    dest: &mut [u16],
+) -> Result<(), NokhwaError> {
+    let mosaic = Mosaic::new(format, resolution, data, "Luma16")?;
+    if dest.len() != mosaic.pixel_count() {
+        return Err(NokhwaError::ProcessFrameError {
+            src: format,
+            destination: "Luma16".to_string(),
+            error: "bad output buffer size".to_string(),
+        });
+    }
+
+    mosaic.luma(|index, luma| dest[index] = mosaic.to_u16(luma));
+    Ok(())
+}
+
+/// Converts a [`FrameFormat::Z16`] depth map to meters and outputs it into a destination buffer. Pixels without a
+/// measurement are 0 in both.
+///
+/// `depth_scale` is the length of one depth unit in meters. It depends on the camera: most UVC depth cameras count
+/// millimeters (0.001), the depth maps of the TUM RGB-D dataset count fifths of a millimeter (0.0002).
+/// # Errors
+/// If the data stream or destination buffer size is wrong, this will error.
+#[inline]
+pub fn buf_z16_to_depth(
+    data: &[u8],
+    dest: &mut [f32],
+    depth_scale: f32,
+) -> Result<(), NokhwaError> {
+    if data.len() != dest.len() * 2 {
+        return Err(NokhwaError::ProcessFrameError {
+            src: FrameFormat::Z16,
+            destination: "Depth".to_string(),
+            error: "bad buffer size".to_string(),
+        });
+    }
+
+    for (sample, px) in data.chunks_exact(2).zip(dest.iter_mut()) {
+        *px = f32::from(u16::from_le_bytes([sample[0], sample[1]])) * depth_scale;
+    }
+    Ok(())
+}
+
+// inverse of the equation used by `yuyv444_to_rgb`, from https://en.wikipedia.org/wiki/YUV#Converting_between_Y%E2%80%B2UV_and_RGB
+/// Convert a RGB888 pixel to `YCbCr` 4:4:4, with the coefficients [`yuyv444_to_rgb`] decodes. [For further reading](https://en.wikipedia.org/wiki/YUV#Converting_between_Y%E2%80%B2UV_and_RGB)
+#[allow(clippy::many_single_char_names)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[must_use]
+#[inline]
+pub fn rgb_to_yuv444(r: u8, g: u8, b: u8) -> [u8; 3] {
+    let r = i32::from(r);
+    let g = i32::from(g);
+    let b = i32::from(b);
+    let y = ((66 * r + 129 * g + 25 * b + 128) >> 8) + 16;
+    let u = ((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128;
+    let v = ((112 * r - 94 * g - 18 * b + 128) >> 8) + 128;
+    [y as u8, u as u8, v as u8]
+}
+
+/// Converts a RGB888 pixel to its full range luminance, as used by [`FrameFormat::GRAY`].
+#[allow(clippy::cast_possible_truncation)]
+#[must_use]
+#[inline]
+pub fn rgb_to_luma(r: u8, g: u8, b: u8) -> u8 {
+    ((77 * u32::from(r) + 150 * u32::from(g) + 29 * u32::from(b) + 128) >> 8) as u8
+}
+
+fn check_rgb_source(
+    resolution: Resolution,
+    data: &[u8],
+    destination: FrameFormat,
+) -> Result<(), NokhwaError> {
+    if data.len() != (resolution.width() * resolution.height() * 3) as usize {
+        return Err(NokhwaError::ProcessFrameError {
+            src: FrameFormat::RAWRGB,
+            destination: destination.to_string(),
+            error: "bad input buffer size".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Converts a RGB888 stream to a YUYV 4:2:2 stream, averaging the chroma of each horizontal pixel pair.
+/// # Errors
+/// This will error if the width is odd or `data` does not match the resolution.
+#[allow(clippy::cast_possible_truncation)]
+pub fn rgb_to_yuyv422(resolution: Resolution, data: &[u8]) -> Result<Vec<u8>, NokhwaError> {
+    check_rgb_source(resolution, data, FrameFormat::YUYV)?;
+    if !resolution.width().is_multiple_of(2) {
+        return Err(NokhwaError::ProcessFrameError {
+            src: FrameFormat::RAWRGB,
+            destination: FrameFormat::YUYV.to_string(),
+            error: "bad resolution".to_string(),
+        });
+    }
+
+    let mut dest = Vec::with_capacity(data.len() / 3 * 2);
+    for pair in data.chunks_exact(6) {
+        let [y0, u0, v0] = rgb_to_yuv444(pair[0], pair[1], pair[2]);
+        let [y1, u1, v1] = rgb_to_yuv444(pair[3], pair[4], pair[5]);
+        let u = (u16::from(u0) + u16::from(u1)).div_ceil(2) as u8;
+        let v = (u16::from(v0) + u16::from(v1)).div_ceil(2) as u8;
+        dest.extend_from_slice(&[y0, u, y1, v]);
+    }
+    Ok(dest)
+}
+
+/// Converts a RGB888 stream to a YUYV 4:2:0 bi-planar (NV12) stream, averaging the chroma of each 2x2 pixel block.
+/// # Errors
+/// This will error if the width or height is odd or `data` does not match the resolution.
+#[allow(clippy::cast_possible_truncation)]
+pub fn rgb_to_nv12(resolution: Resolution, data: &[u8]) -> Result<Vec<u8>, NokhwaError> {
+    check_rgb_source(resolution, data, FrameFormat::NV12)?;
+    if !resolution.width().is_multiple_of(2) || !resolution.height().is_multiple_of(2) {
+        return Err(NokhwaError::ProcessFrameError {
+            src: FrameFormat::RAWRGB,
+            destination: FrameFormat::NV12.to_string(),
+            error: "bad resolution".to_string(),
+        });
+    }
+
+    let width = resolution.width() as usize;
+    let height = resolution.height() as usize;
+    let mut dest = vec![0; width * height * 3 / 2];
+    let (luma, chroma) = dest.split_at_mut(width * height);
+
+    for row in (0..height).step_by(2) {
+        for column in (0..width).step_by(2) {
+            let mut u_sum = 0;
+            let mut v_sum = 0;
+            for (y, x) in [
+                (row, column),
+                (row, column + 1),
+                (row + 1, column),
+                (row + 1, column + 1),
+            ] {
+                let index = (y * width + x) * 3;
+                let [luminance, u, v] =
+                    rgb_to_yuv444(data[index], data[index + 1], data[index + 2]);
+                luma[y * width + x] = luminance;
+                u_sum += u32::from(u);
+                v_sum += u32::from(v);
+            }
+            let chroma_index = (row / 2) * width + column;
+            chroma[chroma_index] = ((u_sum + 2) / 4) as u8;
+            chroma[chroma_index + 1] = ((v_sum + 2) / 4) as u8;
+        }
+    }
+    Ok(dest)
+}
+
+/// Converts a RGB888 stream to a GRAY stream. See [`rgb_to_luma`].
+/// # Errors
+/// This will error if `data` does not match the resolution.
+pub fn rgb_to_gray(resolution: Resolution, data: &[u8]) -> Result<Vec<u8>, NokhwaError> {
+    check_rgb_source(resolution, data, FrameFormat::GRAY)?;
+    Ok(data
+        .chunks_exact(3)
+        .map(|rgb| rgb_to_luma(rgb[0], rgb[1], rgb[2]))
+        .collect())
+}
+
+/// Compresses a RGB888 stream into a single JPEG image, as found in a MJPEG stream. `quality` ranges from 0 to 100.
+/// # Errors
+/// If `data` does not match the resolution, `mozjpeg` fails to compress, or you are doing this on `WebAssembly`, this will error.
+#[cfg(all(feature = "mjpeg", not(target_arch = "wasm")))]
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "mjpeg")))]
+pub fn rgb_to_mjpeg(
+    resolution: Resolution,
+    data: &[u8],
+    quality: u8,
+) -> Result<Vec<u8>, NokhwaError> {
+    use mozjpeg::{ColorSpace, Compress};
+
+    check_rgb_source(resolution, data, FrameFormat::MJPEG)?;
+    let compress_error = |why: std::io::Error| NokhwaError::ProcessFrameError {
+        src: FrameFormat::RAWRGB,
+        destination: FrameFormat::MJPEG.to_string(),
+        error: why.to_string(),
+    };
+
+    let mut jpeg_compress = Compress::new(ColorSpace::JCS_RGB);
+    jpeg_compress.set_size(resolution.width() as usize, resolution.height() as usize);
+    jpeg_compress.set_quality(f32::from(quality.min(100)));
+
+    let mut jpeg_compress = jpeg_compress
+        .start_compress(Vec::new())
+        .map_err(compress_error)?;
+    jpeg_compress
+        .write_scanlines(data)
+        .map_err(compress_error)?;
+    jpeg_compress.finish().map_err(compress_error)
+}
+
+/// Compresses a RGB888 stream into a single JPEG image. Needs the `mjpeg` feature.
+/// # Errors
+/// Always errors, as the `mjpeg` feature is disabled or you are doing this on `WebAssembly`.
+#[cfg(not(all(feature = "mjpeg", not(target_arch = "wasm"))))]
+pub fn rgb_to_mjpeg(
+    _resolution: Resolution,
+    _data: &[u8],
+    _quality: u8,
+) -> Result<Vec<u8>, NokhwaError> {
+    Err(NokhwaError::NotImplementedError(
+        "Not available on WASM".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone_map(map: ToneMap, samples: &[u16]) -> Vec<u8> {
+        let mut dest = vec![0; samples.len()];
+        map.apply(samples, &mut dest).unwrap();
+        dest
+    }
+
+    #[test]
+    fn widens_samples() {
+        assert_eq!(widen_sample(0, 10), 0);
+        assert_eq!(widen_sample(0x3FF, 10), 0xFFFF);
+        assert_eq!(widen_sample(0x200, 10), 0x8020);
+        assert_eq!(widen_sample(0x155, 10), 0x5555);
+        // Bits above the sample are padding
+        assert_eq!(widen_sample(0xFC00 | 0x155, 10), 0x5555);
+        assert_eq!(widen_sample(0xFFF, 12), 0xFFFF);
+        assert_eq!(widen_sample(0x800, 12), 0x8008);
+        assert_eq!(widen_sample(0x123, 12), 0x1231);
+        assert_eq!(widen_sample(0x1234, 16), 0x1234);
+        assert_eq!(widen_sample(0x80, 8), 0x8080);
+    }
+
+    #[test]
+    fn linear_tone_map_clips_to_its_range() {
+        // The default keeps the high 8 bits
+        assert_eq!(
+            tone_map(ToneMap::default(), &[0, 0x1234, 0x8000, u16::MAX]),
+            [0, 0x12, 0x80, 255]
+        );
+        assert_eq!(
+            tone_map(
+                ToneMap::Linear {
+                    black: 1000,
+                    white: 2000
+                },
+                &[500, 1000, 1500, 2000, 3000]
+            ),
+            [0, 0, 128, 255, 255]
+        );
+        // A white point below the black point maps everything to black
+        assert_eq!(
+            tone_map(
+                ToneMap::Linear {
+                    black: 2000,
+                    white: 1000
+                },
+                &[0, 1500, 3000]
+            ),
+            [0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn percentile_tone_map_ignores_outliers() {
+        let samples = [[0; 5], [0x4000; 5], [0x8000; 5], [u16::MAX; 5]]
+            .into_iter()
+            .enumerate()
+            .flat_map(|(index, samples)| {
+                // Most samples are in the middle
+                let repeat = if index == 1 || index == 2 { 9 } else { 1 };
+                std::iter::repeat_n(samples, repeat).flatten()
+            })
+            .collect::<Vec<u16>>();
+        assert_eq!(samples.len(), 100);
+
+        let mapped = tone_map(
+            ToneMap::Percentile {
+                low: 10.0,
+                high: 90.0,
+            },
+            &samples,
+        );
+        assert_eq!(mapped[..5], [0; 5]);
+        assert_eq!(mapped[5..50], [0; 45]);
+        assert!(mapped[50..95].iter().all(|luma| *luma == 254));
+        assert_eq!(mapped[95..], [255; 5]);
+
+        // The full range only stretches from the darkest sample to white
+        let mapped = tone_map(
+            ToneMap::Percentile {
+                low: 0.0,
+                high: 100.0,
+            },
+            &[0x4000, 0x8000],
+        );
+        assert_eq!(mapped, [0, 85]);
+    }
+
+    #[test]
+    fn gamma_tone_map_brightens_shadows() {
+        let samples = (0..=u16::MAX).step_by(257).collect::<Vec<u16>>();
+        let identity = tone_map(ToneMap::Gamma(1.0), &samples);
+        for (sample, luma) in samples.iter().zip(&identity) {
+            assert!(luma.abs_diff((sample >> 8) as u8) <= 1, "{sample} {luma}");
+        }
+
+        let gamma = tone_map(ToneMap::Gamma(2.2), &samples);
+        assert_eq!(gamma[0], 0);
+        assert_eq!(gamma[gamma.len() - 1], 255);
+        assert_eq!(tone_map(ToneMap::Gamma(2.2), &[0x8000]), [186]);
+        assert!(gamma.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(gamma
+            .iter()
+            .zip(&identity)
+            .all(|(gamma, identity)| gamma >= identity));
+    }
+
+    #[test]
+    fn tone_map_checks_buffer_size() {
+        let mut dest = [0; 2];
+        assert!(ToneMap::default().apply(&[0; 3], &mut dest).is_err());
+    }
+
+    #[test]
+    fn tone_map_round_trips_through_strings() {
+        for map in [
+            ToneMap::Linear {
+                black: 64,
+                white: 4095,
+            },
+            ToneMap::Percentile {
+                low: 0.5,
+                high: 99.5,
+            },
+            ToneMap::Gamma(1.8),
+        ] {
+            assert_eq!(map.to_string().parse::<ToneMap>().unwrap(), map);
+        }
+        assert_eq!("linear".parse::<ToneMap>().unwrap(), ToneMap::default());
+        assert!("linear:1".parse::<ToneMap>().is_err());
+        assert!("gamma:bright".parse::<ToneMap>().is_err());
+    }
+}
@@ -0,0 +1,905 @@
+/*
+ * Copyright 2022 l1npengtul <l1npengtul@protonmail.com> / The Nokhwa Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use image::ImageFormat;
+use nokhwa_core::{
+    buffer::Buffer,
+    error::NokhwaError,
+    traits::CaptureBackendTrait,
+    types::{
+        ApiBackend, CameraControl, CameraFormat, CameraIndex, CameraInfo, ControlValueSetter,
+        FrameFormat, KnownCameraControl, RequestedFormat, Resolution,
+    },
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Frame rate of image sequences, which carry no timing of their own.
+const IMAGE_SEQUENCE_FRAME_RATE: u32 = 30;
+/// Playback rates offered besides the native one. Only rates up to the native one are listed.
+const STANDARD_FRAME_RATES: [u32; 8] = [5, 10, 15, 20, 24, 25, 30, 60];
+/// Subdirectories searched for images when the given directory holds none, in order.
+/// These cover the TUM RGB-D (`rgb`) and `EuRoC` (`mav0/cam0/data`) dataset layouts.
+const DATASET_IMAGE_DIRECTORIES: [&str; 4] = ["rgb", "mav0/cam0/data", "cam0/data", "data"];
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const JPEG_SIGNATURE: [u8; 2] = [0xFF, 0xD8];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ImageKind {
+    Png,
+    Jpeg,
+}
+
+enum MediaSource {
+    ImageSequence {
+        kind: ImageKind,
+        files: Vec<PathBuf>,
+    },
+    // Byte offset and length of every frame inside the file
+    MotionJpeg {
+        file: File,
+        frames: Vec<(u64, u32)>,
+    },
+}
+
+/// Reads frames from a directory of PNG/JPEG images or a Motion-JPEG AVI file, as if they came from a camera.
+/// To see what this does, please see [`CaptureBackendTrait`].
+///
+/// Every frame is served exactly once and in order, regardless of how fast they are consumed, so
+/// runs over the same input are deterministic. The frame rate only paces playback, it never skips frames.
+/// # Quirks
+/// - The [`CameraIndex`] must be a [`CameraIndex::String`] holding the path of the directory or AVI file.
+/// - Images are played back in file name order. If the directory holds no images, the `rgb` (TUM RGB-D),
+///   `mav0/cam0/data` (`EuRoC`), `cam0/data` and `data` subdirectories are searched, in that order.
+/// - JPEG images and AVI files are served as [`FrameFormat::MJPEG`]. PNG images are decoded and served as
+///   [`FrameFormat::GRAY`] or [`FrameFormat::RAWRGB`], depending on whether they have color. Alpha and 16-bit depth are dropped.
+/// - All frames must have the same resolution, the resolution cannot be changed.
+/// - Image sequences default to 30 FPS. [`set_frame_rate()`](CaptureBackendTrait::set_frame_rate) accepts any non-zero rate.
+/// - Opening the stream restarts the sequence from 0. Sequence numbers keep counting across loops and seeks, and
+///   buffer timestamps are the sequence number at the current frame rate, not wall-clock time.
+/// - There are no camera controls.
+/// - When the input runs out, [`frame()`](CaptureBackendTrait::frame) errors and the stream closes, unless looping is enabled.
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "input-file")))]
+pub struct FileCaptureDevice {
+    source: MediaSource,
+    info: CameraInfo,
+    native_frame_rate: u32,
+    camera_format: CameraFormat,
+    looping: bool,
+    position: usize,
+    sequence: u64,
+    stream_open: bool,
+    // Wall clock time and sequence number of the first frame served since opening, seeking or changing the frame rate
+    pacing_origin: Option<(Instant, u64)>,
+}
+
+impl FileCaptureDevice {
+    /// Opens the directory or AVI file at the path held by `index`.
+    /// # Errors
+    /// This function will error if the path holds no readable images or video, or it does not fulfill the requested format.
+    pub fn new(index: &CameraIndex, camera_fmt: RequestedFormat) -> Result<Self, NokhwaError> {
+        let path = PathBuf::from(index.as_string());
+
+        let (source, resolution, frame_format, native_frame_rate, description) = if path.is_dir() {
+            let (kind, files) = find_image_sequence(&path)?;
+            let (resolution, frame_format) = probe_image(kind, &files[0])?;
+            (
+                MediaSource::ImageSequence { kind, files },
+                resolution,
+                frame_format,
+                IMAGE_SEQUENCE_FRAME_RATE,
+                "Image Sequence",
+            )
+        } else {
+            let (file, frames, resolution, frame_rate) = open_motion_jpeg(&path)?;
+            (
+                MediaSource::MotionJpeg { file, frames },
+                resolution,
+                FrameFormat::MJPEG,
+                frame_rate,
+                "Motion JPEG Video",
+            )
+        };
+
+        let name = path.file_name().map_or_else(
+            || path.to_string_lossy().to_string(),
+            |name| name.to_string_lossy().to_string(),
+        );
+        let info = CameraInfo::new(&name, description, &path.to_string_lossy(), index.clone());
+
+        let compatible = frame_rates(native_frame_rate)
+            .into_iter()
+            .map(|frame_rate| CameraFormat::new(resolution, frame_format, frame_rate))
+            .collect::<Vec<CameraFormat>>();
+        let camera_format = camera_fmt
+            .fulfill(&compatible)
+            .filter(|format| {
+                format.resolution() == resolution
+                    && format.format() == frame_format
+                    && format.frame_rate() > 0
+            })
+            .ok_or(NokhwaError::InitializeError {
+                backend: ApiBackend::MediaFile,
+                error: format!(
+                    "Failed to fulfill requested format, input is {resolution} {frame_format}"
+                ),
+            })?;
+
+        Ok(FileCaptureDevice {
+            source,
+            info,
+            native_frame_rate,
+            camera_format,
+            looping: false,
+            position: 0,
+            sequence: 0,
+            stream_open: false,
+            pacing_origin: None,
+        })
+    }
+
+    /// The number of frames in the input.
+    #[must_use]
+    pub fn frame_count(&self) -> usize {
+        match &self.source {
+            MediaSource::ImageSequence { files, .. } => files.len(),
+            MediaSource::MotionJpeg { frames, .. } => frames.len(),
+        }
+    }
+
+    /// Whether playback restarts from the first frame when the input runs out.
+    #[must_use]
+    pub fn looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Sets whether playback restarts from the first frame when the input runs out.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// The index of the next frame to be served.
+    #[must_use]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Makes `position` the next frame to be served.
+    /// # Errors
+    /// If `position` is past the end of the input, this will error.
+    pub fn seek(&mut self, position: usize) -> Result<(), NokhwaError> {
+        if position > self.frame_count() {
+            return Err(NokhwaError::SetPropertyError {
+                property: "position".to_string(),
+                value: position.to_string(),
+                error: format!("Input only has {} frames", self.frame_count()),
+            });
+        }
+
+        self.position = position;
+        self.pacing_origin = None;
+        Ok(())
+    }
+
+    /// Waits until the next frame is due according to the frame rate.
+    fn pace(&mut self) {
+        let (origin, origin_sequence) = *self
+            .pacing_origin
+            .get_or_insert((Instant::now(), self.sequence));
+
+        #[allow(clippy::cast_possible_truncation)]
+        let frames_since_origin = (self.sequence - origin_sequence) as u32;
+        let due =
+            origin + Duration::from_secs(1) * frames_since_origin / self.camera_format.frame_rate();
+        let now = Instant::now();
+
+        if due > now {
+            std::thread::sleep(due - now);
+        }
+    }
+
+    fn read_frame(&mut self, position: usize) -> Result<Vec<u8>, NokhwaError> {
+        let resolution = self.camera_format.resolution();
+        match &mut self.source {
+            MediaSource::ImageSequence { kind, files } => {
+                let path = &files[position];
+                let payload = std::fs::read(path).map_err(|why| {
+                    NokhwaError::ReadFrameError(format!("{}: {why}", path.to_string_lossy()))
+                })?;
+
+                match kind {
+                    ImageKind::Jpeg => {
+                        if jpeg_resolution(&payload) != Some(resolution) {
+                            return Err(NokhwaError::ReadFrameError(format!(
+                                "{}: Image is not {resolution}",
+                                path.to_string_lossy()
+                            )));
+                        }
+                        Ok(payload)
+                    }
+                    ImageKind::Png => {
+                        let image = image::load_from_memory_with_format(&payload, ImageFormat::Png)
+                            .map_err(|why| {
+                                NokhwaError::ReadFrameError(format!(
+                                    "{}: {why}",
+                                    path.to_string_lossy()
+                                ))
+                            })?;
+
+                        if image.width() != resolution.width()
+                            || image.height() != resolution.height()
+                        {
+                            return Err(NokhwaError::ReadFrameError(format!(
+                                "{}: Image is not {resolution}",
+                                path.to_string_lossy()
+                            )));
+                        }
+
+                        Ok(match self.camera_format.format() {
+                            FrameFormat::GRAY => image.into_luma8().into_raw(),
+                            _ => image.into_rgb8().into_raw(),
+                        })
+                    }
+                }
+            }
+            MediaSource::MotionJpeg { file, frames } => {
+                let (offset, length) = frames[position];
+                let mut payload = vec![0; length as usize];
+                file.seek(SeekFrom::Start(offset))
+                    .and_then(|_| file.read_exact(&mut payload))
+                    .map_err(|why| NokhwaError::ReadFrameError(why.to_string()))?;
+                Ok(payload)
+            }
+        }
+    }
+
+    fn next_frame(&mut self) -> Result<Vec<u8>, NokhwaError> {
+        if !self.stream_open {
+            return Err(NokhwaError::ReadFrameError(
+                "Stream is not open".to_string(),
+            ));
+        }
+
+        if self.position >= self.frame_count() {
+            if !self.looping || self.frame_count() == 0 {
+                // Closing the stream tells readers such as `CallbackCamera` to stop instead of retrying
+                self.stream_open = false;
+                return Err(NokhwaError::ReadFrameError("End of input".to_string()));
+            }
+
+            self.position = 0;
+        }
+
+        self.pace();
+        let payload = self.read_frame(self.position)?;
+        self.position += 1;
+        self.sequence += 1;
+        Ok(payload)
+    }
+}
+
+impl CaptureBackendTrait for FileCaptureDevice {
+    fn backend(&self) -> ApiBackend {
+        ApiBackend::MediaFile
+    }
+
+    fn camera_info(&self) -> &CameraInfo {
+        &self.info
+    }
+
+    fn refresh_camera_format(&mut self) -> Result<(), NokhwaError> {
+        Ok(())
+    }
+
+    fn camera_format(&self) -> CameraFormat {
+        self.camera_format
+    }
+
+    fn set_camera_format(&mut self, new_fmt: CameraFormat) -> Result<(), NokhwaError> {
+        if new_fmt.resolution() != self.camera_format.resolution()
+            || new_fmt.format() != self.camera_format.format()
+            || new_fmt.frame_rate() == 0
+        {
+            return Err(NokhwaError::SetPropertyError {
+                property: "CameraFormat".to_string(),
+                value: new_fmt.to_string(),
+                error: format!(
+                    "Input is {} {}",
+                    self.camera_format.resolution(),
+                    self.camera_format.format()
+                ),
+            });
+        }
+
+        self.camera_format = new_fmt;
+        self.pacing_origin = None;
+        Ok(())
+    }
+
+    fn compatible_list_by_resolution(
+        &mut self,
+        fourcc: FrameFormat,
+    ) -> Result<HashMap<Resolution, Vec<u32>>, NokhwaError> {
+        let mut resolution_map = HashMap::new();
+        if fourcc == self.camera_format.format() {
+            resolution_map.insert(
+                self.camera_format.resolution(),
+                frame_rates(self.native_frame_rate),
+            );
+        }
+        Ok(resolution_map)
+    }
+
+    fn compatible_fourcc(&mut self) -> Result<Vec<FrameFormat>, NokhwaError> {
+        Ok(vec![self.camera_format.format()])
+    }
+
+    fn resolution(&self) -> Resolution {
+        self.camera_format.resolution()
+    }
+
+    fn set_resolution(&mut self, new_res: Resolution) -> Result<(), NokhwaError> {
+        let mut new_format = self.camera_format;
+        new_format.set_resolution(new_res);
+        self.set_camera_format(new_format)
+    }
+
+    fn frame_rate(&self) -> u32 {
+        self.camera_format.frame_rate()
+    }
+
+    fn set_frame_rate(&mut self, new_fps: u32) -> Result<(), NokhwaError> {
+        let mut new_format = self.camera_format;
+        new_format.set_frame_rate(new_fps);
+        self.set_camera_format(new_format)
+    }
+
+    fn frame_format(&self) -> FrameFormat {
+        self.camera_format.format()
+    }
+
+    fn set_frame_format(&mut self, fourcc: FrameFormat) -> Result<(), NokhwaError> {
+        let mut new_format = self.camera_format;
+        new_format.set_format(fourcc);
+        self.set_camera_format(new_format)
+    }
+
+    fn camera_control(&self, _: KnownCameraControl) -> Result<CameraControl, NokhwaError> {
+        Err(NokhwaError::UnsupportedOperationError(
+            ApiBackend::MediaFile,
+        ))
+    }
+
+    fn camera_controls(&self) -> Result<Vec<CameraControl>, NokhwaError> {
+        Ok(vec![])
+    }
+
+    fn set_camera_control(
+        &mut self,
+        _: KnownCameraControl,
+        _: ControlValueSetter,
+    ) -> Result<(), NokhwaError> {
+        Err(NokhwaError::UnsupportedOperationError(
+            ApiBackend::MediaFile,
+        ))
+    }
+
+    fn open_stream(&mut self) -> Result<(), NokhwaError> {
+        self.stream_open = true;
+        self.sequence = 0;
+        self.pacing_origin = None;
+        Ok(())
+    }
+
+    fn is_stream_open(&self) -> bool {
+        self.stream_open
+    }
+
+    fn frame(&mut self) -> Result<Buffer, NokhwaError> {
+        let payload = self.next_frame()?;
+        let sequence = self.sequence - 1;
+        Ok(Buffer::new(
+            self.camera_format.resolution(),
+            &payload,
+            self.camera_format.format(),
+        )
+        .with_timestamp(Duration::from_secs(sequence) / self.camera_format.frame_rate())
+        .with_sequence(sequence))
+    }
+
+    fn frame_raw(&mut self) -> Result<Cow<[u8]>, NokhwaError> {
+        Ok(Cow::Owned(self.next_frame()?))
+    }
+
+    fn stop_stream(&mut self) -> Result<(), NokhwaError> {
+        self.stream_open = false;
+        Ok(())
+    }
+}
+
+fn initialize_error(path: &Path, error: impl std::fmt::Display) -> NokhwaError {
+    NokhwaError::InitializeError {
+        backend: ApiBackend::MediaFile,
+        error: format!("{}: {error}", path.to_string_lossy()),
+    }
+}
+
+fn frame_rates(native_frame_rate: u32) -> Vec<u32> {
+    let mut frame_rates = STANDARD_FRAME_RATES
+        .into_iter()
+        .filter(|frame_rate| *frame_rate < native_frame_rate)
+        .collect::<Vec<u32>>();
+    frame_rates.push(native_frame_rate);
+    frame_rates
+}
+
+fn image_kind(path: &Path) -> Option<ImageKind> {
+    let extension = path.extension()?.to_string_lossy().to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => Some(ImageKind::Png),
+        "jpg" | "jpeg" => Some(ImageKind::Jpeg),
+        _ => None,
+    }
+}
+
+fn list_images(directory: &Path) -> Result<Vec<PathBuf>, NokhwaError> {
+    let mut images = std::fs::read_dir(directory)
+        .map_err(|why| initialize_error(directory, why))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && image_kind(path).is_some())
+        .collect::<Vec<PathBuf>>();
+    images.sort();
+    Ok(images)
+}
+
+fn find_image_sequence(directory: &Path) -> Result<(ImageKind, Vec<PathBuf>), NokhwaError> {
+    let mut images = list_images(directory)?;
+    for subdirectory in DATASET_IMAGE_DIRECTORIES {
+        if !images.is_empty() {
+            break;
+        }
+        let candidate = directory.join(subdirectory);
+        if candidate.is_dir() {
+            images = list_images(&candidate)?;
+        }
+    }
+
+    let kind = match images.first() {
+        Some(first) => image_kind(first),
+        None => None,
+    }
+    .ok_or_else(|| initialize_error(directory, "No PNG or JPEG images found"))?;
+
+    if images.iter().any(|path| image_kind(path) != Some(kind)) {
+        return Err(initialize_error(
+            directory,
+            "Image sequence mixes PNG and JPEG images",
+        ));
+    }
+
+    Ok((kind, images))
+}
+
+fn probe_image(kind: ImageKind, path: &Path) -> Result<(Resolution, FrameFormat), NokhwaError> {
+    let mut header = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(64 * 1024).read_to_end(&mut header))
+        .map_err(|why| initialize_error(path, why))?;
+
+    match kind {
+        ImageKind::Png => {
+            // The IHDR chunk always comes first: width, height, bit depth, color type
+            if header.len() < 26 || header[..8] != PNG_SIGNATURE || &header[12..16] != b"IHDR" {
+                return Err(initialize_error(path, "Not a PNG image"));
+            }
+            let width = u32::from_be_bytes([header[16], header[17], header[18], header[19]]);
+            let height = u32::from_be_bytes([header[20], header[21], header[22], header[23]]);
+            // Color types 0 and 4 are grayscale without and with alpha
+            let frame_format = match header[25] {
+                0 | 4 => FrameFormat::GRAY,
+                _ => FrameFormat::RAWRGB,
+            };
+            Ok((Resolution::new(width, height), frame_format))
+        }
+        ImageKind::Jpeg => jpeg_resolution(&header)
+            .map(|resolution| (resolution, FrameFormat::MJPEG))
+            .ok_or_else(|| initialize_error(path, "Not a JPEG image")),
+    }
+}
+
+/// Reads the resolution from the start-of-frame segment of a JPEG image.
+fn jpeg_resolution(data: &[u8]) -> Option<Resolution> {
+    if data.get(..2)? != JPEG_SIGNATURE {
+        return None;
+    }
+
+    let mut offset = 2;
+    loop {
+        if *data.get(offset)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(offset + 1)?;
+        // Fill bytes may precede a marker
+        if marker == 0xFF {
+            offset += 1;
+            continue;
+        }
+        let length = usize::from(u16::from_be_bytes([
+            *data.get(offset + 2)?,
+            *data.get(offset + 3)?,
+        ]));
+
+        // SOF0 to SOF15, except DHT (C4), JPG (C8) and DAC (CC)
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let segment = data.get(offset + 4..offset + 9)?;
+            let height = u32::from(u16::from_be_bytes([segment[1], segment[2]]));
+            let width = u32::from(u16::from_be_bytes([segment[3], segment[4]]));
+            return Some(Resolution::new(width, height));
+        }
+
+        offset += 2 + length;
+    }
+}
+
+#[derive(Default)]
+struct AviScan {
+    microseconds_per_frame: u32,
+    resolution: Option<Resolution>,
+    stream_count: u32,
+    video_stream: Option<u32>,
+    compression: Option<[u8; 4]>,
+    frames: Vec<(u64, u32)>,
+}
+
+fn read_bytes<const N: usize>(file: &mut File, offset: u64) -> std::io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_chunk_header(file: &mut File, offset: u64) -> std::io::Result<([u8; 4], u32)> {
+    let header = read_bytes::<8>(file, offset)?;
+    Ok((
+        [header[0], header[1], header[2], header[3]],
+        u32::from_le_bytes([header[4], header[5], header[6], header[7]]),
+    ))
+}
+
+/// Walks the chunks in `start..end`, descending into the lists that hold stream headers and frames.
+fn scan_avi_list(file: &mut File, start: u64, end: u64, scan: &mut AviScan) -> std::io::Result<()> {
+    let mut offset = start;
+    while offset + 8 <= end {
+        let (id, size) = read_chunk_header(file, offset)?;
+        let data = offset + 8;
+
+        match &id {
+            b"LIST" => {
+                let list_type = read_bytes::<4>(file, data)?;
+                if &list_type == b"strl" {
+                    scan.stream_count += 1;
+                }
+                if matches!(&list_type, b"hdrl" | b"strl" | b"movi" | b"rec ") {
+                    scan_avi_list(file, data + 4, (data + u64::from(size)).min(end), scan)?;
+                }
+            }
+            b"avih" => {
+                let header = read_bytes::<40>(file, data)?;
+                scan.microseconds_per_frame =
+                    u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+            }
+            b"strh" => {
+                let header = read_bytes::<4>(file, data)?;
+                if &header == b"vids" && scan.video_stream.is_none() {
+                    scan.video_stream = scan.stream_count.checked_sub(1);
+                }
+            }
+            b"strf"
+                if scan.video_stream.is_some()
+                    && scan.video_stream == scan.stream_count.checked_sub(1)
+                    && scan.resolution.is_none() =>
+            {
+                // BITMAPINFOHEADER, a negative height marks a top-down bitmap
+                let header = read_bytes::<20>(file, data)?;
+                let width = i32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+                let height = i32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+                scan.resolution =
+                    Some(Resolution::new(width.unsigned_abs(), height.unsigned_abs()));
+                scan.compression = Some([header[16], header[17], header[18], header[19]]);
+            }
+            _ => {
+                // Frame chunks are named after their stream number, e.g. `00dc`. A chunk running
+                // past its list or the file is corrupt or truncated, never read it.
+                if let Some(stream) = scan.video_stream {
+                    let stream_id = format!("{stream:02}");
+                    if id[..2] == *stream_id.as_bytes()
+                        && matches!(&id[2..], b"dc" | b"db")
+                        && size > 0
+                        && data + u64::from(size) <= end
+                    {
+                        scan.frames.push((data, size));
+                    }
+                }
+            }
+        }
+
+        // Chunks are padded to an even size
+        offset = data + u64::from(size) + u64::from(size % 2);
+    }
+    Ok(())
+}
+
+/// The opened file, the location of every frame, the resolution and the frame rate of a Motion-JPEG AVI file.
+type MotionJpegFile = (File, Vec<(u64, u32)>, Resolution, u32);
+
+fn open_motion_jpeg(path: &Path) -> Result<MotionJpegFile, NokhwaError> {
+    let mut file = File::open(path).map_err(|why| initialize_error(path, why))?;
+    let length = file
+        .metadata()
+        .map_err(|why| initialize_error(path, why))?
+        .len();
+
+    let mut scan = AviScan::default();
+    let mut offset = 0;
+    // Files over 1GB continue in further `AVIX` RIFF chunks
+    while offset + 12 <= length {
+        let (id, size) =
+            read_chunk_header(&mut file, offset).map_err(|why| initialize_error(path, why))?;
+        let form =
+            read_bytes::<4>(&mut file, offset + 8).map_err(|why| initialize_error(path, why))?;
+        if &id != b"RIFF" || !matches!(&form, b"AVI " | b"AVIX") {
+            return Err(initialize_error(path, "Not an AVI file"));
+        }
+
+        let end = (offset + 8 + u64::from(size)).min(length);
+        scan_avi_list(&mut file, offset + 12, end, &mut scan)
+            .map_err(|why| initialize_error(path, why))?;
+        offset = offset + 8 + u64::from(size) + u64::from(size % 2);
+    }
+
+    let is_motion_jpeg = matches!(scan.compression, Some(compression)
+        if compression.eq_ignore_ascii_case(b"MJPG") || compression.eq_ignore_ascii_case(b"JPEG"));
+    let resolution = match scan.resolution {
+        Some(resolution) if is_motion_jpeg => resolution,
+        _ => {
+            return Err(initialize_error(
+                path,
+                "AVI file has no Motion-JPEG video stream",
+            ))
+        }
+    };
+    if scan.frames.is_empty() {
+        return Err(initialize_error(path, "AVI file has no frames"));
+    }
+
+    let frame_rate = match scan.microseconds_per_frame {
+        0 => IMAGE_SEQUENCE_FRAME_RATE,
+        microseconds => ((1_000_000 + microseconds / 2) / microseconds).max(1),
+    };
+
+    Ok((file, scan.frames, resolution, frame_rate))
+}
+
+#[cfg(all(test, feature = "input-file"))]
+mod tests {
+    use super::*;
+    use crate::{pixel_format::RgbFormat, test_util::TempDirectory};
+    use nokhwa_core::types::RequestedFormatType;
+
+    /// The markers of a JPEG image up to its baseline start-of-frame segment, after a comment segment.
+    fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        let mut data = JPEG_SIGNATURE.to_vec();
+        data.extend_from_slice(&[0xFF, 0xFE, 0x00, 0x04, b'h', b'i']);
+        data.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x11, 0x08]);
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&[0x03, 1, 0x22, 0, 2, 0x11, 1, 3, 0x11, 1]);
+        data.extend_from_slice(&[0xFF, 0xD9]);
+        data
+    }
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend_from_slice(&u32::try_from(data.len()).unwrap().to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn list(list_type: &[u8; 4], chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = list_type.to_vec();
+        for chunk in chunks {
+            data.extend_from_slice(chunk);
+        }
+        chunk(b"LIST", &data)
+    }
+
+    /// A Motion-JPEG AVI file at 25 FPS holding `frames`, whose last frame chunk is cut short by `truncate` bytes.
+    fn avi(width: u16, height: u16, frames: &[Vec<u8>], truncate: usize) -> Vec<u8> {
+        let mut avih = [0; 56];
+        avih[..4].copy_from_slice(&40_000_u32.to_le_bytes());
+        let mut strh = [0; 56];
+        strh[..4].copy_from_slice(b"vids");
+        strh[4..8].copy_from_slice(b"MJPG");
+        let mut strf = [0; 40];
+        strf[..4].copy_from_slice(&40_u32.to_le_bytes());
+        strf[4..8].copy_from_slice(&i32::from(width).to_le_bytes());
+        // A negative height marks a top-down bitmap, the resolution must not depend on it
+        strf[8..12].copy_from_slice(&(-i32::from(height)).to_le_bytes());
+        strf[16..20].copy_from_slice(b"MJPG");
+
+        let header = list(
+            b"hdrl",
+            &[
+                chunk(b"avih", &avih),
+                list(b"strl", &[chunk(b"strh", &strh), chunk(b"strf", &strf)]),
+            ],
+        );
+        // A junk chunk between frames must not be taken for a frame
+        let mut movi = vec![chunk(b"JUNK", &[0; 3])];
+        movi.extend(frames.iter().map(|frame| chunk(b"00dc", frame)));
+        let movi = list(b"movi", &movi);
+
+        let mut riff = b"AVI ".to_vec();
+        riff.extend_from_slice(&header);
+        riff.extend_from_slice(&movi);
+        let mut file = chunk(b"RIFF", &riff);
+        file.truncate(file.len() - truncate);
+        file
+    }
+
+    fn open(path: &Path) -> Result<FileCaptureDevice, NokhwaError> {
+        FileCaptureDevice::new(
+            &CameraIndex::String(path.to_string_lossy().to_string()),
+            RequestedFormat::new::<RgbFormat>(RequestedFormatType::AbsoluteHighestFrameRate),
+        )
+    }
+
+    #[test]
+    fn reads_jpeg_resolution() {
+        assert_eq!(
+            jpeg_resolution(&jpeg(640, 480)),
+            Some(Resolution::new(640, 480))
+        );
+
+        // Fill bytes before a marker are skipped
+        let mut filled = jpeg(320, 240);
+        filled.insert(2, 0xFF);
+        assert_eq!(jpeg_resolution(&filled), Some(Resolution::new(320, 240)));
+
+        // Progressive start-of-frame
+        let mut progressive = jpeg(32, 24);
+        progressive[9] = 0xC2;
+        assert_eq!(jpeg_resolution(&progressive), Some(Resolution::new(32, 24)));
+
+        assert_eq!(jpeg_resolution(&PNG_SIGNATURE), None);
+        assert_eq!(jpeg_resolution(&jpeg(640, 480)[..12]), None);
+        let mut corrupt = jpeg(640, 480);
+        corrupt[8] = 0x00;
+        assert_eq!(jpeg_resolution(&corrupt), None);
+    }
+
+    #[test]
+    fn walks_avi_chunks_and_skips_truncated_frames() {
+        let directory = TempDirectory::new("file-avi");
+        let frames = (0..4)
+            .map(|index| {
+                let mut frame = jpeg(64, 48);
+                // Odd lengths exercise the chunk padding
+                frame.extend(std::iter::repeat(index).take(usize::from(index) + 1));
+                frame
+            })
+            .collect::<Vec<Vec<u8>>>();
+
+        let complete = directory.path().join("complete.avi");
+        std::fs::write(&complete, avi(64, 48, &frames, 0)).unwrap();
+        let mut device = open(&complete).unwrap();
+        assert_eq!(device.frame_count(), 4);
+        assert_eq!(device.camera_format().resolution(), Resolution::new(64, 48));
+        assert_eq!(device.camera_format().format(), FrameFormat::MJPEG);
+        assert_eq!(device.camera_format().frame_rate(), 25);
+
+        device.set_frame_rate(1000).unwrap();
+        device.open_stream().unwrap();
+        for frame in &frames {
+            assert_eq!(device.frame_raw().unwrap().as_ref(), frame.as_slice());
+        }
+
+        let truncated = directory.path().join("truncated.avi");
+        std::fs::write(&truncated, avi(64, 48, &frames, 3)).unwrap();
+        let device = open(&truncated).unwrap();
+        assert_eq!(device.frame_count(), 3);
+
+        let not_avi = directory.path().join("not.avi");
+        std::fs::write(&not_avi, jpeg(64, 48)).unwrap();
+        assert!(open(&not_avi).is_err());
+        let no_frames = directory.path().join("empty.avi");
+        std::fs::write(&no_frames, avi(64, 48, &[], 0)).unwrap();
+        assert!(open(&no_frames).is_err());
+    }
+
+    #[test]
+    fn finds_images_in_dataset_layouts() {
+        for subdirectory in DATASET_IMAGE_DIRECTORIES {
+            let directory = TempDirectory::new(&format!("file-{}", subdirectory.replace('/', "-")));
+            let images = directory.path().join(subdirectory);
+            std::fs::create_dir_all(&images).unwrap();
+            std::fs::write(images.join("2.jpg"), jpeg(32, 24)).unwrap();
+            std::fs::write(images.join("1.jpg"), jpeg(32, 24)).unwrap();
+            std::fs::write(images.join("notes.txt"), "not an image").unwrap();
+
+            let (kind, files) = find_image_sequence(&directory.path()).unwrap();
+            assert_eq!(kind, ImageKind::Jpeg);
+            assert_eq!(files, [images.join("1.jpg"), images.join("2.jpg")]);
+        }
+
+        // Images in the directory itself win over dataset subdirectories
+        let directory = TempDirectory::new("file-direct");
+        std::fs::create_dir_all(directory.path().join("rgb")).unwrap();
+        std::fs::write(directory.path().join("rgb/1.jpg"), jpeg(32, 24)).unwrap();
+        std::fs::write(directory.path().join("0.jpg"), jpeg(32, 24)).unwrap();
+        let (_, files) = find_image_sequence(&directory.path()).unwrap();
+        assert_eq!(files, [directory.path().join("0.jpg")]);
+
+        std::fs::write(directory.path().join("1.png"), PNG_SIGNATURE).unwrap();
+        assert!(find_image_sequence(&directory.path()).is_err());
+        assert!(find_image_sequence(&directory.path().join("rgb/missing")).is_err());
+        let empty = TempDirectory::new("file-empty");
+        assert!(find_image_sequence(&empty.path()).is_err());
+    }
+
+    #[test]
+    fn looping_keeps_sequence_numbers_running() {
+        let directory = TempDirectory::new("file-looping");
+        for index in 0..3 {
+            std::fs::write(directory.path().join(format!("{index}.jpg")), jpeg(32, 24)).unwrap();
+        }
+
+        let mut device = open(&directory.path()).unwrap();
+        device.set_frame_rate(1000).unwrap();
+        device.set_looping(true);
+        device.open_stream().unwrap();
+        for sequence in 0..7 {
+            let frame = device.frame().unwrap();
+            assert_eq!(frame.sequence(), Some(sequence));
+            assert_eq!(frame.timestamp(), Some(Duration::from_millis(sequence)));
+        }
+        assert_eq!(device.position(), 1);
+
+        device.seek(0).unwrap();
+        assert_eq!(device.frame().unwrap().sequence(), Some(7));
+
+        // Without looping the stream closes at the end of the input
+        device.set_looping(false);
+        device.frame().unwrap();
+        device.frame().unwrap();
+        assert!(device.frame().is_err());
+        assert!(!device.is_stream_open());
+
+        device.seek(0).unwrap();
+        device.open_stream().unwrap();
+        assert_eq!(device.frame().unwrap().sequence(), Some(0));
+    }
+}
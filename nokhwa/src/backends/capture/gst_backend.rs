@@ -16,7 +16,7 @@
 
 use crate::{
     mjpeg_to_rgb, yuyv422_to_rgb, ApiBackend, CameraControl, CameraFormat, CameraInfo,
-    CaptureBackendTrait, FrameFormat, KnownCameraControl, NokhwaError, Resolution,
+    CaptureBackendTrait, Colorimetry, FrameFormat, KnownCameraControl, NokhwaError, Resolution,
 };
 use glib::Quark;
 use gstreamer::{
@@ -721,18 +721,20 @@ fn generate_pipeline(fmt: CameraFormat, index: usize) -> Result<PipelineGenRet,
 
                 let image_buffer = match video_info.format() {
                     VideoFormat::Yuy2 => {
-                        let mut decoded_buffer = match yuyv422_to_rgb(&buffer_map, false) {
-                            Ok(buf) => buf,
-                            Err(why) => {
-                                element_error!(
-                                    appsink,
-                                    ResourceError::Failed,
-                                    (format!("Failed to make yuy2 into rgb888: {}", why).as_str())
-                                );
-
-                                return Err(FlowError::Error);
-                            }
-                        };
+                        let mut decoded_buffer =
+                            match yuyv422_to_rgb(&buffer_map, false, Colorimetry::default()) {
+                                Ok(buf) => buf,
+                                Err(why) => {
+                                    element_error!(
+                                        appsink,
+                                        ResourceError::Failed,
+                                        (format!("Failed to make yuy2 into rgb888: {}", why)
+                                            .as_str())
+                                    );
+
+                                    return Err(FlowError::Error);
+                                }
+                            };
 
                         decoded_buffer.resize(
                             (video_info.width() * video_info.height() * channels) as usize,
@@ -81,3 +81,18 @@ mod opencv_backend;
 #[cfg(feature = "input-opencv")]
 #[cfg_attr(feature = "docs-features", doc(cfg(feature = "input-opencv")))]
 pub use opencv_backend::OpenCvCaptureDevice;
+#[cfg(feature = "input-replay")]
+mod replay_backend;
+#[cfg(feature = "input-replay")]
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "input-replay")))]
+pub use replay_backend::{ReplayCaptureDevice, ReplaySpeed};
+#[cfg(feature = "input-file")]
+mod file_backend;
+#[cfg(feature = "input-file")]
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "input-file")))]
+pub use file_backend::FileCaptureDevice;
+#[cfg(feature = "input-virtual")]
+mod virtual_backend;
+#[cfg(feature = "input-virtual")]
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "input-virtual")))]
+pub use virtual_backend::VirtualCaptureDevice;
@@ -234,7 +234,6 @@ impl CaptureBackendTrait for MediaFoundationCaptureDevice {
     }
 
     fn open_stream(&mut self) -> Result<(), NokhwaError> {
-        
         self.inner.start_stream()
     }
 
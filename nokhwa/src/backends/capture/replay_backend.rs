@@ -0,0 +1,486 @@
+/*
+ * Copyright 2022 l1npengtul <l1npengtul@protonmail.com> / The Nokhwa Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::recording::{RecordedFrame, RecordingIndex};
+use image::ImageFormat;
+use nokhwa_core::{
+    buffer::Buffer,
+    error::NokhwaError,
+    traits::CaptureBackendTrait,
+    types::{
+        ApiBackend, CameraControl, CameraFormat, CameraIndex, CameraInfo, ControlValueSetter,
+        FrameFormat, KnownCameraControl, RequestedFormat, Resolution,
+    },
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// How fast a [`ReplayCaptureDevice`] serves frames.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ReplaySpeed {
+    /// Frames are served with the same spacing they were captured with.
+    Recorded,
+    /// Frames are served this many times faster than they were captured.
+    Accelerated(f64),
+    /// Frames are served as soon as they are requested.
+    Unthrottled,
+}
+
+/// Plays back a session written by [`Recorder`](crate::recording::Recorder), or one stream of a
+/// [TUM RGB-D](crate::recording#tum-rgb-d) dataset.
+/// To see what this does, please see [`CaptureBackendTrait`].
+///
+/// Every frame is served exactly once and in order, regardless of how fast they are consumed, so
+/// runs over the same recording are deterministic. The pacing only ever delays frames.
+/// # Quirks
+/// - The [`CameraIndex`] must be a [`CameraIndex::String`] holding the path of the recording directory, or of a TUM RGB-D
+///   image list (`rgb.txt` or `depth.txt`). Pair the two streams of a TUM RGB-D dataset with [`RgbdCamera::tum()`](crate::rgbd::RgbdCamera::tum).
+/// - Only the recorded [`CameraFormat`] is available, other formats are rejected.
+/// - Buffers carry the recorded timestamps, sequence numbers and colorimetry, gaps in the recorded sequence are reported as dropped frames.
+/// - PNG frames are decoded when they are read, TUM RGB-D lists are only as fast as the images decode.
+/// - There are no camera controls.
+/// - When the recording runs out, [`frame()`](CaptureBackendTrait::frame) errors and the stream closes, unless looping is enabled.
+/// - The speed and looping can only be changed on the raw backend, create the [`Camera`](crate::Camera) with [`Camera::with_custom`](crate::Camera::with_custom) to keep access to them.
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "input-replay")))]
+pub struct ReplayCaptureDevice {
+    directory: PathBuf,
+    index: RecordingIndex,
+    info: CameraInfo,
+    camera_format: CameraFormat,
+    speed: ReplaySpeed,
+    looping: bool,
+    position: usize,
+    stream_open: bool,
+    // Wall clock time and recorded timestamp of the first frame served since opening or seeking
+    pacing_origin: Option<(Instant, Duration)>,
+}
+
+impl ReplayCaptureDevice {
+    /// Opens the recording or TUM RGB-D image list at the path held by `index`.
+    /// # Errors
+    /// This function will error if the recording cannot be read, or it does not fulfill the requested format.
+    pub fn new(index: &CameraIndex, camera_fmt: RequestedFormat) -> Result<Self, NokhwaError> {
+        let path = PathBuf::from(index.as_string());
+        let (directory, recording) = if path.is_file() {
+            let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            (directory, RecordingIndex::load_tum(&path)?)
+        } else {
+            let recording = RecordingIndex::load(&path)?;
+            (path, recording)
+        };
+
+        let recorded_format = recording.camera_format();
+        // Requested formats carry no colorimetry, the recorded one is used as is
+        let camera_format = camera_fmt
+            .fulfill(&[recorded_format])
+            .filter(|format| {
+                format.with_colorimetry(recorded_format.colorimetry()) == recorded_format
+            })
+            .map(|_| recorded_format)
+            .ok_or(NokhwaError::InitializeError {
+                backend: ApiBackend::Replay,
+                error: format!(
+                    "Failed to fulfill requested format, recording is {recorded_format}"
+                ),
+            })?;
+
+        let info = CameraInfo::new(
+            recording.camera_name(),
+            recording.description(),
+            &index.as_string(),
+            index.clone(),
+        );
+
+        Ok(ReplayCaptureDevice {
+            directory,
+            index: recording,
+            info,
+            camera_format,
+            speed: ReplaySpeed::Recorded,
+            looping: false,
+            position: 0,
+            stream_open: false,
+            pacing_origin: None,
+        })
+    }
+
+    /// The index of the recording being played back.
+    #[must_use]
+    pub fn recording(&self) -> &RecordingIndex {
+        &self.index
+    }
+
+    /// The current [`ReplaySpeed`].
+    #[must_use]
+    pub fn speed(&self) -> ReplaySpeed {
+        self.speed
+    }
+
+    /// Sets the [`ReplaySpeed`]. Takes effect from the next frame.
+    pub fn set_speed(&mut self, speed: ReplaySpeed) {
+        self.speed = speed;
+        self.pacing_origin = None;
+    }
+
+    /// Whether playback restarts from the first frame when the recording runs out.
+    #[must_use]
+    pub fn looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Sets whether playback restarts from the first frame when the recording runs out.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// The index of the next frame to be served.
+    #[must_use]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Makes `position` the next frame to be served.
+    /// # Errors
+    /// If `position` is past the end of the recording, this will error.
+    pub fn seek(&mut self, position: usize) -> Result<(), NokhwaError> {
+        if position > self.index.frames().len() {
+            return Err(NokhwaError::SetPropertyError {
+                property: "position".to_string(),
+                value: position.to_string(),
+                error: format!("Recording only has {} frames", self.index.frames().len()),
+            });
+        }
+
+        self.position = position;
+        self.pacing_origin = None;
+        Ok(())
+    }
+
+    /// Waits until `frame` is due according to the [`ReplaySpeed`].
+    fn pace(&mut self, frame: &RecordedFrame) {
+        let factor = match self.speed {
+            ReplaySpeed::Recorded => 1.0,
+            ReplaySpeed::Accelerated(factor) if factor > 0.0 => factor,
+            ReplaySpeed::Accelerated(_) | ReplaySpeed::Unthrottled => return,
+        };
+
+        let (origin, origin_timestamp) = *self
+            .pacing_origin
+            .get_or_insert((Instant::now(), frame.timestamp()));
+
+        let due = origin
+            + frame
+                .timestamp()
+                .saturating_sub(origin_timestamp)
+                .div_f64(factor);
+        let now = Instant::now();
+
+        if due > now {
+            std::thread::sleep(due - now);
+        }
+    }
+
+    fn next_frame(&mut self) -> Result<(RecordedFrame, Vec<u8>), NokhwaError> {
+        if !self.stream_open {
+            return Err(NokhwaError::ReadFrameError(
+                "Stream is not open".to_string(),
+            ));
+        }
+
+        if self.position >= self.index.frames().len() {
+            if !self.looping || self.index.frames().is_empty() {
+                // Closing the stream tells readers such as `CallbackCamera` to stop instead of retrying
+                self.stream_open = false;
+                return Err(NokhwaError::ReadFrameError("End of recording".to_string()));
+            }
+
+            self.position = 0;
+            self.pacing_origin = None;
+        }
+
+        let frame = self.index.frames()[self.position].clone();
+        self.pace(&frame);
+
+        let path = self.directory.join(frame.file());
+        let mut payload = std::fs::read(&path).map_err(|why| {
+            NokhwaError::ReadFrameError(format!("{}: {why}", path.to_string_lossy()))
+        })?;
+        if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+        {
+            payload = decode_png(&path, &payload, &frame)?;
+        }
+
+        self.position += 1;
+        Ok((frame, payload))
+    }
+}
+
+impl CaptureBackendTrait for ReplayCaptureDevice {
+    fn backend(&self) -> ApiBackend {
+        ApiBackend::Replay
+    }
+
+    fn camera_info(&self) -> &CameraInfo {
+        &self.info
+    }
+
+    fn refresh_camera_format(&mut self) -> Result<(), NokhwaError> {
+        Ok(())
+    }
+
+    fn camera_format(&self) -> CameraFormat {
+        self.camera_format
+    }
+
+    fn set_camera_format(&mut self, new_fmt: CameraFormat) -> Result<(), NokhwaError> {
+        if new_fmt.with_colorimetry(self.camera_format.colorimetry()) == self.camera_format {
+            return Ok(());
+        }
+
+        Err(NokhwaError::SetPropertyError {
+            property: "CameraFormat".to_string(),
+            value: new_fmt.to_string(),
+            error: format!("Recording is {}", self.camera_format),
+        })
+    }
+
+    fn compatible_list_by_resolution(
+        &mut self,
+        fourcc: FrameFormat,
+    ) -> Result<HashMap<Resolution, Vec<u32>>, NokhwaError> {
+        let mut resolution_map = HashMap::new();
+        if fourcc == self.camera_format.format() {
+            resolution_map.insert(
+                self.camera_format.resolution(),
+                vec![self.camera_format.frame_rate()],
+            );
+        }
+        Ok(resolution_map)
+    }
+
+    fn compatible_fourcc(&mut self) -> Result<Vec<FrameFormat>, NokhwaError> {
+        Ok(vec![self.camera_format.format()])
+    }
+
+    fn resolution(&self) -> Resolution {
+        self.camera_format.resolution()
+    }
+
+    fn set_resolution(&mut self, new_res: Resolution) -> Result<(), NokhwaError> {
+        let mut new_format = self.camera_format;
+        new_format.set_resolution(new_res);
+        self.set_camera_format(new_format)
+    }
+
+    fn frame_rate(&self) -> u32 {
+        self.camera_format.frame_rate()
+    }
+
+    fn set_frame_rate(&mut self, new_fps: u32) -> Result<(), NokhwaError> {
+        let mut new_format = self.camera_format;
+        new_format.set_frame_rate(new_fps);
+        self.set_camera_format(new_format)
+    }
+
+    fn frame_format(&self) -> FrameFormat {
+        self.camera_format.format()
+    }
+
+    fn set_frame_format(&mut self, fourcc: FrameFormat) -> Result<(), NokhwaError> {
+        let mut new_format = self.camera_format;
+        new_format.set_format(fourcc);
+        self.set_camera_format(new_format)
+    }
+
+    fn camera_control(&self, _: KnownCameraControl) -> Result<CameraControl, NokhwaError> {
+        Err(NokhwaError::UnsupportedOperationError(ApiBackend::Replay))
+    }
+
+    fn camera_controls(&self) -> Result<Vec<CameraControl>, NokhwaError> {
+        Ok(vec![])
+    }
+
+    fn set_camera_control(
+        &mut self,
+        _: KnownCameraControl,
+        _: ControlValueSetter,
+    ) -> Result<(), NokhwaError> {
+        Err(NokhwaError::UnsupportedOperationError(ApiBackend::Replay))
+    }
+
+    fn open_stream(&mut self) -> Result<(), NokhwaError> {
+        self.stream_open = true;
+        self.pacing_origin = None;
+        Ok(())
+    }
+
+    fn is_stream_open(&self) -> bool {
+        self.stream_open
+    }
+
+    fn frame(&mut self) -> Result<Buffer, NokhwaError> {
+        let (frame, payload) = self.next_frame()?;
+        // Gaps in the recorded sequence numbers are frames the recorded camera dropped
+        let dropped_frames = match self.position.checked_sub(2) {
+            Some(previous) => frame
+                .sequence()
+                .saturating_sub(self.index.frames()[previous].sequence())
+                .saturating_sub(1),
+            None => 0,
+        };
+
+        Ok(Buffer::new(frame.resolution(), &payload, frame.format())
+            .with_colorimetry(self.camera_format.colorimetry())
+            .with_timestamp(frame.timestamp())
+            .with_sequence(frame.sequence())
+            .with_dropped_frames(dropped_frames))
+    }
+
+    fn frame_raw(&mut self) -> Result<Cow<[u8]>, NokhwaError> {
+        let (_, payload) = self.next_frame()?;
+        Ok(Cow::Owned(payload))
+    }
+
+    fn stop_stream(&mut self) -> Result<(), NokhwaError> {
+        self.stream_open = false;
+        Ok(())
+    }
+}
+
+/// Decodes a PNG frame into the payload of its recorded format.
+fn decode_png(path: &Path, payload: &[u8], frame: &RecordedFrame) -> Result<Vec<u8>, NokhwaError> {
+    let error =
+        |why: String| NokhwaError::ReadFrameError(format!("{}: {why}", path.to_string_lossy()));
+
+    let image = image::load_from_memory_with_format(payload, ImageFormat::Png)
+        .map_err(|why| error(why.to_string()))?;
+    let resolution = frame.resolution();
+    if image.width() != resolution.width() || image.height() != resolution.height() {
+        return Err(error(format!("Image is not {resolution}")));
+    }
+
+    Ok(match frame.format() {
+        FrameFormat::GRAY => image.into_luma8().into_raw(),
+        FrameFormat::GRAY16 | FrameFormat::Z16 => image
+            .into_luma16()
+            .into_raw()
+            .into_iter()
+            .flat_map(u16::to_le_bytes)
+            .collect(),
+        FrameFormat::RAWRGB => image.into_rgb8().into_raw(),
+        format => return Err(error(format!("Cannot decode PNG images to {format}"))),
+    })
+}
+
+#[cfg(all(test, feature = "input-replay"))]
+mod tests {
+    use super::*;
+    use crate::{pixel_format::Luma16Format, test_util::TempDirectory};
+    use image::{ImageBuffer, Luma};
+    use nokhwa_core::types::RequestedFormatType;
+
+    fn open(path: &Path) -> ReplayCaptureDevice {
+        let mut device = ReplayCaptureDevice::new(
+            &CameraIndex::String(path.to_string_lossy().to_string()),
+            RequestedFormat::new::<Luma16Format>(RequestedFormatType::None),
+        )
+        .unwrap();
+        device.set_speed(ReplaySpeed::Unthrottled);
+        device
+    }
+
+    #[test]
+    fn plays_back_tum_depth_lists() {
+        let directory = TempDirectory::new("replay-tum");
+        std::fs::create_dir_all(directory.path().join("depth")).unwrap();
+        let mut list = String::new();
+        let mut depths = vec![];
+        for index in 0..3_u16 {
+            let samples = (0..8)
+                .map(|pixel| index * 5000 + pixel)
+                .collect::<Vec<u16>>();
+            let file = format!("depth/{index}.png");
+            ImageBuffer::<Luma<u16>, Vec<u16>>::from_raw(4, 2, samples.clone())
+                .unwrap()
+                .save(directory.path().join(&file))
+                .unwrap();
+            list += &format!("1305031102.{} {file}\n", 100 + u32::from(index) * 50);
+            depths.push(samples);
+        }
+        let list_path = directory.path().join("depth.txt");
+        std::fs::write(&list_path, list).unwrap();
+
+        let mut device = open(&list_path);
+        assert_eq!(device.camera_format().format(), FrameFormat::Z16);
+        assert_eq!(device.camera_format().frame_rate(), 20);
+        device.open_stream().unwrap();
+        for (sequence, depth) in depths.iter().enumerate() {
+            let frame = device.frame().unwrap();
+            assert_eq!(frame.sequence(), Some(sequence as u64));
+            // Timestamps are only as precise as the seconds parsed as `f64`
+            let expected = Duration::from_secs_f64(1_305_031_102.1 + sequence as f64 * 0.05);
+            assert!(frame.timestamp().unwrap().abs_diff(expected) < Duration::from_micros(1));
+            let samples = frame
+                .buffer()
+                .chunks_exact(2)
+                .map(|sample| u16::from_le_bytes([sample[0], sample[1]]))
+                .collect::<Vec<u16>>();
+            assert_eq!(&samples, depth);
+        }
+
+        assert!(device.frame().is_err());
+        assert!(!device.is_stream_open());
+    }
+
+    #[cfg(feature = "output-recorder")]
+    #[test]
+    fn plays_back_recordings_with_their_gaps() {
+        use crate::recording::Recorder;
+
+        let directory = TempDirectory::new("replay-recording");
+        let format = CameraFormat::new(Resolution::new(2, 2), FrameFormat::GRAY, 30);
+        let info = CameraInfo::new("Camera", "", "", CameraIndex::Index(0));
+        let mut recorder = Recorder::create(directory.path(), &info, format).unwrap();
+        for (index, sequence) in [4_u8, 5, 8].into_iter().enumerate() {
+            let buffer = Buffer::new(format.resolution(), &[sequence; 4], FrameFormat::GRAY)
+                .with_sequence(u64::from(sequence));
+            let timestamp = Duration::from_millis(1000 + 33 * index as u64);
+            recorder.record_at(&buffer, timestamp).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let mut device = open(directory.path());
+        device.set_looping(true);
+        device.open_stream().unwrap();
+        let mut dropped = vec![];
+        for _ in 0..4 {
+            let frame = device.frame().unwrap();
+            assert_eq!(
+                frame.buffer(),
+                [u8::try_from(frame.sequence().unwrap()).unwrap(); 4]
+            );
+            dropped.push(frame.dropped_frames());
+        }
+        assert_eq!(dropped, [0, 0, 2, 0]);
+        assert_eq!(device.position(), 1);
+    }
+}
@@ -0,0 +1,391 @@
+/*
+ * Copyright 2022 l1npengtul <l1npengtul@protonmail.com> / The Nokhwa Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::test_pattern::{ColorBars, MovingCheckerboard, Noise, TexturedPlane};
+use nokhwa_core::{
+    buffer::Buffer,
+    error::NokhwaError,
+    traits::{CaptureBackendTrait, VirtualBackendTrait},
+    types::{
+        rgb_to_gray, rgb_to_mjpeg, rgb_to_nv12, rgb_to_yuyv422, ApiBackend, CameraControl,
+        CameraFormat, CameraIndex, CameraInfo, ControlValueSetter, FrameFormat, KnownCameraControl,
+        RequestedFormat, Resolution,
+    },
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Resolutions offered by default. Any even resolution can be set.
+const RESOLUTIONS: [(u32, u32); 4] = [(320, 240), (640, 480), (1280, 720), (1920, 1080)];
+/// Frame rates offered by default. Any non-zero frame rate can be set.
+const FRAME_RATES: [u32; 3] = [15, 30, 60];
+/// Names of the built-in test patterns, selected by [`CameraIndex::String`] or by their position with [`CameraIndex::Index`].
+const PATTERN_NAMES: [&str; 4] = ["color-bars", "checkerboard", "noise", "textured-plane"];
+
+fn supported_frame_formats() -> Vec<FrameFormat> {
+    let mut formats = vec![
+        FrameFormat::YUYV,
+        FrameFormat::NV12,
+        FrameFormat::GRAY,
+        FrameFormat::RAWRGB,
+    ];
+    if cfg!(feature = "decoding") {
+        formats.insert(0, FrameFormat::MJPEG);
+    }
+    formats
+}
+
+/// A synthetic camera, serving frames rendered by a [`VirtualBackendTrait`] source such as the patterns in [`test_pattern`](crate::test_pattern).
+/// To see what this does, please see [`CaptureBackendTrait`].
+///
+/// Frame `n` is always rendered for the timestamp `n / frame_rate`, however fast frames are consumed,
+/// so the same source and format always yield the same frames.
+/// # Quirks
+/// - With [`new()`](VirtualCaptureDevice::new), the [`CameraIndex`] selects a built-in pattern with its default settings:
+///   `color-bars` (0), `checkerboard` (1), `noise` (2) or `textured-plane` (3). Use [`with_source()`](VirtualCaptureDevice::with_source) for anything else.
+/// - Frames can be emitted in every [`FrameFormat`], at any even resolution and any non-zero frame rate. [`FrameFormat::MJPEG`] needs the `decoding` feature.
+/// - Opening the stream restarts the sequence from 0. Buffer timestamps are [`frame_timestamp()`](VirtualCaptureDevice::frame_timestamp), not wall-clock time.
+/// - Frames are paced to the frame rate by default, see [`set_realtime()`](VirtualCaptureDevice::set_realtime). Only paced
+///   frames are due at their timestamps, so [`timestamp_clock()`](CaptureBackendTrait::timestamp_clock) is `None` otherwise.
+/// - There are no camera controls.
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "input-virtual")))]
+pub struct VirtualCaptureDevice {
+    source: Box<dyn VirtualBackendTrait>,
+    info: CameraInfo,
+    camera_format: CameraFormat,
+    realtime: bool,
+    jpeg_quality: u8,
+    sequence: u64,
+    stream_open: bool,
+    // Wall clock time of the first frame served since opening the stream or changing the format
+    pacing_origin: Option<(Instant, u64)>,
+}
+
+impl VirtualCaptureDevice {
+    /// Creates a virtual camera showing the built-in pattern selected by `index`.
+    /// # Errors
+    /// This function will error if `index` does not name a built-in pattern, or the requested format cannot be fulfilled.
+    pub fn new(index: &CameraIndex, camera_fmt: RequestedFormat) -> Result<Self, NokhwaError> {
+        let name = match index {
+            CameraIndex::Index(number) => PATTERN_NAMES.get(*number as usize).copied(),
+            CameraIndex::String(name) => PATTERN_NAMES
+                .iter()
+                .find(|pattern| pattern.eq_ignore_ascii_case(name))
+                .copied(),
+        };
+
+        let source: Box<dyn VirtualBackendTrait> = match name {
+            Some("color-bars") => Box::new(ColorBars),
+            Some("checkerboard") => Box::<MovingCheckerboard>::default(),
+            Some("noise") => Box::<Noise>::default(),
+            Some("textured-plane") => Box::<TexturedPlane>::default(),
+            _ => {
+                return Err(NokhwaError::OpenDeviceError(
+                    index.to_string(),
+                    format!(
+                        "Not a test pattern, expected one of {}",
+                        PATTERN_NAMES.join(", ")
+                    ),
+                ))
+            }
+        };
+
+        let mut device = Self::with_source(source, camera_fmt)?;
+        device.info = CameraInfo::new(&device.source.name(), "Virtual Camera", "", index.clone());
+        Ok(device)
+    }
+
+    /// Creates a virtual camera showing the frames rendered by `source`.
+    /// # Errors
+    /// This function will error if the requested format cannot be fulfilled.
+    pub fn with_source(
+        source: Box<dyn VirtualBackendTrait>,
+        camera_fmt: RequestedFormat,
+    ) -> Result<Self, NokhwaError> {
+        let camera_format = camera_fmt
+            .fulfill(&default_formats())
+            .filter(|format| is_supported(*format))
+            .ok_or(NokhwaError::InitializeError {
+                backend: ApiBackend::Virtual,
+                error: "Failed to fulfill requested format".to_string(),
+            })?;
+
+        let info = CameraInfo::new(
+            &source.name(),
+            "Virtual Camera",
+            "",
+            CameraIndex::String(source.name()),
+        );
+
+        Ok(VirtualCaptureDevice {
+            source,
+            info,
+            camera_format,
+            realtime: true,
+            jpeg_quality: 90,
+            sequence: 0,
+            stream_open: false,
+            pacing_origin: None,
+        })
+    }
+
+    /// Whether frames are paced to the frame rate, like a real camera.
+    #[must_use]
+    pub fn realtime(&self) -> bool {
+        self.realtime
+    }
+
+    /// Sets whether frames are paced to the frame rate. Without pacing, frames are served as soon as they are rendered.
+    /// The rendered frames are the same either way.
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.realtime = realtime;
+        self.pacing_origin = None;
+    }
+
+    /// The quality of the frames emitted as [`FrameFormat::MJPEG`], from 0 to 100.
+    #[must_use]
+    pub fn jpeg_quality(&self) -> u8 {
+        self.jpeg_quality
+    }
+
+    /// Sets the quality of the frames emitted as [`FrameFormat::MJPEG`], from 0 to 100.
+    pub fn set_jpeg_quality(&mut self, jpeg_quality: u8) {
+        self.jpeg_quality = jpeg_quality.min(100);
+    }
+
+    /// The sequence number of the next frame.
+    #[must_use]
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// The timestamp the frame with the sequence number `sequence` is rendered for, at the current frame rate.
+    #[must_use]
+    pub fn frame_timestamp(&self, sequence: u64) -> Duration {
+        Duration::from_secs(sequence) / self.camera_format.frame_rate()
+    }
+
+    fn pace(&mut self) {
+        let (origin, origin_sequence) = *self
+            .pacing_origin
+            .get_or_insert((Instant::now(), self.sequence));
+
+        let due = origin + self.frame_timestamp(self.sequence - origin_sequence);
+        let now = Instant::now();
+
+        if due > now {
+            std::thread::sleep(due - now);
+        }
+    }
+
+    fn next_frame(&mut self) -> Result<Vec<u8>, NokhwaError> {
+        if !self.stream_open {
+            return Err(NokhwaError::ReadFrameError(
+                "Stream is not open".to_string(),
+            ));
+        }
+
+        if self.realtime {
+            self.pace();
+        }
+
+        let resolution = self.camera_format.resolution();
+        let rgb = self.source.render_frame(
+            self.sequence,
+            self.frame_timestamp(self.sequence),
+            resolution,
+        );
+        if rgb.len() != (resolution.width() * resolution.height() * 3) as usize {
+            return Err(NokhwaError::ReadFrameError(format!(
+                "{} rendered a frame of the wrong size",
+                self.source.name()
+            )));
+        }
+
+        let frame = match self.camera_format.format() {
+            FrameFormat::MJPEG => rgb_to_mjpeg(resolution, &rgb, self.jpeg_quality)?,
+            FrameFormat::YUYV => rgb_to_yuyv422(resolution, &rgb)?,
+            FrameFormat::NV12 => rgb_to_nv12(resolution, &rgb)?,
+            FrameFormat::GRAY => rgb_to_gray(resolution, &rgb)?,
+            FrameFormat::RAWRGB => rgb,
+            format => {
+                return Err(NokhwaError::ReadFrameError(format!(
+                    "Cannot render {format} frames"
+                )))
+            }
+        };
+
+        self.sequence += 1;
+        Ok(frame)
+    }
+}
+
+fn default_formats() -> Vec<CameraFormat> {
+    let mut formats = vec![];
+    for format in supported_frame_formats() {
+        for (width, height) in RESOLUTIONS {
+            for frame_rate in FRAME_RATES {
+                formats.push(CameraFormat::new_from(width, height, format, frame_rate));
+            }
+        }
+    }
+    formats
+}
+
+fn is_supported(format: CameraFormat) -> bool {
+    let resolution = format.resolution();
+    supported_frame_formats().contains(&format.format())
+        && resolution.width() > 0
+        && resolution.height() > 0
+        && resolution.width().is_multiple_of(2)
+        && resolution.height().is_multiple_of(2)
+        && format.frame_rate() > 0
+}
+
+impl CaptureBackendTrait for VirtualCaptureDevice {
+    fn backend(&self) -> ApiBackend {
+        ApiBackend::Virtual
+    }
+
+    fn camera_info(&self) -> &CameraInfo {
+        &self.info
+    }
+
+    fn refresh_camera_format(&mut self) -> Result<(), NokhwaError> {
+        Ok(())
+    }
+
+    fn camera_format(&self) -> CameraFormat {
+        self.camera_format
+    }
+
+    fn set_camera_format(&mut self, new_fmt: CameraFormat) -> Result<(), NokhwaError> {
+        if !is_supported(new_fmt) {
+            return Err(NokhwaError::SetPropertyError {
+                property: "CameraFormat".to_string(),
+                value: new_fmt.to_string(),
+                error: "Resolution must be even and frame rate non-zero".to_string(),
+            });
+        }
+
+        self.camera_format = new_fmt;
+        self.pacing_origin = None;
+        Ok(())
+    }
+
+    fn compatible_list_by_resolution(
+        &mut self,
+        fourcc: FrameFormat,
+    ) -> Result<HashMap<Resolution, Vec<u32>>, NokhwaError> {
+        let mut resolution_map = HashMap::new();
+        if supported_frame_formats().contains(&fourcc) {
+            for (width, height) in RESOLUTIONS {
+                resolution_map.insert(Resolution::new(width, height), FRAME_RATES.to_vec());
+            }
+        }
+        Ok(resolution_map)
+    }
+
+    fn compatible_fourcc(&mut self) -> Result<Vec<FrameFormat>, NokhwaError> {
+        Ok(supported_frame_formats())
+    }
+
+    fn resolution(&self) -> Resolution {
+        self.camera_format.resolution()
+    }
+
+    fn set_resolution(&mut self, new_res: Resolution) -> Result<(), NokhwaError> {
+        let mut new_format = self.camera_format;
+        new_format.set_resolution(new_res);
+        self.set_camera_format(new_format)
+    }
+
+    fn frame_rate(&self) -> u32 {
+        self.camera_format.frame_rate()
+    }
+
+    fn set_frame_rate(&mut self, new_fps: u32) -> Result<(), NokhwaError> {
+        let mut new_format = self.camera_format;
+        new_format.set_frame_rate(new_fps);
+        self.set_camera_format(new_format)
+    }
+
+    fn frame_format(&self) -> FrameFormat {
+        self.camera_format.format()
+    }
+
+    fn set_frame_format(&mut self, fourcc: FrameFormat) -> Result<(), NokhwaError> {
+        let mut new_format = self.camera_format;
+        new_format.set_format(fourcc);
+        self.set_camera_format(new_format)
+    }
+
+    fn camera_control(&self, _: KnownCameraControl) -> Result<CameraControl, NokhwaError> {
+        Err(NokhwaError::UnsupportedOperationError(ApiBackend::Virtual))
+    }
+
+    fn camera_controls(&self) -> Result<Vec<CameraControl>, NokhwaError> {
+        Ok(vec![])
+    }
+
+    fn set_camera_control(
+        &mut self,
+        _: KnownCameraControl,
+        _: ControlValueSetter,
+    ) -> Result<(), NokhwaError> {
+        Err(NokhwaError::UnsupportedOperationError(ApiBackend::Virtual))
+    }
+
+    fn open_stream(&mut self) -> Result<(), NokhwaError> {
+        self.stream_open = true;
+        self.sequence = 0;
+        self.pacing_origin = None;
+        Ok(())
+    }
+
+    fn is_stream_open(&self) -> bool {
+        self.stream_open
+    }
+
+    fn frame(&mut self) -> Result<Buffer, NokhwaError> {
+        let frame = self.next_frame()?;
+        let sequence = self.sequence - 1;
+        Ok(Buffer::new(
+            self.camera_format.resolution(),
+            &frame,
+            self.camera_format.format(),
+        )
+        .with_timestamp(self.frame_timestamp(sequence))
+        .with_sequence(sequence))
+    }
+
+    fn frame_raw(&mut self) -> Result<Cow<[u8]>, NokhwaError> {
+        Ok(Cow::Owned(self.next_frame()?))
+    }
+
+    fn timestamp_clock(&self) -> Option<Duration> {
+        let (origin, origin_sequence) = self.pacing_origin.filter(|_| self.realtime)?;
+        Some(self.frame_timestamp(origin_sequence) + origin.elapsed())
+    }
+
+    fn stop_stream(&mut self) -> Result<(), NokhwaError> {
+        self.stream_open = false;
+        Ok(())
+    }
+}
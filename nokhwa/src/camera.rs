@@ -14,6 +14,8 @@
  * limitations under the License.
  */
 
+use crate::stats::{CaptureStats, StatsCollector};
+use image::Pixel;
 use nokhwa_core::types::RequestedFormatType;
 use nokhwa_core::{
     buffer::Buffer,
@@ -34,6 +36,7 @@ pub struct Camera {
     idx: CameraIndex,
     api: ApiBackend,
     device: Box<dyn CaptureBackendTrait>,
+    stats: StatsCollector,
 }
 
 impl Camera {
@@ -58,6 +61,7 @@ impl Camera {
             idx: index,
             api: backend,
             device: camera_backend,
+            stats: StatsCollector::default(),
         })
     }
 
@@ -89,7 +93,12 @@ impl Camera {
         api: ApiBackend,
         device: Box<dyn CaptureBackendTrait>,
     ) -> Self {
-        Self { idx, api, device }
+        Self {
+            idx,
+            api,
+            device,
+            stats: StatsCollector::default(),
+        }
     }
 
     /// Gets the current Camera's index.
@@ -363,7 +372,9 @@ impl Camera {
     /// # Errors
     /// If the specific backend fails to open the camera (e.g. already taken, busy, doesn't exist anymore) this will error.
     pub fn open_stream(&mut self) -> Result<(), NokhwaError> {
-        self.device.open_stream()
+        self.device.open_stream()?;
+        self.stats.reset();
+        Ok(())
     }
 
     /// Checks if stream if open. If it is, it will return true.
@@ -378,7 +389,10 @@ impl Camera {
     /// If the backend fails to get the frame (e.g. already taken, busy, doesn't exist anymore), the decoding fails (e.g. MJPEG -> u8), or [`open_stream()`](CaptureBackendTrait::open_stream()) has not been called yet,
     /// this will error.
     pub fn frame(&mut self) -> Result<Buffer, NokhwaError> {
-        self.device.frame()
+        let frame = self.device.frame()?;
+        let clock = self.device.timestamp_clock();
+        self.stats.record_frame(&frame, clock);
+        Ok(frame)
     }
 
     /// Will get a frame from the camera **without** any processing applied, meaning you will usually get a frame you need to decode yourself.
@@ -386,7 +400,10 @@ impl Camera {
     /// If the backend fails to get the frame (e.g. already taken, busy, doesn't exist anymore), or [`open_stream()`](CaptureBackendTrait::open_stream()) has not been called yet, this will error.
     pub fn frame_raw(&mut self) -> Result<Cow<[u8]>, NokhwaError> {
         match self.device.frame_raw() {
-            Ok(f) => Ok(f),
+            Ok(f) => {
+                self.stats.record_raw_frame();
+                Ok(f)
+            }
             Err(why) => Err(why),
         }
     }
@@ -396,9 +413,9 @@ impl Camera {
     /// If the backend fails to get the frame (e.g. already taken, busy, doesn't exist anymore), or [`open_stream()`](CaptureBackendTrait::open_stream()) has not been called yet, this will error.
     pub fn write_frame_to_buffer<F: FormatDecoder>(
         &mut self,
-        buffer: &mut [u8],
+        buffer: &mut [<F::Output as Pixel>::Subpixel],
     ) -> Result<(), NokhwaError> {
-        self.device.frame()?.decode_image_to_buffer::<F>(buffer)
+        self.frame()?.decode_image_to_buffer::<F>(buffer)
     }
 
     #[cfg(feature = "output-wgpu")]
@@ -412,7 +429,22 @@ impl Camera {
         queue: &WgpuQueue,
         label: Option<&'a str>,
     ) -> Result<WgpuTexture, NokhwaError> {
-        self.device.frame_texture(device, queue, label)
+        let texture = self.device.frame_texture(device, queue, label)?;
+        self.stats.record_raw_frame();
+        Ok(texture)
+    }
+
+    /// Gets the capture statistics since the stream was last opened: frames delivered and dropped, achieved frame rate and latency.
+    /// See [`CaptureStats`] for how they are measured.
+    #[must_use]
+    pub fn stats(&self) -> CaptureStats {
+        self.stats
+            .snapshot(self.device.camera_format().frame_rate())
+    }
+
+    /// Clears the capture statistics. They are also cleared whenever the stream is opened.
+    pub fn reset_stats(&mut self) {
+        self.stats.reset();
     }
 
     /// Will drop the stream.
@@ -560,7 +592,10 @@ cap_impl_fn! {
     // (UVCCaptureDevice, create, feature = "input-uvc", uvc),
     (V4LCaptureDevice, new, all(feature = "input-v4l", target_os = "linux"), v4l),
     (MediaFoundationCaptureDevice, new, all(feature = "input-msmf", target_os = "windows"), msmf),
-    (AVFoundationCaptureDevice, new, all(feature = "input-avfoundation", any(target_os = "macos", target_os = "ios")), avfoundation)
+    (AVFoundationCaptureDevice, new, all(feature = "input-avfoundation", any(target_os = "macos", target_os = "ios")), avfoundation),
+    (ReplayCaptureDevice, new, feature = "input-replay", replay),
+    (FileCaptureDevice, new, feature = "input-file", file),
+    (VirtualCaptureDevice, new, feature = "input-virtual", virtual)
 }
 
 fn init_camera(
@@ -573,7 +608,10 @@ fn init_camera(
             ("input-v4l", Video4Linux, init_v4l),
             ("input-msmf", MediaFoundation, init_msmf),
             ("input-avfoundation", AVFoundation, init_avfoundation),
-            ("input-opencv", OpenCv, init_opencv)
+            ("input-opencv", OpenCv, init_opencv),
+            ("input-replay", Replay, init_replay),
+            ("input-file", MediaFile, init_file),
+            ("input-virtual", Virtual, init_virtual)
     };
     Ok(camera_backend)
 }
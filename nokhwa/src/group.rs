@@ -0,0 +1,424 @@
+/*
+ * Copyright 2022 l1npengtul <l1npengtul@protonmail.com> / The Nokhwa Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::{
+    stats::LatencyHistogram,
+    threaded::{BackpressurePolicy, CallbackCamera, FrameSubscription},
+    Camera,
+};
+use flume::RecvTimeoutError;
+use nokhwa_core::{buffer::Buffer, error::NokhwaError};
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
+
+/// Number of frames queued per camera while waiting for the other cameras.
+const QUEUE_CAPACITY: usize = 4;
+/// Default for [`CameraGroup::timeout()`].
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// One frame from every camera of a [`CameraGroup`], captured within its [`tolerance()`](CameraGroup::tolerance) of each other.
+#[derive(Clone, Debug)]
+pub struct FrameSet {
+    frames: Vec<Buffer>,
+    timestamp: Duration,
+    skew: Duration,
+}
+
+impl FrameSet {
+    /// The frames, in the order the cameras were added to the group.
+    #[must_use]
+    pub fn frames(&self) -> &[Buffer] {
+        &self.frames
+    }
+
+    /// The frame of the camera at `index`.
+    #[must_use]
+    pub fn frame(&self, index: usize) -> Option<&Buffer> {
+        self.frames.get(index)
+    }
+
+    /// The capture time of the set, the mean of the timestamps of its frames.
+    #[must_use]
+    pub fn timestamp(&self) -> Duration {
+        self.timestamp
+    }
+
+    /// The time between the first and the last frame of the set was captured.
+    #[must_use]
+    pub fn skew(&self) -> Duration {
+        self.skew
+    }
+
+    /// Takes the frames out of the set.
+    #[must_use]
+    pub fn into_frames(self) -> Vec<Buffer> {
+        self.frames
+    }
+}
+
+/// Synchronization statistics of a [`CameraGroup`] since its stream was opened.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GroupStats {
+    framesets: u64,
+    skew: LatencyHistogram,
+    unmatched_frames: Vec<u64>,
+    queue_dropped_frames: Vec<u64>,
+}
+
+impl GroupStats {
+    /// The number of frame sets handed to the caller.
+    #[must_use]
+    pub fn framesets(&self) -> u64 {
+        self.framesets
+    }
+
+    /// The skew of the frame sets handed to the caller, see [`FrameSet::skew()`].
+    #[must_use]
+    pub fn skew(&self) -> &LatencyHistogram {
+        &self.skew
+    }
+
+    /// For every camera, the number of frames that were discarded because no other camera had a frame close enough to it.
+    #[must_use]
+    pub fn unmatched_frames(&self) -> &[u64] {
+        &self.unmatched_frames
+    }
+
+    /// For every camera, the number of frames that were discarded because the frame sets were not consumed in time.
+    #[must_use]
+    pub fn queue_dropped_frames(&self) -> &[u64] {
+        &self.queue_dropped_frames
+    }
+}
+
+impl Display for GroupStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} framesets, skew {}, unmatched {:?}, dropped {:?}",
+            self.framesets, self.skew, self.unmatched_frames, self.queue_dropped_frames,
+        )
+    }
+}
+
+/// Captures from several cameras at once, such as a stereo pair, and hands out their frames in [`FrameSet`]s matched by timestamp.
+///
+/// Every camera runs on its own capture thread as a [`CallbackCamera`]. Frames are matched by their [`Buffer::timestamp()`],
+/// so all cameras must timestamp their frames on the same clock, as V4L2 devices do. Whenever the next frames of the
+/// cameras are further apart than the [`tolerance()`](Self::tolerance), the oldest one is discarded and the next frame of
+/// its camera is used instead, so every frame is used in at most one set and sets come in capture order.
+///
+/// ```no_run
+/// # use nokhwa::{Camera, CameraGroup, pixel_format::RgbFormat, utils::{CameraIndex, RequestedFormat, RequestedFormatType}};
+/// let request = RequestedFormat::new::<RgbFormat>(RequestedFormatType::AbsoluteHighestFrameRate);
+/// let left = Camera::new(CameraIndex::Index(0), request)?;
+/// let right = Camera::new(CameraIndex::Index(1), request)?;
+/// let mut group = CameraGroup::new(vec![left, right])?;
+/// group.open_stream()?;
+/// for _ in 0..100 {
+///     let frames = group.frameset()?;
+///     println!("{:?} apart", frames.skew());
+/// }
+/// println!("{}", group.stats());
+/// # Ok::<(), nokhwa::NokhwaError>(())
+/// ```
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "output-threaded")))]
+pub struct CameraGroup {
+    cameras: Vec<CallbackCamera>,
+    subscriptions: Vec<FrameSubscription>,
+    pending: Vec<Option<Buffer>>,
+    tolerance: Duration,
+    timeout: Duration,
+    stats: GroupStats,
+    // Subscription drop counts at the last reset, the subscriptions count from their creation
+    dropped_at_reset: Vec<u64>,
+}
+
+impl CameraGroup {
+    /// Groups `cameras`. The tolerance defaults to half the frame interval of the fastest camera.
+    /// # Errors
+    /// If `cameras` is empty, this will error.
+    pub fn new(cameras: Vec<Camera>) -> Result<Self, NokhwaError> {
+        if cameras.is_empty() {
+            return Err(NokhwaError::StructureError {
+                structure: "CameraGroup".to_string(),
+                error: "A group needs at least one camera".to_string(),
+            });
+        }
+
+        let fastest_frame_rate = cameras.iter().map(Camera::frame_rate).max().unwrap_or(1);
+        let tolerance = Duration::from_secs(1) / (fastest_frame_rate.max(1) * 2);
+
+        let cameras = cameras
+            .into_iter()
+            .map(|camera| CallbackCamera::with_custom(camera, |_| {}))
+            .collect::<Vec<CallbackCamera>>();
+        let subscriptions = subscribe(&cameras)?;
+
+        let mut group = CameraGroup {
+            pending: vec![None; cameras.len()],
+            cameras,
+            subscriptions,
+            tolerance,
+            timeout: DEFAULT_TIMEOUT,
+            stats: GroupStats::default(),
+            dropped_at_reset: vec![],
+        };
+        group.reset_stats();
+        Ok(group)
+    }
+
+    /// The cameras, in the order they were added to the group.
+    #[must_use]
+    pub fn cameras(&self) -> &[CallbackCamera] {
+        &self.cameras
+    }
+
+    /// The cameras, e.g. to change their controls.
+    pub fn cameras_mut(&mut self) -> &mut [CallbackCamera] {
+        &mut self.cameras
+    }
+
+    /// How far apart the frames of a set may be captured.
+    #[must_use]
+    pub fn tolerance(&self) -> Duration {
+        self.tolerance
+    }
+
+    /// Sets how far apart the frames of a set may be captured.
+    pub fn set_tolerance(&mut self, tolerance: Duration) {
+        self.tolerance = tolerance;
+    }
+
+    /// How long [`frameset()`](Self::frameset) waits for the next frame of a camera before giving up.
+    #[must_use]
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Sets how long [`frameset()`](Self::frameset) waits for the next frame of a camera before giving up.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Opens the streams of all cameras and starts their capture threads.
+    /// # Errors
+    /// If any camera fails to open its stream, this will error. The cameras opened before it stay open.
+    pub fn open_stream(&mut self) -> Result<(), NokhwaError> {
+        // Stopping a camera ends its subscriptions, so the queues start over on every opening
+        self.subscriptions = subscribe(&self.cameras)?;
+        self.pending.fill(None);
+        for camera in &mut self.cameras {
+            camera.open_stream()?;
+        }
+        self.reset_stats();
+        Ok(())
+    }
+
+    /// Checks if the streams of all cameras are open.
+    /// # Errors
+    /// If a camera cannot be queried, this will error.
+    pub fn is_stream_open(&self) -> Result<bool, NokhwaError> {
+        for camera in &self.cameras {
+            if !camera.is_stream_open()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Waits for the next set of frames.
+    /// # Errors
+    /// If a camera fails to capture a frame or delivers none within the [`timeout()`](Self::timeout), a frame has no
+    /// timestamp or the group has been stopped, this will error. Frames already received are kept for the next call.
+    pub fn frameset(&mut self) -> Result<FrameSet, NokhwaError> {
+        loop {
+            for (index, pending) in self.pending.iter_mut().enumerate() {
+                if pending.is_none() {
+                    let frame = self.subscriptions[index]
+                        .recv_timeout(self.timeout)
+                        .map_err(|why| {
+                            NokhwaError::ReadFrameError(match why {
+                                RecvTimeoutError::Timeout => {
+                                    format!("Timed out waiting for camera {index}")
+                                }
+                                RecvTimeoutError::Disconnected => {
+                                    format!("Camera {index} has stopped")
+                                }
+                            })
+                        })??;
+                    if frame.timestamp().is_none() {
+                        return Err(NokhwaError::ReadFrameError(format!(
+                            "Camera {index} does not timestamp its frames"
+                        )));
+                    }
+                    *pending = Some(frame);
+                }
+            }
+
+            let timestamps = self
+                .pending
+                .iter()
+                .map(|frame| {
+                    frame
+                        .as_ref()
+                        .and_then(Buffer::timestamp)
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<Duration>>();
+            let (oldest, first) = timestamps
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, timestamp)| **timestamp)
+                .map(|(index, timestamp)| (index, *timestamp))
+                .unwrap_or_default();
+            let last = timestamps.iter().max().copied().unwrap_or_default();
+
+            let skew = last.saturating_sub(first);
+            if skew <= self.tolerance {
+                #[allow(clippy::cast_possible_truncation)]
+                let timestamp = timestamps.iter().sum::<Duration>() / timestamps.len() as u32;
+                self.stats.framesets += 1;
+                self.stats.skew.record(skew);
+                return Ok(FrameSet {
+                    frames: self.pending.iter_mut().filter_map(Option::take).collect(),
+                    timestamp,
+                    skew,
+                });
+            }
+
+            self.pending[oldest] = None;
+            self.stats.unmatched_frames[oldest] += 1;
+        }
+    }
+
+    /// The synchronization statistics since the stream was opened. The capture statistics of each camera are available
+    /// from its [`stats()`](CallbackCamera::stats).
+    #[must_use]
+    pub fn stats(&self) -> GroupStats {
+        let mut stats = self.stats.clone();
+        stats.queue_dropped_frames = self
+            .subscriptions
+            .iter()
+            .zip(&self.dropped_at_reset)
+            .map(|(subscription, at_reset)| subscription.dropped_frames() - at_reset)
+            .collect();
+        stats
+    }
+
+    /// Starts the synchronization statistics over.
+    pub fn reset_stats(&mut self) {
+        self.stats = GroupStats {
+            unmatched_frames: vec![0; self.cameras.len()],
+            ..GroupStats::default()
+        };
+        self.dropped_at_reset = self
+            .subscriptions
+            .iter()
+            .map(FrameSubscription::dropped_frames)
+            .collect();
+    }
+
+    /// Stops the streams of all cameras.
+    /// # Errors
+    /// If any camera fails to stop its stream, this will error. The other cameras are still stopped.
+    pub fn stop_stream(&mut self) -> Result<(), NokhwaError> {
+        self.pending.fill(None);
+        self.cameras
+            .iter_mut()
+            .map(CallbackCamera::stop_stream)
+            .fold(Ok(()), Result::and)
+    }
+}
+
+fn subscribe(cameras: &[CallbackCamera]) -> Result<Vec<FrameSubscription>, NokhwaError> {
+    cameras
+        .iter()
+        .map(|camera| camera.subscribe(QUEUE_CAPACITY, BackpressurePolicy::DropOldest))
+        .collect()
+}
+
+#[cfg(all(test, feature = "input-virtual"))]
+mod tests {
+    use super::*;
+    use crate::{
+        backends::capture::VirtualCaptureDevice,
+        pixel_format::RgbFormat,
+        utils::{
+            ApiBackend, CameraFormat, CameraIndex, FrameFormat, RequestedFormat,
+            RequestedFormatType,
+        },
+    };
+
+    fn virtual_camera(frame_rate: u32) -> Camera {
+        let format = RequestedFormat::new::<RgbFormat>(RequestedFormatType::Exact(
+            CameraFormat::new_from(32, 24, FrameFormat::RAWRGB, frame_rate),
+        ));
+        let device = VirtualCaptureDevice::new(&CameraIndex::Index(0), format).unwrap();
+        Camera::with_custom(CameraIndex::Index(0), ApiBackend::Virtual, Box::new(device))
+    }
+
+    #[test]
+    fn matches_frames_and_drops_the_oldest() {
+        // Frames every 10ms and every 25ms only line up every 50ms, in between they are 5ms or more apart
+        let mut group = CameraGroup::new(vec![virtual_camera(100), virtual_camera(40)]).unwrap();
+        assert_eq!(group.tolerance(), Duration::from_millis(5));
+        group.set_tolerance(Duration::from_millis(3));
+        group.open_stream().unwrap();
+
+        for set in 0..5 {
+            let frameset = group.frameset().unwrap();
+            assert_eq!(frameset.frames().len(), 2);
+            assert_eq!(frameset.timestamp(), Duration::from_millis(set * 50));
+            assert_eq!(frameset.skew(), Duration::ZERO);
+            assert_eq!(frameset.frame(0).unwrap().sequence(), Some(set * 5));
+            assert_eq!(frameset.frame(1).unwrap().sequence(), Some(set * 2));
+        }
+
+        // Between two sets, the frames at 10, 20, 30 and 40ms and the one at 25ms found no match
+        let stats = group.stats();
+        assert_eq!(stats.framesets(), 5);
+        assert_eq!(stats.unmatched_frames(), [16, 4]);
+        assert_eq!(stats.queue_dropped_frames(), [0, 0]);
+        assert_eq!(stats.skew().max(), Some(Duration::ZERO));
+
+        // Frame sets that are not consumed in time overflow the queues, the matching carries on after them
+        std::thread::sleep(Duration::from_millis(300));
+        let frameset = group.frameset().unwrap();
+        assert!(frameset.skew() <= group.tolerance());
+        let stats = group.stats();
+        assert!(stats
+            .queue_dropped_frames()
+            .iter()
+            .all(|dropped| *dropped > 0));
+
+        group.reset_stats();
+        let stats = group.stats();
+        assert_eq!(stats.framesets(), 0);
+        assert_eq!(stats.unmatched_frames(), [0, 0]);
+        assert_eq!(stats.queue_dropped_frames(), [0, 0]);
+
+        // Stopping ends the subscriptions, once the queued frames are used up
+        group.stop_stream().unwrap();
+        let mut remaining = 0;
+        while group.frameset().is_ok() {
+            remaining += 1;
+            assert!(remaining <= QUEUE_CAPACITY);
+        }
+    }
+}
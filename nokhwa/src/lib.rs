@@ -33,6 +33,10 @@
 /// Raw access to each of Nokhwa's backends.
 pub mod backends;
 mod camera;
+/// Synchronized capture from several cameras at once, such as a stereo pair.
+#[cfg(feature = "output-threaded")]
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "output-threaded")))]
+pub mod group;
 mod init;
 /// A camera that uses native browser APIs meant for WASM applications.
 #[cfg(feature = "input-jscam")]
@@ -41,19 +45,49 @@ pub mod js_camera;
 
 pub use nokhwa_core::pixel_format::FormatDecoder;
 mod query;
+/// Recording camera sessions to disk, for playback with the `Replay` backend.
+#[cfg(any(feature = "input-replay", feature = "output-recorder"))]
+#[cfg_attr(
+    feature = "docs-features",
+    doc(cfg(any(feature = "input-replay", feature = "output-recorder")))
+)]
+pub mod recording;
+/// Paired color and depth capture for RGB-D cameras and datasets.
+pub mod rgbd;
+/// Capture health statistics: delivered and dropped frames, frame rate and latency.
+pub mod stats;
+/// Deterministic synthetic frame sources for the `Virtual` backend.
+#[cfg(feature = "input-virtual")]
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "input-virtual")))]
+pub mod test_pattern;
+#[cfg(all(
+    test,
+    any(
+        feature = "input-file",
+        feature = "input-replay",
+        feature = "output-recorder"
+    )
+))]
+mod test_util;
 /// A camera that runs in a different thread and can call your code based on callbacks.
 #[cfg(feature = "output-threaded")]
 #[cfg_attr(feature = "docs-features", doc(cfg(feature = "output-threaded")))]
 pub mod threaded;
 
 pub use camera::Camera;
+#[cfg(feature = "output-threaded")]
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "output-threaded")))]
+pub use group::{CameraGroup, FrameSet};
 pub use init::*;
 pub use nokhwa_core::buffer::Buffer;
 pub use nokhwa_core::error::NokhwaError;
 pub use query::*;
+#[cfg(feature = "output-async")]
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "output-async")))]
+pub use threaded::FrameStream;
 #[cfg(feature = "output-threaded")]
 #[cfg_attr(feature = "docs-features", doc(cfg(feature = "output-threaded")))]
-pub use threaded::CallbackCamera;
+pub use threaded::{BackpressurePolicy, CallbackCamera, FrameSubscription};
 
 pub mod utils {
     pub use nokhwa_core::types::*;
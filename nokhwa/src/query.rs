@@ -98,9 +98,11 @@ pub fn query(api: ApiBackend) -> Result<Vec<CameraInfo>, NokhwaError> {
         ApiBackend::MediaFoundation => query_msmf(),
         #[allow(deprecated)]
         ApiBackend::GStreamer => query_gstreamer(),
-        ApiBackend::OpenCv | ApiBackend::Network => {
-            Err(NokhwaError::UnsupportedOperationError(api))
-        }
+        ApiBackend::OpenCv
+        | ApiBackend::Network
+        | ApiBackend::Replay
+        | ApiBackend::MediaFile
+        | ApiBackend::Virtual => Err(NokhwaError::UnsupportedOperationError(api)),
         ApiBackend::Browser => query_wasm(),
     }
 }
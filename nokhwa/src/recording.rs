@@ -0,0 +1,751 @@
+/*
+ * Copyright 2022 l1npengtul <l1npengtul@protonmail.com> / The Nokhwa Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! On-disk format of recorded camera sessions.
+//!
+//! A recording is a directory holding an `index.csv` file and a `frames` directory with one
+//! file per frame. Frame payloads are stored exactly as the camera delivered them, so MJPEG
+//! frames stay compressed and raw formats (YUYV, NV12, ...) stay raw.
+//!
+//! The index starts with `# key: value` header lines describing the camera, followed by one
+//! CSV row per frame:
+//! ```text
+//! # nokhwa-recording 1
+//! # camera: HD Webcam
+//! # description: Video4Linux Device @ /dev/video0
+//! # width: 1280
+//! # height: 720
+//! # frame_rate: 30
+//! # format: MJPEG
+//! # color_space: BT.601
+//! # color_range: Limited
+//! sequence,timestamp_us,width,height,format,file
+//! 0,1700000000000000,1280,720,MJPEG,frames/00000000.jpg
+//! ```
+//! `timestamp_us` is the capture time in microseconds since the UNIX epoch. `sequence` is the
+//! sequence number the camera reported for the frame, so gaps in it mark frames the camera
+//! dropped. Cameras that do not number their frames get consecutive numbers from 0.
+//!
+//! `color_space` and `color_range` are the [`Colorimetry`] of the recorded format. Recordings without them are
+//! assumed to have the default one.
+//!
+//! # TUM RGB-D
+//! The `rgb.txt` and `depth.txt` lists of the [TUM RGB-D](https://cvg.cit.tum.de/data/datasets/rgbd-dataset) dataset can
+//! be loaded with [`RecordingIndex::load_tum()`] and played back like a recording. Each list is one stream:
+//! ```text
+//! # timestamp filename
+//! 1305031102.175304 rgb/1305031102.175304.png
+//! ```
+//! The frames are PNG images. 8-bit color images are served as [`FrameFormat::RAWRGB`], 8-bit grayscale images as
+//! [`FrameFormat::GRAY`] and 16-bit grayscale images, which is how the dataset stores depth, as [`FrameFormat::Z16`].
+
+#[cfg(feature = "output-recorder")]
+use nokhwa_core::{buffer::Buffer, types::CameraInfo};
+use nokhwa_core::{
+    error::NokhwaError,
+    types::{CameraFormat, ColorRange, ColorSpace, Colorimetry, FrameFormat, Resolution},
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+#[cfg(feature = "output-recorder")]
+use std::{
+    io::{BufWriter, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Name of the index file inside a recording directory.
+pub const INDEX_FILE_NAME: &str = "index.csv";
+/// Name of the directory holding the frame payloads inside a recording directory.
+pub const FRAMES_DIRECTORY: &str = "frames";
+
+const INDEX_VERSION: &str = "nokhwa-recording 1";
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const INDEX_COLUMNS: &str = "sequence,timestamp_us,width,height,format,file";
+
+/// A single frame of a recording.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct RecordedFrame {
+    sequence: u64,
+    timestamp: Duration,
+    resolution: Resolution,
+    format: FrameFormat,
+    file: PathBuf,
+}
+
+impl RecordedFrame {
+    /// The sequence number of this frame, as reported by the camera. Gaps mark dropped frames.
+    #[must_use]
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// The capture time of this frame, since the UNIX epoch.
+    #[must_use]
+    pub fn timestamp(&self) -> Duration {
+        self.timestamp
+    }
+
+    /// The [`Resolution`] of this frame.
+    #[must_use]
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// The [`FrameFormat`] of this frame's payload.
+    #[must_use]
+    pub fn format(&self) -> FrameFormat {
+        self.format
+    }
+
+    /// The payload file, relative to the recording directory.
+    #[must_use]
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    fn to_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{}",
+            self.sequence,
+            self.timestamp.as_micros(),
+            self.resolution.width(),
+            self.resolution.height(),
+            self.format,
+            self.file.to_string_lossy().replace('\\', "/"),
+        )
+    }
+
+    fn from_row(row: &str) -> Result<Self, NokhwaError> {
+        let error = |why: String| NokhwaError::StructureError {
+            structure: "RecordedFrame".to_string(),
+            error: format!("{why} in row \"{row}\""),
+        };
+
+        let columns = row.split(',').collect::<Vec<&str>>();
+        if columns.len() != 6 {
+            return Err(error(format!(
+                "Expected 6 columns, found {}",
+                columns.len()
+            )));
+        }
+
+        let number = |index: usize| {
+            columns[index]
+                .trim()
+                .parse::<u64>()
+                .map_err(|why| error(why.to_string()))
+        };
+        let dimension = |index: usize| {
+            columns[index]
+                .trim()
+                .parse::<u32>()
+                .map_err(|why| error(why.to_string()))
+        };
+
+        Ok(RecordedFrame {
+            sequence: number(0)?,
+            timestamp: Duration::from_micros(number(1)?),
+            resolution: Resolution::new(dimension(2)?, dimension(3)?),
+            format: columns[4].trim().parse::<FrameFormat>()?,
+            file: PathBuf::from(columns[5].trim()),
+        })
+    }
+}
+
+/// File extension used for payloads of `format`.
+fn payload_extension(format: FrameFormat) -> String {
+    match format {
+        FrameFormat::MJPEG => "jpg".to_string(),
+        other => other.to_string().to_lowercase(),
+    }
+}
+
+/// Reads the resolution and the [`FrameFormat`] it is served as from the header of a PNG image.
+fn probe_png(path: &Path) -> Result<(Resolution, FrameFormat), String> {
+    let mut header = [0; 26];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map_err(|why| format!("{}: {why}", path.to_string_lossy()))?;
+
+    // The IHDR chunk always comes first: width, height, bit depth, color type
+    if header[..8] != PNG_SIGNATURE || &header[12..16] != b"IHDR" {
+        return Err(format!("{}: Not a PNG image", path.to_string_lossy()));
+    }
+    let width = u32::from_be_bytes([header[16], header[17], header[18], header[19]]);
+    let height = u32::from_be_bytes([header[20], header[21], header[22], header[23]]);
+    // Color types 0 and 4 are grayscale without and with alpha
+    let format = match (header[24], header[25]) {
+        (16, 0 | 4) => FrameFormat::Z16,
+        (_, 0 | 4) => FrameFormat::GRAY,
+        _ => FrameFormat::RAWRGB,
+    };
+    Ok((Resolution::new(width, height), format))
+}
+
+/// The index of a recording directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordingIndex {
+    camera_name: String,
+    description: String,
+    camera_format: CameraFormat,
+    frames: Vec<RecordedFrame>,
+}
+
+impl RecordingIndex {
+    /// Reads the index of the recording in `directory`.
+    /// # Errors
+    /// If the index file cannot be read or is malformed, this will error.
+    pub fn load(directory: impl AsRef<Path>) -> Result<Self, NokhwaError> {
+        let path = directory.as_ref().join(INDEX_FILE_NAME);
+        let contents = std::fs::read_to_string(&path).map_err(|why| {
+            NokhwaError::OpenDeviceError(path.to_string_lossy().to_string(), why.to_string())
+        })?;
+
+        Self::parse(&contents)
+    }
+
+    /// Parses the contents of an index file.
+    /// # Errors
+    /// If the index is malformed, this will error.
+    pub fn parse(contents: &str) -> Result<Self, NokhwaError> {
+        let error = |why: &str| NokhwaError::StructureError {
+            structure: "RecordingIndex".to_string(),
+            error: why.to_string(),
+        };
+
+        let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+
+        if lines.next().map(str::trim) != Some(&format!("# {INDEX_VERSION}")) {
+            return Err(error("Missing or unsupported version header"));
+        }
+
+        let mut header = HashMap::new();
+        let mut frames = vec![];
+        let mut seen_columns = false;
+
+        for line in lines {
+            if let Some(entry) = line.strip_prefix('#') {
+                if let Some((key, value)) = entry.split_once(':') {
+                    header.insert(key.trim().to_string(), value.trim().to_string());
+                }
+            } else if !seen_columns {
+                if line.trim() != INDEX_COLUMNS {
+                    return Err(error("Unexpected column names"));
+                }
+                seen_columns = true;
+            } else {
+                frames.push(RecordedFrame::from_row(line)?);
+            }
+        }
+
+        let field = |key: &str| {
+            header
+                .get(key)
+                .ok_or_else(|| error(&format!("Missing header field {key}")))
+        };
+        let number = |key: &str| {
+            field(key)?
+                .parse::<u32>()
+                .map_err(|why| error(&format!("Invalid {key}: {why}")))
+        };
+
+        let colorimetry = Colorimetry::new(
+            match header.get("color_space") {
+                Some(space) => space.parse::<ColorSpace>()?,
+                None => ColorSpace::default(),
+            },
+            match header.get("color_range") {
+                Some(range) => range.parse::<ColorRange>()?,
+                None => ColorRange::default(),
+            },
+        );
+
+        let camera_format = CameraFormat::new(
+            Resolution::new(number("width")?, number("height")?),
+            field("format")?.parse::<FrameFormat>()?,
+            number("frame_rate")?,
+        )
+        .with_colorimetry(colorimetry);
+
+        Ok(RecordingIndex {
+            camera_name: field("camera")?.clone(),
+            description: header.get("description").cloned().unwrap_or_default(),
+            camera_format,
+            frames,
+        })
+    }
+
+    /// Reads a TUM RGB-D image list such as `rgb.txt` or `depth.txt`. The frame files are relative to the directory
+    /// holding the list, the resolution and format are those of the first frame. See [TUM RGB-D](crate::recording#tum-rgb-d).
+    /// # Errors
+    /// If the list or its first frame cannot be read, or the list is malformed, this will error.
+    pub fn load_tum(list: impl AsRef<Path>) -> Result<Self, NokhwaError> {
+        let list = list.as_ref();
+        let error = |why: String| NokhwaError::StructureError {
+            structure: "RecordingIndex".to_string(),
+            error: format!("{}: {why}", list.to_string_lossy()),
+        };
+
+        let contents = std::fs::read_to_string(list).map_err(|why| {
+            NokhwaError::OpenDeviceError(list.to_string_lossy().to_string(), why.to_string())
+        })?;
+
+        let mut entries = vec![];
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (timestamp, file) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| error(format!("Expected a timestamp and a file in \"{line}\"")))?;
+            let timestamp = timestamp
+                .parse::<f64>()
+                .ok()
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                .ok_or_else(|| error(format!("Invalid timestamp in \"{line}\"")))?;
+            entries.push((timestamp, PathBuf::from(file.trim())));
+        }
+
+        let directory = list.parent().unwrap_or_else(|| Path::new(""));
+        let (resolution, format) = match entries.first() {
+            Some((_, file)) => probe_png(&directory.join(file)).map_err(error)?,
+            None => return Err(error("List holds no frames".to_string())),
+        };
+
+        // The lists carry no frame rate, estimate it from the timestamps
+        let span = entries[entries.len() - 1].0.saturating_sub(entries[0].0);
+        #[allow(clippy::cast_possible_truncation)]
+        #[allow(clippy::cast_sign_loss)]
+        #[allow(clippy::cast_precision_loss)]
+        let frame_rate = if span.is_zero() {
+            1
+        } else {
+            (((entries.len() - 1) as f64 / span.as_secs_f64()).round() as u32).max(1)
+        };
+
+        let frames = entries
+            .into_iter()
+            .enumerate()
+            .map(|(sequence, (timestamp, file))| RecordedFrame {
+                sequence: sequence as u64,
+                timestamp,
+                resolution,
+                format,
+                file,
+            })
+            .collect();
+
+        let camera_name = directory.file_name().map_or_else(
+            || list.to_string_lossy().to_string(),
+            |name| name.to_string_lossy().to_string(),
+        );
+        let list_name = list
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Ok(RecordingIndex {
+            camera_name,
+            description: format!("TUM RGB-D {list_name}"),
+            camera_format: CameraFormat::new(resolution, format, frame_rate),
+            frames,
+        })
+    }
+
+    /// The `human_name` of the recorded camera.
+    #[must_use]
+    pub fn camera_name(&self) -> &str {
+        &self.camera_name
+    }
+
+    /// The description of the recorded camera.
+    #[must_use]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// The [`CameraFormat`] the camera was recorded with.
+    #[must_use]
+    pub fn camera_format(&self) -> CameraFormat {
+        self.camera_format
+    }
+
+    /// All recorded frames, in capture order.
+    #[must_use]
+    pub fn frames(&self) -> &[RecordedFrame] {
+        &self.frames
+    }
+}
+
+/// Writes camera frames to a recording directory that can be played back with the `Replay` backend.
+///
+/// ```no_run
+/// # use nokhwa::{Camera, recording::Recorder, utils::{CameraIndex, RequestedFormat, RequestedFormatType}, pixel_format::RgbFormat};
+/// let mut camera = Camera::new(CameraIndex::Index(0), RequestedFormat::new::<RgbFormat>(RequestedFormatType::AbsoluteHighestResolution))?;
+/// camera.open_stream()?;
+/// let mut recorder = Recorder::create("session", camera.info(), camera.camera_format())?;
+/// for _ in 0..100 {
+///     recorder.record(&camera.frame()?)?;
+/// }
+/// recorder.finish()?;
+/// # Ok::<(), nokhwa::NokhwaError>(())
+/// ```
+#[cfg(feature = "output-recorder")]
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "output-recorder")))]
+pub struct Recorder {
+    directory: PathBuf,
+    index: BufWriter<File>,
+    frames_recorded: u64,
+    clock_offset: Option<Duration>,
+}
+
+#[cfg(feature = "output-recorder")]
+impl Recorder {
+    /// Creates a new recording in `directory`, creating the directory if needed.
+    /// # Errors
+    /// If the directory already holds a recording or it cannot be written to, this will error.
+    pub fn create(
+        directory: impl AsRef<Path>,
+        info: &CameraInfo,
+        format: CameraFormat,
+    ) -> Result<Self, NokhwaError> {
+        let directory = directory.as_ref().to_path_buf();
+        let index_path = directory.join(INDEX_FILE_NAME);
+
+        if index_path.exists() {
+            return Err(NokhwaError::GeneralError(format!(
+                "{} already contains a recording",
+                directory.to_string_lossy()
+            )));
+        }
+
+        std::fs::create_dir_all(directory.join(FRAMES_DIRECTORY))
+            .map_err(|why| NokhwaError::GeneralError(why.to_string()))?;
+
+        let file =
+            File::create(&index_path).map_err(|why| NokhwaError::GeneralError(why.to_string()))?;
+
+        // Header values run until the end of their line
+        let single_line = |value: String| value.replace(['\r', '\n'], " ");
+
+        let mut recorder = Recorder {
+            directory,
+            index: BufWriter::new(file),
+            frames_recorded: 0,
+            clock_offset: None,
+        };
+
+        let header = format!(
+            "# {INDEX_VERSION}\n# camera: {}\n# description: {}\n# width: {}\n# height: {}\n# frame_rate: {}\n# format: {}\n# color_space: {}\n# color_range: {}\n{INDEX_COLUMNS}\n",
+            single_line(info.human_name()),
+            single_line(info.description().to_string()),
+            format.width(),
+            format.height(),
+            format.frame_rate(),
+            format.format(),
+            format.colorimetry().space(),
+            format.colorimetry().range(),
+        );
+        recorder.write_index(&header)?;
+        recorder.flush()?;
+
+        Ok(recorder)
+    }
+
+    /// The directory this recorder writes to.
+    #[must_use]
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// The number of frames recorded so far.
+    #[must_use]
+    pub fn frames_recorded(&self) -> u64 {
+        self.frames_recorded
+    }
+
+    /// Records a frame, timestamped with its [`Buffer::timestamp()`] if it has one and the current system time otherwise.
+    ///
+    /// Buffer timestamps are moved onto the system clock using the offset between the two clocks at the first timestamped
+    /// frame, so the spacing between frames is kept as the camera reported it.
+    /// # Errors
+    /// If the payload or the index cannot be written, this will error.
+    pub fn record(&mut self, buffer: &Buffer) -> Result<RecordedFrame, NokhwaError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let timestamp = match buffer.timestamp() {
+            Some(capture_time) => {
+                let offset = *self
+                    .clock_offset
+                    .get_or_insert_with(|| now.saturating_sub(capture_time));
+                offset + capture_time
+            }
+            None => now,
+        };
+
+        self.record_at(buffer, timestamp)
+    }
+
+    /// Records a frame with an explicit capture `timestamp`, since the UNIX epoch.
+    /// # Errors
+    /// If the payload or the index cannot be written, this will error.
+    pub fn record_at(
+        &mut self,
+        buffer: &Buffer,
+        timestamp: Duration,
+    ) -> Result<RecordedFrame, NokhwaError> {
+        let format = buffer.source_frame_format();
+        let file = PathBuf::from(FRAMES_DIRECTORY).join(format!(
+            "{:08}.{}",
+            self.frames_recorded,
+            payload_extension(format)
+        ));
+
+        std::fs::write(self.directory.join(&file), buffer.buffer())
+            .map_err(|why| NokhwaError::GeneralError(why.to_string()))?;
+
+        let frame = RecordedFrame {
+            sequence: buffer.sequence().unwrap_or(self.frames_recorded),
+            timestamp,
+            resolution: buffer.resolution(),
+            format,
+            file,
+        };
+
+        self.write_index(&format!("{}\n", frame.to_row()))?;
+        self.frames_recorded += 1;
+
+        Ok(frame)
+    }
+
+    /// Flushes the index to disk.
+    /// # Errors
+    /// If the index cannot be written, this will error.
+    pub fn flush(&mut self) -> Result<(), NokhwaError> {
+        self.index
+            .flush()
+            .map_err(|why| NokhwaError::GeneralError(why.to_string()))
+    }
+
+    /// Flushes and closes the recording.
+    /// # Errors
+    /// If the index cannot be written, this will error.
+    pub fn finish(mut self) -> Result<(), NokhwaError> {
+        self.flush()
+    }
+
+    fn write_index(&mut self, contents: &str) -> Result<(), NokhwaError> {
+        self.index
+            .write_all(contents.as_bytes())
+            .map_err(|why| NokhwaError::GeneralError(why.to_string()))
+    }
+}
+
+#[cfg(feature = "output-recorder")]
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDirectory;
+
+    const INDEX: &str = "# nokhwa-recording 1
+# camera: HD Webcam
+# description: Video4Linux Device @ /dev/video0
+# width: 1280
+# height: 720
+# frame_rate: 30
+# format: MJPEG
+# color_space: BT.709
+# color_range: Full
+sequence,timestamp_us,width,height,format,file
+0,1700000000000000,1280,720,MJPEG,frames/00000000.jpg
+3,1700000000100000,1280,720,MJPEG,frames/00000001.jpg
+";
+
+    /// The start of a PNG image up to its IHDR chunk, which is all that is probed.
+    fn png_header(width: u32, height: u32, bit_depth: u8, color_type: u8) -> Vec<u8> {
+        let mut data = PNG_SIGNATURE.to_vec();
+        data.extend_from_slice(&13_u32.to_be_bytes());
+        data.extend_from_slice(b"IHDR");
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+        data
+    }
+
+    #[test]
+    fn parses_an_index() {
+        let index = RecordingIndex::parse(INDEX).unwrap();
+        assert_eq!(index.camera_name(), "HD Webcam");
+        assert_eq!(index.description(), "Video4Linux Device @ /dev/video0");
+        assert_eq!(
+            index.camera_format(),
+            CameraFormat::new(Resolution::new(1280, 720), FrameFormat::MJPEG, 30)
+                .with_colorimetry(Colorimetry::new(ColorSpace::Bt709, ColorRange::Full))
+        );
+
+        let frames = index.frames();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].sequence(), 3);
+        assert_eq!(
+            frames[1].timestamp(),
+            Duration::from_micros(1_700_000_000_100_000)
+        );
+        assert_eq!(frames[1].resolution(), Resolution::new(1280, 720));
+        assert_eq!(frames[1].format(), FrameFormat::MJPEG);
+        assert_eq!(frames[1].file(), Path::new("frames/00000001.jpg"));
+
+        // Recordings from before colorimetry was recorded have the default one
+        let without_colorimetry = INDEX
+            .lines()
+            .filter(|line| !line.starts_with("# color_"))
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let index = RecordingIndex::parse(&without_colorimetry).unwrap();
+        assert_eq!(index.camera_format().colorimetry(), Colorimetry::default());
+    }
+
+    #[test]
+    fn rejects_malformed_indices() {
+        let replaced = |from: &str, to: &str| RecordingIndex::parse(&INDEX.replacen(from, to, 1));
+        assert!(replaced("nokhwa-recording 1", "nokhwa-recording 2").is_err());
+        assert!(replaced("# camera: HD Webcam\n", "").is_err());
+        assert!(replaced("# width: 1280", "# width: wide").is_err());
+        assert!(replaced("timestamp_us", "timestamp_ms").is_err());
+        assert!(replaced("0,1700000000000000,", "0,1700000000000000,1,").is_err());
+        assert!(replaced("3,1700000000100000", "x,1700000000100000").is_err());
+        assert!(replaced("MJPEG,frames", "JPEG2000,frames").is_err());
+    }
+
+    #[cfg(feature = "output-recorder")]
+    #[test]
+    fn recorded_indices_load_as_written() {
+        use nokhwa_core::types::CameraIndex;
+
+        let directory = TempDirectory::new("recording-round-trip");
+        let info = CameraInfo::new("Stereo\nCamera", "Left eye", "", CameraIndex::Index(0));
+        let format = CameraFormat::new(Resolution::new(4, 2), FrameFormat::GRAY, 15)
+            .with_colorimetry(Colorimetry::new(ColorSpace::Bt2020, ColorRange::Limited));
+
+        let mut recorder = Recorder::create(directory.path(), &info, format).unwrap();
+        let mut recorded = vec![];
+        for (index, sequence) in [Some(10), Some(13), None].into_iter().enumerate() {
+            let payload = [u8::try_from(index).unwrap(); 8];
+            let mut buffer = Buffer::new(format.resolution(), &payload, FrameFormat::GRAY);
+            if let Some(sequence) = sequence {
+                buffer = buffer.with_sequence(sequence);
+            }
+            let timestamp = Duration::from_micros(1_000_000 + 66_667 * index as u64);
+            recorded.push(recorder.record_at(&buffer, timestamp).unwrap());
+        }
+        recorder.finish().unwrap();
+        // A directory holds one recording only
+        assert!(Recorder::create(directory.path(), &info, format).is_err());
+
+        let index = RecordingIndex::load(directory.path()).unwrap();
+        assert_eq!(index.camera_name(), "Stereo Camera");
+        assert_eq!(index.description(), "Left eye");
+        assert_eq!(index.camera_format(), format);
+        assert_eq!(index.frames(), recorded);
+        // Unnumbered frames are numbered by their position
+        let sequences = index
+            .frames()
+            .iter()
+            .map(RecordedFrame::sequence)
+            .collect::<Vec<u64>>();
+        assert_eq!(sequences, [10, 13, 2]);
+        for (position, frame) in index.frames().iter().enumerate() {
+            let payload = std::fs::read(directory.path().join(frame.file())).unwrap();
+            assert_eq!(payload, [u8::try_from(position).unwrap(); 8]);
+        }
+    }
+
+    #[test]
+    fn loads_tum_lists() {
+        let directory = TempDirectory::new("recording-tum");
+        std::fs::create_dir_all(directory.path().join("rgb")).unwrap();
+        std::fs::create_dir_all(directory.path().join("depth")).unwrap();
+        let mut rgb = "# color images\n# timestamp filename\n".to_string();
+        let mut depth = String::new();
+        for index in 0..3 {
+            let timestamp = format!("1305031102.{:06}", 175_304 + index * 33_333);
+            std::fs::write(
+                directory.path().join(format!("rgb/{timestamp}.png")),
+                png_header(640, 480, 8, 2),
+            )
+            .unwrap();
+            std::fs::write(
+                directory.path().join(format!("depth/{timestamp}.png")),
+                png_header(640, 480, 16, 0),
+            )
+            .unwrap();
+            rgb += &format!("{timestamp} rgb/{timestamp}.png\n");
+            depth += &format!("{timestamp}\tdepth/{timestamp}.png\n");
+        }
+        std::fs::write(directory.path().join("rgb.txt"), rgb).unwrap();
+        std::fs::write(directory.path().join("depth.txt"), depth).unwrap();
+
+        let index = RecordingIndex::load_tum(directory.path().join("rgb.txt")).unwrap();
+        assert_eq!(
+            index.camera_name(),
+            directory.path().file_name().unwrap().to_string_lossy()
+        );
+        assert_eq!(index.description(), "TUM RGB-D rgb.txt");
+        // The frame rate is estimated from the timestamps
+        assert_eq!(
+            index.camera_format(),
+            CameraFormat::new(Resolution::new(640, 480), FrameFormat::RAWRGB, 30)
+        );
+        assert_eq!(index.frames().len(), 3);
+        for (sequence, frame) in index.frames().iter().enumerate() {
+            assert_eq!(frame.sequence(), sequence as u64);
+            assert_eq!(frame.format(), FrameFormat::RAWRGB);
+            assert!(frame.file().starts_with("rgb"));
+        }
+        let span = index.frames()[2].timestamp() - index.frames()[0].timestamp();
+        assert!(span.abs_diff(Duration::from_micros(66_666)) < Duration::from_micros(2));
+
+        let index = RecordingIndex::load_tum(directory.path().join("depth.txt")).unwrap();
+        assert_eq!(index.camera_format().format(), FrameFormat::Z16);
+        assert_eq!(index.frames().len(), 3);
+
+        let list = directory.path().join("broken.txt");
+        for contents in [
+            "",
+            "# only comments\n",
+            "1305031102.1\n",
+            "yesterday rgb/a.png\n",
+        ] {
+            std::fs::write(&list, contents).unwrap();
+            assert!(RecordingIndex::load_tum(&list).is_err(), "{contents:?}");
+        }
+        std::fs::write(&list, "1305031102.1 rgb/missing.png\n").unwrap();
+        assert!(RecordingIndex::load_tum(&list).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+/*
+ * Copyright 2022 l1npengtul <l1npengtul@protonmail.com> / The Nokhwa Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::Camera;
+#[cfg(feature = "input-replay")]
+use nokhwa_core::types::{
+    frame_formats, ApiBackend, CameraIndex, RequestedFormat, RequestedFormatType,
+};
+use nokhwa_core::{
+    buffer::Buffer,
+    error::NokhwaError,
+    types::{buf_z16_to_depth, FrameFormat},
+};
+#[cfg(feature = "input-replay")]
+use std::path::Path;
+use std::time::Duration;
+
+/// Default for [`RgbdCamera::max_time_difference()`], the same as the TUM RGB-D association tool.
+pub const DEFAULT_MAX_TIME_DIFFERENCE: Duration = Duration::from_millis(20);
+/// Default for [`RgbdCamera::depth_scale()`]: millimeters, which is what most UVC depth cameras deliver.
+pub const DEFAULT_DEPTH_SCALE: f32 = 0.001;
+/// The [`RgbdCamera::depth_scale()`] of the TUM RGB-D dataset, which stores depth in fifths of a millimeter.
+pub const TUM_DEPTH_SCALE: f32 = 1.0 / 5000.0;
+
+/// A color frame and the depth frame captured together with it.
+#[derive(Clone, Debug)]
+pub struct RgbdFrame {
+    color: Buffer,
+    depth: Buffer,
+    depth_scale: f32,
+}
+
+impl RgbdFrame {
+    /// The color frame.
+    #[must_use]
+    pub fn color(&self) -> &Buffer {
+        &self.color
+    }
+
+    /// The depth frame, in [`FrameFormat::Z16`].
+    #[must_use]
+    pub fn depth(&self) -> &Buffer {
+        &self.depth
+    }
+
+    /// The capture time of the pair, which is the timestamp of the color frame. The depth frame was captured within the
+    /// [`max_time_difference()`](RgbdCamera::max_time_difference) of it.
+    #[must_use]
+    pub fn timestamp(&self) -> Duration {
+        self.color.timestamp().unwrap_or_default()
+    }
+
+    /// The length of one depth unit in meters.
+    #[must_use]
+    pub fn depth_scale(&self) -> f32 {
+        self.depth_scale
+    }
+
+    /// The depth of every pixel in meters, row by row. Pixels without a measurement are 0.
+    /// # Errors
+    /// If the depth frame is malformed, this will error.
+    pub fn depth_meters(&self) -> Result<Vec<f32>, NokhwaError> {
+        let resolution = self.depth.resolution();
+        let mut depth = vec![0.0; (resolution.width() * resolution.height()) as usize];
+        buf_z16_to_depth(self.depth.buffer(), &mut depth, self.depth_scale)?;
+        Ok(depth)
+    }
+
+    /// Splits the pair into the color and depth frames.
+    #[must_use]
+    pub fn into_parts(self) -> (Buffer, Buffer) {
+        (self.color, self.depth)
+    }
+}
+
+/// Captures color and depth frames in pairs from two [`Camera`]s, such as the color and depth devices of a UVC depth
+/// camera or the two streams of a [TUM RGB-D](crate::recording#tum-rgb-d) dataset.
+///
+/// Frames are paired by their [`Buffer::timestamp()`], so both cameras must timestamp their frames on the same clock.
+/// Whenever the two next frames are further apart than [`max_time_difference()`](Self::max_time_difference), the older
+/// one is dropped and the next frame of its stream is read instead, so every frame is used in at most one pair and pairs
+/// come in capture order.
+///
+/// ```no_run
+/// # use nokhwa::{Camera, rgbd::RgbdCamera, pixel_format::RgbFormat, utils::{CameraIndex, FrameFormat, RequestedFormat, RequestedFormatType}};
+/// let request = RequestedFormatType::AbsoluteHighestFrameRate;
+/// let color = Camera::new(CameraIndex::Index(0), RequestedFormat::new::<RgbFormat>(request))?;
+/// let depth = Camera::new(CameraIndex::Index(1), RequestedFormat::with_formats(request, &[FrameFormat::Z16]))?;
+/// let mut camera = RgbdCamera::new(color, depth)?;
+/// camera.open_stream()?;
+/// let frame = camera.frame()?;
+/// let depth = frame.depth_meters()?;
+/// # Ok::<(), nokhwa::NokhwaError>(())
+/// ```
+pub struct RgbdCamera {
+    color: Camera,
+    depth: Camera,
+    max_time_difference: Duration,
+    depth_scale: f32,
+}
+
+impl RgbdCamera {
+    /// Pairs a `color` camera with a `depth` camera.
+    /// # Errors
+    /// If the depth camera does not deliver [`FrameFormat::Z16`], this will error.
+    pub fn new(color: Camera, depth: Camera) -> Result<Self, NokhwaError> {
+        if depth.frame_format() != FrameFormat::Z16 {
+            return Err(NokhwaError::StructureError {
+                structure: "RgbdCamera".to_string(),
+                error: format!(
+                    "Depth camera delivers {}, not {}",
+                    depth.frame_format(),
+                    FrameFormat::Z16
+                ),
+            });
+        }
+
+        Ok(RgbdCamera {
+            color,
+            depth,
+            max_time_difference: DEFAULT_MAX_TIME_DIFFERENCE,
+            depth_scale: DEFAULT_DEPTH_SCALE,
+        })
+    }
+
+    /// Opens the `rgb.txt` and `depth.txt` streams of the TUM RGB-D dataset in `directory` with the `Replay` backend.
+    /// The depth scale is set to [`TUM_DEPTH_SCALE`].
+    /// # Errors
+    /// If either list cannot be read, this will error.
+    #[cfg(feature = "input-replay")]
+    #[cfg_attr(feature = "docs-features", doc(cfg(feature = "input-replay")))]
+    pub fn tum(directory: impl AsRef<Path>) -> Result<Self, NokhwaError> {
+        let open = |list: &str| {
+            let path = directory.as_ref().join(list);
+            Camera::with_backend(
+                CameraIndex::String(path.to_string_lossy().to_string()),
+                RequestedFormat::with_formats(RequestedFormatType::None, frame_formats()),
+                ApiBackend::Replay,
+            )
+        };
+
+        let mut camera = RgbdCamera::new(open("rgb.txt")?, open("depth.txt")?)?;
+        camera.set_depth_scale(TUM_DEPTH_SCALE);
+        Ok(camera)
+    }
+
+    /// The color camera.
+    #[must_use]
+    pub fn color(&self) -> &Camera {
+        &self.color
+    }
+
+    /// The color camera, e.g. to change its controls.
+    pub fn color_mut(&mut self) -> &mut Camera {
+        &mut self.color
+    }
+
+    /// The depth camera.
+    #[must_use]
+    pub fn depth(&self) -> &Camera {
+        &self.depth
+    }
+
+    /// The depth camera, e.g. to change its controls.
+    pub fn depth_mut(&mut self) -> &mut Camera {
+        &mut self.depth
+    }
+
+    /// How far apart the color and depth frames of a pair may be captured.
+    #[must_use]
+    pub fn max_time_difference(&self) -> Duration {
+        self.max_time_difference
+    }
+
+    /// Sets how far apart the color and depth frames of a pair may be captured.
+    pub fn set_max_time_difference(&mut self, max_time_difference: Duration) {
+        self.max_time_difference = max_time_difference;
+    }
+
+    /// The length of one depth unit in meters.
+    #[must_use]
+    pub fn depth_scale(&self) -> f32 {
+        self.depth_scale
+    }
+
+    /// Sets the length of one depth unit in meters. This depends on the camera, see [`buf_z16_to_depth`].
+    pub fn set_depth_scale(&mut self, depth_scale: f32) {
+        self.depth_scale = depth_scale;
+    }
+
+    /// Opens the streams of both cameras.
+    /// # Errors
+    /// If either camera fails to open its stream, this will error.
+    pub fn open_stream(&mut self) -> Result<(), NokhwaError> {
+        self.color.open_stream()?;
+        self.depth.open_stream()
+    }
+
+    /// Checks if the streams of both cameras are open.
+    #[must_use]
+    pub fn is_stream_open(&self) -> bool {
+        self.color.is_stream_open() && self.depth.is_stream_open()
+    }
+
+    /// Captures the next pair of frames.
+    /// # Errors
+    /// If either camera fails to get a frame, or a frame has no timestamp, this will error.
+    pub fn frame(&mut self) -> Result<RgbdFrame, NokhwaError> {
+        let mut color = self.color.frame()?;
+        let mut depth = self.depth.frame()?;
+
+        loop {
+            let (Some(color_time), Some(depth_time)) = (color.timestamp(), depth.timestamp())
+            else {
+                return Err(NokhwaError::ReadFrameError(
+                    "Cannot pair frames without timestamps".to_string(),
+                ));
+            };
+
+            if color_time.abs_diff(depth_time) <= self.max_time_difference {
+                return Ok(RgbdFrame {
+                    color,
+                    depth,
+                    depth_scale: self.depth_scale,
+                });
+            }
+
+            if color_time < depth_time {
+                color = self.color.frame()?;
+            } else {
+                depth = self.depth.frame()?;
+            }
+        }
+    }
+
+    /// Stops the streams of both cameras.
+    /// # Errors
+    /// If either camera fails to stop its stream, this will error.
+    pub fn stop_stream(&mut self) -> Result<(), NokhwaError> {
+        let color = self.color.stop_stream();
+        self.depth.stop_stream()?;
+        color
+    }
+}
@@ -0,0 +1,530 @@
+/*
+ * Copyright 2022 l1npengtul <l1npengtul@protonmail.com> / The Nokhwa Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use nokhwa_core::buffer::Buffer;
+use std::{
+    collections::VecDeque,
+    fmt::{Display, Formatter},
+    time::{Duration, Instant},
+};
+
+/// Number of buckets of a [`LatencyHistogram`]. Bucket `n` holds durations up to 2^`n` microseconds, the last one everything longer.
+const BUCKET_COUNT: usize = 26;
+/// Number of frame intervals [`CaptureStats::recent_frame_rate()`] is averaged over.
+const RECENT_FRAMES: usize = 30;
+
+/// A histogram of durations with power-of-two microsecond buckets, from 1µs to ~33s.
+///
+/// This is used for the capture statistics, but can also be used to time your own processing of the frames, so that
+/// both show up in the same units.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    buckets: [u64; BUCKET_COUNT],
+    count: u64,
+    total: Duration,
+    min: Option<Duration>,
+    max: Option<Duration>,
+}
+
+impl LatencyHistogram {
+    /// Creates an empty histogram.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a sample to the histogram.
+    pub fn record(&mut self, duration: Duration) {
+        // Rounded up, so that no sample is longer than its bucket's upper bound
+        let micros = duration.as_nanos().div_ceil(1000).max(1);
+        let bucket = (u128::BITS - (micros - 1).leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKET_COUNT - 1)] += 1;
+        self.count += 1;
+        self.total = self.total.saturating_add(duration);
+        self.min = Some(self.min.map_or(duration, |min| min.min(duration)));
+        self.max = Some(self.max.map_or(duration, |max| max.max(duration)));
+    }
+
+    /// Removes all samples.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// The number of samples recorded.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The shortest sample, if any were recorded.
+    #[must_use]
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    /// The longest sample, if any were recorded.
+    #[must_use]
+    pub fn max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    /// The mean of all samples, if any were recorded.
+    #[must_use]
+    pub fn mean(&self) -> Option<Duration> {
+        // Durations only divide by u32, fall back to nanoseconds for very long runs
+        match u32::try_from(self.count) {
+            Ok(0) => None,
+            Ok(count) => Some(self.total / count),
+            Err(_) => u64::try_from(self.total.as_nanos() / u128::from(self.count))
+                .ok()
+                .map(Duration::from_nanos),
+        }
+    }
+
+    /// An upper bound for the `percentile` (0-100) of the samples, accurate to a factor of two.
+    /// Returns `None` if no samples were recorded.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * self.count as f64).ceil() as u64;
+        let mut seen = 0;
+        for (bucket, upper) in self.buckets() {
+            seen += bucket;
+            if seen >= rank.max(1) {
+                return Some(upper.min(self.max?).max(self.min?));
+            }
+        }
+
+        self.max
+    }
+
+    /// The bucket counts, paired with the upper bound of each bucket. The last bucket has no upper bound and is paired with
+    /// [`Duration::MAX`].
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, Duration)> + '_ {
+        self.buckets.iter().enumerate().map(|(bucket, count)| {
+            let upper = if bucket == BUCKET_COUNT - 1 {
+                Duration::MAX
+            } else {
+                Duration::from_micros(1 << bucket)
+            };
+            (*count, upper)
+        })
+    }
+}
+
+impl Display for LatencyHistogram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (Some(mean), Some(p50), Some(p99), Some(max)) = (
+            self.mean(),
+            self.percentile(50.0),
+            self.percentile(99.0),
+            self.max(),
+        ) else {
+            return write!(f, "no samples");
+        };
+
+        write!(
+            f,
+            "mean {:.1}ms, p50 <{:.1}ms, p99 <{:.1}ms, max {:.1}ms",
+            mean.as_secs_f64() * 1000.0,
+            p50.as_secs_f64() * 1000.0,
+            p99.as_secs_f64() * 1000.0,
+            max.as_secs_f64() * 1000.0,
+        )
+    }
+}
+
+/// A snapshot of the capture health of a [`Camera`](crate::Camera) since its stream was opened.
+///
+/// # Dropped Frames
+/// Dropped frames are counted from gaps in the [`Buffer::sequence()`] numbers the backend reports. Backends that do not
+/// number their frames can only report the drops they know of through [`Buffer::dropped_frames()`].
+///
+/// # Latency
+/// When the backend can read the clock its [`Buffer::timestamp()`]s are taken on (see
+/// [`timestamp_clock()`](crate::camera_traits::CaptureBackendTrait::timestamp_clock)), such as `CLOCK_MONOTONIC` on `Video4Linux`,
+/// [`latency()`](Self::latency) is the true capture-to-delivery latency: the time on that clock when a frame was
+/// returned, minus its timestamp.
+///
+/// Otherwise the timestamps' epoch is unknown, and only [`relative_latency()`](Self::relative_latency) is measured: how
+/// much later than the fastest frame delivered so far a frame was delivered, compared to when it was captured. This shows
+/// queueing and stalls between the driver and your code, but not the constant part of the latency.
+///
+/// Only frames with a [`Buffer::timestamp()`] are counted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CaptureStats {
+    frames_delivered: u64,
+    frames_dropped: u64,
+    negotiated_frame_rate: u32,
+    elapsed: Duration,
+    recent_frame_rate: f64,
+    latency: LatencyHistogram,
+    relative_latency: LatencyHistogram,
+    frame_interval: LatencyHistogram,
+}
+
+impl CaptureStats {
+    /// The number of frames handed to the caller.
+    #[must_use]
+    pub fn frames_delivered(&self) -> u64 {
+        self.frames_delivered
+    }
+
+    /// The number of frames the camera captured but that never reached the caller.
+    #[must_use]
+    pub fn frames_dropped(&self) -> u64 {
+        self.frames_dropped
+    }
+
+    /// The share of captured frames that were dropped, from 0 to 1.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn drop_rate(&self) -> f64 {
+        let captured = self.frames_delivered + self.frames_dropped;
+        if captured == 0 {
+            return 0.0;
+        }
+        self.frames_dropped as f64 / captured as f64
+    }
+
+    /// The frame rate of the current [`CameraFormat`](crate::utils::CameraFormat).
+    #[must_use]
+    pub fn negotiated_frame_rate(&self) -> u32 {
+        self.negotiated_frame_rate
+    }
+
+    /// The average rate frames were delivered at, from the first frame to the last.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn achieved_frame_rate(&self) -> f64 {
+        if self.frames_delivered < 2 || self.elapsed.is_zero() {
+            return 0.0;
+        }
+        (self.frames_delivered - 1) as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// The rate frames were delivered at over the last 30 frames.
+    #[must_use]
+    pub fn recent_frame_rate(&self) -> f64 {
+        self.recent_frame_rate
+    }
+
+    /// The time between the first and the last delivered frame.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The capture-to-delivery latency of the delivered frames, if the backend's clock is known. See [Latency](#latency).
+    #[must_use]
+    pub fn latency(&self) -> &LatencyHistogram {
+        &self.latency
+    }
+
+    /// The delivery lag of the frames relative to the fastest one, if the backend's clock is unknown. See [Latency](#latency).
+    #[must_use]
+    pub fn relative_latency(&self) -> &LatencyHistogram {
+        &self.relative_latency
+    }
+
+    /// The time between consecutive delivered frames.
+    #[must_use]
+    pub fn frame_interval(&self) -> &LatencyHistogram {
+        &self.frame_interval
+    }
+}
+
+impl Display for CaptureStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} frames at {:.1}/{} FPS ({:.1} recent), {} dropped ({:.1}%), ",
+            self.frames_delivered,
+            self.achieved_frame_rate(),
+            self.negotiated_frame_rate,
+            self.recent_frame_rate,
+            self.frames_dropped,
+            self.drop_rate() * 100.0,
+        )?;
+
+        if self.relative_latency.count() > self.latency.count() {
+            write!(f, "relative latency {}", self.relative_latency)
+        } else {
+            write!(f, "latency {}", self.latency)
+        }
+    }
+}
+
+/// Collects [`CaptureStats`] from the frames a camera delivers.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StatsCollector {
+    stats: CaptureStats,
+    first_delivery: Option<Instant>,
+    last_sequence: Option<u64>,
+    fastest_lag: Option<i128>,
+    recent_deliveries: VecDeque<Instant>,
+}
+
+impl StatsCollector {
+    /// Records a frame returned by [`frame()`](crate::Camera::frame), read when the backend's
+    /// [`timestamp_clock()`](crate::camera_traits::CaptureBackendTrait::timestamp_clock) was at `clock`.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_possible_wrap)]
+    #[allow(clippy::cast_sign_loss)]
+    pub(crate) fn record_frame(&mut self, buffer: &Buffer, clock: Option<Duration>) {
+        let now = self.record_delivery();
+
+        let gap = match (self.last_sequence, buffer.sequence()) {
+            // A sequence that goes backwards means the stream was restarted
+            (Some(last), Some(sequence)) if sequence > last => sequence - last - 1,
+            (Some(_), Some(_)) => 0,
+            _ => buffer.dropped_frames(),
+        };
+        self.stats.frames_dropped += gap;
+        if let Some(sequence) = buffer.sequence() {
+            self.last_sequence = Some(sequence);
+        }
+
+        let Some(timestamp) = buffer.timestamp() else {
+            return;
+        };
+
+        match (clock, self.first_delivery) {
+            (Some(clock), _) => self.stats.latency.record(clock.saturating_sub(timestamp)),
+            (None, Some(first)) => {
+                // The backend's clock has an unknown epoch, only differences between lags are meaningful
+                let lag = (now - first).as_nanos() as i128 - timestamp.as_nanos() as i128;
+                let fastest = *self
+                    .fastest_lag
+                    .insert(self.fastest_lag.map_or(lag, |fastest| fastest.min(lag)));
+                self.stats
+                    .relative_latency
+                    .record(Duration::from_nanos((lag - fastest) as u64));
+            }
+            (None, None) => {}
+        }
+    }
+
+    /// Records a frame returned by [`frame_raw()`](crate::Camera::frame_raw), which carries no metadata.
+    pub(crate) fn record_raw_frame(&mut self) {
+        self.record_delivery();
+    }
+
+    /// A snapshot of the statistics, for a camera running at `negotiated_frame_rate`.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn snapshot(&self, negotiated_frame_rate: u32) -> CaptureStats {
+        let mut stats = self.stats.clone();
+        stats.negotiated_frame_rate = negotiated_frame_rate;
+        stats.recent_frame_rate = match (
+            self.recent_deliveries.front(),
+            self.recent_deliveries.back(),
+        ) {
+            (Some(oldest), Some(newest)) if newest > oldest => {
+                (self.recent_deliveries.len() - 1) as f64 / (*newest - *oldest).as_secs_f64()
+            }
+            _ => 0.0,
+        };
+        stats
+    }
+
+    /// Starts over, e.g. when the stream is reopened.
+    pub(crate) fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn record_delivery(&mut self) -> Instant {
+        let now = Instant::now();
+        let first = *self.first_delivery.get_or_insert(now);
+
+        if let Some(last) = self.recent_deliveries.back() {
+            self.stats.frame_interval.record(now - *last);
+        }
+        self.recent_deliveries.push_back(now);
+        if self.recent_deliveries.len() > RECENT_FRAMES + 1 {
+            self.recent_deliveries.pop_front();
+        }
+
+        self.stats.frames_delivered += 1;
+        self.stats.elapsed = now - first;
+        now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nokhwa_core::types::{FrameFormat, Resolution};
+
+    fn frame() -> Buffer {
+        Buffer::new(Resolution::new(1, 1), &[0], FrameFormat::GRAY)
+    }
+
+    fn histogram(samples: &[(Duration, usize)]) -> LatencyHistogram {
+        let mut histogram = LatencyHistogram::new();
+        for (duration, count) in samples {
+            for _ in 0..*count {
+                histogram.record(*duration);
+            }
+        }
+        histogram
+    }
+
+    /// The index of the only bucket that holds a sample.
+    fn bucket_of(duration: Duration) -> usize {
+        let histogram = histogram(&[(duration, 1)]);
+        let buckets = histogram
+            .buckets()
+            .enumerate()
+            .filter(|(_, (count, _))| *count > 0)
+            .map(|(bucket, _)| bucket)
+            .collect::<Vec<usize>>();
+        assert_eq!(buckets.len(), 1);
+        buckets[0]
+    }
+
+    #[test]
+    fn records_into_power_of_two_buckets() {
+        assert_eq!(bucket_of(Duration::ZERO), 0);
+        assert_eq!(bucket_of(Duration::from_nanos(1500)), 1);
+        assert_eq!(bucket_of(Duration::from_micros(1)), 0);
+        assert_eq!(bucket_of(Duration::from_micros(2)), 1);
+        assert_eq!(bucket_of(Duration::from_micros(3)), 2);
+        assert_eq!(bucket_of(Duration::from_micros(1024)), 10);
+        assert_eq!(bucket_of(Duration::from_micros(1025)), 11);
+        assert_eq!(bucket_of(Duration::from_secs(3600)), BUCKET_COUNT - 1);
+
+        let bounds = LatencyHistogram::new()
+            .buckets()
+            .map(|(_, upper)| upper)
+            .collect::<Vec<Duration>>();
+        assert_eq!(bounds[0], Duration::from_micros(1));
+        assert_eq!(bounds[10], Duration::from_micros(1024));
+        assert_eq!(bounds[BUCKET_COUNT - 1], Duration::MAX);
+    }
+
+    #[test]
+    fn summarizes_samples() {
+        let mut histogram = histogram(&[
+            (Duration::from_micros(100), 3),
+            (Duration::from_millis(10), 1),
+        ]);
+        assert_eq!(histogram.count(), 4);
+        assert_eq!(histogram.min(), Some(Duration::from_micros(100)));
+        assert_eq!(histogram.max(), Some(Duration::from_millis(10)));
+        assert_eq!(histogram.mean(), Some(Duration::from_micros(2575)));
+
+        histogram.clear();
+        assert_eq!(histogram, LatencyHistogram::new());
+        assert_eq!(histogram.mean(), None);
+        assert_eq!(histogram.to_string(), "no samples");
+    }
+
+    #[test]
+    fn percentiles_are_bounded_by_their_bucket_and_the_samples() {
+        assert_eq!(LatencyHistogram::new().percentile(50.0), None);
+
+        // 90 samples in the bucket up to 128µs, 10 in the one up to 16.384ms
+        let histogram = histogram(&[
+            (Duration::from_micros(100), 90),
+            (Duration::from_millis(10), 10),
+        ]);
+        assert_eq!(histogram.percentile(0.0), Some(Duration::from_micros(128)));
+        assert_eq!(histogram.percentile(50.0), Some(Duration::from_micros(128)));
+        assert_eq!(histogram.percentile(90.0), Some(Duration::from_micros(128)));
+        // The bucket's upper bound is past the longest sample
+        assert_eq!(histogram.percentile(90.5), Some(Duration::from_millis(10)));
+        assert_eq!(histogram.percentile(100.0), Some(Duration::from_millis(10)));
+        assert_eq!(histogram.percentile(250.0), Some(Duration::from_millis(10)));
+
+        // The bucket's upper bound is past the only sample, its lower bound before it
+        let single = self::histogram(&[(Duration::from_micros(5), 1)]);
+        for percentile in [0.0, 50.0, 100.0] {
+            assert_eq!(
+                single.percentile(percentile),
+                Some(Duration::from_micros(5))
+            );
+        }
+
+        for percentile in [0.0, 10.0, 50.0, 99.0, 100.0] {
+            let bound = histogram.percentile(percentile).unwrap();
+            assert!(bound >= histogram.min().unwrap() && bound <= histogram.max().unwrap());
+        }
+    }
+
+    #[test]
+    fn counts_sequence_gaps_as_dropped_frames() {
+        let mut collector = StatsCollector::default();
+        // Drops reported before the first sequence number are taken as they are
+        collector.record_frame(&frame().with_sequence(10).with_dropped_frames(3), None);
+        for sequence in [11, 12, 15] {
+            collector.record_frame(&frame().with_sequence(sequence), None);
+        }
+        let stats = collector.snapshot(30);
+        assert_eq!(stats.frames_delivered(), 4);
+        assert_eq!(stats.frames_dropped(), 5);
+        assert_eq!(stats.negotiated_frame_rate(), 30);
+
+        // A sequence going backwards is a restart, not a gap
+        for sequence in [0, 1, 4] {
+            collector.record_frame(&frame().with_sequence(sequence), None);
+        }
+        let stats = collector.snapshot(30);
+        assert_eq!(stats.frames_delivered(), 7);
+        assert_eq!(stats.frames_dropped(), 7);
+        assert!((stats.drop_rate() - 0.5).abs() < 1e-9);
+
+        // Repeated sequence numbers are not gaps either
+        collector.record_frame(&frame().with_sequence(4), None);
+        assert_eq!(collector.snapshot(30).frames_dropped(), 7);
+    }
+
+    #[test]
+    fn counts_reported_drops_of_unnumbered_frames() {
+        let mut collector = StatsCollector::default();
+        collector.record_frame(&frame(), None);
+        collector.record_frame(&frame().with_dropped_frames(2), None);
+        collector.record_raw_frame();
+        let stats = collector.snapshot(30);
+        assert_eq!(stats.frames_delivered(), 3);
+        assert_eq!(stats.frames_dropped(), 2);
+
+        collector.reset();
+        assert_eq!(collector.snapshot(30).frames_delivered(), 0);
+    }
+
+    #[test]
+    fn measures_latency_on_a_known_clock() {
+        let mut collector = StatsCollector::default();
+        let timestamp = Duration::from_secs(100);
+        collector.record_frame(
+            &frame().with_timestamp(timestamp),
+            Some(timestamp + Duration::from_millis(4)),
+        );
+        // Frames without a timestamp have no latency
+        collector.record_frame(&frame(), Some(timestamp));
+
+        let stats = collector.snapshot(30);
+        assert_eq!(stats.latency().count(), 1);
+        assert_eq!(stats.latency().max(), Some(Duration::from_millis(4)));
+        assert_eq!(stats.relative_latency().count(), 0);
+    }
+}
@@ -0,0 +1,561 @@
+/*
+ * Copyright 2022 l1npengtul <l1npengtul@protonmail.com> / The Nokhwa Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Deterministic test patterns for the `virtual` backend.
+//!
+//! Every pattern implements [`VirtualBackendTrait`] and renders the same frame for the same sequence
+//! number and timestamp, every time. [`TexturedPlane`] also exposes the camera pose and intrinsics it
+//! rendered with, so it can serve as ground truth for visual odometry and SLAM.
+//!
+//! Camera poses use the usual computer vision axes: X points right, Y points down and Z points forward.
+
+use nokhwa_core::{traits::VirtualBackendTrait, types::Resolution};
+use std::{f64::consts::PI, time::Duration};
+
+/// SMPTE style color bars at 75% intensity: white, yellow, cyan, green, magenta, red, blue and black.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ColorBars;
+
+impl ColorBars {
+    const BARS: [[u8; 3]; 8] = [
+        [191, 191, 191],
+        [191, 191, 0],
+        [0, 191, 191],
+        [0, 191, 0],
+        [191, 0, 191],
+        [191, 0, 0],
+        [0, 0, 191],
+        [0, 0, 0],
+    ];
+
+    /// The color of the bar under the pixel column `x`.
+    #[must_use]
+    pub fn color_at(x: u32, width: u32) -> [u8; 3] {
+        Self::BARS[(x as usize * Self::BARS.len()) / width.max(1) as usize]
+    }
+}
+
+impl VirtualBackendTrait for ColorBars {
+    fn name(&self) -> String {
+        "Color Bars".to_string()
+    }
+
+    fn render_frame(&mut self, _: u64, _: Duration, resolution: Resolution) -> Vec<u8> {
+        let row = (0..resolution.width())
+            .flat_map(|x| Self::color_at(x, resolution.width()))
+            .collect::<Vec<u8>>();
+        row.repeat(resolution.height() as usize)
+    }
+}
+
+/// A black and white checkerboard that scrolls across the frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MovingCheckerboard {
+    square_size: u32,
+    velocity: (f64, f64),
+}
+
+impl MovingCheckerboard {
+    /// Creates a checkerboard with squares `square_size` pixels wide, moving `velocity` pixels per second along X and Y.
+    #[must_use]
+    pub fn new(square_size: u32, velocity: (f64, f64)) -> Self {
+        MovingCheckerboard {
+            square_size: square_size.max(1),
+            velocity,
+        }
+    }
+
+    /// The size of a square in pixels.
+    #[must_use]
+    pub fn square_size(&self) -> u32 {
+        self.square_size
+    }
+
+    /// The velocity in pixels per second along X and Y.
+    #[must_use]
+    pub fn velocity(&self) -> (f64, f64) {
+        self.velocity
+    }
+
+    /// How far the board has moved along X and Y at `timestamp`, in pixels.
+    #[must_use]
+    pub fn offset_at(&self, timestamp: Duration) -> (f64, f64) {
+        let seconds = timestamp.as_secs_f64();
+        (self.velocity.0 * seconds, self.velocity.1 * seconds)
+    }
+}
+
+impl Default for MovingCheckerboard {
+    fn default() -> Self {
+        MovingCheckerboard::new(40, (30.0, 15.0))
+    }
+}
+
+impl VirtualBackendTrait for MovingCheckerboard {
+    fn name(&self) -> String {
+        "Moving Checkerboard".to_string()
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn render_frame(&mut self, _: u64, timestamp: Duration, resolution: Resolution) -> Vec<u8> {
+        let (offset_x, offset_y) = self.offset_at(timestamp);
+        let size = f64::from(self.square_size);
+
+        let mut frame = Vec::with_capacity((resolution.width() * resolution.height() * 3) as usize);
+        for y in 0..resolution.height() {
+            let row = ((f64::from(y) - offset_y) / size).floor() as i64;
+            for x in 0..resolution.width() {
+                let column = ((f64::from(x) - offset_x) / size).floor() as i64;
+                let value = if (row + column).rem_euclid(2) == 0 {
+                    255
+                } else {
+                    0
+                };
+                frame.extend_from_slice(&[value; 3]);
+            }
+        }
+        frame
+    }
+}
+
+/// Uniform RGB noise, different for every frame but reproducible from the seed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Noise {
+    seed: u64,
+}
+
+impl Noise {
+    /// Creates noise generated from `seed`.
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Noise { seed }
+    }
+
+    /// The seed the noise is generated from.
+    #[must_use]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl VirtualBackendTrait for Noise {
+    fn name(&self) -> String {
+        "Noise".to_string()
+    }
+
+    fn render_frame(&mut self, sequence: u64, _: Duration, resolution: Resolution) -> Vec<u8> {
+        let mut state = hash(self.seed ^ hash(sequence));
+        let length = (resolution.width() * resolution.height() * 3) as usize;
+
+        let mut frame = Vec::with_capacity(length + 8);
+        while frame.len() < length {
+            state = hash(state);
+            frame.extend_from_slice(&state.to_le_bytes());
+        }
+        frame.truncate(length);
+        frame
+    }
+}
+
+/// A rigid camera pose: the rotation from camera to world coordinates and the camera center in world coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CameraPose {
+    rotation: [[f64; 3]; 3],
+    position: [f64; 3],
+}
+
+impl CameraPose {
+    /// Creates a pose from the camera center and the camera to world rotation as a rotation vector (axis times angle in radians).
+    #[must_use]
+    pub fn new(position: [f64; 3], rotation_vector: [f64; 3]) -> Self {
+        CameraPose {
+            rotation: rotation_matrix(rotation_vector),
+            position,
+        }
+    }
+
+    /// The camera to world rotation matrix, row-major.
+    #[must_use]
+    pub fn rotation(&self) -> [[f64; 3]; 3] {
+        self.rotation
+    }
+
+    /// The camera center in world coordinates.
+    #[must_use]
+    pub fn position(&self) -> [f64; 3] {
+        self.position
+    }
+
+    /// Transforms a point from camera to world coordinates.
+    #[must_use]
+    pub fn camera_to_world(&self, point: [f64; 3]) -> [f64; 3] {
+        let rotated = rotate(&self.rotation, point);
+        [
+            rotated[0] + self.position[0],
+            rotated[1] + self.position[1],
+            rotated[2] + self.position[2],
+        ]
+    }
+
+    /// Transforms a point from world to camera coordinates.
+    #[must_use]
+    pub fn world_to_camera(&self, point: [f64; 3]) -> [f64; 3] {
+        let relative = [
+            point[0] - self.position[0],
+            point[1] - self.position[1],
+            point[2] - self.position[2],
+        ];
+        let r = &self.rotation;
+        [
+            r[0][0] * relative[0] + r[1][0] * relative[1] + r[2][0] * relative[2],
+            r[0][1] * relative[0] + r[1][1] * relative[1] + r[2][1] * relative[2],
+            r[0][2] * relative[0] + r[1][2] * relative[1] + r[2][2] * relative[2],
+        ]
+    }
+}
+
+/// A point of a [`CameraTrajectory`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TrajectoryWaypoint {
+    time: Duration,
+    position: [f64; 3],
+    rotation_vector: [f64; 3],
+}
+
+impl TrajectoryWaypoint {
+    /// Creates a waypoint reached `time` after the stream started. See [`CameraPose::new`] for the other arguments.
+    #[must_use]
+    pub fn new(time: Duration, position: [f64; 3], rotation_vector: [f64; 3]) -> Self {
+        TrajectoryWaypoint {
+            time,
+            position,
+            rotation_vector,
+        }
+    }
+
+    /// When the waypoint is reached.
+    #[must_use]
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// The camera center at the waypoint.
+    #[must_use]
+    pub fn position(&self) -> [f64; 3] {
+        self.position
+    }
+
+    /// The camera to world rotation at the waypoint, as a rotation vector.
+    #[must_use]
+    pub fn rotation_vector(&self) -> [f64; 3] {
+        self.rotation_vector
+    }
+}
+
+/// A scripted camera motion. Poses between waypoints are linearly interpolated, both in position and in rotation vector.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraTrajectory {
+    waypoints: Vec<TrajectoryWaypoint>,
+    repeat: bool,
+}
+
+impl CameraTrajectory {
+    /// Creates a trajectory through `waypoints`. If `repeat` is set, the trajectory starts over after the last waypoint,
+    /// otherwise the camera stays there.
+    #[must_use]
+    pub fn new(mut waypoints: Vec<TrajectoryWaypoint>, repeat: bool) -> Self {
+        waypoints.sort_by_key(TrajectoryWaypoint::time);
+        CameraTrajectory { waypoints, repeat }
+    }
+
+    /// A camera that never moves from the origin.
+    #[must_use]
+    pub fn stationary() -> Self {
+        CameraTrajectory::new(
+            vec![TrajectoryWaypoint::new(Duration::ZERO, [0.0; 3], [0.0; 3])],
+            false,
+        )
+    }
+
+    /// A camera that circles the origin in the XY plane once every `period`, panning slightly from side to side.
+    #[must_use]
+    pub fn circle(radius: f64, period: Duration) -> Self {
+        const SAMPLES: u32 = 72;
+
+        let waypoints = (0..=SAMPLES)
+            .map(|sample| {
+                let angle = 2.0 * PI * f64::from(sample) / f64::from(SAMPLES);
+                TrajectoryWaypoint::new(
+                    period * sample / SAMPLES,
+                    [radius * angle.cos() - radius, radius * angle.sin(), 0.0],
+                    [0.0, 0.1 * angle.sin(), 0.0],
+                )
+            })
+            .collect();
+        CameraTrajectory::new(waypoints, true)
+    }
+
+    /// The waypoints of the trajectory, in order.
+    #[must_use]
+    pub fn waypoints(&self) -> &[TrajectoryWaypoint] {
+        &self.waypoints
+    }
+
+    /// Whether the trajectory starts over after the last waypoint.
+    #[must_use]
+    pub fn repeat(&self) -> bool {
+        self.repeat
+    }
+
+    /// The camera pose `timestamp` after the stream started.
+    #[allow(clippy::cast_possible_truncation)]
+    #[must_use]
+    pub fn pose_at(&self, timestamp: Duration) -> CameraPose {
+        let (Some(first), Some(last)) = (self.waypoints.first(), self.waypoints.last()) else {
+            return CameraPose::new([0.0; 3], [0.0; 3]);
+        };
+
+        let duration = last.time.saturating_sub(first.time);
+        let mut time = timestamp.max(first.time);
+        if self.repeat && !duration.is_zero() && time > last.time {
+            time = first.time
+                + Duration::from_nanos(
+                    (time.saturating_sub(first.time).as_nanos() % duration.as_nanos()) as u64,
+                );
+        }
+
+        let next = self
+            .waypoints
+            .iter()
+            .position(|waypoint| waypoint.time > time)
+            .unwrap_or(self.waypoints.len());
+        if next == 0 || next == self.waypoints.len() {
+            let waypoint = if next == 0 { first } else { last };
+            return CameraPose::new(waypoint.position, waypoint.rotation_vector);
+        }
+
+        let (start, end) = (&self.waypoints[next - 1], &self.waypoints[next]);
+        let fraction = time.saturating_sub(start.time).as_secs_f64()
+            / end.time.saturating_sub(start.time).as_secs_f64();
+        let lerp = |a: [f64; 3], b: [f64; 3]| {
+            [
+                a[0] + (b[0] - a[0]) * fraction,
+                a[1] + (b[1] - a[1]) * fraction,
+                a[2] + (b[2] - a[2]) * fraction,
+            ]
+        };
+        CameraPose::new(
+            lerp(start.position, end.position),
+            lerp(start.rotation_vector, end.rotation_vector),
+        )
+    }
+}
+
+/// A randomly textured plane, seen by a pinhole camera that follows a [`CameraTrajectory`].
+///
+/// The plane lies at `Z = plane_distance` in world coordinates and faces the camera at the origin.
+/// Its texture is made of squares of random color at three scales, which gives feature detectors plenty of corners.
+/// Rays that miss the plane render dark gray.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TexturedPlane {
+    trajectory: CameraTrajectory,
+    field_of_view: f64,
+    plane_distance: f64,
+    seed: u64,
+}
+
+impl TexturedPlane {
+    /// Creates a plane 2 meters away, seen through a 60 degree horizontal field of view.
+    #[must_use]
+    pub fn new(trajectory: CameraTrajectory) -> Self {
+        TexturedPlane {
+            trajectory,
+            field_of_view: PI / 3.0,
+            plane_distance: 2.0,
+            seed: 0,
+        }
+    }
+
+    /// Sets the horizontal field of view in radians.
+    #[must_use]
+    pub fn with_field_of_view(mut self, field_of_view: f64) -> Self {
+        self.field_of_view = field_of_view;
+        self
+    }
+
+    /// Sets the distance of the plane from the world origin in meters.
+    #[must_use]
+    pub fn with_plane_distance(mut self, plane_distance: f64) -> Self {
+        self.plane_distance = plane_distance;
+        self
+    }
+
+    /// Sets the seed the texture is generated from.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// The trajectory of the camera.
+    #[must_use]
+    pub fn trajectory(&self) -> &CameraTrajectory {
+        &self.trajectory
+    }
+
+    /// The horizontal field of view in radians.
+    #[must_use]
+    pub fn field_of_view(&self) -> f64 {
+        self.field_of_view
+    }
+
+    /// The distance of the plane from the world origin in meters.
+    #[must_use]
+    pub fn plane_distance(&self) -> f64 {
+        self.plane_distance
+    }
+
+    /// The ground truth camera pose of the frame shown `timestamp` after the stream started.
+    #[must_use]
+    pub fn pose_at(&self, timestamp: Duration) -> CameraPose {
+        self.trajectory.pose_at(timestamp)
+    }
+
+    /// The ground truth pinhole intrinsics `[fx, fy, cx, cy]` at `resolution`. Pixels are square and there is no distortion.
+    #[must_use]
+    pub fn intrinsics(&self, resolution: Resolution) -> [f64; 4] {
+        let focal_length = f64::from(resolution.width()) / (2.0 * (self.field_of_view / 2.0).tan());
+        [
+            focal_length,
+            focal_length,
+            f64::from(resolution.width()) / 2.0,
+            f64::from(resolution.height()) / 2.0,
+        ]
+    }
+
+    /// The texture color at the point `(x, y)` of the plane, in meters.
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[must_use]
+    pub fn texture_at(&self, x: f64, y: f64) -> [u8; 3] {
+        const SCALES: [(f64, f64); 3] = [(0.4, 0.5), (0.1, 0.3), (0.025, 0.2)];
+
+        let mut color = [0.0; 3];
+        for (layer, (size, weight)) in SCALES.into_iter().enumerate() {
+            let cell = hash(
+                self.seed
+                    ^ hash(layer as u64)
+                    ^ hash((x / size).floor() as i64 as u64)
+                    ^ hash(hash((y / size).floor() as i64 as u64)),
+            );
+            for (channel, value) in color.iter_mut().enumerate() {
+                *value += weight * f64::from((cell >> (channel * 8)) as u8);
+            }
+        }
+        color.map(|value| value.round().clamp(0.0, 255.0) as u8)
+    }
+}
+
+impl Default for TexturedPlane {
+    fn default() -> Self {
+        TexturedPlane::new(CameraTrajectory::circle(0.25, Duration::from_secs(10)))
+    }
+}
+
+impl VirtualBackendTrait for TexturedPlane {
+    fn name(&self) -> String {
+        "Textured Plane".to_string()
+    }
+
+    fn render_frame(&mut self, _: u64, timestamp: Duration, resolution: Resolution) -> Vec<u8> {
+        const BACKGROUND: [u8; 3] = [64, 64, 64];
+
+        let pose = self.pose_at(timestamp);
+        let [fx, fy, cx, cy] = self.intrinsics(resolution);
+
+        let mut frame = Vec::with_capacity((resolution.width() * resolution.height() * 3) as usize);
+        for v in 0..resolution.height() {
+            for u in 0..resolution.width() {
+                // Rays go through pixel centers
+                let ray = rotate(
+                    &pose.rotation,
+                    [
+                        (f64::from(u) + 0.5 - cx) / fx,
+                        (f64::from(v) + 0.5 - cy) / fy,
+                        1.0,
+                    ],
+                );
+                let distance = (self.plane_distance - pose.position[2]) / ray[2];
+
+                if ray[2] <= 0.0 || distance <= 0.0 {
+                    frame.extend_from_slice(&BACKGROUND);
+                } else {
+                    frame.extend_from_slice(&self.texture_at(
+                        pose.position[0] + distance * ray[0],
+                        pose.position[1] + distance * ray[1],
+                    ));
+                }
+            }
+        }
+        frame
+    }
+}
+
+// splitmix64
+fn hash(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn rotate(rotation: &[[f64; 3]; 3], vector: [f64; 3]) -> [f64; 3] {
+    [
+        rotation[0][0] * vector[0] + rotation[0][1] * vector[1] + rotation[0][2] * vector[2],
+        rotation[1][0] * vector[0] + rotation[1][1] * vector[1] + rotation[1][2] * vector[2],
+        rotation[2][0] * vector[0] + rotation[2][1] * vector[1] + rotation[2][2] * vector[2],
+    ]
+}
+
+// Rodrigues' formula
+fn rotation_matrix(rotation_vector: [f64; 3]) -> [[f64; 3]; 3] {
+    let [x, y, z] = rotation_vector;
+    let angle = (x * x + y * y + z * z).sqrt();
+    if angle < f64::EPSILON {
+        return [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    }
+
+    let (x, y, z) = (x / angle, y / angle, z / angle);
+    let (sin, cos) = angle.sin_cos();
+    let one_minus_cos = 1.0 - cos;
+    [
+        [
+            cos + x * x * one_minus_cos,
+            x * y * one_minus_cos - z * sin,
+            x * z * one_minus_cos + y * sin,
+        ],
+        [
+            y * x * one_minus_cos + z * sin,
+            cos + y * y * one_minus_cos,
+            y * z * one_minus_cos - x * sin,
+        ],
+        [
+            z * x * one_minus_cos - y * sin,
+            z * y * one_minus_cos + x * sin,
+            cos + z * z * one_minus_cos,
+        ],
+    ]
+}
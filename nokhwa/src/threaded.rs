@@ -14,7 +14,17 @@
  * limitations under the License.
  */
 
-use crate::Camera;
+use crate::{
+    stats::{CaptureStats, LatencyHistogram},
+    Camera,
+};
+#[cfg(feature = "output-async")]
+use flume::r#async::RecvStream;
+use flume::{
+    Receiver, RecvError, RecvTimeoutError, SendTimeoutError, Sender, TryRecvError, TrySendError,
+};
+#[cfg(feature = "output-async")]
+use futures_core::Stream;
 use nokhwa_core::{
     buffer::Buffer,
     error::NokhwaError,
@@ -27,13 +37,214 @@ use std::thread::JoinHandle;
 use std::{
     collections::HashMap,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, Weak,
     },
+    time::{Duration, Instant},
+};
+#[cfg(feature = "output-async")]
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
 };
 
 type AtomicLock<T> = Arc<Mutex<T>>;
 type HeldCallbackType = Arc<Mutex<Box<dyn FnMut(Buffer) + Send + 'static>>>;
+/// A captured frame, or the error the camera failed to capture it with.
+type FrameResult = Result<Buffer, NokhwaError>;
+
+/// How often a [`BackpressurePolicy::Block`] delivery checks whether the camera is shutting down.
+const BLOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What happens to a new frame when a [`FrameSubscription`]'s queue is full.
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BackpressurePolicy {
+    /// Discard the oldest queued frame to make room for the new one. The subscriber always gets the most recent frames,
+    /// which is usually what a tracker wants.
+    #[default]
+    DropOldest,
+    /// Discard the new frame. The subscriber gets every frame up to the point it fell behind.
+    DropNewest,
+    /// Wait for the subscriber to make room. This holds up the capture thread, and with it the callback and every other subscriber,
+    /// so frames will be dropped by the camera instead. Use this for consumers that must not miss a delivered frame, such as recorders.
+    Block,
+}
+
+/// A bounded queue of frames from a [`CallbackCamera`], created with [`subscribe()`](CallbackCamera::subscribe).
+///
+/// Capture errors are queued like frames, so every item is either a frame or the error the camera failed to capture it
+/// with. The camera thread keeps capturing after an error, unless the camera's stream has closed, e.g. at the end of a
+/// file. The thread then stops and the subscription disconnects once its queue is empty.
+///
+/// Dropping the subscription unsubscribes it.
+pub struct FrameSubscription {
+    receiver: Receiver<FrameResult>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl FrameSubscription {
+    /// Waits for the next frame or capture error.
+    /// # Errors
+    /// This will error if the camera thread has stopped, because the [`CallbackCamera`] has been dropped or its stream
+    /// closed, and all queued frames have been received.
+    pub fn recv(&self) -> Result<FrameResult, RecvError> {
+        self.receiver.recv()
+    }
+
+    /// Gets the next frame or capture error if one is queued.
+    /// # Errors
+    /// This will error if nothing is queued or the camera thread has stopped.
+    pub fn try_recv(&self) -> Result<FrameResult, TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    /// Waits for the next frame or capture error for at most `timeout`.
+    /// # Errors
+    /// This will error if nothing arrives in time or the camera thread has stopped.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<FrameResult, RecvTimeoutError> {
+        self.receiver.recv_timeout(timeout)
+    }
+
+    /// Gets the underlying receiver, e.g. to `select` over several subscriptions.
+    #[must_use]
+    pub fn receiver(&self) -> &Receiver<FrameResult> {
+        &self.receiver
+    }
+
+    /// The number of frames that were discarded because the queue was full.
+    #[must_use]
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Turns this subscription into an async [`FrameStream`].
+    #[cfg(feature = "output-async")]
+    #[cfg_attr(feature = "docs-features", doc(cfg(feature = "output-async")))]
+    #[must_use]
+    pub fn into_stream(self) -> FrameStream {
+        FrameStream {
+            frames: self.receiver.into_stream(),
+            dropped: self.dropped,
+            camera: None,
+        }
+    }
+}
+
+/// An async [`Stream`] of frames, created with [`CallbackCamera::frames()`], [`Camera::frames()`] or
+/// [`FrameSubscription::into_stream()`]. It does not depend on any particular executor.
+///
+/// Capture errors are yielded as `Err` items, after which the camera thread keeps capturing. The stream ends when the
+/// [`CallbackCamera`] it came from is dropped or its stream closes, e.g. at the end of a file.
+///
+/// Dropping the stream unsubscribes it. If it was created by [`Camera::frames()`], this also closes the camera.
+#[cfg(feature = "output-async")]
+#[cfg_attr(feature = "docs-features", doc(cfg(feature = "output-async")))]
+pub struct FrameStream {
+    frames: RecvStream<'static, FrameResult>,
+    dropped: Arc<AtomicU64>,
+    camera: Option<CallbackCamera>,
+}
+
+#[cfg(feature = "output-async")]
+impl FrameStream {
+    /// The number of frames that were discarded because they were not consumed in time.
+    #[must_use]
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// The camera this stream owns, if it was created by [`Camera::frames()`].
+    #[must_use]
+    pub fn camera(&self) -> Option<&CallbackCamera> {
+        self.camera.as_ref()
+    }
+}
+
+#[cfg(feature = "output-async")]
+impl Stream for FrameStream {
+    type Item = Result<Buffer, NokhwaError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.frames).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.frames.size_hint()
+    }
+}
+
+#[cfg(feature = "output-async")]
+impl Camera {
+    /// Turns this camera into an async [`Stream`] of frames, opening its stream without blocking the executor.
+    ///
+    /// The camera is moved into a [`CallbackCamera`], whose capture thread always keeps the newest frame for the stream. It can
+    /// still be reached through [`FrameStream::camera()`], e.g. for its [`stats()`](CallbackCamera::stats).
+    /// # Errors
+    /// If the specific backend fails to open the camera (e.g. already taken, busy, doesn't exist anymore) this will error.
+    #[cfg_attr(feature = "docs-features", doc(cfg(feature = "output-async")))]
+    pub async fn frames(self) -> Result<FrameStream, NokhwaError> {
+        let mut camera = CallbackCamera::with_custom(self, |_| {});
+        let mut frames = camera.frames()?;
+        camera.open_stream_async().await?;
+        frames.camera = Some(camera);
+        Ok(frames)
+    }
+}
+
+#[derive(Clone)]
+struct Subscriber {
+    sender: Sender<FrameResult>,
+    // Only kept for `DropOldest`, to evict the oldest frame. This keeps the channel connected after the subscription is
+    // dropped, so liveness is tracked by `dropped` instead.
+    evict: Option<Receiver<FrameResult>>,
+    policy: BackpressurePolicy,
+    // The subscription owns the counter, it is gone once the subscription is dropped
+    dropped: Weak<AtomicU64>,
+}
+
+impl Subscriber {
+    /// Queues `frame` according to the policy. Returns `false` once the subscription has been dropped.
+    fn deliver(&self, mut frame: FrameResult, die_bool: &AtomicBool) -> bool {
+        let Some(dropped) = self.dropped.upgrade() else {
+            return false;
+        };
+
+        match self.policy {
+            BackpressurePolicy::DropOldest => loop {
+                match self.sender.try_send(frame) {
+                    Ok(()) => return true,
+                    Err(TrySendError::Full(returned)) => {
+                        if let Some(Ok(_)) = self.evict.as_ref().map(Receiver::try_recv) {
+                            dropped.fetch_add(1, Ordering::Relaxed);
+                        }
+                        frame = returned;
+                    }
+                    Err(TrySendError::Disconnected(_)) => return false,
+                }
+            },
+            BackpressurePolicy::DropNewest => match self.sender.try_send(frame) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    dropped.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            },
+            BackpressurePolicy::Block => loop {
+                match self.sender.send_timeout(frame, BLOCK_POLL_INTERVAL) {
+                    Ok(()) => return true,
+                    Err(SendTimeoutError::Timeout(returned)) => {
+                        if die_bool.load(Ordering::SeqCst) {
+                            return true;
+                        }
+                        frame = returned;
+                    }
+                    Err(SendTimeoutError::Disconnected(_)) => return false,
+                }
+            },
+        }
+    }
+}
 
 /// Creates a camera that runs in a different thread that you can use a callback to access the frames of.
 /// It uses a `Arc` and a `Mutex` to ensure that this feels like a normal camera, but callback based.
@@ -43,6 +254,10 @@ type HeldCallbackType = Arc<Mutex<Box<dyn FnMut(Buffer) + Send + 'static>>>;
 /// complete before a new frame is available. If you need to do heavy image processing, it may be
 /// beneficial to directly pipe the data to a new thread to process it there.
 ///
+/// Frames can also be consumed through any number of [`FrameSubscription`]s, each with their own queue and
+/// [`BackpressurePolicy`], see [`subscribe()`](CallbackCamera::subscribe). The callback and the subscribers are served
+/// after the camera has been unlocked, so they can use the camera's other methods.
+///
 /// Note that this does not have `WGPU` capabilities. This should be implemented in your callback.
 /// # SAFETY
 /// The `Mutex` guarantees exclusive access to the underlying camera struct. They should be safe to
@@ -53,6 +268,8 @@ pub struct CallbackCamera {
     camera: Arc<parking_lot::FairMutex<Camera>>,
     frame_callback: HeldCallbackType,
     last_frame_captured: AtomicLock<Buffer>,
+    callback_durations: AtomicLock<LatencyHistogram>,
+    subscribers: AtomicLock<Vec<Subscriber>>,
     die_bool: Arc<AtomicBool>,
     current_camera: CameraInfo,
     handle: AtomicLock<Option<JoinHandle<()>>>,
@@ -84,6 +301,8 @@ impl CallbackCamera {
                 &vec![],
                 FrameFormat::GRAY,
             ))),
+            callback_durations: Arc::new(Mutex::new(LatencyHistogram::new())),
+            subscribers: Arc::new(Mutex::new(vec![])),
             die_bool: Arc::new(Default::default()),
             current_camera,
             handle: Arc::new(Mutex::new(None)),
@@ -319,7 +538,8 @@ impl CallbackCamera {
     }
 
     /// Will open the camera stream with set parameters. This will be called internally if you try and call [`frame()`](crate::Camera::frame()) before you call [`open_stream()`](crate::Camera::open_stream()).
-    /// The callback will be called every frame.
+    /// The callback will be called every frame. If the camera's stream is already open, only the capture thread is started.
+    /// A capture thread that stopped because the stream closed is started again.
     /// # Errors
     /// If the specific backend fails to open the camera (e.g. already taken, busy, doesn't exist anymore) this will error.
     pub fn open_stream(&mut self) -> Result<(), NokhwaError> {
@@ -330,14 +550,31 @@ impl CallbackCamera {
                 property: "thread handle".to_string(),
                 error: why.to_string(),
             })?;
-        if handle_lock.is_none() {
-            self.camera.lock().open_stream()?;
+        if handle_lock.as_ref().is_none_or(JoinHandle::is_finished) {
+            {
+                let mut camera = self.camera.lock();
+                if !camera.is_stream_open() {
+                    camera.open_stream()?;
+                }
+            }
             let die_bool_clone = self.die_bool.clone();
             let camera_clone = self.camera.clone();
             let last_frame = self.last_frame_captured.clone();
             let callback = self.frame_callback.clone();
+            let callback_durations = self.callback_durations.clone();
+            if let Ok(mut durations) = callback_durations.lock() {
+                durations.clear();
+            }
+            let subscribers = self.subscribers.clone();
             let handle = std::thread::spawn(move || {
-                camera_frame_thread_loop(camera_clone, callback, last_frame, die_bool_clone)
+                camera_frame_thread_loop(
+                    camera_clone,
+                    callback,
+                    last_frame,
+                    callback_durations,
+                    subscribers,
+                    die_bool_clone,
+                );
             });
             *handle_lock = Some(handle);
             Ok(())
@@ -348,6 +585,45 @@ impl CallbackCamera {
         }
     }
 
+    /// Opens the camera stream like [`open_stream()`](CallbackCamera::open_stream), without blocking the executor while the backend opens the camera.
+    /// # Errors
+    /// If the specific backend fails to open the camera (e.g. already taken, busy, doesn't exist anymore) this will error.
+    #[cfg(feature = "output-async")]
+    #[cfg_attr(feature = "docs-features", doc(cfg(feature = "output-async")))]
+    pub async fn open_stream_async(&mut self) -> Result<(), NokhwaError> {
+        let camera = self.camera.clone();
+        let (sender, receiver) = flume::bounded(1);
+        std::thread::spawn(move || {
+            let mut camera = camera.lock();
+            let opened = if camera.is_stream_open() {
+                Ok(())
+            } else {
+                camera.open_stream()
+            };
+            let _ = sender.send(opened);
+        });
+
+        receiver
+            .recv_async()
+            .await
+            .map_err(|why| NokhwaError::OpenStreamError(why.to_string()))??;
+
+        // The camera is open now, this only starts the capture thread
+        self.open_stream()
+    }
+
+    /// Gets an async [`Stream`] of the frames captured by the camera thread. Frames that are not consumed before the next one
+    /// arrives are dropped, use [`subscribe()`](CallbackCamera::subscribe) and [`FrameSubscription::into_stream()`] to queue more.
+    /// # Errors
+    /// This will error if the subscriber list cannot be locked.
+    #[cfg(feature = "output-async")]
+    #[cfg_attr(feature = "docs-features", doc(cfg(feature = "output-async")))]
+    pub fn frames(&self) -> Result<FrameStream, NokhwaError> {
+        Ok(self
+            .subscribe(1, BackpressurePolicy::DropOldest)?
+            .into_stream())
+    }
+
     /// Sets the frame callback to the new specified function. This function will be called instead of the previous one(s).
     pub fn set_callback(
         &mut self,
@@ -363,6 +639,35 @@ impl CallbackCamera {
         Ok(())
     }
 
+    /// Subscribes to the frames captured by the camera thread. Each subscription gets every frame the camera delivers, queued
+    /// up to `capacity` frames (at least 1), with `policy` deciding what happens when the queue is full.
+    ///
+    /// Frames only arrive once the stream has been opened with [`open_stream()`](CallbackCamera::open_stream).
+    /// Frames fetched with [`poll_frame()`](CallbackCamera::poll_frame) are not delivered to subscribers.
+    /// # Errors
+    /// This will error if the subscriber list cannot be locked.
+    pub fn subscribe(
+        &self,
+        capacity: usize,
+        policy: BackpressurePolicy,
+    ) -> Result<FrameSubscription, NokhwaError> {
+        let (sender, receiver) = flume::bounded(capacity.max(1));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let subscriber = Subscriber {
+            sender,
+            evict: (policy == BackpressurePolicy::DropOldest).then(|| receiver.clone()),
+            policy,
+            dropped: Arc::downgrade(&dropped),
+        };
+
+        self.subscribers
+            .lock()
+            .map_err(|why| NokhwaError::GeneralError(why.to_string()))?
+            .push(subscriber);
+
+        Ok(FrameSubscription { receiver, dropped })
+    }
+
     /// Polls the camera for a frame, analogous to [`Camera::frame`](crate::Camera::frame)
     /// # Errors
     /// This will error if the camera fails to capture a frame.
@@ -384,12 +689,31 @@ impl CallbackCamera {
             .clone())
     }
 
+    /// Gets the capture statistics of the underlying camera, see [`Camera::stats()`].
+    /// # Errors
+    /// This currently never errors, it returns a `Result` like the other getters.
+    pub fn stats(&self) -> Result<CaptureStats, NokhwaError> {
+        Ok(self.camera.lock().stats())
+    }
+
+    /// Gets how long the frame callback took to run for each frame since the stream was opened.
+    /// A callback that regularly takes longer than the frame interval will cause dropped frames.
+    /// # Errors
+    /// This will error if the durations cannot be locked.
+    pub fn callback_durations(&self) -> Result<LatencyHistogram, NokhwaError> {
+        Ok(self
+            .callback_durations
+            .lock()
+            .map_err(|why| NokhwaError::GeneralError(why.to_string()))?
+            .clone())
+    }
+
     /// Checks if stream if open. If it is, it will return true.
     pub fn is_stream_open(&self) -> Result<bool, NokhwaError> {
         Ok(self.camera.lock().is_stream_open())
     }
 
-    /// Will drop the stream.
+    /// Will drop the stream. The capture thread then stops, which ends every subscription.
     /// # Errors
     /// Please check the `Quirks` section of each backend.
     pub fn stop_stream(&mut self) -> Result<(), NokhwaError> {
@@ -408,20 +732,208 @@ fn camera_frame_thread_loop(
     camera: Arc<parking_lot::FairMutex<Camera>>,
     frame_callback: HeldCallbackType,
     last_frame_captured: AtomicLock<Buffer>,
+    callback_durations: AtomicLock<LatencyHistogram>,
+    subscribers: AtomicLock<Vec<Subscriber>>,
     die_bool: Arc<AtomicBool>,
 ) {
     loop {
-        let mut camera = camera.lock();
-        if let Ok(frame) = camera.frame() {
-            if let Ok(mut last_frame) = last_frame_captured.lock() {
-                *last_frame = frame.clone();
-                if let Ok(mut cb) = frame_callback.lock() {
-                    cb(frame);
+        // Only hold the camera while capturing, so that delivery does not lock out other accessors
+        let (frame, frame_rate, stream_open) = {
+            let mut camera = camera.lock();
+            (camera.frame(), camera.frame_rate(), camera.is_stream_open())
+        };
+
+        match frame {
+            Ok(frame) => {
+                deliver_to_subscribers(&subscribers, &Ok(frame.clone()), &die_bool);
+
+                if let Ok(mut last_frame) = last_frame_captured.lock() {
+                    *last_frame = frame.clone();
+                    if let Ok(mut cb) = frame_callback.lock() {
+                        let started = Instant::now();
+                        cb(frame);
+                        if let Ok(mut durations) = callback_durations.lock() {
+                            durations.record(started.elapsed());
+                        }
+                    }
                 }
             }
+            Err(why) => {
+                deliver_to_subscribers(&subscribers, &Err(why), &die_bool);
+
+                // A closed stream, e.g. at the end of a file, has no more frames. Dropping the senders ends the
+                // subscriptions once their queues are empty.
+                if !stream_open {
+                    if let Ok(mut subscribers) = subscribers.lock() {
+                        subscribers.clear();
+                    }
+                    break;
+                }
+
+                // Don't spin on a failing stream, retry once per frame interval
+                std::thread::sleep(Duration::from_secs(1) / frame_rate.max(1));
+            }
         }
         if die_bool.load(Ordering::SeqCst) {
             break;
         }
     }
 }
+
+fn deliver_to_subscribers(
+    subscribers: &AtomicLock<Vec<Subscriber>>,
+    frame: &FrameResult,
+    die_bool: &AtomicBool,
+) {
+    // Deliver to a snapshot, so that a blocked subscriber does not keep others from subscribing
+    let Ok(snapshot) = subscribers.lock().map(|subscribers| subscribers.clone()) else {
+        return;
+    };
+    if snapshot.is_empty() {
+        return;
+    }
+
+    let gone = snapshot
+        .iter()
+        .filter(|subscriber| !subscriber.deliver(frame.clone(), die_bool))
+        .map(|subscriber| subscriber.dropped.as_ptr())
+        .collect::<Vec<_>>();
+
+    if !gone.is_empty() {
+        if let Ok(mut subscribers) = subscribers.lock() {
+            subscribers.retain(|subscriber| !gone.contains(&subscriber.dropped.as_ptr()));
+        }
+    }
+}
+
+#[cfg(all(test, feature = "input-virtual"))]
+mod tests {
+    use super::*;
+    use crate::{backends::capture::VirtualCaptureDevice, pixel_format::RgbFormat};
+
+    fn virtual_camera() -> CallbackCamera {
+        let format = RequestedFormat::new::<RgbFormat>(RequestedFormatType::Exact(
+            CameraFormat::new_from(64, 48, FrameFormat::RAWRGB, 100),
+        ));
+        let device = VirtualCaptureDevice::new(&CameraIndex::Index(0), format).unwrap();
+        let camera = Camera::with_custom(CameraIndex::Index(0), ApiBackend::Virtual, Box::new(device));
+        CallbackCamera::with_custom(camera, |_| {})
+    }
+
+    fn subscriber_count(camera: &CallbackCamera) -> usize {
+        camera.subscribers.lock().unwrap().len()
+    }
+
+    #[test]
+    fn dropping_a_subscription_unsubscribes_it() {
+        let mut camera = virtual_camera();
+        let kept = camera.subscribe(1, BackpressurePolicy::DropNewest).unwrap();
+        let drop_oldest = camera.subscribe(1, BackpressurePolicy::DropOldest).unwrap();
+        let drop_newest = camera.subscribe(1, BackpressurePolicy::DropNewest).unwrap();
+        let block = camera.subscribe(1, BackpressurePolicy::Block).unwrap();
+        assert_eq!(subscriber_count(&camera), 4);
+
+        camera.open_stream().unwrap();
+        drop_oldest.recv_timeout(Duration::from_secs(2)).unwrap().unwrap();
+        drop(drop_oldest);
+        drop(drop_newest);
+        drop(block);
+
+        // Dropped subscriptions are removed on the next delivery
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while subscriber_count(&camera) > 1 && Instant::now() < deadline {
+            let _ = kept.recv_timeout(Duration::from_millis(100));
+        }
+        assert_eq!(subscriber_count(&camera), 1);
+
+        kept.recv_timeout(Duration::from_secs(2)).unwrap().unwrap();
+        camera.stop_stream().unwrap();
+    }
+
+    #[cfg(feature = "output-async")]
+    #[test]
+    fn dropping_a_frame_stream_unsubscribes_it() {
+        let mut camera = virtual_camera();
+        let kept = camera.subscribe(1, BackpressurePolicy::DropOldest).unwrap();
+        let frames = camera.frames().unwrap();
+        assert_eq!(subscriber_count(&camera), 2);
+
+        camera.open_stream().unwrap();
+        kept.recv_timeout(Duration::from_secs(2)).unwrap().unwrap();
+        drop(frames);
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while subscriber_count(&camera) > 1 && Instant::now() < deadline {
+            let _ = kept.recv_timeout(Duration::from_millis(100));
+        }
+        assert_eq!(subscriber_count(&camera), 1);
+
+        camera.stop_stream().unwrap();
+    }
+
+    /// Receives until the subscription disconnects, returning whether the last item was a capture error.
+    fn drain(subscription: &FrameSubscription) -> bool {
+        let mut last = None;
+        loop {
+            match subscription.recv_timeout(Duration::from_secs(2)) {
+                Ok(item) => last = Some(item),
+                Err(RecvTimeoutError::Disconnected) => return matches!(last, Some(Err(_))),
+                Err(RecvTimeoutError::Timeout) => panic!("the subscription did not end"),
+            }
+        }
+    }
+
+    #[test]
+    fn closing_the_stream_ends_subscriptions() {
+        let mut camera = virtual_camera();
+        let subscription = camera.subscribe(4, BackpressurePolicy::DropOldest).unwrap();
+
+        camera.open_stream().unwrap();
+        subscription.recv_timeout(Duration::from_secs(2)).unwrap().unwrap();
+        camera.stop_stream().unwrap();
+
+        // The capture error that found the stream closed comes last
+        assert!(drain(&subscription));
+        assert_eq!(subscriber_count(&camera), 0);
+
+        // Opening again starts a new capture thread
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !camera.handle.lock().unwrap().as_ref().unwrap().is_finished() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let subscription = camera.subscribe(4, BackpressurePolicy::DropOldest).unwrap();
+        camera.open_stream().unwrap();
+        subscription.recv_timeout(Duration::from_secs(2)).unwrap().unwrap();
+        camera.stop_stream().unwrap();
+    }
+
+    #[cfg(feature = "output-async")]
+    #[test]
+    fn frame_streams_yield_capture_errors() {
+        let mut camera = virtual_camera();
+        let mut frames = camera.frames().unwrap();
+        let mut context = Context::from_waker(std::task::Waker::noop());
+
+        let mut poll = |frames: &mut FrameStream| {
+            let deadline = Instant::now() + Duration::from_secs(2);
+            loop {
+                if let Poll::Ready(item) = Pin::new(&mut *frames).poll_next(&mut context) {
+                    return item;
+                }
+                assert!(Instant::now() < deadline, "the stream yielded nothing");
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        };
+
+        camera.open_stream().unwrap();
+        assert!(poll(&mut frames).unwrap().is_ok());
+        camera.stop_stream().unwrap();
+
+        // The queue only holds the newest item, the error, and then the stream ends
+        let mut last = None;
+        while let Some(item) = poll(&mut frames) {
+            last = Some(item);
+        }
+        assert!(matches!(last, Some(Err(NokhwaError::ReadFrameError(_)))));
+    }
+}
@@ -0,0 +1,500 @@
+use nalgebra::{
+    DMatrix, DVector, Isometry3, Matrix2x3, Matrix2x6, Matrix3, Matrix6, Matrix6x3, Point2, Point3, SMatrix,
+    Vector2, Vector3, Vector6
+};
+
+use crate::camera_model::CameraIntrinsics;
+
+/// Reprojection error given to observations behind their camera, so that moving a point
+/// behind a camera never lowers the cost.
+const BEHIND_CAMERA_ERROR: f64 = 1e3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BundleAdjustmentConfig {
+    /// Largest number of Levenberg-Marquardt iterations.
+    pub iterations: usize,
+    /// Reprojection error, in pixels of the keypoint's pyramid level, beyond which the Huber
+    /// cost grows linearly instead of quadratically. Observations still above it after the
+    /// adjustment are reported as outliers.
+    pub huber_threshold: f64
+}
+
+impl Default for BundleAdjustmentConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 10,
+            // 95% quantile of the chi-square distribution with 2 degrees of freedom, as in
+            // ORB-SLAM
+            huber_threshold: 5.991f64.sqrt()
+        }
+    }
+}
+
+/// A camera of the adjustment. Fixed cameras anchor the solution and keep their pose.
+#[derive(Clone, Copy, Debug)]
+pub struct BundleCamera {
+    pub camera_from_world: Isometry3<f64>,
+    pub fixed: bool
+}
+
+/// A point seen by a camera.
+#[derive(Clone, Copy, Debug)]
+pub struct BundleObservation {
+    pub camera: usize,
+    pub point: usize,
+    /// Undistorted pixel coordinates of the keypoint, see `CameraIntrinsics::undistort_pixel`.
+    pub pixel: Point2<f64>,
+    /// Standard deviation of the keypoint position in pixels, i.e. the scale of its pyramid
+    /// level.
+    pub sigma: f64
+}
+
+#[derive(Clone, Debug)]
+pub struct BundleAdjustmentReport {
+    /// Robust cost before and after the adjustment.
+    pub initial_cost: f64,
+    pub final_cost: f64,
+    pub iterations: usize,
+    /// Indices of the observations whose reprojection error is still above the Huber
+    /// threshold, or whose point is behind the camera.
+    pub outliers: Vec<usize>
+}
+
+/// Huber cost of a reprojection error `error`, and the weight of its residual in the
+/// iteratively reweighted normal equations.
+fn huber(error: f64, threshold: f64) -> (f64, f64) {
+    if error <= threshold {
+        (error * error, 1.0)
+    } else {
+        (2.0 * threshold * error - threshold * threshold, threshold / error)
+    }
+}
+
+fn skew(v: &Vector3<f64>) -> Matrix3<f64> {
+    Matrix3::new(
+        0.0, -v.z, v.y,
+        v.z, 0.0, -v.x,
+        -v.y, v.x, 0.0
+    )
+}
+
+/// Whitened residual of an observation and its Jacobians with respect to a left
+/// perturbation of the camera pose (rotation first) and to the point, or `None` if the point
+/// is behind the camera.
+fn linearize(
+    intrinsics: &CameraIntrinsics,
+    camera: &Isometry3<f64>,
+    point: &Point3<f64>,
+    observation: &BundleObservation
+) -> Option<(Vector2<f64>, Matrix2x6<f64>, Matrix2x3<f64>)> {
+    let p = camera * point;
+
+    if p.z <= 1e-9 {
+        return None;
+    }
+
+    let inverse_z = 1.0 / p.z;
+    let projected = Point2::new(
+        intrinsics.fx * p.x * inverse_z + intrinsics.cx,
+        intrinsics.fy * p.y * inverse_z + intrinsics.cy
+    );
+    let residual = (projected - observation.pixel) / observation.sigma;
+
+    let projection = Matrix2x3::new(
+        intrinsics.fx * inverse_z, 0.0, -intrinsics.fx * p.x * inverse_z * inverse_z,
+        0.0, intrinsics.fy * inverse_z, -intrinsics.fy * p.y * inverse_z * inverse_z
+    ) / observation.sigma;
+
+    let mut pose_jacobian = Matrix2x6::zeros();
+    pose_jacobian.fixed_view_mut::<2, 3>(0, 0).copy_from(&(projection * -skew(&p.coords)));
+    pose_jacobian.fixed_view_mut::<2, 3>(0, 3).copy_from(&projection);
+
+    let point_jacobian = projection * camera.rotation.to_rotation_matrix().matrix();
+
+    Some((residual, pose_jacobian, point_jacobian))
+}
+
+/// Whitened reprojection error of every observation, `None` for those behind their camera.
+fn reprojection_errors(
+    intrinsics: &CameraIntrinsics,
+    cameras: &[BundleCamera],
+    points: &[Point3<f64>],
+    observations: &[BundleObservation]
+) -> Vec<Option<f64>> {
+    observations.iter()
+        .map(|observation| {
+            let camera = &cameras[observation.camera].camera_from_world;
+            let (residual, _, _) = linearize(intrinsics, camera, &points[observation.point], observation)?;
+            Some(residual.norm())
+        })
+        .collect()
+}
+
+fn robust_cost(errors: &[Option<f64>], threshold: f64) -> f64 {
+    errors.iter()
+        .map(|error| huber(error.unwrap_or(BEHIND_CAMERA_ERROR), threshold).0)
+        .sum()
+}
+
+/// Gauss-Newton normal equations of the adjustment, split into the blocks of the free
+/// cameras (`U`), of the points (`V`) and the camera-point cross terms of every observation
+/// (`W`).
+struct NormalEquations {
+    camera_hessians: Vec<Matrix6<f64>>,
+    camera_gradients: Vec<Vector6<f64>>,
+    point_hessians: Vec<Matrix3<f64>>,
+    point_gradients: Vec<Vector3<f64>>,
+    cross_terms: Vec<Option<Matrix6x3<f64>>>
+}
+
+/// Steps of the free cameras, rotation first, and of the points.
+type Steps = (Vec<Vector6<f64>>, Vec<Vector3<f64>>);
+
+/// Marquardt's damping: the diagonal is scaled by `1 + damping`.
+fn damped<const N: usize>(hessian: &SMatrix<f64, N, N>, damping: f64) -> SMatrix<f64, N, N> {
+    let mut damped = *hessian;
+    for i in 0..N {
+        damped[(i, i)] += damping * hessian[(i, i)].max(1e-9);
+    }
+    damped
+}
+
+impl NormalEquations {
+    /// Solves the damped equations for the steps of the free cameras and of the points.
+    ///
+    /// The points are eliminated first: the reduced camera system `(U - W V⁻¹ Wᵀ) δc =
+    /// W V⁻¹ bp - bc` only couples cameras seeing common points, then every point step is
+    /// recovered from its own 3x3 block.
+    fn solve(
+        &self,
+        observations: &[BundleObservation],
+        free_index: &[Option<usize>],
+        by_point: &[Vec<usize>],
+        damping: f64
+    ) -> Option<Steps> {
+        let size = 6 * self.camera_hessians.len();
+        let mut reduced = DMatrix::zeros(size, size);
+        let mut rhs = DVector::zeros(size);
+
+        for (free, (hessian, gradient)) in self.camera_hessians.iter().zip(&self.camera_gradients).enumerate() {
+            let mut block = reduced.fixed_view_mut::<6, 6>(6 * free, 6 * free);
+            block += damped(hessian, damping);
+
+            let mut rows = rhs.fixed_rows_mut::<6>(6 * free);
+            rows -= gradient;
+        }
+
+        let mut inverse_point_hessians = Vec::with_capacity(by_point.len());
+
+        for (point, point_observations) in by_point.iter().enumerate() {
+            let inverse = damped(&self.point_hessians[point], damping).try_inverse()?;
+
+            // Every free camera seeing the point, with W and W V⁻¹
+            let terms: Vec<(usize, Matrix6x3<f64>, Matrix6x3<f64>)> = point_observations.iter()
+                .filter_map(|index| {
+                    let free = free_index[observations[*index].camera]?;
+                    let cross = self.cross_terms[*index]?;
+                    Some((free, cross, cross * inverse))
+                })
+                .collect();
+
+            for (a, _, cross_inverse) in &terms {
+                let mut rows = rhs.fixed_rows_mut::<6>(6 * a);
+                rows += cross_inverse * self.point_gradients[point];
+
+                for (b, cross, _) in &terms {
+                    let mut block = reduced.fixed_view_mut::<6, 6>(6 * a, 6 * b);
+                    block -= cross_inverse * cross.transpose();
+                }
+            }
+
+            inverse_point_hessians.push(inverse);
+        }
+
+        let camera_steps: Vec<Vector6<f64>> = if size == 0 {
+            Vec::new()
+        } else {
+            let solution = reduced.cholesky()?.solve(&rhs);
+            (0..size / 6).map(|free| solution.fixed_rows::<6>(6 * free).into_owned()).collect()
+        };
+
+        let point_steps = by_point.iter()
+            .enumerate()
+            .map(|(point, point_observations)| {
+                let mut rhs = -self.point_gradients[point];
+
+                for index in point_observations {
+                    if let (Some(free), Some(cross)) = (free_index[observations[*index].camera], &self.cross_terms[*index]) {
+                        rhs -= cross.transpose() * camera_steps[free];
+                    }
+                }
+
+                inverse_point_hessians[point] * rhs
+            })
+            .collect();
+
+        Some((camera_steps, point_steps))
+    }
+}
+
+/// Sparse Levenberg-Marquardt bundle adjustment: jointly refines the poses of the cameras
+/// that are not fixed and the positions of all points, minimizing the Huber cost of the
+/// reprojection errors.
+///
+/// Every step eliminates the points with the Schur complement, so only the reduced system
+/// over the free camera poses is solved densely. The intrinsics are not refined and only
+/// their pinhole part is used, which is why observations are undistorted beforehand.
+pub fn bundle_adjust(
+    intrinsics: &CameraIntrinsics,
+    cameras: &mut [BundleCamera],
+    points: &mut [Point3<f64>],
+    observations: &[BundleObservation],
+    config: &BundleAdjustmentConfig
+) -> BundleAdjustmentReport {
+    // Position of each free camera in the reduced system
+    let mut free_index: Vec<Option<usize>> = vec![None; cameras.len()];
+    let mut free_count = 0;
+
+    for (index, camera) in cameras.iter().enumerate() {
+        if !camera.fixed {
+            free_index[index] = Some(free_count);
+            free_count += 1;
+        }
+    }
+
+    let mut by_point: Vec<Vec<usize>> = vec![Vec::new(); points.len()];
+    for (index, observation) in observations.iter().enumerate() {
+        by_point[observation.point].push(index);
+    }
+
+    let threshold = config.huber_threshold;
+    let mut cost = robust_cost(&reprojection_errors(intrinsics, cameras, points, observations), threshold);
+    let initial_cost = cost;
+    let mut damping = 1e-3;
+    let mut iterations = 0;
+
+    while iterations < config.iterations {
+        iterations += 1;
+
+        // Normal equations with the Huber weights of the current residuals
+        let mut equations = NormalEquations {
+            camera_hessians: vec![Matrix6::zeros(); free_count],
+            camera_gradients: vec![Vector6::zeros(); free_count],
+            point_hessians: vec![Matrix3::zeros(); points.len()],
+            point_gradients: vec![Vector3::zeros(); points.len()],
+            cross_terms: vec![None; observations.len()]
+        };
+
+        for (index, observation) in observations.iter().enumerate() {
+            let camera = &cameras[observation.camera].camera_from_world;
+
+            let Some((residual, pose_jacobian, point_jacobian)) = linearize(intrinsics, camera, &points[observation.point], observation) else {
+                continue;
+            };
+
+            let (_, weight) = huber(residual.norm(), threshold);
+
+            equations.point_hessians[observation.point] += weight * point_jacobian.transpose() * point_jacobian;
+            equations.point_gradients[observation.point] += weight * point_jacobian.transpose() * residual;
+
+            if let Some(free) = free_index[observation.camera] {
+                equations.camera_hessians[free] += weight * pose_jacobian.transpose() * pose_jacobian;
+                equations.camera_gradients[free] += weight * pose_jacobian.transpose() * residual;
+                equations.cross_terms[index] = Some(weight * pose_jacobian.transpose() * point_jacobian);
+            }
+        }
+
+        let mut improved = false;
+
+        while damping < 1e10 {
+            let Some((camera_steps, point_steps)) = equations.solve(observations, &free_index, &by_point, damping) else {
+                damping *= 10.0;
+                continue;
+            };
+
+            let candidate_cameras: Vec<BundleCamera> = cameras.iter()
+                .zip(&free_index)
+                .map(|(camera, free)| match free {
+                    Some(free) => {
+                        let step = &camera_steps[*free];
+                        let update = Isometry3::new(step.fixed_rows::<3>(3).into_owned(), step.fixed_rows::<3>(0).into_owned());
+                        BundleCamera { camera_from_world: update * camera.camera_from_world, fixed: false }
+                    },
+                    None => *camera
+                })
+                .collect();
+
+            let candidate_points: Vec<Point3<f64>> = points.iter()
+                .zip(&point_steps)
+                .map(|(point, step)| point + step)
+                .collect();
+
+            let candidate_cost = robust_cost(
+                &reprojection_errors(intrinsics, &candidate_cameras, &candidate_points, observations),
+                threshold
+            );
+
+            if candidate_cost < cost {
+                let converged = (cost - candidate_cost) < 1e-10 * cost;
+
+                cameras.copy_from_slice(&candidate_cameras);
+                points.copy_from_slice(&candidate_points);
+                cost = candidate_cost;
+                damping = (damping * 0.1).max(1e-12);
+                improved = !converged;
+                break;
+            }
+
+            damping *= 10.0;
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    let outliers = reprojection_errors(intrinsics, cameras, points, observations)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, error)| error.is_none_or(|error| error > threshold))
+        .map(|(index, _)| index)
+        .collect();
+
+    BundleAdjustmentReport { initial_cost, final_cost: cost, iterations, outliers }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{Rotation3, Translation3, UnitQuaternion};
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use crate::test_util::intrinsics;
+
+    const NOISE: f64 = 0.5;
+
+    /// Cameras on a line looking at a box of points, all observations with up to `NOISE`
+    /// pixels of error. The first two cameras are fixed, which anchors scale and gauge.
+    fn synthetic_scene(rng: &mut SmallRng) -> (Vec<BundleCamera>, Vec<Point3<f64>>, Vec<BundleObservation>) {
+        let intrinsics = intrinsics();
+
+        let cameras: Vec<BundleCamera> = (0..5)
+            .map(|i| {
+                let i = i as f64;
+                let rotation = UnitQuaternion::from_euler_angles(0.01 * i, -0.05 * i, 0.0);
+                BundleCamera {
+                    camera_from_world: Isometry3::from_parts(Translation3::new(-0.4 * i, 0.05 * i, 0.0), rotation),
+                    fixed: i < 2.0
+                }
+            })
+            .collect();
+
+        let points: Vec<Point3<f64>> = (0..100)
+            .map(|_| Point3::new(rng.gen_range(-2.0..2.0), rng.gen_range(-1.5..1.5), rng.gen_range(4.0..8.0)))
+            .collect();
+
+        let mut observations = Vec::new();
+
+        for (camera_index, camera) in cameras.iter().enumerate() {
+            for (point_index, point) in points.iter().enumerate() {
+                let p = camera.camera_from_world * point;
+                let pixel = Point2::new(
+                    intrinsics.fx * p.x / p.z + intrinsics.cx + rng.gen_range(-NOISE..NOISE),
+                    intrinsics.fy * p.y / p.z + intrinsics.cy + rng.gen_range(-NOISE..NOISE)
+                );
+                observations.push(BundleObservation { camera: camera_index, point: point_index, pixel, sigma: 1.0 });
+            }
+        }
+
+        (cameras, points, observations)
+    }
+
+    fn rms_error(cameras: &[BundleCamera], points: &[Point3<f64>], observations: &[BundleObservation]) -> f64 {
+        let errors = reprojection_errors(&intrinsics(), cameras, points, observations);
+        let squared: f64 = errors.iter().map(|error| error.unwrap().powi(2)).sum();
+        (squared / errors.len() as f64).sqrt()
+    }
+
+    /// Moves the free cameras and every point away from the true scene.
+    fn perturb(cameras: &mut [BundleCamera], points: &mut [Point3<f64>], rng: &mut SmallRng) {
+        for camera in cameras.iter_mut().filter(|camera| !camera.fixed) {
+            let rotation = Rotation3::from_euler_angles(rng.gen_range(-0.03..0.03), rng.gen_range(-0.03..0.03), rng.gen_range(-0.03..0.03));
+            let translation = Vector3::new(rng.gen_range(-0.1..0.1), rng.gen_range(-0.1..0.1), rng.gen_range(-0.1..0.1));
+            camera.camera_from_world = Isometry3::from_parts(translation.into(), rotation.into()) * camera.camera_from_world;
+        }
+
+        for point in points.iter_mut() {
+            *point += Vector3::new(rng.gen_range(-0.1..0.1), rng.gen_range(-0.1..0.1), rng.gen_range(-0.1..0.1));
+        }
+    }
+
+    #[test]
+    fn converges_to_noise_level() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let (truth, true_points, observations) = synthetic_scene(&mut rng);
+
+        // Uniform noise in [-NOISE, NOISE] on both axes
+        let noise_rms = (2.0 * NOISE * NOISE / 3.0).sqrt();
+        assert!(rms_error(&truth, &true_points, &observations) < 1.1 * noise_rms);
+
+        let mut cameras = truth.clone();
+        let mut points = true_points.clone();
+        perturb(&mut cameras, &mut points, &mut rng);
+
+        let initial_rms = rms_error(&cameras, &points, &observations);
+        assert!(initial_rms > 10.0 * noise_rms);
+
+        let config = BundleAdjustmentConfig { iterations: 20, ..Default::default() };
+        let report = bundle_adjust(&intrinsics(), &mut cameras, &mut points, &observations, &config);
+
+        assert!(report.final_cost < report.initial_cost);
+        assert!(rms_error(&cameras, &points, &observations) < 1.1 * noise_rms);
+        assert!(report.outliers.is_empty());
+
+        for (camera, truth) in cameras.iter().zip(&truth) {
+            let error = camera.camera_from_world * truth.camera_from_world.inverse();
+            // The perturbation was up to 0.17 in translation and 0.05 radians in rotation
+            assert!(error.translation.vector.norm() < 0.02);
+            assert!(error.rotation.angle() < 3e-3);
+        }
+
+        // Far points are poorly constrained by the short baseline, so only their overall error is bounded
+        let point_error = points.iter().zip(&true_points).map(|(a, b)| (a - b).norm_squared()).sum::<f64>();
+        assert!((point_error / points.len() as f64).sqrt() < 0.03);
+    }
+
+    #[test]
+    fn reports_outliers() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        let (mut cameras, mut points, mut observations) = synthetic_scene(&mut rng);
+        perturb(&mut cameras, &mut points, &mut rng);
+
+        let corrupted = [3, 150, 420];
+        for index in corrupted {
+            observations[index].pixel += Vector2::new(40.0, -30.0);
+        }
+
+        let config = BundleAdjustmentConfig { iterations: 20, ..Default::default() };
+        let report = bundle_adjust(&intrinsics(), &mut cameras, &mut points, &observations, &config);
+
+        assert_eq!(report.outliers, corrupted);
+    }
+
+    #[test]
+    fn fixed_cameras_keep_their_pose() {
+        let mut rng = SmallRng::seed_from_u64(3);
+        let (mut cameras, mut points, observations) = synthetic_scene(&mut rng);
+        for camera in &mut cameras {
+            camera.fixed = true;
+        }
+        let fixed = cameras.clone();
+        perturb(&mut cameras, &mut points, &mut rng);
+
+        let report = bundle_adjust(&intrinsics(), &mut cameras, &mut points, &observations, &BundleAdjustmentConfig::default());
+
+        assert!(report.final_cost < report.initial_cost);
+        for (camera, fixed) in cameras.iter().zip(&fixed) {
+            assert_eq!(camera.camera_from_world, fixed.camera_from_world);
+        }
+    }
+}
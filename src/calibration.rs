@@ -0,0 +1,1186 @@
+use std::collections::{HashMap, VecDeque};
+
+use nalgebra::{DMatrix, DVector, Matrix3, Point2, Point3, Rotation3, Vector3};
+
+use crate::camera_model::{CameraIntrinsics, Distortion, StereoRig};
+use crate::geometry::smallest_eigenvector;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CalibrationConfig {
+    /// Inner corners per row and per column of the checkerboard.
+    pub pattern: (usize, usize),
+    /// Side of a square, in the unit the extrinsics are expressed in.
+    pub square_size: f64,
+    /// Number of views collected before calibrating.
+    pub views: usize,
+    /// Minimum mean corner displacement in pixels between two collected views, so the same
+    /// pose is not collected over and over.
+    pub min_view_distance: f64
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        Self {
+            pattern: (9, 6),
+            square_size: 1.0,
+            views: 20,
+            min_view_distance: 40.0
+        }
+    }
+}
+
+/// Grayscale image used by the checkerboard detector.
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f32>
+}
+
+impl GrayImage {
+    pub fn from_rgba(rgba: &[u8], width: usize, height: usize) -> Self {
+        assert_eq!(rgba.len(), width * height * 4);
+
+        let data = rgba.chunks_exact(4)
+            .map(|p| 0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32)
+            .collect();
+
+        Self { width, height, data }
+    }
+
+    fn at(&self, x: usize, y: usize) -> f32 {
+        self.data[y * self.width + x]
+    }
+
+    /// Bilinear sample, clamped to the image.
+    fn sample(&self, x: f64, y: f64) -> f32 {
+        let x = x.clamp(0.0, (self.width - 1) as f64);
+        let y = y.clamp(0.0, (self.height - 1) as f64);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = ((x - x0 as f64) as f32, (y - y0 as f64) as f32);
+
+        let top = self.at(x0, y0) * (1.0 - fx) + self.at(x1, y0) * fx;
+        let bottom = self.at(x0, y1) * (1.0 - fx) + self.at(x1, y1) * fx;
+
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    fn downsample(&self) -> Self {
+        let (width, height) = (self.width / 2, self.height / 2);
+        let mut data = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                data.push((
+                    self.at(2 * x, 2 * y) + self.at(2 * x + 1, 2 * y) +
+                    self.at(2 * x, 2 * y + 1) + self.at(2 * x + 1, 2 * y + 1)
+                ) * 0.25);
+            }
+        }
+
+        Self { width, height, data }
+    }
+
+    fn box_blur(&self) -> Self {
+        let mut data = self.data.clone();
+
+        for y in 1..self.height.saturating_sub(1) {
+            for x in 1..self.width.saturating_sub(1) {
+                let mut sum = 0.0;
+                for dy in 0..3 {
+                    for dx in 0..3 {
+                        sum += self.at(x + dx - 1, y + dy - 1);
+                    }
+                }
+                data[y * self.width + x] = sum / 9.0;
+            }
+        }
+
+        Self { width: self.width, height: self.height, data }
+    }
+}
+
+/// Sampling ring of the ChESS X-corner detector, in circular order.
+const RING: [(i32, i32); 16] = [
+    (0, -5), (2, -5), (3, -3), (5, -2), (5, 0), (5, 2), (3, 3), (2, 5),
+    (0, 5), (-2, 5), (-3, 3), (-5, 2), (-5, 0), (-5, -2), (-3, -3), (-2, -5)
+];
+
+/// Images are downsampled until they are at most this wide before running ChESS, whose ring
+/// has a fixed radius.
+const DETECTION_WIDTH: usize = 1000;
+
+/// ChESS response (Bennett and Lasenby): large where opposite quadrants look alike and
+/// adjacent ones differ, as at the meeting point of four checkerboard squares.
+fn chess_response(image: &GrayImage) -> Vec<f32> {
+    let (width, height) = (image.width, image.height);
+    let mut response = vec![0.0; width * height];
+
+    for y in 5..height.saturating_sub(5) {
+        for x in 5..width.saturating_sub(5) {
+            let ring = RING.map(|(dx, dy)| image.at((x as i32 + dx) as usize, (y as i32 + dy) as usize));
+
+            let sum: f32 = (0..4)
+                .map(|n| (ring[n] + ring[n + 8] - ring[n + 4] - ring[n + 12]).abs())
+                .sum();
+            let diff: f32 = (0..8)
+                .map(|n| (ring[n] - ring[n + 8]).abs())
+                .sum();
+
+            let ring_mean = ring.iter().sum::<f32>() / 16.0;
+            let local_mean = (
+                image.at(x, y) + image.at(x - 1, y) + image.at(x + 1, y) + image.at(x, y - 1) + image.at(x, y + 1)
+            ) / 5.0;
+
+            response[y * width + x] = sum - diff - 16.0 * (ring_mean - local_mean).abs();
+        }
+    }
+
+    response
+}
+
+/// X-corner candidates in full resolution pixels, strongest first.
+fn corner_candidates(image: &GrayImage, max_candidates: usize) -> Vec<Point2<f64>> {
+    let mut level = image.box_blur();
+    let mut scale = 1.0;
+
+    while level.width > DETECTION_WIDTH {
+        level = level.downsample();
+        scale *= 2.0;
+    }
+
+    let response = chess_response(&level);
+    let max_response = response.iter().copied().fold(0.0, f32::max);
+
+    if max_response <= 0.0 {
+        return Vec::new();
+    }
+
+    // Non-maximum suppression in a 7x7 window
+    const RADIUS: usize = 3;
+    let mut candidates = Vec::new();
+
+    for y in RADIUS..level.height.saturating_sub(RADIUS) {
+        for x in RADIUS..level.width.saturating_sub(RADIUS) {
+            let value = response[y * level.width + x];
+
+            if value < 0.1 * max_response {
+                continue;
+            }
+
+            // Ties on plateaus go to the first pixel in scan order
+            let is_max = (y - RADIUS..=y + RADIUS).all(|ny| {
+                (x - RADIUS..=x + RADIUS).all(|nx| {
+                    let other = response[ny * level.width + nx];
+                    (nx, ny) == (x, y) || other < value || (other == value && (ny, nx) > (y, x))
+                })
+            });
+
+            if is_max {
+                candidates.push((value, x, y));
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    candidates.truncate(max_candidates);
+
+    candidates.into_iter()
+        .map(|(_, x, y)| Point2::new((x as f64 + 0.5) * scale - 0.5, (y as f64 + 0.5) * scale - 0.5))
+        .collect()
+}
+
+fn nearest_unused(
+    points: &[Point2<f64>],
+    used: &[bool],
+    target: &Point2<f64>,
+    tolerance: f64
+) -> Option<usize> {
+    points.iter()
+        .enumerate()
+        .filter(|(index, _)| !used[*index])
+        .map(|(index, point)| (index, (point - target).norm()))
+        .filter(|(_, distance)| *distance < tolerance)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(index, _)| index)
+}
+
+/// Grows a lattice of X-corners outwards from `seed`, predicting each new corner from its
+/// already placed neighbours so perspective distortion is followed.
+fn grow_grid(points: &[Point2<f64>], seed: usize, pattern: (usize, usize)) -> Option<Vec<Point2<f64>>> {
+    let origin = points[seed];
+
+    let mut by_distance: Vec<usize> = (0..points.len()).filter(|i| *i != seed).collect();
+    by_distance.sort_by(|a, b| (points[*a] - origin).norm().total_cmp(&(points[*b] - origin).norm()));
+
+    let first = *by_distance.first()?;
+    let u = points[first] - origin;
+
+    let second = by_distance.iter().skip(1).take(8).copied().find(|&index| {
+        let v = points[index] - origin;
+        let ratio = v.norm() / u.norm();
+        (u.dot(&v) / (u.norm() * v.norm())).abs() < 0.5 && (0.5..2.0).contains(&ratio)
+    })?;
+    let v = points[second] - origin;
+
+    let mut used = vec![false; points.len()];
+    let mut grid: HashMap<(i32, i32), usize> = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    for (cell, index) in [((0, 0), seed), ((1, 0), first), ((0, 1), second)] {
+        grid.insert(cell, index);
+        used[index] = true;
+        queue.push_back(cell);
+    }
+
+    let max_cells = pattern.0 * pattern.1;
+
+    while let Some((i, j)) = queue.pop_front() {
+        let here = points[grid[&(i, j)]];
+
+        for (di, dj) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let cell = (i + di, j + dj);
+
+            if grid.contains_key(&cell) {
+                continue;
+            }
+
+            // Extrapolate along the row or column if possible, otherwise reuse the step of a
+            // parallel neighbour, otherwise fall back to the seed basis
+            let parallel = [(dj, di), (-dj, -di)].into_iter().find_map(|(ei, ej)| {
+                let a = grid.get(&(i + ei, j + ej))?;
+                let b = grid.get(&(i + ei + di, j + ej + dj))?;
+                Some(points[*b] - points[*a])
+            });
+
+            let step = match grid.get(&(i - di, j - dj)) {
+                Some(behind) => here - points[*behind],
+                None => parallel.unwrap_or(if di != 0 { u * di as f64 } else { v * dj as f64 })
+            };
+
+            let Some(index) = nearest_unused(points, &used, &(here + step), 0.3 * step.norm()) else {
+                continue;
+            };
+
+            grid.insert(cell, index);
+            used[index] = true;
+            queue.push_back(cell);
+
+            if grid.len() > max_cells {
+                return None;
+            }
+        }
+    }
+
+    let (min_i, max_i) = (grid.keys().map(|c| c.0).min()?, grid.keys().map(|c| c.0).max()?);
+    let (min_j, max_j) = (grid.keys().map(|c| c.1).min()?, grid.keys().map(|c| c.1).max()?);
+    let span = ((max_i - min_i + 1) as usize, (max_j - min_j + 1) as usize);
+
+    if grid.len() != max_cells {
+        return None;
+    }
+
+    let at = |column: usize, row: usize, transposed: bool| {
+        let (i, j) = if transposed { (row, column) } else { (column, row) };
+        points[grid[&(min_i + i as i32, min_j + j as i32)]]
+    };
+
+    let transposed = if span == pattern {
+        false
+    } else if span == (pattern.1, pattern.0) {
+        true
+    } else {
+        return None;
+    };
+
+    Some((0..pattern.1)
+        .flat_map(|row| (0..pattern.0).map(move |column| (column, row)))
+        .map(|(column, row)| at(column, row, transposed))
+        .collect())
+}
+
+/// Gradient based sub-pixel refinement, as in OpenCV's cornerSubPix: at the corner every
+/// gradient in the window is orthogonal to the vector from the corner to its pixel.
+fn refine_corner(image: &GrayImage, corner: Point2<f64>, half_window: i32) -> Point2<f64> {
+    let mut estimate = corner;
+    let sigma = half_window as f64 * 0.5;
+
+    for _ in 0..20 {
+        let mut a = nalgebra::Matrix2::zeros();
+        let mut b = nalgebra::Vector2::zeros();
+
+        for dy in -half_window..=half_window {
+            for dx in -half_window..=half_window {
+                let (x, y) = (estimate.x + dx as f64, estimate.y + dy as f64);
+                let gx = (image.sample(x + 1.0, y) - image.sample(x - 1.0, y)) as f64 * 0.5;
+                let gy = (image.sample(x, y + 1.0) - image.sample(x, y - 1.0)) as f64 * 0.5;
+
+                let weight = (-((dx * dx + dy * dy) as f64) / (2.0 * sigma * sigma)).exp();
+                let g = nalgebra::Vector2::new(gx, gy);
+                let ggt = g * g.transpose() * weight;
+
+                a += ggt;
+                b += ggt * nalgebra::Vector2::new(x, y);
+            }
+        }
+
+        let Some(next) = a.try_inverse().map(|inverse| Point2::from(inverse * b)) else {
+            break;
+        };
+
+        let shift = (next - estimate).norm();
+        estimate = next;
+
+        if shift < 0.01 {
+            break;
+        }
+    }
+
+    if (estimate - corner).norm() > half_window as f64 {
+        corner
+    } else {
+        estimate
+    }
+}
+
+/// Finds the inner corners of a checkerboard with `pattern` corners per row and column.
+///
+/// Corners are returned row by row, each row holding `pattern.0` corners.
+pub fn detect_checkerboard(image: &GrayImage, pattern: (usize, usize)) -> Option<Vec<Point2<f64>>> {
+    let expected = pattern.0 * pattern.1;
+    let candidates = corner_candidates(image, expected * 3 + 50);
+
+    if candidates.len() < expected {
+        return None;
+    }
+
+    // Seed from the candidates closest to the centroid of the strongest ones, which are the
+    // most likely to lie on the board
+    let strongest = &candidates[..expected];
+    let centroid = strongest.iter().fold(Vector3::zeros(), |sum, p| sum + Vector3::new(p.x, p.y, 1.0)) / expected as f64;
+    let centroid = Point2::new(centroid.x, centroid.y);
+
+    let mut seeds: Vec<usize> = (0..candidates.len()).collect();
+    seeds.sort_by(|a, b| (candidates[*a] - centroid).norm().total_cmp(&(candidates[*b] - centroid).norm()));
+
+    let corners = seeds.into_iter()
+        .take(10)
+        .find_map(|seed| grow_grid(&candidates, seed, pattern))?;
+
+    let spacing = corners.windows(2)
+        .enumerate()
+        .filter(|(index, _)| (index + 1) % pattern.0 != 0)
+        .map(|(_, pair)| (pair[1] - pair[0]).norm())
+        .fold(f64::INFINITY, f64::min);
+    let half_window = ((spacing * 0.3) as i32).clamp(2, 15);
+
+    Some(corners.into_iter()
+        .map(|corner| refine_corner(image, corner, half_window))
+        .collect())
+}
+
+/// Normalized DLT homography mapping board coordinates to pixels.
+fn homography(board: &[Point2<f64>], image: &[Point2<f64>]) -> Option<Matrix3<f64>> {
+    fn normalization(points: &[Point2<f64>]) -> Matrix3<f64> {
+        let n = points.len() as f64;
+        let mean = points.iter().fold(Vector3::zeros(), |sum, p| sum + Vector3::new(p.x, p.y, 0.0)) / n;
+        let spread = points.iter()
+            .map(|p| ((p.x - mean.x).powi(2) + (p.y - mean.y).powi(2)).sqrt())
+            .sum::<f64>() / n;
+        let s = std::f64::consts::SQRT_2 / spread.max(f64::EPSILON);
+
+        Matrix3::new(
+            s, 0.0, -s * mean.x,
+            0.0, s, -s * mean.y,
+            0.0, 0.0, 1.0
+        )
+    }
+
+    let (tb, ti) = (normalization(board), normalization(image));
+    let mut ata = DMatrix::<f64>::zeros(9, 9);
+
+    for (b, i) in board.iter().zip(image) {
+        let b = tb * Vector3::new(b.x, b.y, 1.0);
+        let i = ti * Vector3::new(i.x, i.y, 1.0);
+
+        let rows = [
+            [b.x, b.y, 1.0, 0.0, 0.0, 0.0, -i.x * b.x, -i.x * b.y, -i.x],
+            [0.0, 0.0, 0.0, b.x, b.y, 1.0, -i.y * b.x, -i.y * b.y, -i.y]
+        ];
+
+        for row in rows {
+            let row = DVector::from_row_slice(&row);
+            ata += &row * row.transpose();
+        }
+    }
+
+    let h = smallest_eigenvector(ata);
+    let h = ti.try_inverse()? * Matrix3::from_row_slice(h.as_slice()) * tb;
+
+    (h[(2, 2)].abs() > f64::EPSILON).then(|| h / h[(2, 2)])
+}
+
+/// Zhang's closed form intrinsics from three or more homographies, assuming no skew.
+fn intrinsics_from_homographies(homographies: &[Matrix3<f64>]) -> Option<Matrix3<f64>> {
+    let v = |h: &Matrix3<f64>, i: usize, j: usize| {
+        let (hi, hj) = (h.column(i), h.column(j));
+        [
+            hi[0] * hj[0],
+            hi[0] * hj[1] + hi[1] * hj[0],
+            hi[1] * hj[1],
+            hi[2] * hj[0] + hi[0] * hj[2],
+            hi[2] * hj[1] + hi[1] * hj[2],
+            hi[2] * hj[2]
+        ]
+    };
+
+    let mut vtv = DMatrix::<f64>::zeros(6, 6);
+
+    for h in homographies {
+        let v12 = v(h, 0, 1);
+        let v11 = v(h, 0, 0);
+        let v22 = v(h, 1, 1);
+        let difference: Vec<f64> = v11.iter().zip(&v22).map(|(a, b)| a - b).collect();
+
+        for row in [DVector::from_row_slice(&v12), DVector::from_vec(difference)] {
+            vtv += &row * row.transpose();
+        }
+    }
+
+    let mut b = smallest_eigenvector(vtv);
+    if b[0] < 0.0 {
+        b = -b;
+    }
+
+    let (b11, b12, b22, b13, b23, b33) = (b[0], b[1], b[2], b[3], b[4], b[5]);
+    let denominator = b11 * b22 - b12 * b12;
+
+    let v0 = (b12 * b13 - b11 * b23) / denominator;
+    let lambda = b33 - (b13 * b13 + v0 * (b12 * b13 - b11 * b23)) / b11;
+    let alpha = (lambda / b11).sqrt();
+    let beta = (lambda * b11 / denominator).sqrt();
+    let gamma = -b12 * alpha * alpha * beta / lambda;
+    let u0 = gamma * v0 / beta - b13 * alpha * alpha / lambda;
+
+    let k = Matrix3::new(
+        alpha, 0.0, u0,
+        0.0, beta, v0,
+        0.0, 0.0, 1.0
+    );
+
+    k.iter().all(|v| v.is_finite()).then_some(k)
+}
+
+/// Board pose from a homography and the camera matrix.
+fn extrinsics_from_homography(k_inverse: &Matrix3<f64>, h: &Matrix3<f64>) -> (Rotation3<f64>, Vector3<f64>) {
+    let mut lambda = 1.0 / (k_inverse * h.column(0)).norm();
+
+    // The board has to be in front of the camera
+    if (k_inverse * h.column(2)).z * lambda < 0.0 {
+        lambda = -lambda;
+    }
+
+    let r1 = k_inverse * h.column(0) * lambda;
+    let r2 = k_inverse * h.column(1) * lambda;
+    let t = k_inverse * h.column(2) * lambda;
+
+    let r = Matrix3::from_columns(&[r1, r2, r1.cross(&r2)]);
+
+    (Rotation3::from_matrix_eps(&r, 1e-12, 100, Rotation3::identity()), t)
+}
+
+/// Corners of the checkerboard in board coordinates, ordered like `detect_checkerboard`
+/// returns them.
+fn board_points(config: &CalibrationConfig) -> Vec<Point3<f64>> {
+    let (columns, rows) = config.pattern;
+
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .map(|(column, row)| Point3::new(column as f64 * config.square_size, row as f64 * config.square_size, 0.0))
+        .collect()
+}
+
+/// Result of calibrating a camera from checkerboard views.
+#[derive(Clone, Debug)]
+pub struct Calibration {
+    pub intrinsics: CameraIntrinsics,
+    /// Root mean square reprojection error over all corners, in pixels.
+    pub rms_error: f64,
+    /// Root mean square reprojection error of each view, in pixels.
+    pub view_errors: Vec<f64>
+}
+
+/// Number of intrinsic parameters refined: fx, fy, cx, cy, k1, k2, p1, p2. `k3` is left at
+/// zero, a single webcam sequence rarely constrains it.
+const INTRINSIC_PARAMETERS: usize = 8;
+
+fn unpack_intrinsics(parameters: &DVector<f64>, width: u32, height: u32) -> CameraIntrinsics {
+    CameraIntrinsics {
+        width,
+        height,
+        fx: parameters[0],
+        fy: parameters[1],
+        cx: parameters[2],
+        cy: parameters[3],
+        distortion: Distortion::BrownConrady {
+            k1: parameters[4],
+            k2: parameters[5],
+            p1: parameters[6],
+            p2: parameters[7],
+            k3: 0.0
+        }
+    }
+}
+
+/// Reprojection residuals of one view.
+fn view_residuals(
+    intrinsics: &CameraIntrinsics,
+    pose: &[f64],
+    board: &[Point3<f64>],
+    corners: &[Point2<f64>]
+) -> Vec<f64> {
+    let rotation = Rotation3::new(Vector3::new(pose[0], pose[1], pose[2]));
+    let translation = Vector3::new(pose[3], pose[4], pose[5]);
+
+    board.iter()
+        .zip(corners)
+        .flat_map(|(point, corner)| {
+            let camera = rotation * point + translation;
+            // Points behind the camera get a large residual instead of disappearing
+            let projected = intrinsics.project(&camera).unwrap_or(Point2::new(1e6, 1e6));
+            [projected.x - corner.x, projected.y - corner.y]
+        })
+        .collect()
+}
+
+/// Levenberg-Marquardt with Marquardt's scaling of the damping by the diagonal of `JᵀJ`.
+///
+/// Returns the refined parameters and their residuals.
+fn levenberg_marquardt(
+    mut parameters: DVector<f64>,
+    residuals_of: impl Fn(&DVector<f64>) -> DVector<f64>,
+    jacobian_of: impl Fn(&DVector<f64>, &DVector<f64>) -> DMatrix<f64>
+) -> (DVector<f64>, DVector<f64>) {
+    let mut residuals = residuals_of(&parameters);
+    let mut cost = residuals.norm_squared();
+    let mut damping = 1e-3;
+
+    for _ in 0..100 {
+        let jacobian = jacobian_of(&parameters, &residuals);
+
+        let jtj = jacobian.transpose() * &jacobian;
+        let jtr = jacobian.transpose() * &residuals;
+
+        let mut improved = false;
+
+        while damping < 1e10 {
+            let mut system = jtj.clone();
+            for i in 0..system.nrows() {
+                system[(i, i)] += damping * jtj[(i, i)].max(1e-9);
+            }
+
+            let Some(delta) = system.cholesky().map(|cholesky| cholesky.solve(&-&jtr)) else {
+                damping *= 10.0;
+                continue;
+            };
+
+            let candidate = &parameters + &delta;
+            let candidate_residuals = residuals_of(&candidate);
+            let candidate_cost = candidate_residuals.norm_squared();
+
+            if candidate_cost < cost {
+                let converged = (cost - candidate_cost) < 1e-10 * cost;
+
+                parameters = candidate;
+                residuals = candidate_residuals;
+                cost = candidate_cost;
+                damping = (damping * 0.1).max(1e-12);
+                improved = !converged;
+                break;
+            }
+
+            damping *= 10.0;
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    (parameters, residuals)
+}
+
+/// Zhang's method: closed form initialization followed by Levenberg-Marquardt refinement of
+/// the intrinsics, the distortion and every board pose.
+///
+/// `views` holds the detected corners of each view, ordered like `detect_checkerboard`
+/// returns them.
+pub fn calibrate_camera(
+    views: &[Vec<Point2<f64>>],
+    config: &CalibrationConfig,
+    width: u32,
+    height: u32
+) -> Option<Calibration> {
+    if views.len() < 3 {
+        return None;
+    }
+
+    let board = board_points(config);
+    let board_2d: Vec<Point2<f64>> = board.iter().map(|p| Point2::new(p.x, p.y)).collect();
+
+    let homographies: Vec<Matrix3<f64>> = views.iter()
+        .map(|corners| homography(&board_2d, corners))
+        .collect::<Option<_>>()?;
+
+    let k = intrinsics_from_homographies(&homographies)?;
+    let k_inverse = k.try_inverse()?;
+
+    let mut parameters = DVector::zeros(INTRINSIC_PARAMETERS + 6 * views.len());
+    parameters[0] = k[(0, 0)];
+    parameters[1] = k[(1, 1)];
+    parameters[2] = k[(0, 2)];
+    parameters[3] = k[(1, 2)];
+
+    for (view, h) in homographies.iter().enumerate() {
+        let (rotation, translation) = extrinsics_from_homography(&k_inverse, h);
+        let offset = INTRINSIC_PARAMETERS + 6 * view;
+
+        parameters.rows_mut(offset, 3).copy_from(&rotation.scaled_axis());
+        parameters.rows_mut(offset + 3, 3).copy_from(&translation);
+    }
+
+    let residuals_of = |parameters: &DVector<f64>, view: usize| {
+        let intrinsics = unpack_intrinsics(parameters, width, height);
+        let offset = INTRINSIC_PARAMETERS + 6 * view;
+        view_residuals(&intrinsics, &parameters.as_slice()[offset..offset + 6], &board, &views[view])
+    };
+
+    let all_residuals = |parameters: &DVector<f64>| {
+        DVector::from_vec((0..views.len()).flat_map(|view| residuals_of(parameters, view)).collect())
+    };
+
+    let per_view = board.len() * 2;
+
+    // Forward difference Jacobian, pose parameters only touch the rows of their view
+    let jacobian_of = |parameters: &DVector<f64>, residuals: &DVector<f64>| {
+        let mut jacobian = DMatrix::<f64>::zeros(residuals.len(), parameters.len());
+
+        for column in 0..parameters.len() {
+            let step = 1e-6 * parameters[column].abs().max(1.0);
+            let mut perturbed = parameters.clone();
+            perturbed[column] += step;
+
+            if column < INTRINSIC_PARAMETERS {
+                let shifted = all_residuals(&perturbed);
+                jacobian.column_mut(column).copy_from(&((shifted - residuals) / step));
+            } else {
+                let view = (column - INTRINSIC_PARAMETERS) / 6;
+                let shifted = DVector::from_vec(residuals_of(&perturbed, view));
+                let rows = residuals.rows(view * per_view, per_view);
+                jacobian.view_mut((view * per_view, column), (per_view, 1)).copy_from(&((shifted - rows) / step));
+            }
+        }
+
+        jacobian
+    };
+
+    let (parameters, residuals) = levenberg_marquardt(parameters, all_residuals, jacobian_of);
+    let cost = residuals.norm_squared();
+
+    let view_errors = residuals.as_slice()
+        .chunks(per_view)
+        .map(|view| (view.iter().map(|r| r * r).sum::<f64>() / board.len() as f64).sqrt())
+        .collect();
+
+    Some(Calibration {
+        intrinsics: unpack_intrinsics(&parameters, width, height),
+        rms_error: (cost / (views.len() * board.len()) as f64).sqrt(),
+        view_errors
+    })
+}
+
+/// Result of calibrating the extrinsics of a stereo pair.
+#[derive(Clone, Debug)]
+pub struct StereoCalibration {
+    pub rig: StereoRig,
+    /// Root mean square reprojection error over all corners of both cameras, in pixels.
+    pub rms_error: f64,
+    /// Root mean square reprojection error of each view pair, in pixels.
+    pub view_errors: Vec<f64>
+}
+
+/// Corners of the same board detected by the left and the right camera.
+pub type ViewPair = (Vec<Point2<f64>>, Vec<Point2<f64>>);
+
+/// Parameters of the pose of the right camera relative to the left one, which come before
+/// the board poses.
+const RELATIVE_POSE_PARAMETERS: usize = 6;
+
+fn pose_parameters(rotation: &Rotation3<f64>, translation: &Vector3<f64>) -> [f64; 6] {
+    let axis = rotation.scaled_axis();
+    [axis.x, axis.y, axis.z, translation.x, translation.y, translation.z]
+}
+
+fn unpack_pose(pose: &[f64]) -> (Rotation3<f64>, Vector3<f64>) {
+    (Rotation3::new(Vector3::new(pose[0], pose[1], pose[2])), Vector3::new(pose[3], pose[4], pose[5]))
+}
+
+/// Board pose seen by a calibrated camera, from the homography between the board and the
+/// undistorted corners.
+fn board_pose(
+    intrinsics: &CameraIntrinsics,
+    board: &[Point2<f64>],
+    corners: &[Point2<f64>]
+) -> Option<(Rotation3<f64>, Vector3<f64>)> {
+    let normalized: Vec<Point2<f64>> = corners.iter().map(|corner| intrinsics.unproject(corner)).collect();
+    let h = homography(board, &normalized)?;
+
+    Some(extrinsics_from_homography(&Matrix3::identity(), &h))
+}
+
+/// Calibrates the pose of the right camera relative to the left one, keeping the intrinsics
+/// of both cameras fixed.
+///
+/// Every view pair gives an estimate of the relative pose from the board poses seen by both
+/// cameras. The one that best explains all view pairs is refined with Levenberg-Marquardt
+/// together with the board poses, minimizing the reprojection error in both images.
+pub fn calibrate_stereo(
+    views: &[ViewPair],
+    config: &CalibrationConfig,
+    left: &CameraIntrinsics,
+    right: &CameraIntrinsics
+) -> Option<StereoCalibration> {
+    if views.is_empty() {
+        return None;
+    }
+
+    let board = board_points(config);
+    let board_2d: Vec<Point2<f64>> = board.iter().map(|p| Point2::new(p.x, p.y)).collect();
+
+    let poses: Vec<_> = views.iter()
+        .map(|(left_corners, right_corners)| {
+            Some((board_pose(left, &board_2d, left_corners)?, board_pose(right, &board_2d, right_corners)?))
+        })
+        .collect::<Option<_>>()?;
+
+    // Residuals of a view pair: the left corners, then the right ones
+    let residuals_of = |parameters: &DVector<f64>, view: usize| {
+        let (rotation, translation) = unpack_pose(&parameters.as_slice()[..RELATIVE_POSE_PARAMETERS]);
+        let offset = RELATIVE_POSE_PARAMETERS + 6 * view;
+        let left_pose = &parameters.as_slice()[offset..offset + 6];
+
+        let (board_rotation, board_translation) = unpack_pose(left_pose);
+        let right_pose = pose_parameters(&(rotation * board_rotation), &(rotation * board_translation + translation));
+
+        let (left_corners, right_corners) = &views[view];
+        let mut residuals = view_residuals(left, left_pose, &board, left_corners);
+        residuals.extend(view_residuals(right, &right_pose, &board, right_corners));
+        residuals
+    };
+
+    let all_residuals = |parameters: &DVector<f64>| {
+        DVector::from_vec((0..views.len()).flat_map(|view| residuals_of(parameters, view)).collect())
+    };
+
+    let mut initial = DVector::zeros(RELATIVE_POSE_PARAMETERS + 6 * views.len());
+
+    for (view, ((rotation, translation), _)) in poses.iter().enumerate() {
+        initial.rows_mut(RELATIVE_POSE_PARAMETERS + 6 * view, 6).copy_from_slice(&pose_parameters(rotation, translation));
+    }
+
+    let parameters = poses.iter()
+        .map(|((left_rotation, left_translation), (right_rotation, right_translation))| {
+            let rotation = right_rotation * left_rotation.inverse();
+            let translation = right_translation - rotation * left_translation;
+
+            let mut candidate = initial.clone();
+            candidate.rows_mut(0, RELATIVE_POSE_PARAMETERS).copy_from_slice(&pose_parameters(&rotation, &translation));
+            let cost = all_residuals(&candidate).norm_squared();
+
+            (candidate, cost)
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(candidate, _)| candidate)?;
+
+    let per_view = board.len() * 4;
+
+    // The relative pose moves every right corner, board poses only the corners of their view
+    let jacobian_of = |parameters: &DVector<f64>, residuals: &DVector<f64>| {
+        let mut jacobian = DMatrix::<f64>::zeros(residuals.len(), parameters.len());
+
+        for column in 0..parameters.len() {
+            let step = 1e-6 * parameters[column].abs().max(1.0);
+            let mut perturbed = parameters.clone();
+            perturbed[column] += step;
+
+            if column < RELATIVE_POSE_PARAMETERS {
+                let shifted = all_residuals(&perturbed);
+                jacobian.column_mut(column).copy_from(&((shifted - residuals) / step));
+            } else {
+                let view = (column - RELATIVE_POSE_PARAMETERS) / 6;
+                let shifted = DVector::from_vec(residuals_of(&perturbed, view));
+                let rows = residuals.rows(view * per_view, per_view);
+                jacobian.view_mut((view * per_view, column), (per_view, 1)).copy_from(&((shifted - rows) / step));
+            }
+        }
+
+        jacobian
+    };
+
+    let (parameters, residuals) = levenberg_marquardt(parameters, all_residuals, jacobian_of);
+    let (rotation, translation) = unpack_pose(&parameters.as_slice()[..RELATIVE_POSE_PARAMETERS]);
+
+    let corners_per_view = board.len() * 2;
+    let view_errors = residuals.as_slice()
+        .chunks(per_view)
+        .map(|view| (view.iter().map(|r| r * r).sum::<f64>() / corners_per_view as f64).sqrt())
+        .collect();
+
+    Some(StereoCalibration {
+        rig: StereoRig { left: *left, right: *right, rotation, translation },
+        rms_error: (residuals.norm_squared() / (views.len() * corners_per_view) as f64).sqrt(),
+        view_errors
+    })
+}
+
+/// Mean distance between the corners of two detections of the same board.
+fn mean_displacement(a: &[Point2<f64>], b: &[Point2<f64>]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b).norm())
+        .sum::<f64>() / a.len() as f64
+}
+
+/// Checkerboard detection in a single frame.
+pub struct Detection {
+    pub corners: Vec<Point2<f64>>,
+    /// Whether the view was collected for calibration.
+    pub collected: bool
+}
+
+/// Collects checkerboard views from a live camera.
+pub struct CalibrationSession {
+    pub config: CalibrationConfig,
+    width: u32,
+    height: u32,
+    views: Vec<Vec<Point2<f64>>>
+}
+
+impl CalibrationSession {
+    pub fn new(config: CalibrationConfig, width: u32, height: u32) -> Self {
+        Self { config, width, height, views: Vec::new() }
+    }
+
+    pub fn views(&self) -> &[Vec<Point2<f64>>] {
+        &self.views
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.views.len() >= self.config.views
+    }
+
+    /// Detects the checkerboard in an RGBA frame and collects it if it differs enough from
+    /// the previously collected view.
+    pub fn process_frame(&mut self, rgba: &[u8]) -> Option<Detection> {
+        let image = GrayImage::from_rgba(rgba, self.width as usize, self.height as usize);
+        let corners = detect_checkerboard(&image, self.config.pattern)?;
+
+        let moved = match self.views.last() {
+            Some(last) => mean_displacement(last, &corners) >= self.config.min_view_distance,
+            None => true
+        };
+
+        let collected = moved && !self.is_complete();
+
+        if collected {
+            self.views.push(corners.clone());
+        }
+
+        Some(Detection { corners, collected })
+    }
+
+    pub fn calibrate(&self) -> Option<Calibration> {
+        calibrate_camera(&self.views, &self.config, self.width, self.height)
+    }
+}
+
+/// Relabels `corners` like `reference`, the same board seen by the other camera of a stereo
+/// pair.
+///
+/// A checkerboard looks the same after half a turn or flipped over, so the two detections
+/// may start from different corners. Of the labellings of the grid, the one that moves every
+/// corner by nearly the same amount between the cameras is kept.
+fn match_labels(reference: &[Point2<f64>], corners: &[Point2<f64>], pattern: (usize, usize)) -> Vec<Point2<f64>> {
+    let (columns, rows) = pattern;
+    let transposes: &[bool] = if columns == rows { &[false, true] } else { &[false] };
+
+    let labellings = transposes.iter().flat_map(|&transposed| {
+        [(false, false), (true, false), (false, true), (true, true)].map(|(flip_columns, flip_rows)| {
+            (0..rows)
+                .flat_map(|row| (0..columns).map(move |column| (column, row)))
+                .map(|(column, row)| {
+                    let column = if flip_columns { columns - 1 - column } else { column };
+                    let row = if flip_rows { rows - 1 - row } else { row };
+                    let (column, row) = if transposed { (row, column) } else { (column, row) };
+                    corners[row * columns + column]
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+
+    let spread = |labelling: &Vec<Point2<f64>>| {
+        let displacements: Vec<_> = reference.iter().zip(labelling).map(|(a, b)| b - a).collect();
+        let mean = displacements.iter().sum::<nalgebra::Vector2<f64>>() / displacements.len() as f64;
+        displacements.iter().map(|d| (d - mean).norm_squared()).sum::<f64>()
+    };
+
+    labellings
+        .min_by(|a, b| spread(a).total_cmp(&spread(b)))
+        .unwrap_or_else(|| corners.to_vec())
+}
+
+/// Checkerboard detection in the two frames of a stereo pair.
+pub struct StereoDetection {
+    pub left: Vec<Point2<f64>>,
+    pub right: Vec<Point2<f64>>,
+    /// Whether the view pair was collected for calibration.
+    pub collected: bool
+}
+
+/// Collects checkerboard view pairs from a stereo pair whose cameras have already been
+/// calibrated on their own.
+pub struct StereoCalibrationSession {
+    pub config: CalibrationConfig,
+    left: CameraIntrinsics,
+    right: CameraIntrinsics,
+    views: Vec<ViewPair>
+}
+
+impl StereoCalibrationSession {
+    pub fn new(config: CalibrationConfig, left: CameraIntrinsics, right: CameraIntrinsics) -> Self {
+        Self { config, left, right, views: Vec::new() }
+    }
+
+    pub fn views(&self) -> &[ViewPair] {
+        &self.views
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.views.len() >= self.config.views
+    }
+
+    /// Detects the checkerboard in the RGBA frames of both cameras, which must be captured
+    /// at the same time, and collects them if both see the whole board and it moved enough
+    /// since the previously collected pair.
+    pub fn process_frames(&mut self, left: &[u8], right: &[u8]) -> Option<StereoDetection> {
+        let detect = |rgba: &[u8], intrinsics: &CameraIntrinsics| {
+            let image = GrayImage::from_rgba(rgba, intrinsics.width as usize, intrinsics.height as usize);
+            detect_checkerboard(&image, self.config.pattern)
+        };
+
+        let left_corners = detect(left, &self.left)?;
+        let right_corners = match_labels(&left_corners, &detect(right, &self.right)?, self.config.pattern);
+
+        let moved = match self.views.last() {
+            Some((last, _)) => mean_displacement(last, &left_corners) >= self.config.min_view_distance,
+            None => true
+        };
+
+        let collected = moved && !self.is_complete();
+
+        if collected {
+            self.views.push((left_corners.clone(), right_corners.clone()));
+        }
+
+        Some(StereoDetection { left: left_corners, right: right_corners, collected })
+    }
+
+    pub fn calibrate(&self) -> Option<StereoCalibration> {
+        calibrate_stereo(&self.views, &self.config, &self.left, &self.right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATTERN: (usize, usize) = (7, 5);
+    const SQUARE_SIZE: f64 = 0.03;
+
+    fn intrinsics() -> CameraIntrinsics {
+        CameraIntrinsics {
+            width: 320,
+            height: 240,
+            fx: 305.0,
+            fy: 302.5,
+            cx: 161.5,
+            cy: 118.0,
+            distortion: Distortion::BrownConrady { k1: -0.2, k2: 0.0, p1: 0.0, p2: 0.0, k3: 0.0 }
+        }
+    }
+
+    fn config() -> CalibrationConfig {
+        CalibrationConfig { pattern: PATTERN, square_size: SQUARE_SIZE, ..CalibrationConfig::default() }
+    }
+
+    /// Board poses tilted in every direction and spread over the image, so the distortion
+    /// is seen towards the corners.
+    fn board_poses() -> Vec<(Rotation3<f64>, Vector3<f64>)> {
+        let center = Vector3::new(3.0 * SQUARE_SIZE, 2.0 * SQUARE_SIZE, 0.0);
+
+        [
+            ((0.0, 0.0, 0.0), (0.0, 0.0, 0.5)),
+            ((0.4, 0.0, 0.05), (-0.1, -0.07, 0.5)),
+            ((-0.4, 0.1, -0.05), (0.1, 0.07, 0.5)),
+            ((0.0, 0.45, 0.1), (0.12, -0.06, 0.55)),
+            ((0.1, -0.45, 0.0), (-0.12, 0.06, 0.55)),
+            ((0.3, 0.3, 0.3), (0.08, 0.08, 0.45)),
+            ((-0.3, -0.3, -0.2), (-0.08, -0.08, 0.45)),
+            ((0.2, -0.3, 1.2), (0.0, 0.0, 0.6))
+        ]
+        .into_iter()
+        .map(|((roll, pitch, yaw), (x, y, z))| {
+            let rotation = Rotation3::from_euler_angles(roll, pitch, yaw);
+            (rotation, Vector3::new(x, y, z) - rotation * center)
+        })
+        .collect()
+    }
+
+    /// Ray traces a checkerboard with a white border one square wide in front of a grey
+    /// background. Only the pixel corners are traced, the board is close enough to affine
+    /// within a pixel to supersample it 8x8 by interpolating their board coordinates.
+    fn render(intrinsics: &CameraIntrinsics, rotation: &Rotation3<f64>, translation: &Vector3<f64>) -> GrayImage {
+        const SAMPLES: usize = 8;
+
+        let (width, height) = (intrinsics.width as usize, intrinsics.height as usize);
+        let normal = rotation * Vector3::z();
+        let (columns, rows) = (PATTERN.0 as i32, PATTERN.1 as i32);
+
+        let traced: Vec<Option<(f64, f64)>> = (0..=height)
+            .flat_map(|y| (0..=width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let ray = intrinsics.unproject(&Point2::new(x as f64 - 0.5, y as f64 - 0.5)).to_homogeneous();
+                let depth = normal.dot(translation) / normal.dot(&ray);
+                let board = rotation.inverse() * (ray * depth - translation) / SQUARE_SIZE;
+                (depth > 0.0).then_some((board.x, board.y))
+            })
+            .collect();
+
+        let shade = |x: f64, y: f64| {
+            let (i, j) = (x.floor() as i32, y.floor() as i32);
+
+            if !(-2..=columns).contains(&i) || !(-2..=rows).contains(&j) {
+                100.0
+            } else if !(-1..columns).contains(&i) || !(-1..rows).contains(&j) || (i + j) % 2 != 0 {
+                230.0
+            } else {
+                20.0
+            }
+        };
+
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let corner = |dx: usize, dy: usize| traced[(y + dy) * (width + 1) + x + dx];
+                let (Some(a), Some(b), Some(c), Some(d)) = (corner(0, 0), corner(1, 0), corner(0, 1), corner(1, 1)) else {
+                    return 100.0;
+                };
+
+                let sum: f64 = (0..SAMPLES * SAMPLES)
+                    .map(|n| {
+                        let u = ((n % SAMPLES) as f64 + 0.5) / SAMPLES as f64;
+                        let v = ((n / SAMPLES) as f64 + 0.5) / SAMPLES as f64;
+                        let lerp = |a: f64, b: f64, c: f64, d: f64| (a * (1.0 - u) + b * u) * (1.0 - v) + (c * (1.0 - u) + d * u) * v;
+                        shade(lerp(a.0, b.0, c.0, d.0), lerp(a.1, b.1, c.1, d.1))
+                    })
+                    .sum();
+
+                (sum / (SAMPLES * SAMPLES) as f64) as f32
+            })
+            .collect();
+
+        GrayImage { width, height, data }
+    }
+
+    /// Corners of the board as seen by the camera, in the order of `board_points`.
+    fn project_corners(intrinsics: &CameraIntrinsics, rotation: &Rotation3<f64>, translation: &Vector3<f64>) -> Vec<Point2<f64>> {
+        board_points(&config()).iter()
+            .map(|point| intrinsics.project(&(rotation * point + translation)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn detects_corners_to_subpixel_accuracy() {
+        let intrinsics = intrinsics();
+
+        for (view, (rotation, translation)) in board_poses().iter().enumerate() {
+            let image = render(&intrinsics, rotation, translation);
+            let truth = project_corners(&intrinsics, rotation, translation);
+
+            let detected = detect_checkerboard(&image, PATTERN)
+                .unwrap_or_else(|| panic!("board not detected in view {view}"));
+            assert_eq!(detected.len(), truth.len());
+
+            // The detector may start from any corner of the board
+            let detected = match_labels(&truth, &detected, PATTERN);
+            let error = truth.iter().zip(&detected).map(|(a, b)| (a - b).norm()).fold(0.0, f64::max);
+            assert!(error < 0.25, "view {view} corners off by {error} px");
+        }
+    }
+
+    #[test]
+    fn rejects_images_without_a_board() {
+        let intrinsics = intrinsics();
+        let mut image = render(&intrinsics, &Rotation3::identity(), &Vector3::new(0.0, 0.0, 0.5));
+        image.data.fill(128.0);
+
+        assert!(detect_checkerboard(&image, PATTERN).is_none());
+    }
+
+    #[test]
+    fn recovers_intrinsics_from_rendered_views() {
+        let truth = intrinsics();
+
+        let views: Vec<_> = board_poses().iter()
+            .map(|(rotation, translation)| detect_checkerboard(&render(&truth, rotation, translation), PATTERN).unwrap())
+            .collect();
+
+        let calibration = calibrate_camera(&views, &config(), truth.width, truth.height).unwrap();
+        let estimate = calibration.intrinsics;
+
+        assert!(calibration.rms_error < 0.1, "rms error {}", calibration.rms_error);
+        assert!(calibration.view_errors.iter().all(|error| *error < 0.2));
+        assert!((estimate.fx - truth.fx).abs() < 0.005 * truth.fx, "fx {}", estimate.fx);
+        assert!((estimate.fy - truth.fy).abs() < 0.005 * truth.fy, "fy {}", estimate.fy);
+        assert!((estimate.cx - truth.cx).abs() < 2.0, "cx {}", estimate.cx);
+        assert!((estimate.cy - truth.cy).abs() < 2.0, "cy {}", estimate.cy);
+
+        let Distortion::BrownConrady { k1, .. } = estimate.distortion else {
+            panic!("calibration always estimates Brown-Conrady distortion");
+        };
+        assert!((k1 + 0.2).abs() < 0.02, "k1 {k1}");
+    }
+
+    #[test]
+    fn too_few_views_are_not_calibrated() {
+        let views = vec![board_points(&config()).iter().map(|p| Point2::new(p.x, p.y)).collect(); 2];
+
+        assert!(calibrate_camera(&views, &config(), 640, 480).is_none());
+    }
+
+    #[test]
+    fn matches_labels_of_a_rotated_detection() {
+        let reference: Vec<_> = board_points(&config()).iter()
+            .map(|p| Point2::new(100.0 + p.x * 1000.0, 50.0 + p.y * 1000.0))
+            .collect();
+
+        // The other camera sees the board shifted, and the detector started from the
+        // opposite corner
+        let shifted: Vec<_> = reference.iter().rev().map(|p| p + nalgebra::Vector2::new(-40.0, 3.0)).collect();
+        let matched = match_labels(&reference, &shifted, PATTERN);
+
+        for (a, b) in reference.iter().zip(&matched) {
+            assert!((b - a - nalgebra::Vector2::new(-40.0, 3.0)).norm() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,781 @@
+use std::{fmt, path::Path};
+
+use nalgebra::{Matrix3, Point2, Point3, Rotation3, Vector3};
+use serde::{Deserialize, Serialize};
+
+/// Lens distortion, applied to normalized image coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Distortion {
+    None,
+    /// Radial and tangential model used by OpenCV (`plumb_bob` / `radtan`).
+    BrownConrady { k1: f64, k2: f64, p1: f64, p2: f64, k3: f64 },
+    /// Equidistant fisheye model (OpenCV `cv::fisheye`).
+    KannalaBrandt { k1: f64, k2: f64, k3: f64, k4: f64 }
+}
+
+/// Pinhole intrinsics of a camera at a given resolution.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraIntrinsics {
+    pub width: u32,
+    pub height: u32,
+    pub fx: f64,
+    pub fy: f64,
+    pub cx: f64,
+    pub cy: f64,
+    pub distortion: Distortion
+}
+
+const UNDISTORT_ITERATIONS: usize = 20;
+
+impl CameraIntrinsics {
+    /// Uncalibrated guess: principal point at the center and roughly a 53 degree horizontal
+    /// field of view.
+    pub fn guess(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            fx: width as f64,
+            fy: width as f64,
+            cx: width as f64 * 0.5,
+            cy: height as f64 * 0.5,
+            distortion: Distortion::None
+        }
+    }
+
+    pub fn camera_matrix(&self) -> Matrix3<f64> {
+        Matrix3::new(
+            self.fx, 0.0, self.cx,
+            0.0, self.fy, self.cy,
+            0.0, 0.0, 1.0
+        )
+    }
+
+    /// Intrinsics for the same camera at a different resolution with the same aspect ratio.
+    pub fn scaled(&self, width: u32, height: u32) -> Self {
+        let sx = width as f64 / self.width as f64;
+        let sy = height as f64 / self.height as f64;
+
+        Self {
+            width,
+            height,
+            fx: self.fx * sx,
+            fy: self.fy * sy,
+            cx: self.cx * sx,
+            cy: self.cy * sy,
+            distortion: self.distortion
+        }
+    }
+
+    /// Applies the lens distortion to a point on the normalized image plane.
+    pub fn distort(&self, point: &Point2<f64>) -> Point2<f64> {
+        let (x, y) = (point.x, point.y);
+
+        match self.distortion {
+            Distortion::None => *point,
+            Distortion::BrownConrady { k1, k2, p1, p2, k3 } => {
+                let r2 = x * x + y * y;
+                let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+
+                Point2::new(
+                    x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x),
+                    y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y
+                )
+            },
+            Distortion::KannalaBrandt { k1, k2, k3, k4 } => {
+                let r = (x * x + y * y).sqrt();
+
+                if r < 1e-12 {
+                    return *point;
+                }
+
+                let theta = r.atan();
+                let theta2 = theta * theta;
+                let theta_d = theta * (1.0 + theta2 * (k1 + theta2 * (k2 + theta2 * (k3 + theta2 * k4))));
+
+                *point * (theta_d / r)
+            }
+        }
+    }
+
+    /// Removes the lens distortion from a point on the normalized image plane.
+    pub fn undistort(&self, point: &Point2<f64>) -> Point2<f64> {
+        match self.distortion {
+            Distortion::None => *point,
+            Distortion::BrownConrady { k1, k2, p1, p2, k3 } => {
+                // Fixed point iteration, as in OpenCV's undistortPoints
+                let (x0, y0) = (point.x, point.y);
+                let (mut x, mut y) = (x0, y0);
+
+                for _ in 0..UNDISTORT_ITERATIONS {
+                    let r2 = x * x + y * y;
+                    let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+                    let dx = 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x);
+                    let dy = p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y;
+
+                    x = (x0 - dx) / radial;
+                    y = (y0 - dy) / radial;
+                }
+
+                Point2::new(x, y)
+            },
+            Distortion::KannalaBrandt { k1, k2, k3, k4 } => {
+                let theta_d = point.coords.norm();
+
+                if theta_d < 1e-12 {
+                    return *point;
+                }
+
+                // Newton iterations on theta_d = theta (1 + k1 theta^2 + ...)
+                let mut theta = theta_d;
+                for _ in 0..UNDISTORT_ITERATIONS {
+                    let theta2 = theta * theta;
+                    let f = theta * (1.0 + theta2 * (k1 + theta2 * (k2 + theta2 * (k3 + theta2 * k4)))) - theta_d;
+                    let df = 1.0 + theta2 * (3.0 * k1 + theta2 * (5.0 * k2 + theta2 * (7.0 * k3 + theta2 * 9.0 * k4)));
+
+                    let step = f / df;
+                    theta -= step;
+
+                    if step.abs() < 1e-12 {
+                        break;
+                    }
+                }
+
+                *point * (theta.tan() / theta_d)
+            }
+        }
+    }
+
+    /// Maps a pixel to undistorted normalized image coordinates.
+    pub fn unproject(&self, pixel: &Point2<f64>) -> Point2<f64> {
+        self.undistort(&Point2::new(
+            (pixel.x - self.cx) / self.fx,
+            (pixel.y - self.cy) / self.fy
+        ))
+    }
+
+    /// Maps undistorted normalized image coordinates to a pixel.
+    pub fn pixel(&self, normalized: &Point2<f64>) -> Point2<f64> {
+        let distorted = self.distort(normalized);
+
+        Point2::new(
+            distorted.x * self.fx + self.cx,
+            distorted.y * self.fy + self.cy
+        )
+    }
+
+    /// Projects a point in camera coordinates, or `None` if it is behind the camera.
+    pub fn project(&self, point: &Point3<f64>) -> Option<Point2<f64>> {
+        (point.z > 0.0).then(|| self.pixel(&Point2::new(point.x / point.z, point.y / point.z)))
+    }
+
+    /// Removes the lens distortion from a pixel, keeping it in pixel units.
+    pub fn undistort_pixel(&self, pixel: &Point2<f64>) -> Point2<f64> {
+        let normalized = self.unproject(pixel);
+
+        Point2::new(
+            normalized.x * self.fx + self.cx,
+            normalized.y * self.fy + self.cy
+        )
+    }
+}
+
+/// Two calibrated cameras with a known relative pose.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StereoRig {
+    pub left: CameraIntrinsics,
+    pub right: CameraIntrinsics,
+    /// Rotation and translation mapping left camera coordinates into right camera coordinates.
+    pub rotation: Rotation3<f64>,
+    pub translation: Vector3<f64>
+}
+
+/// Rotation and translation mapping coordinates of one camera into another's.
+pub type Extrinsics = (Rotation3<f64>, Vector3<f64>);
+
+impl StereoRig {
+    /// Distance between the camera centers, in the unit of the calibration board.
+    pub fn baseline(&self) -> f64 {
+        self.translation.norm()
+    }
+}
+
+#[derive(Debug)]
+pub enum CalibrationError {
+    Io(std::io::Error),
+    Parse(String),
+    Invalid(String),
+    NotFound { camera_name: String, width: u32, height: u32 },
+    NoExtrinsics { camera_name: String, reference_camera: String }
+}
+
+impl fmt::Display for CalibrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalibrationError::Io(why) => write!(f, "could not access calibration file: {why}"),
+            CalibrationError::Parse(why) => write!(f, "could not parse calibration file: {why}"),
+            CalibrationError::Invalid(why) => write!(f, "invalid calibration: {why}"),
+            CalibrationError::NotFound { camera_name, width, height } => {
+                write!(f, "no calibration for \"{camera_name}\" at {width}x{height}")
+            },
+            CalibrationError::NoExtrinsics { camera_name, reference_camera } => {
+                write!(f, "no pose of \"{camera_name}\" relative to \"{reference_camera}\"")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CalibrationError {}
+
+/// An OpenCV `FileStorage` matrix (`!!opencv-matrix`).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OpenCvMatrix {
+    pub rows: usize,
+    pub cols: usize,
+    #[serde(default)]
+    pub dt: Option<String>,
+    pub data: Vec<f64>
+}
+
+/// One camera of a calibration file, in the layout written by OpenCV's calibration samples
+/// and ROS `camera_calibration`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CalibrationEntry {
+    pub camera_name: String,
+    pub image_width: u32,
+    pub image_height: u32,
+    pub camera_matrix: OpenCvMatrix,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distortion_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distortion_coefficients: Option<OpenCvMatrix>,
+    /// Camera the `rotation` and `translation` of a stereo pair are relative to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference_camera: Option<String>,
+    /// Rotation mapping reference camera coordinates into this camera's, like OpenCV's `R`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<OpenCvMatrix>,
+    /// Translation mapping reference camera coordinates into this camera's, like OpenCV's `T`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation: Option<OpenCvMatrix>
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum CalibrationFile {
+    Many { cameras: Vec<CalibrationEntry> },
+    One(Box<CalibrationEntry>)
+}
+
+impl OpenCvMatrix {
+    fn new(rows: usize, cols: usize, data: Vec<f64>) -> Self {
+        Self { rows, cols, dt: Some("d".to_string()), data }
+    }
+
+    fn write_yaml(&self, out: &mut String, indent: &str) {
+        let data: Vec<String> = self.data.iter().map(|v| format!("{v:?}")).collect();
+
+        out.push_str(" !!opencv-matrix\n");
+        out.push_str(&format!("{indent}   rows: {}\n", self.rows));
+        out.push_str(&format!("{indent}   cols: {}\n", self.cols));
+        out.push_str(&format!("{indent}   dt: {}\n", self.dt.as_deref().unwrap_or("d")));
+        out.push_str(&format!("{indent}   data: [ {} ]\n", data.join(", ")));
+    }
+}
+
+impl CalibrationEntry {
+    pub fn new(camera_name: &str, intrinsics: &CameraIntrinsics) -> Self {
+        let (model, coefficients) = match intrinsics.distortion {
+            Distortion::None => (None, None),
+            Distortion::BrownConrady { k1, k2, p1, p2, k3 } => {
+                (Some("plumb_bob"), Some(vec![k1, k2, p1, p2, k3]))
+            },
+            Distortion::KannalaBrandt { k1, k2, k3, k4 } => {
+                (Some("equidistant"), Some(vec![k1, k2, k3, k4]))
+            }
+        };
+
+        Self {
+            camera_name: camera_name.to_string(),
+            image_width: intrinsics.width,
+            image_height: intrinsics.height,
+            camera_matrix: OpenCvMatrix::new(3, 3, intrinsics.camera_matrix().transpose().as_slice().to_vec()),
+            distortion_model: model.map(str::to_string),
+            distortion_coefficients: coefficients.map(|c| OpenCvMatrix::new(1, c.len(), c)),
+            reference_camera: None,
+            rotation: None,
+            translation: None
+        }
+    }
+
+    /// Entry of a camera whose pose relative to `reference_camera` is known.
+    pub fn with_extrinsics(
+        camera_name: &str,
+        intrinsics: &CameraIntrinsics,
+        reference_camera: &str,
+        rotation: &Rotation3<f64>,
+        translation: &Vector3<f64>
+    ) -> Self {
+        Self {
+            reference_camera: Some(reference_camera.to_string()),
+            rotation: Some(OpenCvMatrix::new(3, 3, rotation.matrix().transpose().as_slice().to_vec())),
+            translation: Some(OpenCvMatrix::new(3, 1, translation.as_slice().to_vec())),
+            ..Self::new(camera_name, intrinsics)
+        }
+    }
+
+    fn write_yaml(&self, out: &mut String, indent: &str) {
+        out.push_str(&format!("{indent}camera_name: {:?}\n", self.camera_name));
+        out.push_str(&format!("{indent}image_width: {}\n", self.image_width));
+        out.push_str(&format!("{indent}image_height: {}\n", self.image_height));
+        out.push_str(&format!("{indent}camera_matrix:"));
+        self.camera_matrix.write_yaml(out, indent);
+
+        if let Some(model) = &self.distortion_model {
+            out.push_str(&format!("{indent}distortion_model: {model}\n"));
+        }
+
+        if let Some(coefficients) = &self.distortion_coefficients {
+            out.push_str(&format!("{indent}distortion_coefficients:"));
+            coefficients.write_yaml(out, indent);
+        }
+
+        if let Some(reference_camera) = &self.reference_camera {
+            out.push_str(&format!("{indent}reference_camera: {reference_camera:?}\n"));
+        }
+
+        if let Some(rotation) = &self.rotation {
+            out.push_str(&format!("{indent}rotation:"));
+            rotation.write_yaml(out, indent);
+        }
+
+        if let Some(translation) = &self.translation {
+            out.push_str(&format!("{indent}translation:"));
+            translation.write_yaml(out, indent);
+        }
+    }
+
+    pub fn intrinsics(&self) -> Result<CameraIntrinsics, CalibrationError> {
+        let k = &self.camera_matrix;
+
+        if k.rows != 3 || k.cols != 3 || k.data.len() != 9 {
+            return Err(CalibrationError::Invalid("camera_matrix must be 3x3".to_string()));
+        }
+
+        let coefficients = self.distortion_coefficients.as_ref()
+            .map(|d| d.data.as_slice())
+            .unwrap_or_default();
+        let coefficient = |i: usize| coefficients.get(i).copied().unwrap_or(0.0);
+
+        let model = self.distortion_model.as_deref().map(str::to_ascii_lowercase);
+
+        let distortion = match model.as_deref() {
+            // A fisheye lens without distortion coefficients still follows the equidistant
+            // projection, only the radial polynomial vanishes
+            Some("equidistant" | "fisheye" | "kannala_brandt") => Distortion::KannalaBrandt {
+                k1: coefficient(0),
+                k2: coefficient(1),
+                k3: coefficient(2),
+                k4: coefficient(3)
+            },
+            None | Some("plumb_bob" | "radtan" | "brown_conrady") if coefficients.len() <= 5 => {
+                if coefficients.iter().all(|c| *c == 0.0) {
+                    Distortion::None
+                } else {
+                    Distortion::BrownConrady {
+                        k1: coefficient(0),
+                        k2: coefficient(1),
+                        p1: coefficient(2),
+                        p2: coefficient(3),
+                        k3: coefficient(4)
+                    }
+                }
+            },
+            _ => {
+                return Err(CalibrationError::Invalid(format!(
+                    "unsupported distortion model {:?} with {} coefficients",
+                    self.distortion_model,
+                    coefficients.len()
+                )))
+            }
+        };
+
+        Ok(CameraIntrinsics {
+            width: self.image_width,
+            height: self.image_height,
+            fx: k.data[0],
+            fy: k.data[4],
+            cx: k.data[2],
+            cy: k.data[5],
+            distortion
+        })
+    }
+
+    /// Rotation and translation mapping `reference_camera` coordinates into this camera's, if
+    /// the entry has them.
+    pub fn extrinsics(&self) -> Result<Option<Extrinsics>, CalibrationError> {
+        let (Some(r), Some(t)) = (&self.rotation, &self.translation) else {
+            return Ok(None);
+        };
+
+        if r.rows != 3 || r.cols != 3 || r.data.len() != 9 {
+            return Err(CalibrationError::Invalid("rotation must be 3x3".to_string()));
+        }
+
+        if t.rows * t.cols != 3 || t.data.len() != 3 {
+            return Err(CalibrationError::Invalid("translation must have 3 elements".to_string()));
+        }
+
+        let rotation = Rotation3::from_matrix_eps(&Matrix3::from_row_slice(&r.data), 1e-12, 100, Rotation3::identity());
+
+        Ok(Some((rotation, Vector3::from_column_slice(&t.data))))
+    }
+}
+
+/// Parses a calibration file, either a single camera or a `cameras:` list.
+///
+/// YAML files may use OpenCV's `%YAML:1.0` header and `!!opencv-matrix` tags, neither of
+/// which are standard YAML, so both are stripped before parsing.
+pub fn parse_calibration(source: &str, json: bool) -> Result<Vec<CalibrationEntry>, CalibrationError> {
+    let file: CalibrationFile = if json {
+        serde_json::from_str(source).map_err(|why| CalibrationError::Parse(why.to_string()))?
+    } else {
+        let cleaned: String = source.lines()
+            .filter(|line| !line.trim_start().starts_with("%YAML"))
+            .map(|line| line.replace("!!opencv-matrix", ""))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        serde_yaml::from_str(&cleaned).map_err(|why| CalibrationError::Parse(why.to_string()))?
+    };
+
+    Ok(match file {
+        CalibrationFile::Many { cameras } => cameras,
+        CalibrationFile::One(entry) => vec![*entry]
+    })
+}
+
+/// Formats calibration entries the way OpenCV's `FileStorage` writes them, or as JSON.
+pub fn format_calibration(entries: &[CalibrationEntry], json: bool) -> String {
+    if json {
+        let file = match entries {
+            [entry] => CalibrationFile::One(Box::new(entry.clone())),
+            _ => CalibrationFile::Many { cameras: entries.to_vec() }
+        };
+
+        return serde_json::to_string_pretty(&file).unwrap();
+    }
+
+    let mut out = String::from("%YAML:1.0\n---\n");
+
+    match entries {
+        [entry] => entry.write_yaml(&mut out, ""),
+        _ => {
+            out.push_str("cameras:\n");
+            for entry in entries {
+                out.push_str("   -\n");
+                entry.write_yaml(&mut out, "      ");
+            }
+        }
+    }
+
+    out
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+fn read_calibration(path: &Path) -> Result<Vec<CalibrationEntry>, CalibrationError> {
+    let source = std::fs::read_to_string(path).map_err(CalibrationError::Io)?;
+    parse_calibration(&source, is_json(path))
+}
+
+/// Adds `new_entries` to a calibration file, replacing any entry for the same camera and
+/// resolution and keeping the others.
+fn update_calibration(path: &Path, new_entries: Vec<CalibrationEntry>) -> Result<(), CalibrationError> {
+    let mut entries = match read_calibration(path) {
+        Ok(entries) => entries,
+        Err(CalibrationError::Io(why)) if why.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(why) => return Err(why)
+    };
+
+    entries.retain(|entry| {
+        !new_entries.iter().any(|new| {
+            entry.camera_name == new.camera_name &&
+            entry.image_width == new.image_width &&
+            entry.image_height == new.image_height
+        })
+    });
+    entries.extend(new_entries);
+
+    std::fs::write(path, format_calibration(&entries, is_json(path))).map_err(CalibrationError::Io)
+}
+
+/// Stores the intrinsics of `camera_name` in a YAML or JSON calibration file, replacing any
+/// entry for the same camera and resolution and keeping the others.
+pub fn save_calibration(
+    path: impl AsRef<Path>,
+    camera_name: &str,
+    intrinsics: &CameraIntrinsics
+) -> Result<(), CalibrationError> {
+    update_calibration(path.as_ref(), vec![CalibrationEntry::new(camera_name, intrinsics)])
+}
+
+/// Stores both cameras of a stereo pair, the right one with its pose relative to the left
+/// one.
+pub fn save_stereo_calibration(
+    path: impl AsRef<Path>,
+    left_name: &str,
+    right_name: &str,
+    rig: &StereoRig
+) -> Result<(), CalibrationError> {
+    update_calibration(path.as_ref(), vec![
+        CalibrationEntry::new(left_name, &rig.left),
+        CalibrationEntry::with_extrinsics(right_name, &rig.right, left_name, &rig.rotation, &rig.translation)
+    ])
+}
+
+/// Finds the entry of `camera_name` at `width`x`height`, or else one with the same aspect
+/// ratio, which is returned with the intrinsics rescaled.
+fn find_calibration<'a>(
+    entries: &'a [CalibrationEntry],
+    camera_name: &str,
+    width: u32,
+    height: u32
+) -> Result<(&'a CalibrationEntry, CameraIntrinsics), CalibrationError> {
+    let for_camera = || entries.iter().filter(|entry| entry.camera_name == camera_name);
+
+    if let Some(entry) = for_camera().find(|entry| entry.image_width == width && entry.image_height == height) {
+        return Ok((entry, entry.intrinsics()?));
+    }
+
+    let same_aspect = for_camera().find(|entry| {
+        entry.image_width as u64 * height as u64 == entry.image_height as u64 * width as u64
+    });
+
+    match same_aspect {
+        Some(entry) => Ok((entry, entry.intrinsics()?.scaled(width, height))),
+        None => Err(CalibrationError::NotFound {
+            camera_name: camera_name.to_string(),
+            width,
+            height
+        })
+    }
+}
+
+/// Loads the intrinsics of the camera named `camera_name` (as reported by
+/// `CameraInfo::human_name`) from a YAML or JSON calibration file.
+///
+/// An entry at a different resolution with the same aspect ratio is rescaled if there is no
+/// exact match.
+pub fn load_calibration(
+    path: impl AsRef<Path>,
+    camera_name: &str,
+    width: u32,
+    height: u32
+) -> Result<CameraIntrinsics, CalibrationError> {
+    let entries = read_calibration(path.as_ref())?;
+
+    find_calibration(&entries, camera_name, width, height).map(|(_, intrinsics)| intrinsics)
+}
+
+/// Loads a stereo pair stored by `save_stereo_calibration`, both cameras at `width`x`height`.
+pub fn load_stereo_calibration(
+    path: impl AsRef<Path>,
+    left_name: &str,
+    right_name: &str,
+    width: u32,
+    height: u32
+) -> Result<StereoRig, CalibrationError> {
+    let entries = read_calibration(path.as_ref())?;
+
+    let (_, left) = find_calibration(&entries, left_name, width, height)?;
+    let (right_entry, right) = find_calibration(&entries, right_name, width, height)?;
+
+    let no_extrinsics = || CalibrationError::NoExtrinsics {
+        camera_name: right_name.to_string(),
+        reference_camera: left_name.to_string()
+    };
+
+    if right_entry.reference_camera.as_deref() != Some(left_name) {
+        return Err(no_extrinsics());
+    }
+
+    let (rotation, translation) = right_entry.extrinsics()?.ok_or_else(no_extrinsics)?;
+
+    Ok(StereoRig { left, right, rotation, translation })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brown_conrady() -> CameraIntrinsics {
+        CameraIntrinsics {
+            width: 640,
+            height: 480,
+            fx: 612.5,
+            fy: 610.25,
+            cx: 318.0,
+            cy: 243.5,
+            distortion: Distortion::BrownConrady { k1: -0.28, k2: 0.09, p1: 0.001, p2: -0.0005, k3: -0.01 }
+        }
+    }
+
+    fn kannala_brandt() -> CameraIntrinsics {
+        CameraIntrinsics {
+            width: 1280,
+            height: 800,
+            fx: 410.0,
+            fy: 409.5,
+            cx: 640.5,
+            cy: 399.0,
+            distortion: Distortion::KannalaBrandt { k1: 0.02, k2: -0.005, k3: 0.001, k4: -0.0002 }
+        }
+    }
+
+    fn rig() -> StereoRig {
+        StereoRig {
+            left: brown_conrady(),
+            right: brown_conrady(),
+            rotation: Rotation3::from_euler_angles(0.01, -0.02, 0.005),
+            translation: Vector3::new(-0.12, 0.001, 0.002)
+        }
+    }
+
+    fn entries() -> Vec<CalibrationEntry> {
+        let rig = rig();
+
+        vec![
+            CalibrationEntry::new("left", &rig.left),
+            CalibrationEntry::with_extrinsics("right", &rig.right, "left", &rig.rotation, &rig.translation),
+            CalibrationEntry::new("fisheye", &kannala_brandt()),
+            CalibrationEntry::new("pinhole", &CameraIntrinsics::guess(320, 240))
+        ]
+    }
+
+    fn assert_round_trip(json: bool) {
+        let entries = entries();
+        let parsed = parse_calibration(&format_calibration(&entries, json), json).unwrap();
+
+        assert_eq!(parsed.len(), entries.len());
+        for (parsed, entry) in parsed.iter().zip(&entries) {
+            assert_eq!(parsed.camera_name, entry.camera_name);
+            assert_eq!(parsed.intrinsics().unwrap(), entry.intrinsics().unwrap());
+            assert_eq!(parsed.reference_camera, entry.reference_camera);
+        }
+
+        let (rotation, translation) = parsed[1].extrinsics().unwrap().unwrap();
+        assert!((rotation.matrix() - rig().rotation.matrix()).norm() < 1e-9);
+        assert_eq!(translation, rig().translation);
+        assert!(parsed[0].extrinsics().unwrap().is_none());
+
+        // A single camera is written without the list
+        let single = parse_calibration(&format_calibration(&entries[2..3], json), json).unwrap();
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].intrinsics().unwrap(), kannala_brandt());
+    }
+
+    #[test]
+    fn yaml_round_trip() {
+        assert_round_trip(false);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        assert_round_trip(true);
+    }
+
+    #[test]
+    fn parses_opencv_file_storage() {
+        let source = "%YAML:1.0
+---
+camera_name: \"Integrated Camera\"
+image_width: 640
+image_height: 480
+camera_matrix: !!opencv-matrix
+   rows: 3
+   cols: 3
+   dt: d
+   data: [ 6.1250000000000000e+02, 0., 3.1800000000000000e+02, 0.,
+       6.1025000000000000e+02, 2.4350000000000000e+02, 0., 0., 1. ]
+distortion_model: plumb_bob
+distortion_coefficients: !!opencv-matrix
+   rows: 1
+   cols: 5
+   dt: d
+   data: [ -2.8e-01, 9.0e-02, 1.0e-03, -5.0e-04, -1.0e-02 ]
+";
+
+        let entries = parse_calibration(source, false).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].camera_name, "Integrated Camera");
+        assert_eq!(entries[0].intrinsics().unwrap(), brown_conrady());
+    }
+
+    #[test]
+    fn zero_coefficients_keep_the_model() {
+        let mut fisheye = CalibrationEntry::new("fisheye", &kannala_brandt());
+        fisheye.distortion_coefficients = Some(OpenCvMatrix::new(1, 4, vec![0.0; 4]));
+
+        assert_eq!(
+            fisheye.intrinsics().unwrap().distortion,
+            Distortion::KannalaBrandt { k1: 0.0, k2: 0.0, k3: 0.0, k4: 0.0 }
+        );
+
+        let mut pinhole = CalibrationEntry::new("pinhole", &brown_conrady());
+        pinhole.distortion_coefficients = Some(OpenCvMatrix::new(1, 5, vec![0.0; 5]));
+
+        assert_eq!(pinhole.intrinsics().unwrap().distortion, Distortion::None);
+    }
+
+    #[test]
+    fn rejects_unknown_models() {
+        let mut entry = CalibrationEntry::new("camera", &brown_conrady());
+        entry.distortion_model = Some("rational_polynomial".to_string());
+
+        assert!(matches!(entry.intrinsics(), Err(CalibrationError::Invalid(_))));
+    }
+
+    /// Normalized points out to `max_angle` from the optical axis.
+    fn field_of_view(max_angle: f64) -> Vec<Point2<f64>> {
+        (0..=20)
+            .flat_map(|i| (0..=20).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let angle = max_angle * (i as f64 / 20.0);
+                let direction = std::f64::consts::TAU * j as f64 / 20.0;
+                Point2::new(direction.cos(), direction.sin()) * angle.tan()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn brown_conrady_undistorts_its_distortion() {
+        let intrinsics = brown_conrady();
+
+        for point in field_of_view(35f64.to_radians()) {
+            let distorted = intrinsics.distort(&point);
+            assert!((intrinsics.undistort(&distorted) - point).norm() < 1e-9);
+
+            let pixel = intrinsics.pixel(&point);
+            assert!((intrinsics.unproject(&pixel) - point).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn kannala_brandt_undistorts_its_distortion() {
+        let intrinsics = kannala_brandt();
+
+        for point in field_of_view(80f64.to_radians()) {
+            let distorted = intrinsics.distort(&point);
+            assert!((intrinsics.undistort(&distorted) - point).norm() < 1e-9 * (1.0 + point.coords.norm()));
+        }
+
+        // Without coefficients the image radius is proportional to the angle, not its tangent
+        let equidistant = CameraIntrinsics { distortion: Distortion::KannalaBrandt { k1: 0.0, k2: 0.0, k3: 0.0, k4: 0.0 }, ..intrinsics };
+        let angle = 60f64.to_radians();
+        let distorted = equidistant.distort(&Point2::new(angle.tan(), 0.0));
+        assert!((distorted.x - angle).abs() < 1e-12);
+    }
+}
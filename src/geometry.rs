@@ -0,0 +1,638 @@
+use nalgebra::{
+    DMatrix, DVector, Isometry3, Matrix2x3, Matrix3, Matrix3x4, Matrix4, Point2, Point3, Rotation3, SMatrix,
+    Similarity3, Translation3, UnitQuaternion, Vector2, Vector3
+};
+use rand::{seq::index::sample, Rng};
+
+/// A pair of corresponding points in normalized image coordinates (z = 1 plane).
+pub type Correspondence = (Point2<f64>, Point2<f64>);
+
+/// Eigenvector of the smallest eigenvalue of a symmetric `AᵀA`, i.e. the least squares null
+/// vector of `A`.
+pub fn smallest_eigenvector(ata: DMatrix<f64>) -> DVector<f64> {
+    let eigen = ata.symmetric_eigen();
+    let (index, _) = eigen.eigenvalues.argmin();
+    eigen.eigenvectors.column(index).into_owned()
+}
+
+/// Eight-point estimate of the essential matrix `E` such that `x2ᵀ E x1 = 0`.
+///
+/// Works on any number of correspondences greater than or equal to 8, and returns `None` for
+/// degenerate input.
+pub fn essential_eight_point(correspondences: &[Correspondence]) -> Option<Matrix3<f64>> {
+    if correspondences.len() < 8 {
+        return None;
+    }
+
+    let mut ata = SMatrix::<f64, 9, 9>::zeros();
+
+    for (a, b) in correspondences {
+        let row = SMatrix::<f64, 1, 9>::from_row_slice(&[
+            b.x * a.x, b.x * a.y, b.x,
+            b.y * a.x, b.y * a.y, b.y,
+            a.x, a.y, 1.0
+        ]);
+
+        ata += row.transpose() * row;
+    }
+
+    let e = smallest_eigenvector(DMatrix::from_column_slice(9, 9, ata.as_slice()));
+    let e = Matrix3::from_row_slice(e.as_slice());
+
+    // Project onto the essential manifold: two equal singular values and one zero
+    let svd = e.try_svd(true, true, f64::EPSILON, 0)?;
+    let (u, v_t) = (svd.u?, svd.v_t?);
+
+    let mut singular = svd.singular_values;
+    let order = {
+        let mut order = [0, 1, 2];
+        order.sort_by(|i, j| singular[*j].total_cmp(&singular[*i]));
+        order
+    };
+    let mean = (singular[order[0]] + singular[order[1]]) * 0.5;
+    singular[order[0]] = mean;
+    singular[order[1]] = mean;
+    singular[order[2]] = 0.0;
+
+    let e = u * Matrix3::from_diagonal(&singular) * v_t;
+
+    if e.iter().any(|v| !v.is_finite()) {
+        return None;
+    }
+
+    Some(e / e.norm())
+}
+
+/// First order approximation of the geometric error of a correspondence under `e`.
+pub fn sampson_error(e: &Matrix3<f64>, (a, b): &Correspondence) -> f64 {
+    let x1 = Vector3::new(a.x, a.y, 1.0);
+    let x2 = Vector3::new(b.x, b.y, 1.0);
+
+    let ex1 = e * x1;
+    let etx2 = e.transpose() * x2;
+    let numerator = x2.dot(&ex1);
+    let denominator = ex1.x * ex1.x + ex1.y * ex1.y + etx2.x * etx2.x + etx2.y * etx2.y;
+
+    if denominator <= f64::EPSILON {
+        return f64::INFINITY;
+    }
+
+    numerator * numerator / denominator
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RansacConfig {
+    pub iterations: usize,
+    /// Inlier threshold on the Sampson error, in squared normalized image units.
+    pub threshold: f64,
+    pub confidence: f64
+}
+
+impl Default for RansacConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 200,
+            // Roughly one pixel at a focal length of 1000 pixels
+            threshold: 1e-6,
+            confidence: 0.999
+        }
+    }
+}
+
+/// Adaptive RANSAC over a minimal `solve`r of `SAMPLE_SIZE` data, which keeps the hypothesis
+/// with the most inliers and stops once an all-inlier sample has been drawn with `confidence`.
+/// The best hypothesis is then `refine`d on all of its inliers.
+///
+/// Returns the refined model and its inlier mask.
+fn ransac<const SAMPLE_SIZE: usize, D: Copy, M>(
+    data: &[D],
+    (mut iterations, confidence): (usize, f64),
+    rng: &mut impl Rng,
+    solve: impl Fn(&[D]) -> Option<M>,
+    is_inlier: impl Fn(&M, &D) -> bool,
+    refine: impl FnOnce(&M, &[D]) -> M
+) -> Option<(M, Vec<bool>)> {
+    if data.len() < SAMPLE_SIZE {
+        return None;
+    }
+
+    let mut best: Option<(M, usize)> = None;
+    let mut iteration = 0;
+
+    while iteration < iterations {
+        iteration += 1;
+
+        let sample: Vec<D> = sample(rng, data.len(), SAMPLE_SIZE)
+            .into_iter()
+            .map(|i| data[i])
+            .collect();
+
+        let Some(model) = solve(&sample) else { continue; };
+
+        let inliers = data.iter()
+            .filter(|datum| is_inlier(&model, datum))
+            .count();
+
+        let improved = match best {
+            Some((_, count)) => inliers > count,
+            None => true
+        };
+
+        if improved {
+            best = Some((model, inliers));
+
+            // Adaptive termination
+            let ratio = inliers as f64 / data.len() as f64;
+            let p_fail = 1.0 - ratio.powi(SAMPLE_SIZE as i32);
+            if p_fail <= f64::EPSILON {
+                break;
+            }
+            let needed = ((1.0 - confidence).ln() / p_fail.ln()).ceil();
+            if needed.is_finite() && needed >= 0.0 {
+                iterations = iterations.min(needed as usize);
+            }
+        }
+    }
+
+    let (model, _) = best?;
+    let inlier_set: Vec<D> = data.iter()
+        .copied()
+        .filter(|datum| is_inlier(&model, datum))
+        .collect();
+
+    let model = refine(&model, &inlier_set);
+    let mask = data.iter()
+        .map(|datum| is_inlier(&model, datum))
+        .collect();
+
+    Some((model, mask))
+}
+
+/// RANSAC over the eight-point solver, refit on all inliers of the best hypothesis.
+///
+/// Returns the essential matrix and the inlier mask.
+pub fn ransac_essential(
+    correspondences: &[Correspondence],
+    config: &RansacConfig,
+    rng: &mut impl Rng
+) -> Option<(Matrix3<f64>, Vec<bool>)> {
+    ransac::<8, _, _>(
+        correspondences,
+        (config.iterations, config.confidence),
+        rng,
+        essential_eight_point,
+        |e, c| sampson_error(e, c) < config.threshold,
+        |e, inliers| essential_eight_point(inliers).unwrap_or(*e)
+    )
+}
+
+/// The four `(R, t)` factorizations of an essential matrix, with `‖t‖ = 1`.
+pub fn decompose_essential(e: &Matrix3<f64>) -> Option<[(Rotation3<f64>, Vector3<f64>); 4]> {
+    let svd = e.try_svd(true, true, f64::EPSILON, 0)?;
+    let (mut u, mut v_t) = (svd.u?, svd.v_t?);
+
+    // Order singular values so the null space is the last column
+    let (null_index, _) = svd.singular_values.argmin();
+    if null_index != 2 {
+        u.swap_columns(null_index, 2);
+        v_t.swap_rows(null_index, 2);
+    }
+
+    if u.determinant() < 0.0 {
+        u = -u;
+    }
+    if v_t.determinant() < 0.0 {
+        v_t = -v_t;
+    }
+
+    let w = Matrix3::new(
+        0.0, -1.0, 0.0,
+        1.0, 0.0, 0.0,
+        0.0, 0.0, 1.0
+    );
+
+    let r1 = Rotation3::from_matrix_unchecked(u * w * v_t);
+    let r2 = Rotation3::from_matrix_unchecked(u * w.transpose() * v_t);
+    let t = u.column(2).normalize();
+
+    Some([(r1, t), (r1, -t), (r2, t), (r2, -t)])
+}
+
+pub fn isometry(rotation: Rotation3<f64>, translation: Vector3<f64>) -> Isometry3<f64> {
+    Isometry3::from_parts(
+        Translation3::from(translation),
+        UnitQuaternion::from_rotation_matrix(&rotation)
+    )
+}
+
+/// Linear (DLT) triangulation of a point observed by two cameras.
+///
+/// The poses map world coordinates into each camera frame and the observations are in
+/// normalized image coordinates.
+pub fn triangulate(
+    first: &Isometry3<f64>,
+    second: &Isometry3<f64>,
+    a: &Point2<f64>,
+    b: &Point2<f64>
+) -> Option<Point3<f64>> {
+    let p1 = first.to_homogeneous();
+    let p2 = second.to_homogeneous();
+
+    let rows = [
+        p1.row(2) * a.x - p1.row(0),
+        p1.row(2) * a.y - p1.row(1),
+        p2.row(2) * b.x - p2.row(0),
+        p2.row(2) * b.y - p2.row(1)
+    ];
+
+    let mut ata = Matrix4::zeros();
+    for row in &rows {
+        ata += row.transpose() * row;
+    }
+
+    let x = smallest_eigenvector(DMatrix::from_column_slice(4, 4, ata.as_slice()));
+
+    if x[3].abs() < 1e-12 {
+        return None;
+    }
+
+    let point = Point3::new(x[0] / x[3], x[1] / x[3], x[2] / x[3]);
+
+    point.coords.iter().all(|v| v.is_finite()).then_some(point)
+}
+
+/// Picks the factorization of `e` that puts the most triangulated inliers in front of both
+/// cameras.
+///
+/// Returns the pose of the second camera relative to the first and the number of points
+/// that passed the cheirality check.
+pub fn recover_pose(
+    e: &Matrix3<f64>,
+    correspondences: &[Correspondence],
+    inliers: &[bool]
+) -> Option<(Isometry3<f64>, usize)> {
+    let identity = Isometry3::identity();
+
+    decompose_essential(e)?
+        .into_iter()
+        .map(|(rotation, translation)| {
+            let second = isometry(rotation, translation);
+
+            let in_front = correspondences.iter()
+                .zip(inliers)
+                .filter(|(_, inlier)| **inlier)
+                .filter_map(|((a, b), _)| triangulate(&identity, &second, a, b))
+                .filter(|point| point.z > 0.0 && (second * point).z > 0.0)
+                .count();
+
+            (second, in_front)
+        })
+        .max_by_key(|(_, in_front)| *in_front)
+}
+
+/// Umeyama's least squares similarity `s R x + t` mapping the points `from` onto `to`.
+///
+/// Returns `None` for fewer than three points or degenerate, e.g. collinear, configurations.
+pub fn umeyama(from: &[Point3<f64>], to: &[Point3<f64>]) -> Option<Similarity3<f64>> {
+    assert_eq!(from.len(), to.len());
+
+    if from.len() < 3 {
+        return None;
+    }
+
+    let count = from.len() as f64;
+    let from_mean = from.iter().map(|point| point.coords).sum::<Vector3<f64>>() / count;
+    let to_mean = to.iter().map(|point| point.coords).sum::<Vector3<f64>>() / count;
+
+    let mut covariance = Matrix3::zeros();
+    let mut variance = 0.0;
+
+    for (a, b) in from.iter().zip(to) {
+        let (a, b) = (a.coords - from_mean, b.coords - to_mean);
+        covariance += b * a.transpose();
+        variance += a.norm_squared();
+    }
+
+    covariance /= count;
+    variance /= count;
+
+    let svd = covariance.try_svd(true, true, f64::EPSILON, 0)?;
+    let (u, v_t) = (svd.u?, svd.v_t?);
+
+    // Singular values are sorted in decreasing order
+    let singular_values = svd.singular_values;
+
+    if variance < 1e-12 || singular_values[1] < 1e-9 * singular_values[0] {
+        return None;
+    }
+
+    // Reflections are turned into the closest rotation
+    let mut sign = Matrix3::identity();
+    if u.determinant() * v_t.determinant() < 0.0 {
+        sign[(2, 2)] = -1.0;
+    }
+
+    let rotation = u * sign * v_t;
+    let scale = singular_values.dot(&sign.diagonal()) / variance;
+
+    if !(scale.is_finite() && scale > 0.0) {
+        return None;
+    }
+
+    let translation = to_mean - scale * rotation * from_mean;
+
+    Some(Similarity3::from_parts(
+        Translation3::from(translation),
+        UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(rotation)),
+        scale
+    ))
+}
+
+/// A 3D point in world coordinates and where it is seen, in normalized image coordinates.
+pub type PointCorrespondence = (Point3<f64>, Point2<f64>);
+
+#[derive(Clone, Copy, Debug)]
+pub struct PnpConfig {
+    pub iterations: usize,
+    /// Inlier threshold on the reprojection error, in normalized image units.
+    pub threshold: f64,
+    pub confidence: f64
+}
+
+impl Default for PnpConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 300,
+            // Roughly four pixels at a focal length of 800 pixels
+            threshold: 0.005,
+            confidence: 0.999
+        }
+    }
+}
+
+fn pnp_error(pose: &Isometry3<f64>, (point, observed): &PointCorrespondence) -> f64 {
+    let camera = pose * point;
+
+    if camera.z <= 0.0 {
+        return f64::INFINITY;
+    }
+
+    (Point2::new(camera.x / camera.z, camera.y / camera.z) - observed).norm()
+}
+
+/// Direct linear transform estimate of the pose of a calibrated camera from at least six
+/// correspondences. The 3x4 projection is solved linearly, then its left 3x3 block is
+/// projected onto the closest rotation.
+pub fn pnp_dlt(correspondences: &[PointCorrespondence]) -> Option<Isometry3<f64>> {
+    if correspondences.len() < 6 {
+        return None;
+    }
+
+    // Hartley's normalization: centered points at an average distance of √3
+    let centroid = correspondences.iter().map(|(point, _)| point.coords).sum::<Vector3<f64>>() / correspondences.len() as f64;
+    let spread = correspondences.iter().map(|(point, _)| (point.coords - centroid).norm()).sum::<f64>() / correspondences.len() as f64;
+
+    if spread < 1e-12 {
+        return None;
+    }
+
+    let point_scale = 3f64.sqrt() / spread;
+    let mut normalization = Matrix4::identity() * point_scale;
+    normalization.fixed_view_mut::<3, 1>(0, 3).copy_from(&(-centroid * point_scale));
+    normalization[(3, 3)] = 1.0;
+
+    let mut ata = SMatrix::<f64, 12, 12>::zeros();
+
+    for (point, observed) in correspondences {
+        let normalized = (point.coords - centroid) * point_scale;
+        let x = [normalized.x, normalized.y, normalized.z, 1.0];
+        let mut rows = [[0.0; 12]; 2];
+
+        for i in 0..4 {
+            rows[0][i] = x[i];
+            rows[0][8 + i] = -observed.x * x[i];
+            rows[1][4 + i] = x[i];
+            rows[1][8 + i] = -observed.y * x[i];
+        }
+
+        for row in &rows {
+            let row = SMatrix::<f64, 1, 12>::from_row_slice(row);
+            ata += row.transpose() * row;
+        }
+    }
+
+    let p = smallest_eigenvector(DMatrix::from_column_slice(12, 12, ata.as_slice()));
+    let mut projection = Matrix3x4::from_row_slice(p.as_slice()) * normalization;
+
+    // The solution is up to sign: most points must be in front of the camera
+    let in_front = correspondences.iter()
+        .filter(|(point, _)| projection.row(2).dot(&point.to_homogeneous().transpose()) > 0.0)
+        .count();
+
+    if in_front * 2 < correspondences.len() {
+        projection = -projection;
+    }
+
+    let block = projection.fixed_view::<3, 3>(0, 0).into_owned();
+    let svd = block.try_svd(true, true, f64::EPSILON, 0)?;
+    let rotation = svd.u? * svd.v_t?;
+
+    if rotation.determinant() <= 0.0 {
+        return None;
+    }
+
+    let scale = svd.singular_values.mean();
+
+    if scale < 1e-12 {
+        return None;
+    }
+
+    let translation = projection.column(3) / scale;
+
+    Some(isometry(Rotation3::from_matrix_unchecked(rotation), translation))
+}
+
+/// Gauss-Newton refinement of a camera pose minimizing the reprojection error of the
+/// correspondences, in normalized image coordinates.
+pub fn refine_pose(pose: &Isometry3<f64>, correspondences: &[PointCorrespondence]) -> Isometry3<f64> {
+    let cost = |pose: &Isometry3<f64>| -> f64 {
+        correspondences.iter().map(|c| pnp_error(pose, c).powi(2)).sum()
+    };
+
+    let mut pose = *pose;
+    let mut current = cost(&pose);
+
+    for _ in 0..10 {
+        let mut jtj = SMatrix::<f64, 6, 6>::zeros();
+        let mut jtr = SMatrix::<f64, 6, 1>::zeros();
+
+        for (point, observed) in correspondences {
+            let p = pose * point;
+
+            if p.z <= 0.0 {
+                continue;
+            }
+
+            let residual = Vector2::new(p.x / p.z - observed.x, p.y / p.z - observed.y);
+            let projection = Matrix2x3::new(
+                1.0 / p.z, 0.0, -p.x / (p.z * p.z),
+                0.0, 1.0 / p.z, -p.y / (p.z * p.z)
+            );
+
+            // Left perturbation of the pose, rotation first
+            let skew = Matrix3::new(
+                0.0, -p.z, p.y,
+                p.z, 0.0, -p.x,
+                -p.y, p.x, 0.0
+            );
+            let mut jacobian = SMatrix::<f64, 2, 6>::zeros();
+            jacobian.fixed_view_mut::<2, 3>(0, 0).copy_from(&(projection * -skew));
+            jacobian.fixed_view_mut::<2, 3>(0, 3).copy_from(&projection);
+
+            jtj += jacobian.transpose() * jacobian;
+            jtr += jacobian.transpose() * residual;
+        }
+
+        let Some(step) = jtj.cholesky().map(|cholesky| cholesky.solve(&-jtr)) else {
+            break;
+        };
+
+        let candidate = Isometry3::new(step.fixed_rows::<3>(3).into_owned(), step.fixed_rows::<3>(0).into_owned()) * pose;
+        let candidate_cost = cost(&candidate);
+
+        if candidate_cost >= current {
+            break;
+        }
+
+        pose = candidate;
+        current = candidate_cost;
+    }
+
+    pose
+}
+
+/// RANSAC over the six point DLT, refined on all inliers of the best hypothesis.
+///
+/// Returns the pose mapping world coordinates into the camera frame and the inlier mask.
+pub fn ransac_pnp(
+    correspondences: &[PointCorrespondence],
+    config: &PnpConfig,
+    rng: &mut impl Rng
+) -> Option<(Isometry3<f64>, Vec<bool>)> {
+    ransac::<6, _, _>(
+        correspondences,
+        (config.iterations, config.confidence),
+        rng,
+        pnp_dlt,
+        |pose, c| pnp_error(pose, c) < config.threshold,
+        refine_pose
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    /// Random points in front of both cameras and their projections, in normalized image
+    /// coordinates, into the identity camera and `second`.
+    fn project_scene(second: &Isometry3<f64>, count: usize, rng: &mut SmallRng) -> (Vec<Point3<f64>>, Vec<Correspondence>) {
+        let points: Vec<Point3<f64>> = (0..count)
+            .map(|_| Point3::new(rng.gen_range(-2.0..2.0), rng.gen_range(-2.0..2.0), rng.gen_range(4.0..8.0)))
+            .collect();
+
+        let correspondences = points.iter()
+            .map(|point| {
+                let moved = second * point;
+                (Point2::new(point.x / point.z, point.y / point.z), Point2::new(moved.x / moved.z, moved.y / moved.z))
+            })
+            .collect();
+
+        (points, correspondences)
+    }
+
+    fn relative_motion() -> Isometry3<f64> {
+        isometry(Rotation3::from_euler_angles(0.02, -0.1, 0.05), Vector3::new(-0.6, 0.1, 0.2))
+    }
+
+    /// Skew-symmetric matrix of `t`, so that `[t]ₓ x = t × x`.
+    fn skew(t: &Vector3<f64>) -> Matrix3<f64> {
+        Matrix3::new(
+            0.0, -t.z, t.y,
+            t.z, 0.0, -t.x,
+            -t.y, t.x, 0.0
+        )
+    }
+
+    #[test]
+    fn eight_point_recovers_essential_matrix() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let motion = relative_motion();
+        let (_, correspondences) = project_scene(&motion, 20, &mut rng);
+
+        let expected = skew(&motion.translation.vector) * motion.rotation.to_rotation_matrix().matrix();
+        let expected = expected / expected.norm();
+
+        let e = essential_eight_point(&correspondences).unwrap();
+
+        // Up to sign
+        assert!((e - expected).norm().min((e + expected).norm()) < 1e-6);
+        assert!(correspondences.iter().all(|c| sampson_error(&e, c) < 1e-12));
+    }
+
+    #[test]
+    fn ransac_recovers_pose_despite_outliers() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        let motion = relative_motion();
+        let (_, mut correspondences) = project_scene(&motion, 200, &mut rng);
+
+        // Half a pixel of noise at a focal length of 1000 pixels, and a quarter of outliers
+        for (index, (_, b)) in correspondences.iter_mut().enumerate() {
+            if index % 4 == 0 {
+                *b = Point2::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5));
+            } else {
+                b.x += rng.gen_range(-5e-4..5e-4);
+                b.y += rng.gen_range(-5e-4..5e-4);
+            }
+        }
+
+        let (e, inliers) = ransac_essential(&correspondences, &RansacConfig::default(), &mut rng).unwrap();
+
+        let outliers_rejected = inliers.iter().step_by(4).filter(|inlier| !**inlier).count();
+        assert!(outliers_rejected >= 48);
+        assert!(inliers.iter().filter(|inlier| **inlier).count() >= 110);
+
+        let (pose, in_front) = recover_pose(&e, &correspondences, &inliers).unwrap();
+        assert!(in_front >= 110);
+
+        // Translation is only known up to scale
+        assert!((pose.rotation.to_rotation_matrix().matrix() - motion.rotation.to_rotation_matrix().matrix()).norm() < 0.01);
+        assert!((pose.translation.vector - motion.translation.vector.normalize()).norm() < 0.05);
+    }
+
+    #[test]
+    fn decomposition_contains_true_motion() {
+        let motion = relative_motion();
+        let e = skew(&motion.translation.vector) * motion.rotation.to_rotation_matrix().matrix();
+        let direction = motion.translation.vector.normalize();
+
+        let found = decompose_essential(&e).unwrap().iter().any(|(rotation, translation)| {
+            (rotation.matrix() - motion.rotation.to_rotation_matrix().matrix()).norm() < 1e-9 &&
+                (translation - direction).norm() < 1e-9
+        });
+
+        assert!(found);
+    }
+
+    #[test]
+    fn triangulates_projected_points() {
+        let mut rng = SmallRng::seed_from_u64(3);
+        let motion = relative_motion();
+        let (points, correspondences) = project_scene(&motion, 50, &mut rng);
+
+        for (point, (a, b)) in points.iter().zip(&correspondences) {
+            let triangulated = triangulate(&Isometry3::identity(), &motion, a, b).unwrap();
+            assert!((triangulated - point).norm() < 1e-8);
+        }
+    }
+}
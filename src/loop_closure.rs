@@ -0,0 +1,446 @@
+use std::collections::{BTreeMap, HashMap};
+
+use nalgebra::{Point3, Similarity3};
+use rand::{rngs::SmallRng, seq::index::sample, SeedableRng};
+
+use crate::geometry::umeyama;
+use crate::map::{KeyFrameId, Map, MapPointId};
+use crate::matching::{match_features, Keypoint, MatcherConfig};
+use crate::vocabulary::{BowVector, Vocabulary, WordId};
+
+#[derive(Clone, Copy, Debug)]
+pub struct LoopConfig {
+    /// Keyframes less than this many keyframes older than the query are never loop
+    /// candidates.
+    pub min_keyframe_gap: usize,
+    /// Candidates geometrically verified per query, the best scores first.
+    pub max_candidates: usize,
+    /// Candidates must share at least this fraction of the most words any candidate shares
+    /// with the query.
+    pub min_common_words_ratio: f64,
+    /// Matched map points consistent with the similarity needed to accept a loop.
+    pub min_inliers: usize,
+    pub ransac_iterations: usize,
+    /// Inlier threshold on the reprojection error in both keyframes, in pixels of the
+    /// keypoint's pyramid level.
+    pub max_reprojection_error: f64,
+    pub matcher: MatcherConfig
+}
+
+impl Default for LoopConfig {
+    fn default() -> Self {
+        Self {
+            min_keyframe_gap: 20,
+            max_candidates: 3,
+            min_common_words_ratio: 0.8,
+            min_inliers: 20,
+            ransac_iterations: 200,
+            // 99% quantile of the chi-square distribution with 2 degrees of freedom
+            max_reprojection_error: 9.21f64.sqrt(),
+            matcher: MatcherConfig::default()
+        }
+    }
+}
+
+/// Bag-of-words vectors of keyframes with an inverted index from every word to the
+/// keyframes containing it, so a query only scores keyframes sharing words with it.
+#[derive(Clone, Debug, Default)]
+pub struct KeyFrameDatabase {
+    inverted_index: HashMap<WordId, Vec<KeyFrameId>>,
+    vectors: BTreeMap<KeyFrameId, BowVector>
+}
+
+impl KeyFrameDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    pub fn vector(&self, keyframe: KeyFrameId) -> Option<&BowVector> {
+        self.vectors.get(&keyframe)
+    }
+
+    pub fn add(&mut self, keyframe: KeyFrameId, vector: BowVector) {
+        self.remove(keyframe);
+
+        for word in vector.0.keys() {
+            self.inverted_index.entry(*word).or_default().push(keyframe);
+        }
+
+        self.vectors.insert(keyframe, vector);
+    }
+
+    pub fn remove(&mut self, keyframe: KeyFrameId) {
+        let Some(vector) = self.vectors.remove(&keyframe) else {
+            return;
+        };
+
+        for word in vector.0.keys() {
+            if let Some(keyframes) = self.inverted_index.get_mut(word) {
+                keyframes.retain(|other| *other != keyframe);
+            }
+        }
+    }
+
+    /// Keyframes similar to `vector`, best first, as in ORB-SLAM's loop candidate search:
+    /// among the keyframes that are not `excluded`, only those sharing nearly as many words
+    /// as the best one and scoring at least `min_score` are kept.
+    pub fn query(
+        &self,
+        vector: &BowVector,
+        excluded: impl Fn(KeyFrameId) -> bool,
+        min_common_words_ratio: f64,
+        min_score: f64
+    ) -> Vec<(KeyFrameId, f64)> {
+        let mut common_words: HashMap<KeyFrameId, usize> = HashMap::new();
+
+        for word in vector.0.keys() {
+            for keyframe in self.inverted_index.get(word).into_iter().flatten() {
+                if !excluded(*keyframe) {
+                    *common_words.entry(*keyframe).or_default() += 1;
+                }
+            }
+        }
+
+        let most_common = common_words.values().copied().max().unwrap_or(0);
+        let min_common = min_common_words_ratio * most_common as f64;
+
+        let mut scores: Vec<(KeyFrameId, f64)> = common_words.into_iter()
+            .filter(|(_, common)| *common as f64 >= min_common)
+            .map(|(keyframe, _)| (keyframe, vector.score(&self.vectors[&keyframe])))
+            .filter(|(_, score)| *score >= min_score)
+            .collect();
+
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores
+    }
+}
+
+/// A verified loop between a new keyframe and an earlier keyframe of the same place.
+#[derive(Clone, Debug)]
+pub struct LoopClosure {
+    pub query: KeyFrameId,
+    pub candidate: KeyFrameId,
+    /// Bag-of-words score of the candidate.
+    pub score: f64,
+    /// Maps candidate camera coordinates into query camera coordinates. Monocular maps drift
+    /// in scale too, hence a similarity.
+    pub query_from_candidate: Similarity3<f64>,
+    /// Map points of the query and of the candidate that are the same point, consistent
+    /// with `query_from_candidate`.
+    pub matches: Vec<(MapPointId, MapPointId)>
+}
+
+/// A map point of the query matched with one of the candidate, with their keypoints and
+/// positions in each camera frame.
+#[derive(Clone, Copy)]
+struct PointPair {
+    points: (MapPointId, MapPointId),
+    keypoints: (Keypoint, Keypoint),
+    positions: (Point3<f64>, Point3<f64>)
+}
+
+/// Place recognition over the keyframes of a map: finds keyframes that look like a new
+/// keyframe with the bag-of-words database, then verifies them geometrically by fitting a
+/// similarity between their matched map points with RANSAC.
+pub struct LoopDetector {
+    pub config: LoopConfig,
+    vocabulary: Vocabulary,
+    database: KeyFrameDatabase,
+    rng: SmallRng
+}
+
+impl LoopDetector {
+    pub fn new(vocabulary: Vocabulary, config: LoopConfig) -> Self {
+        Self {
+            config,
+            vocabulary,
+            database: KeyFrameDatabase::new(),
+            rng: SmallRng::seed_from_u64(0)
+        }
+    }
+
+    pub fn vocabulary(&self) -> &Vocabulary {
+        &self.vocabulary
+    }
+
+    pub fn database(&self) -> &KeyFrameDatabase {
+        &self.database
+    }
+
+    /// Looks for an earlier keyframe of the same place as `keyframe`, then adds `keyframe`
+    /// to the database.
+    pub fn detect(&mut self, map: &Map, keyframe: KeyFrameId) -> Option<LoopClosure> {
+        let frame = map.keyframe(keyframe)?;
+        let vector = self.vocabulary.transform(&frame.features.descriptors);
+        let covisible = map.covisible_keyframes(keyframe, usize::MAX);
+
+        // Like ORB-SLAM, a loop candidate must look at least as similar as the least similar
+        // keyframe seeing the same map points
+        let min_score = covisible.iter()
+            .filter_map(|id| self.database.vector(*id))
+            .map(|other| vector.score(other))
+            .reduce(f64::min)
+            .unwrap_or(0.0);
+
+        let gap = self.config.min_keyframe_gap;
+        let candidates = self.database.query(
+            &vector,
+            |id| id + gap > keyframe || covisible.contains(&id),
+            self.config.min_common_words_ratio,
+            min_score
+        );
+
+        let closure = candidates.into_iter()
+            .take(self.config.max_candidates)
+            .find_map(|(candidate, score)| self.verify(map, keyframe, candidate, score));
+
+        self.database.add(keyframe, vector);
+
+        closure
+    }
+
+    /// Matches the map points of `query` and `candidate` and fits the similarity between
+    /// their camera frames with RANSAC over Umeyama's three point solver.
+    fn verify(&mut self, map: &Map, query: KeyFrameId, candidate: KeyFrameId, score: f64) -> Option<LoopClosure> {
+        let query_frame = map.keyframe(query)?;
+        let candidate_frame = map.keyframe(candidate)?;
+
+        let pairs: Vec<PointPair> = match_features(&candidate_frame.features, &query_frame.features, &self.config.matcher)
+            .into_iter()
+            .filter_map(|m| {
+                let query_point = map.point(query_frame.map_points[m.current]?)?;
+                let candidate_point = map.point(candidate_frame.map_points[m.previous]?)?;
+
+                (query_point.id != candidate_point.id).then(|| PointPair {
+                    points: (query_point.id, candidate_point.id),
+                    keypoints: (query_frame.features.keypoints[m.current], candidate_frame.features.keypoints[m.previous]),
+                    positions: (
+                        query_frame.pose.camera_from_world * query_point.position,
+                        candidate_frame.pose.camera_from_world * candidate_point.position
+                    )
+                })
+            })
+            .collect();
+
+        if pairs.len() < self.config.min_inliers.max(3) {
+            return None;
+        }
+
+        let intrinsics = map.intrinsics();
+        let is_inlier = |similarity: &Similarity3<f64>, pair: &PointPair| {
+            let reprojects = |position: Point3<f64>, keypoint: &Keypoint| {
+                let tolerance = self.config.max_reprojection_error * (1u32 << keypoint.octave) as f64;
+                intrinsics.project(&position).is_some_and(|pixel| (pixel - keypoint.pixel()).norm() <= tolerance)
+            };
+
+            reprojects(similarity * pair.positions.1, &pair.keypoints.0) &&
+                reprojects(similarity.inverse() * pair.positions.0, &pair.keypoints.1)
+        };
+
+        let fit = |pairs: &[PointPair]| {
+            let from: Vec<Point3<f64>> = pairs.iter().map(|pair| pair.positions.1).collect();
+            let to: Vec<Point3<f64>> = pairs.iter().map(|pair| pair.positions.0).collect();
+            umeyama(&from, &to)
+        };
+
+        let mut best: Option<(Similarity3<f64>, usize)> = None;
+
+        for _ in 0..self.config.ransac_iterations {
+            let sample: Vec<PointPair> = sample(&mut self.rng, pairs.len(), 3)
+                .into_iter()
+                .map(|index| pairs[index])
+                .collect();
+
+            let Some(similarity) = fit(&sample) else { continue; };
+
+            let inliers = pairs.iter().filter(|pair| is_inlier(&similarity, pair)).count();
+
+            if best.is_none_or(|(_, count)| inliers > count) {
+                best = Some((similarity, inliers));
+            }
+        }
+
+        let (similarity, _) = best?;
+
+        // Refit on all inliers
+        let inliers: Vec<PointPair> = pairs.iter().copied().filter(|pair| is_inlier(&similarity, pair)).collect();
+        let similarity = fit(&inliers)
+            .filter(|refined| pairs.iter().filter(|pair| is_inlier(refined, pair)).count() >= inliers.len())
+            .unwrap_or(similarity);
+
+        let matches: Vec<(MapPointId, MapPointId)> = pairs.iter()
+            .filter(|pair| is_inlier(&similarity, pair))
+            .map(|pair| pair.points)
+            .collect();
+
+        (matches.len() >= self.config.min_inliers).then_some(LoopClosure {
+            query,
+            candidate,
+            score,
+            query_from_candidate: similarity,
+            matches
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Vector3;
+    use rand::Rng;
+
+    use crate::map::{MapConfig, Observation};
+    use crate::matching::{Descriptor, FrameFeatures};
+    use crate::odometry::Pose;
+    use crate::test_util::{intrinsics, observe_all, pose};
+
+    /// Adds a keyframe with a map point at each of `positions`, in the order of its keypoints.
+    fn add_keyframe(map: &mut Map, pose: Pose, features: FrameFeatures, positions: &[Point3<f64>]) -> (KeyFrameId, Vec<MapPointId>) {
+        let keyframe = map.insert_keyframe(pose, features.clone(), vec![None; features.len()]);
+        let points = positions.iter()
+            .enumerate()
+            .map(|(keypoint, position)| map.add_point(*position, &[Observation { keyframe, keypoint }]))
+            .collect();
+
+        (keyframe, points)
+    }
+
+    fn bow(words: &[WordId]) -> BowVector {
+        BowVector(words.iter().map(|word| (*word, 1.0 / words.len() as f64)).collect())
+    }
+
+    fn database() -> KeyFrameDatabase {
+        let mut database = KeyFrameDatabase::new();
+        database.add(0, bow(&[1, 2, 3, 4]));
+        database.add(1, bow(&[1, 2, 3, 4]));
+        database.add(2, bow(&[1, 2, 3, 9]));
+        database.add(3, bow(&[1, 9]));
+        database.add(4, bow(&[7, 8]));
+        database
+    }
+
+    #[test]
+    fn query_ranks_keyframes_sharing_words() {
+        let database = database();
+        let query = bow(&[1, 2, 3, 4]);
+
+        assert_eq!(database.len(), 5);
+        assert_eq!(database.query(&query, |_| false, 0.0, 0.0), vec![(0, 1.0), (1, 1.0), (2, 0.75), (3, 0.25)]);
+        assert_eq!(database.query(&query, |_| false, 0.0, 0.5), vec![(0, 1.0), (1, 1.0), (2, 0.75)]);
+    }
+
+    #[test]
+    fn query_skips_excluded_keyframes() {
+        let database = database();
+        let query = bow(&[1, 2, 3, 4]);
+
+        assert_eq!(database.query(&query, |id| id < 2, 0.0, 0.0), vec![(2, 0.75), (3, 0.25)]);
+        assert!(database.query(&query, |_| true, 0.0, 0.0).is_empty());
+    }
+
+    #[test]
+    fn query_keeps_keyframes_sharing_nearly_the_most_words() {
+        let database = database();
+        let query = bow(&[1, 2, 3, 4]);
+
+        // Four common words at best, so at least three are needed
+        assert_eq!(database.query(&query, |_| false, 0.7, 0.0), vec![(0, 1.0), (1, 1.0), (2, 0.75)]);
+        assert_eq!(database.query(&query, |_| false, 0.8, 0.0), vec![(0, 1.0), (1, 1.0)]);
+
+        // The best is counted among the keyframes left after exclusion
+        assert_eq!(database.query(&query, |id| id < 2, 0.8, 0.0), vec![(2, 0.75)]);
+    }
+
+    #[test]
+    fn removed_keyframes_are_not_returned() {
+        let mut database = database();
+        database.remove(0);
+        database.add(1, bow(&[7]));
+
+        assert_eq!(database.len(), 4);
+        assert!(database.vector(0).is_none());
+        assert_eq!(database.query(&bow(&[1, 2, 3, 4]), |_| false, 0.0, 0.0), vec![(2, 0.75), (3, 0.25)]);
+        assert_eq!(database.query(&bow(&[7]), |_| false, 0.0, 0.0), vec![(1, 1.0), (4, 0.5)]);
+    }
+
+    #[test]
+    fn verify_recovers_drift_of_a_revisit() {
+        let mut rng = SmallRng::seed_from_u64(1);
+
+        let candidate_pose = pose(0.0, 0.0);
+        let query_pose = pose(0.2, 0.05);
+
+        let points: Vec<Point3<f64>> = (0..100)
+            .map(|_| Point3::new(rng.gen_range(-1.5..1.5), rng.gen_range(-1.0..1.0), rng.gen_range(4.0..8.0)))
+            .collect();
+        let descriptors: Vec<Descriptor> = (0..points.len()).map(|_| Descriptor(rng.gen())).collect();
+
+        // By the time the place is revisited the map has drifted by `drift`, scale included.
+        // The query sees the same pixels, its camera frame is scaled by the drift.
+        let drift = Similarity3::new(Vector3::new(0.5, -0.2, 1.0), Vector3::new(0.05, 0.3, -0.1), 1.4);
+        let drifted_pose = (Similarity3::from_scaling(drift.scaling()) * query_pose.camera_from_world * drift.inverse()).isometry;
+
+        // A quarter of the query's map points were triangulated wrongly
+        let outliers = 25;
+        let query_positions: Vec<Point3<f64>> = points.iter()
+            .enumerate()
+            .map(|(index, point)| {
+                let offset = if index < outliers { Vector3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 1.0) } else { Vector3::zeros() };
+                drift * (point + offset)
+            })
+            .collect();
+
+        let mut map = Map::new(MapConfig::default(), intrinsics());
+        let (candidate, candidate_points) = add_keyframe(&mut map, candidate_pose, observe_all(&candidate_pose, &points, &descriptors), &points);
+        let (query, query_points) = add_keyframe(&mut map, Pose { camera_from_world: drifted_pose }, observe_all(&query_pose, &points, &descriptors), &query_positions);
+
+        let vocabulary = Vocabulary::parse("10 5 0 0").unwrap();
+        let mut detector = LoopDetector::new(vocabulary, LoopConfig::default());
+        let closure = detector.verify(&map, query, candidate, 0.5).unwrap();
+
+        assert_eq!((closure.query, closure.candidate, closure.score), (query, candidate, 0.5));
+
+        let mut matches = closure.matches.clone();
+        matches.sort_unstable();
+        let expected: Vec<_> = query_points.into_iter().zip(candidate_points).skip(outliers).collect();
+        assert_eq!(matches, expected);
+
+        let similarity = closure.query_from_candidate;
+        assert!((similarity.scaling() - drift.scaling()).abs() < 1e-4);
+
+        for point in &points {
+            let expected = query_pose.camera_from_world * point * drift.scaling();
+            assert!((similarity * (candidate_pose.camera_from_world * point) - expected).norm() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn verify_rejects_unrelated_places() {
+        let mut rng = SmallRng::seed_from_u64(2);
+
+        let random_points = |rng: &mut SmallRng| -> Vec<Point3<f64>> {
+            (0..60)
+                .map(|_| Point3::new(rng.gen_range(-1.5..1.5), rng.gen_range(-1.0..1.0), rng.gen_range(4.0..8.0)))
+                .collect()
+        };
+        let descriptors: Vec<Descriptor> = (0..60).map(|_| Descriptor(rng.gen())).collect();
+
+        // Same descriptors, but the points have nothing in common
+        let (first, second) = (random_points(&mut rng), random_points(&mut rng));
+
+        let mut map = Map::new(MapConfig::default(), intrinsics());
+        let (candidate, _) = add_keyframe(&mut map, Pose::identity(), observe_all(&Pose::identity(), &first, &descriptors), &first);
+        let (query, _) = add_keyframe(&mut map, Pose::identity(), observe_all(&Pose::identity(), &second, &descriptors), &second);
+
+        let mut detector = LoopDetector::new(Vocabulary::parse("10 5 0 0").unwrap(), LoopConfig::default());
+        assert!(detector.verify(&map, query, candidate, 0.5).is_none());
+    }
+}
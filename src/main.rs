@@ -7,6 +7,7 @@ TODO:
 */
 
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use bytemuck::Zeroable;
 use pollster::FutureExt;
@@ -15,14 +16,55 @@ use winit::{
     dpi::PhysicalSize, event::{Event, WindowEvent}, event_loop::EventLoop, window::Window
 };
 
-use nokhwa::{pixel_format::RgbAFormat, utils::RequestedFormat, Camera};
+use nalgebra::{Point3, Vector3};
+use nokhwa::{
+    pixel_format::{Luma16Format, RgbAFormat}, recording::Recorder, stats::LatencyHistogram,
+    utils::{frame_formats, RequestedFormat, ToneMap}, Buffer, Camera, CameraGroup, NokhwaError
+};
+use rand::{rngs::SmallRng, SeedableRng};
 
 use tinyslam::orb::{CornerData, CornerDescriptor, OrbConfig, OrbProgram};
 
+mod bundle_adjustment;
+mod calibration;
+mod camera_model;
+mod geometry;
+mod loop_closure;
+mod map;
+mod matching;
+mod odometry;
+mod pose_graph;
+mod stereo;
+#[cfg(test)]
+mod test_util;
+mod tracking;
+mod vocabulary;
+
+use calibration::{CalibrationConfig, CalibrationSession, StereoCalibrationSession};
+use camera_model::{
+    load_calibration, load_stereo_calibration, save_calibration, save_stereo_calibration, CameraIntrinsics
+};
+use loop_closure::{LoopConfig, LoopDetector};
+use map::{KeyFrameId, Map, MapConfig, MapPointId, Observation};
+use matching::{match_features, Descriptor, FrameFeatures, MatcherConfig};
+use odometry::{OdometryConfig, Pose, VisualOdometry};
+use stereo::{match_stereo, Rectification, RectificationMap, StereoMatcherConfig};
+use tracking::{Tracker, TrackingConfig, TrackingState};
+use vocabulary::{Vocabulary, VocabularyConfig};
+
 use tiny_wgpu::{
     BindGroupItem, Compute, ComputeProgram, RenderKernel, Storage
 };
 
+/// Capacity of the buffer drawn by `VisualizationProgram::run_overlay`.
+const MAX_OVERLAY_CORNERS: u64 = 1024;
+
+/// How often capture and pipeline statistics are printed.
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Triangulated points needed to initialize from a stereo pair.
+const MIN_STEREO_POINTS: usize = 100;
+
 struct VisualizationProgram<'a> {
     pub surface: wgpu::Surface<'a>,
 
@@ -48,7 +90,28 @@ impl<'a> ComputeProgram for VisualizationProgram<'a> {
     }
 }
 
-impl<'a> VisualizationProgram<'a> {    
+impl<'a> VisualizationProgram<'a> {
+    /// Draws the input image of `orb_program` to `window`.
+    pub fn new(orb_program: &'a OrbProgram, window: &'a Arc<Window>, width: u32, height: u32) -> Self {
+        let mut visualization_program = VisualizationProgram {
+            compute: orb_program.compute(),
+            surface: orb_program.compute().instance.create_surface(window).unwrap(),
+            storage: Default::default(),
+            orb_storage: orb_program.storage(),
+            image_size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1
+            }
+        };
+
+        visualization_program.init();
+
+        visualization_program.configure_surface(width, height);
+
+        visualization_program
+    }
+
     pub fn init(&mut self) {
         self.add_module("blit", wgpu::include_wgsl!("shaders/blit.wgsl"));
         self.add_module("draw_corners", wgpu::include_wgsl!("shaders/draw_corners.wgsl"));
@@ -92,9 +155,17 @@ impl<'a> VisualizationProgram<'a> {
             );
         }
 
+        // Border color, fully transparent until a tracking state is set
+        self.add_buffer(
+            "status_color",
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            4 * 4
+        );
+
         self.add_bind_group("blit_to_screen", &[
             BindGroupItem::Sampler { label: "linear_sampler" },
-            BindGroupItem::Texture { label: "visualization" }
+            BindGroupItem::Texture { label: "visualization" },
+            BindGroupItem::UniformBuffer { label: "status_color", min_binding_size: 16 }
         ]);
 
         let swapchain_capabilities = self.surface.get_capabilities(&self.compute().adapter);
@@ -111,6 +182,12 @@ impl<'a> VisualizationProgram<'a> {
             None
         );
 
+        self.add_buffer(
+            "overlay_corners",
+            BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            MAX_OVERLAY_CORNERS * 4 * 4
+        );
+
         self.add_bind_group("base_resolution", &[
             BindGroupItem::UniformBuffer { label: "base_resolution", min_binding_size: 8 }
         ]);
@@ -156,7 +233,35 @@ impl<'a> VisualizationProgram<'a> {
         );
     }
 
+    /// Draws a border of `color` around the image, e.g. to show the tracking state. An alpha
+    /// of 0 hides it.
+    pub fn set_status_color(&self, color: [f32; 4]) {
+        self.compute().queue.write_buffer(
+            &self.storage().buffers["status_color"],
+            0,
+            bytemuck::cast_slice(&color)
+        );
+    }
+
     pub fn run(&self, num_corners: u32) {
+        self.render(&self.orb_storage.buffers["corners"], num_corners);
+    }
+
+    /// Draws corners computed on the CPU instead of the ones found by `OrbProgram`, in the
+    /// same layout as `CornerData`.
+    pub fn run_overlay(&self, corners: &[[u32; 4]]) {
+        let corners = &corners[..corners.len().min(MAX_OVERLAY_CORNERS as usize)];
+
+        self.compute().queue.write_buffer(
+            &self.storage().buffers["overlay_corners"],
+            0,
+            bytemuck::cast_slice(corners)
+        );
+
+        self.render(&self.storage().buffers["overlay_corners"], corners.len() as u32);
+    }
+
+    fn render(&self, corner_buffer: &wgpu::Buffer, num_corners: u32) {
 
         let mut encoder = self.compute().device.create_command_encoder(&Default::default());
 
@@ -192,7 +297,7 @@ impl<'a> VisualizationProgram<'a> {
 
                 rpass.set_pipeline(&self.storage().render_pipelines["draw_corners"]);
                 rpass.set_bind_group(0, &self.storage().bind_groups["base_resolution"], &[]);
-                rpass.set_vertex_buffer(0, self.orb_storage.buffers["corners"].slice(..(num_corners as u64 * 4 * 4)));
+                rpass.set_vertex_buffer(0, corner_buffer.slice(..(num_corners as u64 * 4 * 4)));
                 rpass.draw(0..6, 0..num_corners);
             }
         }
@@ -237,13 +342,38 @@ fn run(
     event_loop: EventLoop<()>,
     window: Arc<Window>,
 ) -> Result<(), winit::error::EventLoopError> {
+    if let Some(cameras) = arg_value("--stereo") {
+        return run_stereo(event_loop, window, &cameras);
+    }
+
     let mut camera = {
-        let index = nokhwa::utils::CameraIndex::Index(0);
         let requested_format = nokhwa::utils::RequestedFormatType::AbsoluteHighestResolution;
-        type Decoder = nokhwa::pixel_format::RgbAFormat;
-        let format = RequestedFormat::new::<Decoder>(requested_format);
+        // Any format will do, high bit depth ones are tone-mapped by `FrameDecoder`
+        let format = RequestedFormat::with_formats(requested_format, frame_formats());
 
-        let mut camera = Camera::new(index, format).unwrap();
+        let mut camera = if let Some(directory) = arg_value("--replay") {
+            Camera::with_backend(
+                nokhwa::utils::CameraIndex::String(directory),
+                format,
+                nokhwa::utils::ApiBackend::Replay
+            ).expect("Could not open recording.")
+        } else if let Some(path) = arg_value("--input") {
+            // A directory of PNG/JPEG images (TUM, EuRoC) or a Motion-JPEG AVI file
+            Camera::with_backend(
+                nokhwa::utils::CameraIndex::String(path),
+                format,
+                nokhwa::utils::ApiBackend::MediaFile
+            ).expect("Could not open input.")
+        } else if let Some(pattern) = arg_value("--virtual") {
+            // color-bars, checkerboard, noise or textured-plane
+            Camera::with_backend(
+                nokhwa::utils::CameraIndex::String(pattern),
+                format,
+                nokhwa::utils::ApiBackend::Virtual
+            ).expect("Could not open virtual camera.")
+        } else {
+            Camera::new(nokhwa::utils::CameraIndex::Index(0), format).unwrap()
+        };
 
         camera.open_stream().expect("Could not open stream.");
 
@@ -257,67 +387,367 @@ fn run(
         (resolution.width(), resolution.height())
     };
 
+    let camera_name = camera.info().human_name();
+
+    let mut recorder = arg_value("--record").map(|directory| {
+        Recorder::create(&directory, camera.info(), camera.camera_format())
+            .expect("Could not create recording.")
+    });
+
+    let intrinsics = {
+        let path = calibration_path();
+
+        match load_calibration(&path, &camera_name, frame_width, frame_height) {
+            Ok(intrinsics) => intrinsics,
+            Err(why) => {
+                println!("Using uncalibrated intrinsics ({}): {}", path, why);
+                CameraIntrinsics::guess(frame_width, frame_height)
+            }
+        }
+    };
+
     let mut frame_buffer = vec![0u8; (frame_width * frame_height * 4) as usize];
 
+    let mut frame_decoder = FrameDecoder::new(&camera, frame_width, frame_height);
+
     let _ = window.request_inner_size(PhysicalSize {
         width: frame_width,
         height: frame_height
     });
 
-    let orb_program = {
-        let mut orb_program = OrbProgram {
-            config: OrbConfig {
-                max_features: 4096,
-                image_size: wgpu::Extent3d { 
-                    width: frame_width, 
-                    height: frame_height, 
-                    depth_or_array_layers: 1
-                },
-                hierarchy_depth: 3,
-                initial_threshold: 0.4,
+    let orb_program = create_orb_program(frame_width, frame_height);
+
+    let visualization_program = VisualizationProgram::new(&orb_program, &window, frame_width, frame_height);
+
+    let window = &window;
+    let orb_program = &orb_program;
+
+    let matcher_config = MatcherConfig::default();
+    let mut previous_features: Option<FrameFeatures> = None;
+
+    let mut odometry = VisualOdometry::new(OdometryConfig::default());
+
+    let mut map = Map::new(MapConfig::default(), intrinsics);
+    let mut frames_since_keyframe = 0;
+
+    let mut tracker = Tracker::new(TrackingConfig::default());
+    visualization_program.set_status_color(status_color(tracker.state()));
+
+    let mut loop_detector = arg_value("--vocabulary").map(|path| {
+        let vocabulary = Vocabulary::load(&path).unwrap_or_else(|why| panic!("Could not load vocabulary {}: {}", path, why));
+        println!("Loaded vocabulary of {} words from {}", vocabulary.len(), path);
+        LoopDetector::new(vocabulary, LoopConfig::default())
+    });
+
+    // Descriptors of every keyframe, to train a vocabulary from with `--train-vocabulary <path>`
+    let vocabulary_path = arg_value("--train-vocabulary");
+    let mut training_images: Vec<Vec<Descriptor>> = Vec::new();
+
+    // Time from a frame leaving the camera to the end of its processing
+    let mut pipeline_durations = LatencyHistogram::new();
+    let mut last_stats_report = Instant::now();
+
+    let mut calibration_session = has_flag("--calibrate").then(|| {
+        let config = calibration_config();
+        println!(
+            "Calibrating with a {}x{} checkerboard, show it in {} different poses.",
+            config.pattern.0, config.pattern.1, config.views
+        );
+        CalibrationSession::new(config, frame_width, frame_height)
+    });
+
+    event_loop.run(move |event, target| {
+
+        let Event::WindowEvent { event, .. } = event else { return; };
+
+        match event {
+            WindowEvent::Resized(new_size) => {
+                visualization_program.configure_surface(new_size.width, new_size.height);
+                window.request_redraw();
+            },
+            WindowEvent::RedrawRequested => {
+                let Ok(new_camera_frame) = camera.frame() else {
+                    // A replayed session or input file has run out of frames
+                    if let Some(path) = &vocabulary_path {
+                        finish_vocabulary(&training_images, path);
+                    }
+                    target.exit();
+                    return;
+                };
+
+                let pipeline_started = Instant::now();
+
+                if let Some(recorder) = &mut recorder {
+                    recorder.record(&new_camera_frame).expect("Could not record frame.");
+                }
+
+                frame_decoder.decode(&new_camera_frame, &mut frame_buffer).unwrap();
+
+                orb_program.write_input_image(&frame_buffer);
+
+                if let Some(session) = &mut calibration_session {
+                    let detection = session.process_frame(&frame_buffer);
+
+                    // Draw detected corners one octave up so they are easy to see
+                    let overlay: Vec<[u32; 4]> = detection.iter()
+                        .flat_map(|detection| &detection.corners)
+                        .map(|corner| [(corner.x * 0.5).round() as u32, (corner.y * 0.5).round() as u32, 0, 1])
+                        .collect();
+
+                    visualization_program.run_overlay(&overlay);
+
+                    if detection.is_some_and(|detection| detection.collected) {
+                        println!("Collected view {}/{}", session.views().len(), session.config.views);
+                    }
+
+                    if session.is_complete() {
+                        finish_calibration(session, &camera_name);
+                        target.exit();
+                    }
+
+                    window.request_redraw();
+                    return;
+                }
+
+                let features = read_features(orb_program);
+                let corner_count = features.len() as u32;
+
+                let matches = match &previous_features {
+                    Some(previous) => match_features(previous, &features, &matcher_config),
+                    None => Vec::new()
+                };
+
+                println!("Detected {} corners, {} matched with previous frame.", corner_count, matches.len());
+
+                frames_since_keyframe += 1;
+
+                // Map points seen again by this frame
+                let mut map_points: Vec<Option<MapPointId>> = vec![None; features.len()];
+                // Whether the pose of this frame is known
+                let mut located = true;
+
+                if tracker.state() == TrackingState::Lost {
+                    let database = loop_detector.as_ref().map(|detector| (detector.vocabulary(), detector.database()));
+
+                    if let Some(relocalization) = tracker.relocalize(&map, &features, database) {
+                        println!(
+                            "Relocalized against keyframe {} with {} inliers.",
+                            relocalization.keyframe, relocalization.inliers
+                        );
+
+                        odometry.set_current_pose(relocalization.pose);
+                        map_points = relocalization.map_points;
+                    }
+                } else {
+                    if let Some(keyframe) = map.last_keyframe() {
+                        for m in match_features(&keyframe.features, &features, &matcher_config) {
+                            map_points[m.current] = keyframe.map_points[m.previous];
+                        }
+                    }
+
+                    // Monocular odometry only has a direction of travel, so it just bootstraps the
+                    // first keyframes. Once they have triangulated points, frames are located from the
+                    // map points they see, which keeps the scale of the map.
+                    if map.points().next().is_some() {
+                        // If the matches do not agree on a pose the frame tracks nothing, which loses
+                        // tracking
+                        match tracker.track(&map, &features, &map_points) {
+                            Some(tracked) => {
+                                odometry.set_current_pose(tracked.pose);
+                                map_points = tracked.map_points;
+                            },
+                            None => {
+                                map_points = vec![None; features.len()];
+                                located = false;
+                            }
+                        }
+                    } else if let Some(previous) = &previous_features {
+                        let (previous_points, current_points): (Vec<_>, Vec<_>) = matches.iter()
+                            .map(|m| {
+                                let a = previous.keypoints[m.previous].pixel();
+                                let b = features.keypoints[m.current].pixel();
+                                (intrinsics.unproject(&a), intrinsics.unproject(&b))
+                            })
+                            .unzip();
+
+                        odometry.track(&previous_points, &current_points);
+                    }
+
+                    let position = odometry.current_pose().position();
+                    println!("Camera position: ({:.3}, {:.3}, {:.3})", position.x, position.y, position.z);
+                }
+
+                let tracked_points = map_points.iter().flatten().count();
+                let state = update_tracking_state(&mut tracker, &map, tracked_points, window, &visualization_program);
+
+                // Lost frames are not trusted enough to extend the map, nor are frames without a pose
+                if state != TrackingState::Lost && located && map.needs_keyframe(frames_since_keyframe, tracked_points) {
+                    let (keyframe, refined_pose) = insert_keyframe(
+                        &mut map,
+                        odometry.current_pose(),
+                        features.clone(),
+                        map_points,
+                        |map, keyframe| map.triangulate_new_points(keyframe)
+                    );
+
+                    // Chain the next frames onto the refined pose so odometry drift does not accumulate
+                    odometry.set_current_pose(refined_pose);
+
+                    if let Some(closure) = loop_detector.as_mut().and_then(|detector| detector.detect(&map, keyframe)) {
+                        println!(
+                            "Loop detected between keyframes {} and {}: score {:.3}, {} matched map points, scale {:.3}.",
+                            closure.query, closure.candidate, closure.score, closure.matches.len(), closure.query_from_candidate.scaling()
+                        );
+
+                        if let Some(report) = map.close_loop(&closure) {
+                            println!(
+                                "Pose graph optimization: cost {:.4} -> {:.4} in {} iterations.",
+                                report.initial_cost, report.final_cost, report.iterations
+                            );
+
+                            odometry.set_current_pose(map.keyframe(keyframe).unwrap().pose);
+                        }
+                    }
+
+                    if vocabulary_path.is_some() {
+                        training_images.push(map.keyframe(keyframe).unwrap().features.descriptors.clone());
+                    }
+                    frames_since_keyframe = 0;
+                }
+
+                previous_features = Some(features);
+
+                visualization_program.run(corner_count);
+
+                pipeline_durations.record(pipeline_started.elapsed());
+
+                if last_stats_report.elapsed() >= STATS_INTERVAL {
+                    println!("Capture: {}", camera.stats());
+                    println!("Pipeline: {}", pipeline_durations);
+                    pipeline_durations.clear();
+                    last_stats_report = Instant::now();
+                }
+
+                window.request_redraw();
             },
-            compute: Compute::new(
-                wgpu::Features::PUSH_CONSTANTS,
-                {
-                    let mut limits = wgpu::Limits::default();
-                    limits.max_push_constant_size = 4;
-                    limits.max_storage_buffers_per_shader_stage = 8;
-                    limits.max_texture_dimension_1d = 4096;
-                    limits.max_texture_dimension_2d = 4096;
-                    limits
+            WindowEvent::CloseRequested => {
+                if let Some(path) = &vocabulary_path {
+                    finish_vocabulary(&training_images, path);
                 }
-                
-            ).block_on(),
-            storage: Default::default()
-        };
+                target.exit();
+            },
+            _ => {}
+        }
+        
+    })
+}
 
-        orb_program.init();
-        orb_program
+/// Stereo mode, `--stereo <left>,<right>` with the indices of two cameras.
+///
+/// With `--calibrate`, collects checkerboard views seen by both cameras and stores the pose of
+/// the right camera relative to the left one, both cameras must have been calibrated on their
+/// own first. Otherwise both images are rectified and the ORB features matched between them
+/// are triangulated into metric points, which initializes without the scale ambiguity of a
+/// single camera. The left camera is then tracked against that map, and its keyframes add
+/// the stereo points of the keypoints that do not see a map point yet.
+fn run_stereo(
+    event_loop: EventLoop<()>,
+    window: Arc<Window>,
+    cameras: &str
+) -> Result<(), winit::error::EventLoopError> {
+    let Some((left_index, right_index)) = cameras.split_once(',')
+        .and_then(|(left, right)| Some((left.parse().ok()?, right.parse().ok()?)))
+    else {
+        panic!("Invalid --stereo {}, expected two camera indices, e.g. 0,1", cameras);
     };
 
-    let visualization_program = {
-        let mut visualization_program = VisualizationProgram {
-            compute: orb_program.compute(),
-            surface: orb_program.compute().instance.create_surface(&window).unwrap(),
-            storage: Default::default(),
-            orb_storage: orb_program.storage(),
-            image_size: wgpu::Extent3d {
-                width: frame_width,
-                height: frame_height,
-                depth_or_array_layers: 1
-            }
-        };
-    
-        visualization_program.init();
+    let format = RequestedFormat::with_formats(
+        nokhwa::utils::RequestedFormatType::AbsoluteHighestResolution,
+        frame_formats()
+    );
 
-        visualization_program.configure_surface(frame_width, frame_height);
+    let left = Camera::new(nokhwa::utils::CameraIndex::Index(left_index), format)
+        .expect("Could not open left camera.");
+    let mut right = Camera::new(nokhwa::utils::CameraIndex::Index(right_index), format)
+        .expect("Could not open right camera.");
 
-        visualization_program
+    // Both images go through the same OrbProgram
+    let resolution = left.resolution();
+    if right.resolution() != resolution {
+        right.set_resolution(resolution).expect("The right camera does not support the resolution of the left one.");
+    }
+
+    let (frame_width, frame_height) = (resolution.width(), resolution.height());
+
+    let camera_names = [left.info().human_name(), right.info().human_name()];
+
+    let mut frame_decoders = [
+        FrameDecoder::new(&left, frame_width, frame_height),
+        FrameDecoder::new(&right, frame_width, frame_height)
+    ];
+
+    let mut group = CameraGroup::new(vec![left, right]).expect("Could not group cameras.");
+    group.open_stream().expect("Could not open streams.");
+
+    let mut mode = {
+        let path = calibration_path();
+
+        if has_flag("--calibrate") {
+            let load = |camera_name: &str| {
+                load_calibration(&path, camera_name, frame_width, frame_height).unwrap_or_else(|why| {
+                    panic!("Calibrate \"{}\" on its own with --calibrate first: {}", camera_name, why)
+                })
+            };
+
+            let config = calibration_config();
+            println!(
+                "Calibrating stereo pair with a {}x{} checkerboard, show it to both cameras in {} different poses.",
+                config.pattern.0, config.pattern.1, config.views
+            );
+
+            StereoMode::Calibrate(StereoCalibrationSession::new(config, load(&camera_names[0]), load(&camera_names[1])))
+        } else {
+            let rig = load_stereo_calibration(&path, &camera_names[0], &camera_names[1], frame_width, frame_height)
+                .unwrap_or_else(|why| panic!("Run with --stereo {} --calibrate first ({}): {}", cameras, path, why));
+            let rectification = Rectification::new(&rig).expect("Could not rectify the stereo pair.");
+
+            println!("Baseline {:.4}, rectified focal length {:.2}", rig.baseline(), rectification.intrinsics.fx);
+
+            StereoMode::Triangulate {
+                maps: [rectification.left_map(), rectification.right_map()],
+                rectification
+            }
+        }
     };
 
+    let _ = window.request_inner_size(PhysicalSize {
+        width: frame_width,
+        height: frame_height
+    });
+
+    let orb_program = create_orb_program(frame_width, frame_height);
+
+    let visualization_program = VisualizationProgram::new(&orb_program, &window, frame_width, frame_height);
+
     let window = &window;
     let orb_program = &orb_program;
 
+    let mut frame_buffers = [(); 2].map(|_| vec![0u8; (frame_width * frame_height * 4) as usize]);
+    let mut rectified_buffers = frame_buffers.clone();
+
+    let matcher_config = StereoMatcherConfig::default();
+    let mut map: Option<Map> = None;
+    let mut frames_since_keyframe = 0;
+
+    // Pose of the rectified left camera
+    let mut pose = Pose::identity();
+
+    let mut tracker = Tracker::new(TrackingConfig::default());
+    visualization_program.set_status_color(status_color(tracker.state()));
+
+    let mut pipeline_durations = LatencyHistogram::new();
+    let mut last_stats_report = Instant::now();
+
     event_loop.run(move |event, target| {
 
         let Event::WindowEvent { event, .. } = event else { return; };
@@ -328,28 +758,167 @@ fn run(
                 window.request_redraw();
             },
             WindowEvent::RedrawRequested => {
-                let new_camera_frame = camera.frame().unwrap();
+                let Ok(frames) = group.frameset() else {
+                    target.exit();
+                    return;
+                };
 
-                new_camera_frame
-                    .decode_image_to_buffer::<RgbAFormat>(&mut frame_buffer)
-                    .unwrap();
+                let pipeline_started = Instant::now();
 
-                orb_program.write_input_image(&frame_buffer);
+                for ((decoder, frame), buffer) in frame_decoders.iter_mut().zip(frames.frames()).zip(&mut frame_buffers) {
+                    decoder.decode(frame, buffer).unwrap();
+                }
 
-                let corner_count = orb_program.extract_corners();
+                match &mut mode {
+                    StereoMode::Calibrate(session) => {
+                        orb_program.write_input_image(&frame_buffers[0]);
 
-                // Read corner data
-                let mut corners = vec![CornerData::zeroed(); corner_count as usize];
-                let mut descriptors = vec![CornerDescriptor::zeroed(); corner_count as usize];
+                        let detection = session.process_frames(&frame_buffers[0], &frame_buffers[1]);
 
-                orb_program.read_corners(&mut corners);
-                orb_program.read_descriptors(&mut descriptors);
+                        // Draw the corners found in the left image one octave up
+                        let overlay: Vec<[u32; 4]> = detection.iter()
+                            .flat_map(|detection| &detection.left)
+                            .map(|corner| [(corner.x * 0.5).round() as u32, (corner.y * 0.5).round() as u32, 0, 1])
+                            .collect();
 
-                // TODO: Do something with corners / descriptors
+                        visualization_program.run_overlay(&overlay);
 
-                println!("Detected {} corners.", corner_count);
+                        if detection.is_some_and(|detection| detection.collected) {
+                            println!("Collected view pair {}/{}", session.views().len(), session.config.views);
+                        }
 
-                visualization_program.run(corner_count);
+                        if session.is_complete() {
+                            finish_stereo_calibration(session, &camera_names);
+                            target.exit();
+                        }
+                    },
+                    StereoMode::Triangulate { rectification, maps } => {
+                        for ((map, buffer), rectified) in maps.iter().zip(&frame_buffers).zip(&mut rectified_buffers) {
+                            map.remap_rgba(buffer, rectified);
+                        }
+
+                        // The left image goes last, so its corners are the ones drawn
+                        orb_program.write_input_image(&rectified_buffers[1]);
+                        let right_features = read_features(orb_program);
+
+                        orb_program.write_input_image(&rectified_buffers[0]);
+                        let left_features = read_features(orb_program);
+
+                        let matches = match_stereo(&left_features, &right_features, &matcher_config);
+
+                        // Left keypoint and position of every triangulated point
+                        let points: Vec<(usize, Point3<f64>)> = matches.iter()
+                            .filter_map(|m| {
+                                let a = left_features.keypoints[m.left].pixel();
+                                let b = right_features.keypoints[m.right].pixel();
+                                Some((m.left, rectification.triangulate(&a, &b)?))
+                            })
+                            .collect();
+
+                        println!(
+                            "Detected {} left and {} right corners, {} matched, {} triangulated.",
+                            left_features.len(), right_features.len(), matches.len(), points.len()
+                        );
+
+                        if let Some(map) = &mut map {
+                            frames_since_keyframe += 1;
+
+                            // Map points seen again by the left image
+                            let mut map_points: Vec<Option<MapPointId>> = vec![None; left_features.len()];
+
+                            if tracker.state() == TrackingState::Lost {
+                                if let Some(relocalization) = tracker.relocalize(map, &left_features, None) {
+                                    println!(
+                                        "Relocalized against keyframe {} with {} inliers.",
+                                        relocalization.keyframe, relocalization.inliers
+                                    );
+
+                                    pose = relocalization.pose;
+                                    map_points = relocalization.map_points;
+                                }
+                            } else {
+                                if let Some(keyframe) = map.last_keyframe() {
+                                    for m in match_features(&keyframe.features, &left_features, &map.config.matcher) {
+                                        map_points[m.current] = keyframe.map_points[m.previous];
+                                    }
+                                }
+
+                                // If the matches do not agree on a pose the frame tracks nothing, which
+                                // loses tracking
+                                match tracker.track(map, &left_features, &map_points) {
+                                    Some(tracked) => {
+                                        pose = tracked.pose;
+                                        map_points = tracked.map_points;
+                                    },
+                                    None => map_points = vec![None; left_features.len()]
+                                }
+
+                                let position = pose.position();
+                                println!("Camera position: ({:.3}, {:.3}, {:.3})", position.x, position.y, position.z);
+                            }
+
+                            let tracked_points = map_points.iter().flatten().count();
+                            let state = update_tracking_state(&mut tracker, map, tracked_points, window, &visualization_program);
+
+                            if state != TrackingState::Lost && map.needs_keyframe(frames_since_keyframe, tracked_points) {
+                                // Keypoints that do not see a map point yet add their stereo point, which
+                                // keeps the metric scale instead of triangulating across keyframes
+                                let world_from_left = pose.camera_from_world.inverse() * geometry::isometry(rectification.left_rotation, Vector3::zeros());
+
+                                let (_, refined_pose) = insert_keyframe(map, pose, left_features.clone(), map_points, |map, keyframe| {
+                                    let mut created = 0;
+
+                                    for (keypoint, point) in &points {
+                                        if map.keyframe(keyframe).unwrap().map_points[*keypoint].is_none() {
+                                            map.add_point(world_from_left * point, &[Observation { keyframe, keypoint: *keypoint }]);
+                                            created += 1;
+                                        }
+                                    }
+
+                                    created
+                                });
+
+                                pose = refined_pose;
+                                frames_since_keyframe = 0;
+                            }
+                        } else if points.len() >= MIN_STEREO_POINTS {
+                            let mut depths: Vec<f64> = points.iter().map(|(_, point)| point.z).collect();
+                            depths.sort_by(f64::total_cmp);
+
+                            // The world frame is the left camera frame, seen through the rectified intrinsics
+                            // Stereo maps have a metric scale, loops only correct rotation and translation
+                            let mut map_config = MapConfig::default();
+                            map_config.pose_graph.optimize_scale = false;
+
+                            let mut stereo_map = Map::new(map_config, rectification.intrinsics);
+                            pose = Pose { camera_from_world: geometry::isometry(rectification.left_rotation, Vector3::zeros()) };
+                            let keyframe = stereo_map.insert_keyframe(pose, left_features.clone(), vec![None; left_features.len()]);
+
+                            for (keypoint, point) in &points {
+                                stereo_map.add_point(*point, &[Observation { keyframe, keypoint: *keypoint }]);
+                            }
+
+                            println!("Initialized map from stereo with {} points, median depth {:.3}", points.len(), depths[depths.len() / 2]);
+
+                            // Enough points to start tracking right away
+                            update_tracking_state(&mut tracker, &stereo_map, points.len(), window, &visualization_program);
+
+                            frames_since_keyframe = 0;
+                            map = Some(stereo_map);
+                        }
+
+                        visualization_program.run(left_features.len() as u32);
+                    }
+                }
+
+                pipeline_durations.record(pipeline_started.elapsed());
+
+                if last_stats_report.elapsed() >= STATS_INTERVAL {
+                    println!("Capture: {}", group.stats());
+                    println!("Pipeline: {}", pipeline_durations);
+                    pipeline_durations.clear();
+                    last_stats_report = Instant::now();
+                }
 
                 window.request_redraw();
             },
@@ -358,10 +927,272 @@ fn run(
             },
             _ => {}
         }
-        
+
     })
 }
 
+enum StereoMode {
+    Calibrate(StereoCalibrationSession),
+    Triangulate {
+        rectification: Rectification,
+        maps: [RectificationMap; 2]
+    }
+}
+
+fn create_orb_program(width: u32, height: u32) -> OrbProgram {
+    let mut orb_program = OrbProgram {
+        config: OrbConfig {
+            max_features: 4096,
+            image_size: wgpu::Extent3d { 
+                width, 
+                height, 
+                depth_or_array_layers: 1
+            },
+            hierarchy_depth: 3,
+            initial_threshold: 0.4,
+        },
+        compute: Compute::new(
+            wgpu::Features::PUSH_CONSTANTS,
+            {
+                let mut limits = wgpu::Limits::default();
+                limits.max_push_constant_size = 4;
+                limits.max_storage_buffers_per_shader_stage = 8;
+                limits.max_texture_dimension_1d = 4096;
+                limits.max_texture_dimension_2d = 4096;
+                limits
+            }
+            
+        ).block_on(),
+        storage: Default::default()
+    };
+
+    orb_program.init();
+    orb_program
+}
+
+/// Extracts the ORB features of the image last written to `orb_program`.
+fn read_features(orb_program: &OrbProgram) -> FrameFeatures {
+    let corner_count = orb_program.extract_corners();
+
+    // Read corner data
+    let mut corners = vec![CornerData::zeroed(); corner_count as usize];
+    let mut descriptors = vec![CornerDescriptor::zeroed(); corner_count as usize];
+
+    orb_program.read_corners(&mut corners);
+    orb_program.read_descriptors(&mut descriptors);
+
+    FrameFeatures::from_orb(&corners, &descriptors)
+}
+
+fn finish_calibration(session: &CalibrationSession, camera_name: &str) {
+    let Some(calibration) = session.calibrate() else {
+        println!("Calibration failed, try again with more varied views.");
+        return;
+    };
+
+    let intrinsics = &calibration.intrinsics;
+
+    println!("Reprojection error: {:.3} px RMS", calibration.rms_error);
+    for (view, error) in calibration.view_errors.iter().enumerate() {
+        println!("  view {}: {:.3} px", view, error);
+    }
+    println!(
+        "fx = {:.2}, fy = {:.2}, cx = {:.2}, cy = {:.2}, distortion = {:?}",
+        intrinsics.fx, intrinsics.fy, intrinsics.cx, intrinsics.cy, intrinsics.distortion
+    );
+
+    let path = calibration_path();
+    match save_calibration(&path, camera_name, intrinsics) {
+        Ok(()) => println!("Wrote calibration of \"{}\" to {}", camera_name, path),
+        Err(why) => println!("Could not write calibration: {}", why)
+    }
+}
+
+fn finish_stereo_calibration(session: &StereoCalibrationSession, camera_names: &[String; 2]) {
+    let Some(calibration) = session.calibrate() else {
+        println!("Stereo calibration failed, try again with more varied views.");
+        return;
+    };
+
+    let rig = &calibration.rig;
+
+    println!("Reprojection error: {:.3} px RMS", calibration.rms_error);
+    for (view, error) in calibration.view_errors.iter().enumerate() {
+        println!("  view pair {}: {:.3} px", view, error);
+    }
+    println!(
+        "baseline = {:.4}, translation = ({:.4}, {:.4}, {:.4}), rotation = {:.3} degrees",
+        rig.baseline(), rig.translation.x, rig.translation.y, rig.translation.z, rig.rotation.angle().to_degrees()
+    );
+
+    let path = calibration_path();
+    match save_stereo_calibration(&path, &camera_names[0], &camera_names[1], rig) {
+        Ok(()) => println!("Wrote stereo calibration of \"{}\" and \"{}\" to {}", camera_names[0], camera_names[1], path),
+        Err(why) => println!("Could not write calibration: {}", why)
+    }
+}
+
+/// Color of the border drawn around the image for each tracking state.
+fn status_color(state: TrackingState) -> [f32; 4] {
+    match state {
+        TrackingState::NotInitialized => [0.5, 0.5, 0.5, 1.0],
+        TrackingState::Initializing => [1.0, 0.8, 0.0, 1.0],
+        TrackingState::Tracking => [0.0, 0.8, 0.2, 1.0],
+        TrackingState::Lost => [0.9, 0.1, 0.1, 1.0]
+    }
+}
+
+/// Updates the tracking state from the number of map points a frame tracks, and shows any
+/// change in the console, the window title and the status border.
+fn update_tracking_state(
+    tracker: &mut Tracker,
+    map: &Map,
+    tracked_points: usize,
+    window: &Window,
+    visualization_program: &VisualizationProgram
+) -> TrackingState {
+    let previous_state = tracker.state();
+    let state = tracker.update(map, tracked_points);
+
+    if state != previous_state {
+        println!("Tracking state: {} -> {} ({} map points tracked).", previous_state, state, tracked_points);
+        window.set_title(&format!("tinyslam example ({})", state));
+        visualization_program.set_status_color(status_color(state));
+    }
+
+    state
+}
+
+/// Inserts a frame located at `pose` as a keyframe, lets `add_points` extend the map with the
+/// points first seen from it, then refines it with local bundle adjustment.
+///
+/// Returns the keyframe and its pose after bundle adjustment.
+fn insert_keyframe(
+    map: &mut Map,
+    pose: Pose,
+    features: FrameFeatures,
+    map_points: Vec<Option<MapPointId>>,
+    add_points: impl FnOnce(&mut Map, KeyFrameId) -> usize
+) -> (KeyFrameId, Pose) {
+    let tracked_points = map_points.iter().flatten().count();
+    let keyframe = map.insert_keyframe(pose, features, map_points);
+    let created = add_points(map, keyframe);
+
+    println!("Keyframe {}: tracking {} map points, {} new ones.", keyframe, tracked_points, created);
+
+    if let Some(report) = map.local_bundle_adjustment(keyframe) {
+        println!(
+            "Local bundle adjustment: cost {:.1} -> {:.1} in {} iterations, {} outliers removed.",
+            report.initial_cost, report.final_cost, report.iterations, report.outliers.len()
+        );
+    }
+
+    (keyframe, map.keyframe(keyframe).unwrap().pose)
+}
+
+fn finish_vocabulary(images: &[Vec<Descriptor>], path: &str) {
+    let descriptors: usize = images.iter().map(Vec::len).sum();
+    println!("Training vocabulary on {} descriptors of {} keyframes...", descriptors, images.len());
+
+    let vocabulary = Vocabulary::train(images, &VocabularyConfig::default(), &mut SmallRng::seed_from_u64(0));
+
+    match vocabulary.save(path) {
+        Ok(()) => println!("Wrote vocabulary of {} words to {}", vocabulary.len(), path),
+        Err(why) => println!("Could not write vocabulary: {}", why)
+    }
+}
+
+/// Decodes camera frames into the RGBA buffer read by the ORB pipeline.
+///
+/// Frames with more than 8 bits per sample, or all frames if `--tone-map <linear|percentile|gamma>` is given,
+/// are decoded to 16-bit luminance and tone-mapped down to 8-bit gray. See `ToneMap` for the parameters.
+struct FrameDecoder {
+    tone_map: Option<ToneMap>,
+    wide_luma: Vec<u16>,
+    luma: Vec<u8>
+}
+
+impl FrameDecoder {
+    fn new(camera: &Camera, width: u32, height: u32) -> Self {
+        let tone_map = match arg_value("--tone-map").map(|tone_map| tone_map.parse::<ToneMap>()) {
+            Some(Ok(tone_map)) => Some(tone_map),
+            Some(Err(why)) => {
+                println!("Ignoring invalid --tone-map: {}", why);
+                None
+            },
+            None => None
+        }.or_else(|| (camera.camera_format().format().bit_depth() > 8).then(ToneMap::default));
+
+        if let Some(tone_map) = tone_map {
+            println!("Tone-mapping frames with {}", tone_map);
+        }
+
+        let pixel_count = (width * height) as usize;
+
+        FrameDecoder {
+            tone_map,
+            wide_luma: vec![0; pixel_count],
+            luma: vec![0; pixel_count]
+        }
+    }
+
+    fn decode(&mut self, frame: &Buffer, rgba: &mut [u8]) -> Result<(), NokhwaError> {
+        let Some(tone_map) = self.tone_map else {
+            return frame.decode_image_to_buffer::<RgbAFormat>(rgba);
+        };
+
+        frame.decode_image_to_buffer::<Luma16Format>(&mut self.wide_luma)?;
+        tone_map.apply(&self.wide_luma, &mut self.luma)?;
+
+        for (luma, pixel) in self.luma.iter().zip(rgba.chunks_exact_mut(4)) {
+            pixel.copy_from_slice(&[*luma, *luma, *luma, 255]);
+        }
+
+        Ok(())
+    }
+}
+
+/// Value following `name` on the command line.
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
+    }
+
+    None
+}
+
+fn has_flag(name: &str) -> bool {
+    std::env::args().skip(1).any(|arg| arg == name)
+}
+
+/// Calibration file given with `--calibration <path>`, `calibration.yaml` otherwise.
+fn calibration_path() -> String {
+    arg_value("--calibration").unwrap_or_else(|| "calibration.yaml".to_string())
+}
+
+/// Checkerboard given with `--board <columns>x<rows>` (inner corners) and
+/// `--square-size <size>`.
+fn calibration_config() -> CalibrationConfig {
+    let mut config = CalibrationConfig::default();
+
+    if let Some(board) = arg_value("--board") {
+        match board.split_once('x').and_then(|(c, r)| Some((c.parse().ok()?, r.parse().ok()?))) {
+            Some(pattern) => config.pattern = pattern,
+            None => println!("Ignoring invalid --board {}, expected e.g. 9x6", board)
+        }
+    }
+
+    if let Some(size) = arg_value("--square-size").and_then(|size| size.parse().ok()) {
+        config.square_size = size;
+    }
+
+    config
+}
+
 fn main() -> Result<(), winit::error::EventLoopError> {
     std::env::set_var("RUST_BACKTRACE", "1");
 
@@ -0,0 +1,721 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use nalgebra::{Isometry3, Point3, Similarity3, Vector3};
+
+use crate::bundle_adjustment::{
+    bundle_adjust, BundleAdjustmentConfig, BundleAdjustmentReport, BundleCamera, BundleObservation
+};
+use crate::camera_model::CameraIntrinsics;
+use crate::geometry::triangulate;
+use crate::loop_closure::LoopClosure;
+use crate::matching::{match_features, Descriptor, FrameFeatures, MatcherConfig};
+use crate::odometry::Pose;
+use crate::pose_graph::{PoseGraph, PoseGraphConfig, PoseGraphReport};
+
+pub type KeyFrameId = usize;
+pub type MapPointId = usize;
+
+/// A frame kept in the map, with the features it was tracked with.
+#[derive(Clone, Debug)]
+pub struct KeyFrame {
+    pub id: KeyFrameId,
+    pub pose: Pose,
+    pub features: FrameFeatures,
+    /// Map point observed by each keypoint, index aligned with `features.keypoints`.
+    pub map_points: Vec<Option<MapPointId>>
+}
+
+impl KeyFrame {
+    /// Number of keypoints observing a map point.
+    pub fn tracked_points(&self) -> usize {
+        self.map_points.iter().flatten().count()
+    }
+}
+
+/// A keypoint of a keyframe that observes a map point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Observation {
+    pub keyframe: KeyFrameId,
+    pub keypoint: usize
+}
+
+#[derive(Clone, Debug)]
+pub struct MapPoint {
+    pub id: MapPointId,
+    /// Position in world coordinates.
+    pub position: Point3<f64>,
+    pub observations: Vec<Observation>,
+    /// The observed descriptor closest to all the others, which new frames are matched
+    /// against.
+    pub descriptor: Descriptor
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MapConfig {
+    /// Frames after the last keyframe that never become keyframes.
+    pub min_frames: usize,
+    /// A keyframe is inserted at the latest this many frames after the last one.
+    pub max_frames: usize,
+    /// A frame becomes a keyframe once it tracks fewer than this fraction of the map points
+    /// of the last keyframe.
+    pub tracked_ratio: f64,
+    /// Frames tracking fewer map points than this are too unreliable to become keyframes.
+    pub min_tracked_points: usize,
+    /// Number of keyframes new points are triangulated with, the most covisible first.
+    pub triangulation_neighbours: usize,
+    /// Smallest angle between the two viewing rays of a new point, in radians.
+    pub min_parallax: f64,
+    /// Largest reprojection error of a new point in either keyframe, in pixels of the
+    /// keypoint's pyramid level.
+    pub max_reprojection_error: f64,
+    pub matcher: MatcherConfig,
+    /// Number of covisible keyframes refined together with a new keyframe by local bundle
+    /// adjustment.
+    pub local_keyframes: usize,
+    pub bundle_adjustment: BundleAdjustmentConfig,
+    /// Keyframes sharing at least this many map points keep their relative pose in the pose
+    /// graph optimized after a loop closure, besides consecutive keyframes.
+    pub min_shared_points: usize,
+    /// Set `optimize_scale` for monocular maps, clear it when the map has a metric scale,
+    /// e.g. from stereo.
+    pub pose_graph: PoseGraphConfig
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        Self {
+            min_frames: 0,
+            max_frames: 30,
+            tracked_ratio: 0.9,
+            min_tracked_points: 15,
+            triangulation_neighbours: 10,
+            min_parallax: 1.0f64.to_radians(),
+            max_reprojection_error: 2.0,
+            matcher: MatcherConfig::default(),
+            local_keyframes: 10,
+            bundle_adjustment: BundleAdjustmentConfig::default(),
+            min_shared_points: 50,
+            pose_graph: PoseGraphConfig::default()
+        }
+    }
+}
+
+/// Keyframes and the map points triangulated from them.
+///
+/// Keyframes store which map point each of their keypoints observes and map points store
+/// which keypoints observe them, the map keeps both sides in sync.
+pub struct Map {
+    pub config: MapConfig,
+    intrinsics: CameraIntrinsics,
+    keyframes: BTreeMap<KeyFrameId, KeyFrame>,
+    points: BTreeMap<MapPointId, MapPoint>,
+    next_keyframe: KeyFrameId,
+    next_point: MapPointId
+}
+
+impl Map {
+    pub fn new(config: MapConfig, intrinsics: CameraIntrinsics) -> Self {
+        Self {
+            config,
+            intrinsics,
+            keyframes: BTreeMap::new(),
+            points: BTreeMap::new(),
+            next_keyframe: 0,
+            next_point: 0
+        }
+    }
+
+    pub fn intrinsics(&self) -> &CameraIntrinsics {
+        &self.intrinsics
+    }
+
+    /// Keyframes in insertion order.
+    pub fn keyframes(&self) -> impl DoubleEndedIterator<Item = &KeyFrame> {
+        self.keyframes.values()
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = &MapPoint> {
+        self.points.values()
+    }
+
+    pub fn keyframe(&self, id: KeyFrameId) -> Option<&KeyFrame> {
+        self.keyframes.get(&id)
+    }
+
+    pub fn point(&self, id: MapPointId) -> Option<&MapPoint> {
+        self.points.get(&id)
+    }
+
+    pub fn last_keyframe(&self) -> Option<&KeyFrame> {
+        self.keyframes.values().next_back()
+    }
+
+    /// Keyframe insertion criteria, loosely following ORB-SLAM: a frame becomes a keyframe
+    /// once it tracks noticeably fewer map points than the last keyframe, or after
+    /// `max_frames`, as long as it still tracks enough points to be reliable.
+    ///
+    /// While the last keyframe has too few points to compare against, as before the first
+    /// monocular triangulation, keyframes are only spaced by `max_frames`.
+    pub fn needs_keyframe(&self, frames_since_keyframe: usize, tracked_points: usize) -> bool {
+        let Some(last) = self.last_keyframe() else {
+            return true;
+        };
+
+        if frames_since_keyframe < self.config.min_frames {
+            return false;
+        }
+
+        let reference = last.tracked_points();
+
+        if reference < self.config.min_tracked_points {
+            return frames_since_keyframe >= self.config.max_frames;
+        }
+
+        tracked_points >= self.config.min_tracked_points && (
+            frames_since_keyframe >= self.config.max_frames ||
+            (tracked_points as f64) < self.config.tracked_ratio * reference as f64
+        )
+    }
+
+    /// Adds a keyframe whose keypoints observe `map_points`, as found while tracking it.
+    /// Unknown map points are ignored.
+    pub fn insert_keyframe(
+        &mut self,
+        pose: Pose,
+        features: FrameFeatures,
+        map_points: Vec<Option<MapPointId>>
+    ) -> KeyFrameId {
+        assert_eq!(features.len(), map_points.len());
+
+        let id = self.next_keyframe;
+        self.next_keyframe += 1;
+
+        let map_points: Vec<Option<MapPointId>> = map_points.into_iter()
+            .map(|point| point.filter(|point| self.points.contains_key(point)))
+            .collect();
+
+        for (keypoint, point) in map_points.iter().enumerate() {
+            if let Some(point) = point {
+                self.points.get_mut(point).unwrap().observations.push(Observation { keyframe: id, keypoint });
+            }
+        }
+
+        self.keyframes.insert(id, KeyFrame { id, pose, features, map_points: map_points.clone() });
+
+        for point in map_points.into_iter().flatten() {
+            self.update_descriptor(point);
+        }
+
+        id
+    }
+
+    /// Adds a point at `position` in world coordinates, seen by the given keypoints.
+    ///
+    /// Panics if an observation refers to an unknown keyframe or keypoint.
+    pub fn add_point(&mut self, position: Point3<f64>, observations: &[Observation]) -> MapPointId {
+        let id = self.next_point;
+        self.next_point += 1;
+
+        for observation in observations {
+            self.keyframes.get_mut(&observation.keyframe).unwrap().map_points[observation.keypoint] = Some(id);
+        }
+
+        self.points.insert(id, MapPoint {
+            id,
+            position,
+            observations: observations.to_vec(),
+            descriptor: Descriptor::default()
+        });
+        self.update_descriptor(id);
+
+        id
+    }
+
+    /// Removes an observation of a point, and the point once nothing observes it anymore.
+    fn remove_observation(&mut self, id: MapPointId, observation: Observation) {
+        let Some(point) = self.points.get_mut(&id) else {
+            return;
+        };
+
+        point.observations.retain(|other| *other != observation);
+
+        if let Some(keyframe) = self.keyframes.get_mut(&observation.keyframe) {
+            keyframe.map_points[observation.keypoint] = None;
+        }
+
+        if point.observations.is_empty() {
+            self.points.remove(&id);
+        } else {
+            self.update_descriptor(id);
+        }
+    }
+
+    /// Picks the observed descriptor with the smallest median distance to the others, as
+    /// ORB-SLAM does.
+    fn update_descriptor(&mut self, id: MapPointId) {
+        let Some(point) = self.points.get(&id) else {
+            return;
+        };
+
+        let descriptors: Vec<Descriptor> = point.observations.iter()
+            .filter_map(|observation| {
+                let keyframe = self.keyframes.get(&observation.keyframe)?;
+                keyframe.features.descriptors.get(observation.keypoint).copied()
+            })
+            .collect();
+
+        let median_distance = |descriptor: &Descriptor| {
+            let mut distances: Vec<u32> = descriptors.iter().map(|other| descriptor.distance(other)).collect();
+            distances.sort_unstable();
+            distances[(distances.len() - 1) / 2]
+        };
+
+        if let Some(best) = descriptors.iter().min_by_key(|descriptor| median_distance(descriptor)) {
+            self.points.get_mut(&id).unwrap().descriptor = *best;
+        }
+    }
+
+    /// Up to `count` keyframes sharing the most map points with `keyframe`, most shared
+    /// first.
+    pub fn covisible_keyframes(&self, keyframe: KeyFrameId, count: usize) -> Vec<KeyFrameId> {
+        let Some(frame) = self.keyframes.get(&keyframe) else {
+            return Vec::new();
+        };
+
+        let mut shared: HashMap<KeyFrameId, usize> = HashMap::new();
+
+        for point in frame.map_points.iter().flatten().filter_map(|id| self.points.get(id)) {
+            for observation in &point.observations {
+                if observation.keyframe != keyframe {
+                    *shared.entry(observation.keyframe).or_default() += 1;
+                }
+            }
+        }
+
+        let mut ranked: Vec<(KeyFrameId, usize)> = shared.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        ranked.into_iter()
+            .take(count)
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// The local map of `keyframe`: the points seen by it and by its `count` most covisible
+    /// keyframes.
+    pub fn local_points(&self, keyframe: KeyFrameId, count: usize) -> Vec<MapPointId> {
+        std::iter::once(keyframe)
+            .chain(self.covisible_keyframes(keyframe, count))
+            .filter_map(|id| self.keyframes.get(&id))
+            .flat_map(|frame| frame.map_points.iter().flatten().copied())
+            .collect::<BTreeSet<MapPointId>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Triangulates new map points from the keypoints of `keyframe` that do not observe one
+    /// yet, by matching them against its most covisible keyframes, or the most recent ones
+    /// if it has none. Returns the number of points created.
+    pub fn triangulate_new_points(&mut self, keyframe: KeyFrameId) -> usize {
+        if !self.keyframes.contains_key(&keyframe) {
+            return 0;
+        }
+
+        let count = self.config.triangulation_neighbours;
+        let mut neighbours = self.covisible_keyframes(keyframe, count);
+
+        for id in self.keyframes.keys().rev() {
+            if neighbours.len() >= count {
+                break;
+            }
+
+            if *id != keyframe && !neighbours.contains(id) {
+                neighbours.push(*id);
+            }
+        }
+
+        let mut created = 0;
+
+        for neighbour in neighbours {
+            let current = &self.keyframes[&keyframe];
+            let other = &self.keyframes[&neighbour];
+
+            let (current_features, current_indices) = unmapped_features(current);
+            let (other_features, other_indices) = unmapped_features(other);
+
+            let new_points: Vec<(Point3<f64>, [Observation; 2])> = match_features(&other_features, &current_features, &self.config.matcher)
+                .into_iter()
+                .filter_map(|m| {
+                    let (a, b) = (other_indices[m.previous], current_indices[m.current]);
+                    let position = self.triangulate_pair(other, a, current, b)?;
+
+                    Some((position, [
+                        Observation { keyframe: neighbour, keypoint: a },
+                        Observation { keyframe, keypoint: b }
+                    ]))
+                })
+                .collect();
+
+            created += new_points.len();
+
+            for (position, observations) in new_points {
+                self.add_point(position, &observations);
+            }
+        }
+
+        created
+    }
+
+    /// Local bundle adjustment around `keyframe`, as in ORB-SLAM's local mapping: refines
+    /// the poses of `keyframe` and its most covisible keyframes together with the points they
+    /// see. The other keyframes seeing these points, and the first keyframe which fixes the
+    /// world frame, keep their pose. Observations that remain outliers are removed.
+    ///
+    /// Returns `None` if the keyframe does not exist.
+    pub fn local_bundle_adjustment(&mut self, keyframe: KeyFrameId) -> Option<BundleAdjustmentReport> {
+        if !self.keyframes.contains_key(&keyframe) {
+            return None;
+        }
+
+        let first = *self.keyframes.keys().next()?;
+
+        let local: BTreeSet<KeyFrameId> = std::iter::once(keyframe)
+            .chain(self.covisible_keyframes(keyframe, self.config.local_keyframes))
+            .collect();
+        let point_ids = self.local_points(keyframe, self.config.local_keyframes);
+
+        let mut camera_ids: Vec<KeyFrameId> = local.iter().copied().collect();
+        let mut camera_index: HashMap<KeyFrameId, usize> = camera_ids.iter()
+            .enumerate()
+            .map(|(index, id)| (*id, index))
+            .collect();
+
+        let mut points = Vec::with_capacity(point_ids.len());
+        let mut observations = Vec::new();
+        let mut observed: Vec<(MapPointId, Observation)> = Vec::new();
+
+        for (index, id) in point_ids.iter().enumerate() {
+            let point = &self.points[id];
+            points.push(point.position);
+
+            for observation in &point.observations {
+                let camera = *camera_index.entry(observation.keyframe).or_insert_with(|| {
+                    camera_ids.push(observation.keyframe);
+                    camera_ids.len() - 1
+                });
+
+                let keypoint = self.keyframes[&observation.keyframe].features.keypoints[observation.keypoint];
+
+                observations.push(BundleObservation {
+                    camera,
+                    point: index,
+                    pixel: self.intrinsics.undistort_pixel(&keypoint.pixel()),
+                    sigma: (1u32 << keypoint.octave) as f64
+                });
+                observed.push((*id, *observation));
+            }
+        }
+
+        let mut cameras: Vec<BundleCamera> = camera_ids.iter()
+            .map(|id| BundleCamera {
+                camera_from_world: self.keyframes[id].pose.camera_from_world,
+                fixed: *id == first || !local.contains(id)
+            })
+            .collect();
+
+        let report = bundle_adjust(&self.intrinsics, &mut cameras, &mut points, &observations, &self.config.bundle_adjustment);
+
+        for (id, camera) in camera_ids.iter().zip(&cameras) {
+            self.keyframes.get_mut(id).unwrap().pose.camera_from_world = camera.camera_from_world;
+        }
+
+        for (id, position) in point_ids.iter().zip(points) {
+            self.points.get_mut(id).unwrap().position = position;
+        }
+
+        for outlier in &report.outliers {
+            let (id, observation) = observed[*outlier];
+            self.remove_observation(id, observation);
+        }
+
+        Some(report)
+    }
+
+    /// Closes a loop found by `LoopDetector`: optimizes the pose graph of all keyframes with
+    /// the loop edge added, which spreads the accumulated drift along the loop, then moves
+    /// every map point with its first observing keyframe and fuses the matched points of the
+    /// loop.
+    ///
+    /// The graph has edges between consecutive keyframes and between keyframes sharing at
+    /// least `min_shared_points` points. The candidate keyframe and the first one keep their
+    /// pose.
+    pub fn close_loop(&mut self, closure: &LoopClosure) -> Option<PoseGraphReport> {
+        if !self.keyframes.contains_key(&closure.query) || !self.keyframes.contains_key(&closure.candidate) {
+            return None;
+        }
+
+        let first = *self.keyframes.keys().next()?;
+        let ids: Vec<KeyFrameId> = self.keyframes.keys().copied().collect();
+        let node: HashMap<KeyFrameId, usize> = ids.iter().enumerate().map(|(index, id)| (*id, index)).collect();
+
+        let mut graph = PoseGraph::new();
+
+        for id in &ids {
+            let pose = self.keyframes[id].pose.camera_from_world;
+            graph.add_node(Similarity3::from_isometry(pose, 1.0), *id == first || *id == closure.candidate);
+        }
+
+        for index in 1..ids.len() {
+            graph.add_relative_edge(index - 1, index);
+        }
+
+        for (index, id) in ids.iter().enumerate() {
+            let mut shared: HashMap<KeyFrameId, usize> = HashMap::new();
+
+            for point in self.keyframes[id].map_points.iter().flatten().filter_map(|point| self.points.get(point)) {
+                for observation in &point.observations {
+                    *shared.entry(observation.keyframe).or_default() += 1;
+                }
+            }
+
+            for (other, count) in shared {
+                let other = node[&other];
+
+                // Consecutive keyframes are already connected
+                if other > index + 1 && count >= self.config.min_shared_points {
+                    graph.add_relative_edge(index, other);
+                }
+            }
+        }
+
+        let mut measurement = closure.query_from_candidate;
+        if !self.config.pose_graph.optimize_scale {
+            measurement.set_scaling(1.0);
+        }
+
+        graph.add_edge(node[&closure.candidate], node[&closure.query], measurement);
+
+        let report = graph.optimize(&self.config.pose_graph);
+
+        // Points move rigidly with their first observing keyframe
+        for point in self.points.values_mut() {
+            let Some(observation) = point.observations.first() else { continue; };
+            let before = self.keyframes[&observation.keyframe].pose.camera_from_world;
+            let after = graph.poses()[node[&observation.keyframe]];
+
+            point.position = after.inverse() * (before * point.position);
+        }
+
+        for (id, pose) in ids.iter().zip(graph.poses()) {
+            // The camera frame of a similarity pose is scaled, its metric pose divides the
+            // translation by the scale
+            let scale = pose.scaling();
+            let camera_from_world = Isometry3::from_parts(
+                (pose.isometry.translation.vector / scale).into(),
+                pose.isometry.rotation
+            );
+
+            self.keyframes.get_mut(id).unwrap().pose = Pose { camera_from_world };
+        }
+
+        for (query_point, candidate_point) in &closure.matches {
+            self.merge_points(*candidate_point, *query_point);
+        }
+
+        Some(report)
+    }
+
+    /// Merges point `other` into point `id`, which takes over its observations.
+    fn merge_points(&mut self, id: MapPointId, other: MapPointId) {
+        if id == other || !self.points.contains_key(&id) {
+            return;
+        }
+
+        let Some(removed) = self.points.remove(&other) else {
+            return;
+        };
+
+        for observation in removed.observations {
+            let keyframe = self.keyframes.get_mut(&observation.keyframe).unwrap();
+
+            // A keyframe seeing both points keeps its observation of `id`
+            if self.points[&id].observations.iter().any(|existing| existing.keyframe == observation.keyframe) {
+                keyframe.map_points[observation.keypoint] = None;
+                continue;
+            }
+
+            keyframe.map_points[observation.keypoint] = Some(id);
+            self.points.get_mut(&id).unwrap().observations.push(observation);
+        }
+
+        self.update_descriptor(id);
+    }
+
+    /// Triangulates keypoint `a` of `first` and keypoint `b` of `second`, rejecting points
+    /// with too little parallax, behind either camera or with a large reprojection error.
+    fn triangulate_pair(&self, first: &KeyFrame, a: usize, second: &KeyFrame, b: usize) -> Option<Point3<f64>> {
+        let keypoints = [first.features.keypoints[a], second.features.keypoints[b]];
+        let poses = [first.pose.camera_from_world, second.pose.camera_from_world];
+        let normalized = keypoints.map(|keypoint| self.intrinsics.unproject(&keypoint.pixel()));
+
+        // Angle between the viewing rays, in world coordinates
+        let rays: Vec<Vector3<f64>> = poses.iter()
+            .zip(&normalized)
+            .map(|(pose, point)| (pose.rotation.inverse() * Vector3::new(point.x, point.y, 1.0)).normalize())
+            .collect();
+
+        if rays[0].dot(&rays[1]) > self.config.min_parallax.cos() {
+            return None;
+        }
+
+        let position = triangulate(&poses[0], &poses[1], &normalized[0], &normalized[1])?;
+
+        for (pose, keypoint) in poses.iter().zip(&keypoints) {
+            let projected = self.intrinsics.project(&(pose * position))?;
+            let tolerance = self.config.max_reprojection_error * (1u32 << keypoint.octave) as f64;
+
+            if (projected - keypoint.pixel()).norm() > tolerance {
+                return None;
+            }
+        }
+
+        Some(position)
+    }
+}
+
+/// The features of the keypoints of `keyframe` without a map point, and their indices in the
+/// keyframe.
+fn unmapped_features(keyframe: &KeyFrame) -> (FrameFeatures, Vec<usize>) {
+    let indices: Vec<usize> = (0..keyframe.map_points.len())
+        .filter(|index| keyframe.map_points[*index].is_none())
+        .collect();
+
+    let features = FrameFeatures {
+        keypoints: indices.iter().map(|index| keyframe.features.keypoints[*index]).collect(),
+        descriptors: indices.iter().map(|index| keyframe.features.descriptors[*index]).collect()
+    };
+
+    (features, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use crate::test_util::{intrinsics, observe, pose};
+
+    /// World points in front of the origin, each with its own random descriptor.
+    fn synthetic_scene(count: usize, rng: &mut SmallRng) -> (Vec<Point3<f64>>, Vec<Descriptor>) {
+        let points = (0..count)
+            .map(|_| Point3::new(rng.gen_range(-2.0..2.0), rng.gen_range(-1.5..1.5), rng.gen_range(4.0..8.0)))
+            .collect();
+        let descriptors = (0..count).map(|_| Descriptor(rng.gen())).collect();
+
+        (points, descriptors)
+    }
+
+    /// A map whose only keyframe tracks `tracked` map points.
+    fn map_with_keyframe(tracked: usize) -> Map {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let (points, descriptors) = synthetic_scene(tracked, &mut rng);
+        let (features, indices) = observe(&Pose::identity(), &points, &descriptors);
+
+        let mut map = Map::new(MapConfig { min_frames: 2, ..Default::default() }, intrinsics());
+        let keyframe = map.insert_keyframe(Pose::identity(), features.clone(), vec![None; features.len()]);
+
+        for (keypoint, index) in indices.iter().enumerate() {
+            map.add_point(points[*index], &[Observation { keyframe, keypoint }]);
+        }
+
+        map
+    }
+
+    #[test]
+    fn first_frame_is_a_keyframe() {
+        let map = Map::new(MapConfig::default(), intrinsics());
+        assert!(map.needs_keyframe(0, 0));
+    }
+
+    #[test]
+    fn keyframe_when_tracking_drops() {
+        let map = map_with_keyframe(100);
+        assert_eq!(map.last_keyframe().unwrap().tracked_points(), 100);
+
+        // Still tracking most of the last keyframe's points
+        assert!(!map.needs_keyframe(5, 95));
+        assert!(!map.needs_keyframe(5, 90));
+        // Noticeably fewer
+        assert!(map.needs_keyframe(5, 89));
+        // Too soon after the last keyframe
+        assert!(!map.needs_keyframe(1, 50));
+        // Too few to be reliable
+        assert!(!map.needs_keyframe(5, 10));
+        // Too long since the last keyframe
+        assert!(map.needs_keyframe(30, 95));
+        assert!(!map.needs_keyframe(30, 10));
+    }
+
+    #[test]
+    fn keyframes_spaced_without_reference_points() {
+        let map = map_with_keyframe(5);
+
+        assert!(!map.needs_keyframe(5, 0));
+        assert!(!map.needs_keyframe(29, 3));
+        assert!(map.needs_keyframe(30, 0));
+    }
+
+    #[test]
+    fn triangulates_two_keyframes() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        let (points, descriptors) = synthetic_scene(200, &mut rng);
+
+        let poses = [pose(0.0, 0.0), pose(0.5, 0.05)];
+        let (first_features, first_indices) = observe(&poses[0], &points, &descriptors);
+        let (second_features, second_indices) = observe(&poses[1], &points, &descriptors);
+        let shared = first_indices.iter().filter(|index| second_indices.contains(index)).count();
+        assert!(shared >= 100);
+
+        let mut map = Map::new(MapConfig::default(), intrinsics());
+        let first = map.insert_keyframe(poses[0], first_features.clone(), vec![None; first_features.len()]);
+        let second = map.insert_keyframe(poses[1], second_features.clone(), vec![None; second_features.len()]);
+
+        let created = map.triangulate_new_points(second);
+        assert!(created >= shared * 9 / 10);
+        assert_eq!(map.points().count(), created);
+
+        for point in map.points() {
+            assert_eq!(point.observations.len(), 2);
+
+            let [a, b] = [0, 1].map(|i| point.observations[i]);
+            assert_eq!((a.keyframe, b.keyframe), (first, second));
+            assert_eq!(first_indices[a.keypoint], second_indices[b.keypoint]);
+            assert_eq!(map.keyframe(first).unwrap().map_points[a.keypoint], Some(point.id));
+            assert_eq!(map.keyframe(second).unwrap().map_points[b.keypoint], Some(point.id));
+
+            let truth = points[first_indices[a.keypoint]];
+            assert!((point.position - truth).norm() < 1e-3 * truth.z);
+            assert_eq!(point.descriptor, descriptors[first_indices[a.keypoint]]);
+        }
+
+        // Every keypoint seen by both is mapped already
+        assert_eq!(map.triangulate_new_points(second), 0);
+    }
+
+    #[test]
+    fn rejects_points_without_parallax() {
+        let mut rng = SmallRng::seed_from_u64(3);
+        let (points, descriptors) = synthetic_scene(200, &mut rng);
+
+        // A pure rotation, which can not triangulate anything
+        let poses = [pose(0.0, 0.0), pose(0.0, 0.05)];
+
+        let mut map = Map::new(MapConfig::default(), intrinsics());
+        for pose in poses {
+            let (features, _) = observe(&pose, &points, &descriptors);
+            map.insert_keyframe(pose, features.clone(), vec![None; features.len()]);
+        }
+
+        assert_eq!(map.triangulate_new_points(1), 0);
+    }
+}
@@ -0,0 +1,366 @@
+use tinyslam::orb::{CornerData, CornerDescriptor};
+
+/// A corner reported by `OrbProgram`, in full resolution pixel coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keypoint {
+    pub x: f32,
+    pub y: f32,
+    /// Orientation in radians.
+    pub angle: f32,
+    pub octave: u32
+}
+
+impl Keypoint {
+    pub fn from_corner(corner: &CornerData) -> Self {
+        // Same layout the draw_corners vertex buffer reads: x, y, angle (milliradians), octave
+        let [x, y, angle, octave] = bytemuck::cast::<CornerData, [u32; 4]>(*corner);
+        let scale = (1u32 << octave) as f32;
+
+        Self {
+            x: x as f32 * scale,
+            y: y as f32 * scale,
+            angle: angle as f32 / 1000.0,
+            octave
+        }
+    }
+
+    pub fn pixel(&self) -> nalgebra::Point2<f64> {
+        nalgebra::Point2::new(self.x as f64, self.y as f64)
+    }
+}
+
+/// A 256-bit rBRIEF descriptor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Descriptor(pub [u32; 8]);
+
+impl Descriptor {
+    pub fn distance(&self, other: &Descriptor) -> u32 {
+        self.0.iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum()
+    }
+}
+
+impl From<&CornerDescriptor> for Descriptor {
+    fn from(descriptor: &CornerDescriptor) -> Self {
+        Self(bytemuck::cast::<CornerDescriptor, [u32; 8]>(*descriptor))
+    }
+}
+
+/// Keypoints and descriptors of a single frame, index aligned.
+#[derive(Clone, Debug, Default)]
+pub struct FrameFeatures {
+    pub keypoints: Vec<Keypoint>,
+    pub descriptors: Vec<Descriptor>
+}
+
+impl FrameFeatures {
+    pub fn from_orb(corners: &[CornerData], descriptors: &[CornerDescriptor]) -> Self {
+        assert_eq!(corners.len(), descriptors.len());
+
+        Self {
+            keypoints: corners.iter().map(Keypoint::from_corner).collect(),
+            descriptors: descriptors.iter().map(Descriptor::from).collect()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keypoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keypoints.is_empty()
+    }
+}
+
+/// A correspondence between a keypoint of the previous frame and one of the current frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Match {
+    pub previous: usize,
+    pub current: usize,
+    pub distance: u32
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MatcherConfig {
+    /// Matches with a larger Hamming distance are rejected outright.
+    pub max_distance: u32,
+    /// Lowe's ratio test: the best distance must be below `ratio` times the second best.
+    pub ratio: f32,
+    /// Only keep matches that are also the best match in the opposite direction.
+    pub cross_check: bool,
+    /// Candidates more than this many pyramid levels apart are never compared.
+    pub max_octave_difference: u32,
+    /// Number of bins of the rotation consistency histogram, 0 disables the filter.
+    pub rotation_bins: usize
+}
+
+impl Default for MatcherConfig {
+    fn default() -> Self {
+        Self {
+            max_distance: 64,
+            ratio: 0.8,
+            cross_check: true,
+            max_octave_difference: 1,
+            rotation_bins: 30
+        }
+    }
+}
+
+struct Nearest {
+    index: usize,
+    best: u32,
+    second: u32
+}
+
+fn nearest(
+    descriptor: &Descriptor,
+    keypoint: &Keypoint,
+    keypoints: &[Keypoint],
+    descriptors: &[Descriptor],
+    config: &MatcherConfig
+) -> Option<Nearest> {
+    let mut result: Option<Nearest> = None;
+
+    for (index, (other_keypoint, other)) in keypoints.iter().zip(descriptors).enumerate() {
+        if keypoint.octave.abs_diff(other_keypoint.octave) > config.max_octave_difference {
+            continue;
+        }
+
+        let distance = descriptor.distance(other);
+
+        match &mut result {
+            None => result = Some(Nearest { index, best: distance, second: u32::MAX }),
+            Some(nearest) if distance < nearest.best => {
+                nearest.second = nearest.best;
+                nearest.best = distance;
+                nearest.index = index;
+            },
+            Some(nearest) if distance < nearest.second => {
+                nearest.second = distance;
+            },
+            _ => {}
+        }
+    }
+
+    result
+}
+
+/// Brute force matching of the previous frame's descriptors against the current frame's.
+///
+/// Matches are filtered by Hamming distance, the ratio test, an optional cross check and
+/// finally by rotation consistency, since the whole image rotates by the same amount
+/// between two consecutive frames.
+pub fn match_features(
+    previous: &FrameFeatures,
+    current: &FrameFeatures,
+    config: &MatcherConfig
+) -> Vec<Match> {
+    let reverse: Vec<Option<usize>> = if config.cross_check {
+        previous.keypoints.iter()
+            .zip(&previous.descriptors)
+            .map(|(keypoint, descriptor)| {
+                nearest(descriptor, keypoint, &current.keypoints, &current.descriptors, config)
+                    .map(|nearest| nearest.index)
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut matches = Vec::new();
+
+    for (index, (keypoint, descriptor)) in current.keypoints.iter().zip(&current.descriptors).enumerate() {
+        let Some(nearest) = nearest(descriptor, keypoint, &previous.keypoints, &previous.descriptors, config) else {
+            continue;
+        };
+
+        if nearest.best > config.max_distance {
+            continue;
+        }
+
+        if nearest.second != u32::MAX && nearest.best as f32 >= config.ratio * nearest.second as f32 {
+            continue;
+        }
+
+        if config.cross_check && reverse[nearest.index] != Some(index) {
+            continue;
+        }
+
+        matches.push(Match {
+            previous: nearest.index,
+            current: index,
+            distance: nearest.best
+        });
+    }
+
+    if config.rotation_bins > 0 {
+        matches = filter_rotation(matches, previous, current, config.rotation_bins);
+    }
+
+    matches
+}
+
+/// Keeps the matches whose change in orientation falls in one of the three most populated
+/// histogram bins.
+fn filter_rotation(
+    matches: Vec<Match>,
+    previous: &FrameFeatures,
+    current: &FrameFeatures,
+    bins: usize
+) -> Vec<Match> {
+    let tau = std::f32::consts::TAU;
+
+    let bin_of = |m: &Match| {
+        let delta = (current.keypoints[m.current].angle - previous.keypoints[m.previous].angle).rem_euclid(tau);
+        ((delta / tau * bins as f32) as usize).min(bins - 1)
+    };
+
+    let mut histogram = vec![0usize; bins];
+    for m in &matches {
+        histogram[bin_of(m)] += 1;
+    }
+
+    let mut ranked: Vec<usize> = (0..bins).filter(|&bin| histogram[bin] > 0).collect();
+    ranked.sort_by(|a, b| histogram[*b].cmp(&histogram[*a]));
+
+    // Like ORB-SLAM, secondary peaks only count if they are reasonably large
+    let Some(&top) = ranked.first() else { return matches; };
+    let kept: Vec<usize> = ranked.iter()
+        .take(3)
+        .copied()
+        .filter(|&bin| bin == top || histogram[bin] * 10 >= histogram[top])
+        .collect();
+
+    matches.into_iter()
+        .filter(|m| kept.contains(&bin_of(m)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    fn keypoint(angle: f32) -> Keypoint {
+        Keypoint { x: 0.0, y: 0.0, angle, octave: 0 }
+    }
+
+    fn random_descriptor(rng: &mut SmallRng) -> Descriptor {
+        Descriptor(rng.gen())
+    }
+
+    /// Flips `bits` distinct bits of `descriptor`.
+    fn flip_bits(descriptor: &Descriptor, bits: usize, rng: &mut SmallRng) -> Descriptor {
+        let mut flipped = *descriptor;
+
+        for bit in rand::seq::index::sample(rng, 256, bits) {
+            flipped.0[bit / 32] ^= 1 << (bit % 32);
+        }
+
+        flipped
+    }
+
+    fn features(descriptors: Vec<Descriptor>, angle: f32) -> FrameFeatures {
+        FrameFeatures {
+            keypoints: vec![keypoint(angle); descriptors.len()],
+            descriptors
+        }
+    }
+
+    #[test]
+    fn matches_shuffled_noisy_descriptors() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let descriptors: Vec<Descriptor> = (0..200).map(|_| random_descriptor(&mut rng)).collect();
+
+        // The current frame sees the same descriptors in another order with a few flipped bits
+        let order: Vec<usize> = rand::seq::index::sample(&mut rng, 200, 200).into_vec();
+        let noisy: Vec<Descriptor> = order.iter().map(|index| flip_bits(&descriptors[*index], 10, &mut rng)).collect();
+
+        let matches = match_features(&features(descriptors, 0.0), &features(noisy, 0.5), &MatcherConfig::default());
+
+        assert_eq!(matches.len(), 200);
+
+        for m in &matches {
+            assert_eq!(m.previous, order[m.current]);
+            assert_eq!(m.distance, 10);
+        }
+    }
+
+    #[test]
+    fn rejects_distant_and_ambiguous_matches() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        let a = random_descriptor(&mut rng);
+        let b = flip_bits(&a, 20, &mut rng);
+        let previous = features(vec![a, b], 0.0);
+
+        // Halfway between both previous descriptors, so the ratio test rejects it
+        let mut between = a;
+        let mut differing = (0..256).filter(|bit| (a.0[bit / 32] ^ b.0[bit / 32]) & (1 << (bit % 32)) != 0);
+        for bit in differing.by_ref().take(10) {
+            between.0[bit / 32] ^= 1 << (bit % 32);
+        }
+
+        let far = flip_bits(&a, 100, &mut rng);
+        let current = features(vec![between, far], 0.0);
+
+        let config = MatcherConfig { cross_check: false, ..MatcherConfig::default() };
+        assert!(match_features(&previous, &current, &config).is_empty());
+
+        // Without the ratio test the ambiguous match goes through, the distant one never does
+        let config = MatcherConfig { ratio: 1.1, ..config };
+        let matches = match_features(&previous, &current, &config);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].current, 0);
+    }
+
+    #[test]
+    fn cross_check_keeps_mutual_best_matches() {
+        let mut rng = SmallRng::seed_from_u64(3);
+        let descriptor = random_descriptor(&mut rng);
+        let previous = features(vec![descriptor, random_descriptor(&mut rng)], 0.0);
+
+        // Both current descriptors are closest to the first previous one
+        let current = features(vec![flip_bits(&descriptor, 5, &mut rng), flip_bits(&descriptor, 15, &mut rng)], 0.0);
+
+        let config = MatcherConfig { cross_check: false, ..MatcherConfig::default() };
+        assert_eq!(match_features(&previous, &current, &config).len(), 2);
+
+        let config = MatcherConfig { cross_check: true, ..config };
+        let matches = match_features(&previous, &current, &config);
+        assert_eq!(matches, vec![Match { previous: 0, current: 0, distance: 5 }]);
+    }
+
+    #[test]
+    fn skips_other_octaves() {
+        let mut rng = SmallRng::seed_from_u64(4);
+        let descriptor = random_descriptor(&mut rng);
+        let previous = FrameFeatures {
+            keypoints: vec![Keypoint { octave: 3, ..keypoint(0.0) }],
+            descriptors: vec![descriptor]
+        };
+
+        assert!(match_features(&previous, &features(vec![descriptor], 0.0), &MatcherConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn rotation_histogram_removes_inconsistent_matches() {
+        let mut rng = SmallRng::seed_from_u64(5);
+        let descriptors: Vec<Descriptor> = (0..100).map(|_| random_descriptor(&mut rng)).collect();
+        let previous = features(descriptors.clone(), 1.0);
+
+        // Every keypoint rotates by 0.3 radians, except five that rotate by half a turn
+        let mut current = features(descriptors, 1.3);
+        for keypoint in &mut current.keypoints[..5] {
+            keypoint.angle = 1.0 + std::f32::consts::PI;
+        }
+
+        let config = MatcherConfig { rotation_bins: 0, ..MatcherConfig::default() };
+        assert_eq!(match_features(&previous, &current, &config).len(), 100);
+
+        let matches = match_features(&previous, &current, &MatcherConfig::default());
+        assert_eq!(matches.len(), 95);
+        assert!(matches.iter().all(|m| m.current >= 5));
+    }
+}
@@ -0,0 +1,195 @@
+use nalgebra::{Isometry3, Point2, Point3};
+use rand::{rngs::SmallRng, SeedableRng};
+
+use crate::geometry::{ransac_essential, recover_pose, Correspondence, RansacConfig};
+
+/// Pose of the camera, mapping world coordinates into the camera frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pose {
+    pub camera_from_world: Isometry3<f64>
+}
+
+impl Pose {
+    pub fn identity() -> Self {
+        Self { camera_from_world: Isometry3::identity() }
+    }
+
+    /// Position of the camera center in world coordinates.
+    pub fn position(&self) -> Point3<f64> {
+        self.camera_from_world.inverse() * Point3::origin()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct OdometryConfig {
+    pub ransac: RansacConfig,
+    /// Frames with fewer RANSAC inliers than this are not used to update the pose.
+    pub min_inliers: usize,
+    /// Median displacement (normalized image units) below which the translation direction is
+    /// considered unobservable and the camera is assumed to be static.
+    pub min_parallax: f64
+}
+
+impl Default for OdometryConfig {
+    fn default() -> Self {
+        Self {
+            ransac: RansacConfig::default(),
+            min_inliers: 30,
+            min_parallax: 0.01
+        }
+    }
+}
+
+/// Monocular two-view visual odometry.
+///
+/// Each step estimates the essential matrix between two consecutive frames and chains the
+/// recovered relative motion onto the trajectory. Monocular translation has no scale, so
+/// every step is normalized to unit length.
+pub struct VisualOdometry {
+    config: OdometryConfig,
+    trajectory: Vec<Pose>,
+    rng: SmallRng
+}
+
+impl VisualOdometry {
+    pub fn new(config: OdometryConfig) -> Self {
+        Self {
+            config,
+            trajectory: vec![Pose::identity()],
+            rng: SmallRng::seed_from_u64(0)
+        }
+    }
+
+    pub fn trajectory(&self) -> &[Pose] {
+        &self.trajectory
+    }
+
+    pub fn current_pose(&self) -> Pose {
+        *self.trajectory.last().unwrap()
+    }
+
+    /// Replaces the current pose, e.g. with its refinement by bundle adjustment. The next
+    /// frames are chained onto it.
+    pub fn set_current_pose(&mut self, pose: Pose) {
+        *self.trajectory.last_mut().unwrap() = pose;
+    }
+
+    /// Adds a frame given its matched keypoints with the previous frame, in normalized image
+    /// coordinates. Returns the relative motion if it could be estimated, otherwise the
+    /// previous pose is repeated.
+    pub fn track(&mut self, previous: &[Point2<f64>], current: &[Point2<f64>]) -> Option<Isometry3<f64>> {
+        let relative = self.estimate_motion(previous, current);
+
+        let pose = match relative {
+            Some(relative) => Pose { camera_from_world: relative * self.current_pose().camera_from_world },
+            None => self.current_pose()
+        };
+
+        self.trajectory.push(pose);
+
+        relative
+    }
+
+    fn estimate_motion(&mut self, previous: &[Point2<f64>], current: &[Point2<f64>]) -> Option<Isometry3<f64>> {
+        assert_eq!(previous.len(), current.len());
+
+        if previous.len() < self.config.min_inliers {
+            return None;
+        }
+
+        let correspondences: Vec<Correspondence> = previous.iter()
+            .copied()
+            .zip(current.iter().copied())
+            .collect();
+
+        let mut parallax: Vec<f64> = correspondences.iter()
+            .map(|(a, b)| (b - a).norm())
+            .collect();
+        parallax.sort_by(f64::total_cmp);
+
+        if parallax[parallax.len() / 2] < self.config.min_parallax {
+            return None;
+        }
+
+        let (e, inliers) = ransac_essential(&correspondences, &self.config.ransac, &mut self.rng)?;
+
+        if inliers.iter().filter(|inlier| **inlier).count() < self.config.min_inliers {
+            return None;
+        }
+
+        let (relative, in_front) = recover_pose(&e, &correspondences, &inliers)?;
+
+        (in_front >= self.config.min_inliers).then_some(relative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::isometry;
+    use nalgebra::{Rotation3, Vector3};
+    use rand::Rng;
+
+    /// Camera moving sideways while slowly turning, mapping world coordinates into the camera
+    /// frame of frame `index`.
+    fn true_pose(index: usize) -> Isometry3<f64> {
+        let index = index as f64;
+        isometry(Rotation3::from_euler_angles(0.0, -0.02 * index, 0.0), Vector3::new(-0.2 * index, 0.0, 0.0))
+    }
+
+    fn project(pose: &Isometry3<f64>, point: &Point3<f64>) -> Point2<f64> {
+        let camera = pose * point;
+        Point2::new(camera.x / camera.z, camera.y / camera.z)
+    }
+
+    #[test]
+    fn tracks_synthetic_trajectory_up_to_scale() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let points: Vec<Point3<f64>> = (0..300)
+            .map(|_| Point3::new(rng.gen_range(-3.0..5.0), rng.gen_range(-2.0..2.0), rng.gen_range(5.0..10.0)))
+            .collect();
+
+        let mut odometry = VisualOdometry::new(OdometryConfig::default());
+
+        for index in 1..6 {
+            let previous: Vec<Point2<f64>> = points.iter().map(|point| project(&true_pose(index - 1), point)).collect();
+            let current: Vec<Point2<f64>> = points.iter().map(|point| project(&true_pose(index), point)).collect();
+
+            let relative = odometry.track(&previous, &current).unwrap();
+            let expected = true_pose(index) * true_pose(index - 1).inverse();
+
+            assert!((relative.rotation.to_rotation_matrix().matrix() - expected.rotation.to_rotation_matrix().matrix()).norm() < 1e-6);
+            assert!((relative.translation.vector - expected.translation.vector.normalize()).norm() < 1e-6);
+        }
+
+        // Every step has unit length, so the camera centers are spaced by one along the true
+        // directions of motion
+        let trajectory = odometry.trajectory();
+        assert_eq!(trajectory.len(), 6);
+
+        let center = |index: usize| true_pose(index).inverse() * Point3::<f64>::origin();
+        let mut expected = Point3::origin();
+
+        for (index, pose) in trajectory.iter().enumerate() {
+            if index > 0 {
+                expected += (center(index) - center(index - 1)).normalize();
+            }
+
+            assert!((pose.position() - expected).norm() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn static_camera_keeps_pose() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        let points: Vec<Point2<f64>> = (0..100)
+            .map(|_| Point2::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5)))
+            .collect();
+
+        let mut odometry = VisualOdometry::new(OdometryConfig::default());
+
+        assert!(odometry.track(&points, &points).is_none());
+        assert_eq!(odometry.current_pose(), Pose::identity());
+        assert_eq!(odometry.trajectory().len(), 2);
+    }
+}
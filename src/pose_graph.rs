@@ -0,0 +1,322 @@
+use nalgebra::{DMatrix, DVector, SVector, Similarity3, Translation3, UnitQuaternion, Vector3};
+
+type Vector7 = SVector<f64, 7>;
+
+/// Step of the numerical derivatives of the edge residuals.
+const DERIVATIVE_STEP: f64 = 1e-6;
+
+#[derive(Clone, Copy, Debug)]
+pub struct PoseGraphConfig {
+    /// Largest number of Levenberg-Marquardt iterations.
+    pub iterations: usize,
+    /// Optimizes over Sim(3) when set, for monocular maps whose scale drifts, otherwise over
+    /// SE(3) and the scale of every pose is kept.
+    pub optimize_scale: bool
+}
+
+impl Default for PoseGraphConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 20,
+            optimize_scale: true
+        }
+    }
+}
+
+/// A relative pose measured between two nodes.
+#[derive(Clone, Copy, Debug)]
+pub struct PoseGraphEdge {
+    pub from: usize,
+    pub to: usize,
+    /// Maps the camera coordinates of `from` into those of `to`.
+    pub measurement: Similarity3<f64>
+}
+
+#[derive(Clone, Debug)]
+pub struct PoseGraphReport {
+    /// Sum of the squared edge residuals before and after the optimization.
+    pub initial_cost: f64,
+    pub final_cost: f64,
+    pub iterations: usize
+}
+
+/// Camera poses, as similarities mapping world coordinates into each camera frame, tied
+/// together by relative pose edges.
+///
+/// Optimizing the graph after adding a loop edge spreads the loop error over all the edges
+/// along the loop instead of leaving it at the loop closure.
+#[derive(Clone, Debug, Default)]
+pub struct PoseGraph {
+    poses: Vec<Similarity3<f64>>,
+    fixed: Vec<bool>,
+    edges: Vec<PoseGraphEdge>
+}
+
+/// Residual of an edge: rotation vector, translation and log scale of `measurement * from *
+/// to⁻¹`, which is the identity when the poses agree with the measurement.
+fn edge_residual(edge: &PoseGraphEdge, from: &Similarity3<f64>, to: &Similarity3<f64>) -> Vector7 {
+    let error = edge.measurement * from * to.inverse();
+    let rotation = error.isometry.rotation.scaled_axis();
+    let translation = error.isometry.translation.vector;
+
+    Vector7::from_iterator(rotation.iter().chain(translation.iter()).copied().chain([error.scaling().ln()]))
+}
+
+/// Applies a step, rotation vector, translation and log scale, by left multiplication.
+fn apply_step(pose: &Similarity3<f64>, step: &[f64]) -> Similarity3<f64> {
+    let scale = step.get(6).map_or(1.0, |log_scale| log_scale.exp());
+    let update = Similarity3::from_parts(
+        Translation3::new(step[3], step[4], step[5]),
+        UnitQuaternion::from_scaled_axis(Vector3::new(step[0], step[1], step[2])),
+        scale
+    );
+
+    update * pose
+}
+
+impl PoseGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a node with pose `camera_from_world`. Fixed nodes anchor the graph and keep
+    /// their pose. Returns the index of the node.
+    pub fn add_node(&mut self, camera_from_world: Similarity3<f64>, fixed: bool) -> usize {
+        self.poses.push(camera_from_world);
+        self.fixed.push(fixed);
+        self.poses.len() - 1
+    }
+
+    pub fn add_edge(&mut self, from: usize, to: usize, measurement: Similarity3<f64>) {
+        self.edges.push(PoseGraphEdge { from, to, measurement });
+    }
+
+    /// Adds an edge measuring the current relative pose of two nodes, which keeps it
+    /// unchanged unless other edges disagree.
+    pub fn add_relative_edge(&mut self, from: usize, to: usize) {
+        let measurement = self.poses[to] * self.poses[from].inverse();
+        self.add_edge(from, to, measurement);
+    }
+
+    pub fn poses(&self) -> &[Similarity3<f64>] {
+        &self.poses
+    }
+
+    pub fn edges(&self) -> &[PoseGraphEdge] {
+        &self.edges
+    }
+
+    fn cost(&self, poses: &[Similarity3<f64>]) -> f64 {
+        self.edges.iter()
+            .map(|edge| edge_residual(edge, &poses[edge.from], &poses[edge.to]).norm_squared())
+            .sum()
+    }
+
+    /// Levenberg-Marquardt over the poses of the nodes that are not fixed.
+    ///
+    /// Every edge only involves two nodes, so its Jacobian is computed by numerical
+    /// differentiation and the normal equations are accumulated block by block.
+    pub fn optimize(&mut self, config: &PoseGraphConfig) -> PoseGraphReport {
+        let dimension = if config.optimize_scale { 7 } else { 6 };
+
+        let mut free_index: Vec<Option<usize>> = vec![None; self.poses.len()];
+        let mut free_count = 0;
+
+        for (index, fixed) in self.fixed.iter().enumerate() {
+            if !fixed {
+                free_index[index] = Some(free_count);
+                free_count += 1;
+            }
+        }
+
+        let size = dimension * free_count;
+        let mut cost = self.cost(&self.poses);
+        let initial_cost = cost;
+        let mut damping: f64 = 1e-3;
+        let mut iterations = 0;
+
+        while iterations < config.iterations && size > 0 {
+            iterations += 1;
+
+            let mut hessian = DMatrix::<f64>::zeros(size, size);
+            let mut gradient = DVector::zeros(size);
+
+            for edge in &self.edges {
+                let residual = edge_residual(edge, &self.poses[edge.from], &self.poses[edge.to]);
+
+                // Jacobian columns of both ends, with their position in the system
+                let mut columns: Vec<(usize, Vector7)> = Vec::new();
+
+                for (node, is_from) in [(edge.from, true), (edge.to, false)] {
+                    let Some(free) = free_index[node] else { continue; };
+
+                    for parameter in 0..dimension {
+                        let mut step = [0.0; 7];
+                        step[parameter] = DERIVATIVE_STEP;
+                        let perturbed = apply_step(&self.poses[node], &step[..dimension]);
+
+                        let moved = if is_from {
+                            edge_residual(edge, &perturbed, &self.poses[edge.to])
+                        } else {
+                            edge_residual(edge, &self.poses[edge.from], &perturbed)
+                        };
+
+                        columns.push((free * dimension + parameter, (moved - residual) / DERIVATIVE_STEP));
+                    }
+                }
+
+                for (row, a) in &columns {
+                    gradient[*row] += a.dot(&residual);
+
+                    for (column, b) in &columns {
+                        hessian[(*row, *column)] += a.dot(b);
+                    }
+                }
+            }
+
+            let mut improved = false;
+
+            while damping < 1e10 {
+                let mut system = hessian.clone();
+                for i in 0..size {
+                    system[(i, i)] += damping * hessian[(i, i)].max(1e-9);
+                }
+
+                let Some(step) = system.cholesky().map(|cholesky| cholesky.solve(&-&gradient)) else {
+                    damping *= 10.0;
+                    continue;
+                };
+
+                let candidate: Vec<Similarity3<f64>> = self.poses.iter()
+                    .zip(&free_index)
+                    .map(|(pose, free)| match free {
+                        Some(free) => apply_step(pose, &step.as_slice()[free * dimension..(free + 1) * dimension]),
+                        None => *pose
+                    })
+                    .collect();
+
+                let candidate_cost = self.cost(&candidate);
+
+                if candidate_cost < cost {
+                    let converged = (cost - candidate_cost) < 1e-10 * cost;
+
+                    self.poses = candidate;
+                    cost = candidate_cost;
+                    damping = (damping * 0.1).max(1e-12);
+                    improved = !converged;
+                    break;
+                }
+
+                damping *= 10.0;
+            }
+
+            if !improved {
+                break;
+            }
+        }
+
+        PoseGraphReport { initial_cost, final_cost: cost, iterations }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Isometry3;
+
+    const NODES: usize = 12;
+
+    /// Cameras on a circle of radius 2, each turned a step further around it.
+    fn true_poses() -> Vec<Similarity3<f64>> {
+        (0..NODES)
+            .map(|i| {
+                let angle = std::f64::consts::TAU * i as f64 / NODES as f64;
+                let rotation = UnitQuaternion::from_euler_angles(0.0, angle, 0.0);
+                let center = Vector3::new(2.0 * angle.sin(), 0.1 * angle.cos(), 2.0 * angle.cos());
+                let world_from_camera = Isometry3::from_parts(center.into(), rotation);
+                Similarity3::from_isometry(world_from_camera.inverse(), 1.0)
+            })
+            .collect()
+    }
+
+    /// A graph with exact odometry edges along the loop and a loop edge from the last node
+    /// back to the first, whose poses are chained from the odometry with `drift` added to
+    /// every step. The first node is fixed.
+    fn drifted_loop(drift: &Similarity3<f64>) -> PoseGraph {
+        let truth = true_poses();
+        let mut graph = PoseGraph::new();
+        graph.add_node(truth[0], true);
+
+        for i in 1..NODES {
+            let measurement = truth[i] * truth[i - 1].inverse();
+            let drifted = drift * measurement * graph.poses()[i - 1];
+            graph.add_node(drifted, false);
+            graph.add_edge(i - 1, i, measurement);
+        }
+
+        graph.add_edge(NODES - 1, 0, truth[0] * truth[NODES - 1].inverse());
+        graph
+    }
+
+    fn assert_recovers_truth(graph: &PoseGraph) {
+        for (pose, truth) in graph.poses().iter().zip(true_poses()) {
+            let error = pose * truth.inverse();
+            assert!(error.isometry.rotation.angle() < 1e-6);
+            assert!(error.isometry.translation.vector.norm() < 1e-6);
+            assert!((error.scaling() - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn closes_drifted_se3_loop() {
+        let drift = Similarity3::from_parts(
+            Translation3::new(0.02, -0.01, 0.03),
+            UnitQuaternion::from_euler_angles(0.005, 0.01, -0.005),
+            1.0
+        );
+        let mut graph = drifted_loop(&drift);
+
+        let end = graph.poses()[NODES - 1] * true_poses()[NODES - 1].inverse();
+        assert!(end.isometry.translation.vector.norm() > 0.1);
+
+        let config = PoseGraphConfig { optimize_scale: false, ..Default::default() };
+        let report = graph.optimize(&config);
+
+        assert!(report.final_cost < 1e-12 * report.initial_cost);
+        assert_recovers_truth(&graph);
+    }
+
+    #[test]
+    fn closes_drifted_sim3_loop() {
+        // Monocular drift also grows or shrinks the map along the loop
+        let drift = Similarity3::from_parts(
+            Translation3::new(0.02, -0.01, 0.03),
+            UnitQuaternion::from_euler_angles(0.005, 0.01, -0.005),
+            1.03
+        );
+        let mut graph = drifted_loop(&drift);
+
+        assert!(graph.poses()[NODES - 1].scaling() > 1.3);
+
+        let report = graph.optimize(&PoseGraphConfig::default());
+
+        assert!(report.final_cost < 1e-12 * report.initial_cost);
+        assert_recovers_truth(&graph);
+    }
+
+    #[test]
+    fn se3_keeps_scale() {
+        let drift = Similarity3::from_parts(Translation3::new(0.02, 0.0, 0.0), UnitQuaternion::identity(), 1.03);
+        let mut graph = drifted_loop(&drift);
+        let scales: Vec<f64> = graph.poses().iter().map(|pose| pose.scaling()).collect();
+
+        let config = PoseGraphConfig { optimize_scale: false, ..Default::default() };
+        let report = graph.optimize(&config);
+
+        // The scale drift can not be corrected, but the rest of the loop error is spread out
+        assert!(report.final_cost < report.initial_cost);
+        for (pose, scale) in graph.poses().iter().zip(scales) {
+            assert!((pose.scaling() - scale).abs() < 1e-12);
+        }
+    }
+}
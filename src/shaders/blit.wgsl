@@ -4,6 +4,12 @@ var r_sampler: sampler;
 @group(0) @binding(1)
 var r_color: texture_2d<f32>;
 
+@group(0) @binding(2)
+var<uniform> status_color: vec4<f32>;
+
+// Width of the status border, in image pixels
+const BORDER_WIDTH: f32 = 6.0;
+
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
@@ -31,5 +37,15 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
 
 @fragment
 fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(r_color, r_sampler, vertex.tex_coords);
-}
\ No newline at end of file
+    let color = textureSample(r_color, r_sampler, vertex.tex_coords);
+
+    let size = vec2<f32>(textureDimensions(r_color));
+    let pixel = vertex.tex_coords * size;
+    let edge_distance = min(min(pixel.x, pixel.y), min(size.x - pixel.x, size.y - pixel.y));
+
+    if status_color.a > 0.0 && edge_distance < BORDER_WIDTH {
+        return vec4<f32>(mix(color.rgb, status_color.rgb, status_color.a), 1.0);
+    }
+
+    return color;
+}
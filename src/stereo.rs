@@ -0,0 +1,460 @@
+use nalgebra::{Matrix3, Point2, Point3, Rotation3, Vector3};
+
+use crate::camera_model::{CameraIntrinsics, Distortion, StereoRig};
+use crate::matching::FrameFeatures;
+
+/// Bouguet's rectification of a stereo pair: each camera is rotated by half of their
+/// relative rotation, then both are turned so the image rows are parallel to the baseline.
+/// Corresponding points then lie on the same row of the rectified images.
+#[derive(Clone, Copy, Debug)]
+pub struct Rectification {
+    pub rig: StereoRig,
+    /// Undistorted intrinsics shared by both rectified images.
+    pub intrinsics: CameraIntrinsics,
+    /// Rotation from the left camera frame into the rectified left camera frame.
+    pub left_rotation: Rotation3<f64>,
+    /// Rotation from the right camera frame into the rectified right camera frame.
+    pub right_rotation: Rotation3<f64>
+}
+
+impl Rectification {
+    /// Returns `None` if the cameras do not have the same resolution or the baseline is
+    /// degenerate, i.e. zero or along the optical axis.
+    pub fn new(rig: &StereoRig) -> Option<Self> {
+        if (rig.left.width, rig.left.height) != (rig.right.width, rig.right.height) {
+            return None;
+        }
+
+        // Once both cameras are half rotated they only differ by a translation
+        let half = Rotation3::new(rig.rotation.scaled_axis() * 0.5);
+        let translation = half.inverse() * rig.translation;
+
+        if translation.norm() < 1e-12 {
+            return None;
+        }
+
+        // The rectified x axis points from the left camera center to the right one
+        let e1 = -translation.normalize();
+        let e2 = Vector3::new(-e1.y, e1.x, 0.0);
+
+        if e2.norm() < 1e-6 {
+            return None;
+        }
+
+        let e2 = e2.normalize();
+        let e3 = e1.cross(&e2);
+        let align = Rotation3::from_matrix_unchecked(Matrix3::from_rows(&[e1.transpose(), e2.transpose(), e3.transpose()]));
+
+        let left_rotation = align * half;
+        let right_rotation = align * half.inverse();
+
+        // Keep the optical axes of both cameras close to where they were in the image
+        let focal = (rig.left.fy + rig.right.fy) * 0.5;
+        let principal_points = [(&rig.left, &left_rotation), (&rig.right, &right_rotation)].map(|(intrinsics, rotation)| {
+            let axis = rotation * Vector3::z();
+            Point2::new(intrinsics.cx - focal * axis.x / axis.z, intrinsics.cy - focal * axis.y / axis.z)
+        });
+
+        let intrinsics = CameraIntrinsics {
+            width: rig.left.width,
+            height: rig.left.height,
+            fx: focal,
+            fy: focal,
+            cx: (principal_points[0].x + principal_points[1].x) * 0.5,
+            cy: (principal_points[0].y + principal_points[1].y) * 0.5,
+            distortion: Distortion::None
+        };
+
+        Some(Self { rig: *rig, intrinsics, left_rotation, right_rotation })
+    }
+
+    pub fn left_map(&self) -> RectificationMap {
+        self.map(&self.rig.left, &self.left_rotation)
+    }
+
+    pub fn right_map(&self) -> RectificationMap {
+        self.map(&self.rig.right, &self.right_rotation)
+    }
+
+    fn map(&self, original: &CameraIntrinsics, rotation: &Rotation3<f64>) -> RectificationMap {
+        let (width, height) = (self.intrinsics.width, self.intrinsics.height);
+        let inverse = rotation.inverse();
+
+        let source = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let normalized = self.intrinsics.unproject(&Point2::new(x as f64, y as f64));
+                let ray = inverse * Vector3::new(normalized.x, normalized.y, 1.0);
+
+                original.project(&Point3::from(ray))
+                    .map(|pixel| Point2::new(pixel.x as f32, pixel.y as f32))
+                    .unwrap_or(Point2::new(-1.0, -1.0))
+            })
+            .collect();
+
+        RectificationMap { width, height, source }
+    }
+
+    /// Triangulates a point seen at `left` and `right` in the rectified images from its
+    /// disparity.
+    ///
+    /// The point is returned in left camera coordinates, in the unit of the baseline, or
+    /// `None` if it is not in front of both cameras.
+    pub fn triangulate(&self, left: &Point2<f64>, right: &Point2<f64>) -> Option<Point3<f64>> {
+        let disparity = left.x - right.x;
+
+        if disparity <= 0.0 {
+            return None;
+        }
+
+        let depth = self.intrinsics.fx * self.rig.baseline() / disparity;
+        let rectified = Point3::new(
+            (left.x - self.intrinsics.cx) * depth / self.intrinsics.fx,
+            (left.y - self.intrinsics.cy) * depth / self.intrinsics.fy,
+            depth
+        );
+
+        Some(self.left_rotation.inverse() * rectified)
+    }
+}
+
+/// For every pixel of a rectified image, where it is sampled from in the original image.
+pub struct RectificationMap {
+    pub width: u32,
+    pub height: u32,
+    source: Vec<Point2<f32>>
+}
+
+impl RectificationMap {
+    /// Bilinearly resamples an RGBA image of the original camera into the rectified image.
+    /// Pixels that fall outside of the original image are black.
+    pub fn remap_rgba(&self, original: &[u8], rectified: &mut [u8]) {
+        let (width, height) = (self.width as usize, self.height as usize);
+
+        assert_eq!(original.len(), width * height * 4);
+        assert_eq!(rectified.len(), width * height * 4);
+
+        for (source, pixel) in self.source.iter().zip(rectified.chunks_exact_mut(4)) {
+            let inside = source.x >= 0.0 && source.y >= 0.0 &&
+                source.x <= (width - 1) as f32 && source.y <= (height - 1) as f32;
+
+            if !inside {
+                pixel.copy_from_slice(&[0, 0, 0, 255]);
+                continue;
+            }
+
+            let (x0, y0) = (source.x as usize, source.y as usize);
+            let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+            let (fx, fy) = (source.x - x0 as f32, source.y - y0 as f32);
+
+            let at = |x: usize, y: usize, channel: usize| original[(y * width + x) * 4 + channel] as f32;
+
+            for (channel, value) in pixel.iter_mut().enumerate() {
+                let top = at(x0, y0, channel) * (1.0 - fx) + at(x1, y0, channel) * fx;
+                let bottom = at(x0, y1, channel) * (1.0 - fx) + at(x1, y1, channel) * fx;
+
+                *value = (top * (1.0 - fy) + bottom * fy).round() as u8;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct StereoMatcherConfig {
+    /// Matches with a larger Hamming distance are rejected outright.
+    pub max_distance: u32,
+    /// Lowe's ratio test: the best distance must be below `ratio` times the second best.
+    pub ratio: f32,
+    /// Largest difference in rows between matched keypoints, in pixels of their pyramid
+    /// level.
+    pub max_row_difference: f32,
+    /// Disparities searched, in pixels. The smallest one bounds the depth of the
+    /// triangulated points.
+    pub min_disparity: f32,
+    pub max_disparity: f32,
+    /// Candidates more than this many pyramid levels apart are never compared.
+    pub max_octave_difference: u32
+}
+
+impl Default for StereoMatcherConfig {
+    fn default() -> Self {
+        Self {
+            max_distance: 64,
+            ratio: 0.8,
+            max_row_difference: 2.0,
+            min_disparity: 1.0,
+            max_disparity: 256.0,
+            max_octave_difference: 1
+        }
+    }
+}
+
+/// A correspondence between a keypoint of the left image and one of the right image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StereoMatch {
+    pub left: usize,
+    pub right: usize,
+    pub distance: u32
+}
+
+/// Matches the keypoints of rectified left and right images, only comparing keypoints on
+/// nearly the same row and within the disparity range.
+///
+/// Like ORB-SLAM's stereo search, right keypoints are binned by the rows they may match,
+/// and every right keypoint keeps only its best left match.
+pub fn match_stereo(
+    left: &FrameFeatures,
+    right: &FrameFeatures,
+    config: &StereoMatcherConfig
+) -> Vec<StereoMatch> {
+    let rows = right.keypoints.iter()
+        .chain(&left.keypoints)
+        .map(|keypoint| keypoint.y.ceil() as usize + 1)
+        .max()
+        .unwrap_or(0);
+
+    let mut candidates_by_row: Vec<Vec<usize>> = vec![Vec::new(); rows];
+
+    for (index, keypoint) in right.keypoints.iter().enumerate() {
+        let tolerance = config.max_row_difference * (1u32 << keypoint.octave) as f32;
+        let first = (keypoint.y - tolerance).floor().max(0.0) as usize;
+        let last = ((keypoint.y + tolerance).ceil() as usize).min(rows - 1);
+
+        for row in &mut candidates_by_row[first..=last] {
+            row.push(index);
+        }
+    }
+
+    let mut best_for_right: Vec<Option<StereoMatch>> = vec![None; right.len()];
+
+    for (index, (keypoint, descriptor)) in left.keypoints.iter().zip(&left.descriptors).enumerate() {
+        let mut best: Option<(usize, u32)> = None;
+        let mut second = u32::MAX;
+
+        for &candidate in &candidates_by_row[keypoint.y.round() as usize] {
+            let other = &right.keypoints[candidate];
+            let disparity = keypoint.x - other.x;
+
+            if keypoint.octave.abs_diff(other.octave) > config.max_octave_difference || !(config.min_disparity..=config.max_disparity).contains(&disparity) {
+                continue;
+            }
+
+            let distance = descriptor.distance(&right.descriptors[candidate]);
+
+            match best {
+                Some((_, best_distance)) if distance >= best_distance => second = second.min(distance),
+                _ => {
+                    second = best.map_or(second, |(_, best_distance)| best_distance);
+                    best = Some((candidate, distance));
+                }
+            }
+        }
+
+        let Some((candidate, distance)) = best else {
+            continue;
+        };
+
+        if distance > config.max_distance {
+            continue;
+        }
+
+        if second != u32::MAX && distance as f32 >= config.ratio * second as f32 {
+            continue;
+        }
+
+        let current = &mut best_for_right[candidate];
+
+        if current.is_none_or(|current| distance < current.distance) {
+            *current = Some(StereoMatch { left: index, right: candidate, distance });
+        }
+    }
+
+    best_for_right.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use crate::matching::{Descriptor, Keypoint};
+
+    const BASELINE: f64 = 0.12;
+
+    fn intrinsics() -> CameraIntrinsics {
+        CameraIntrinsics {
+            width: 640,
+            height: 480,
+            fx: 612.5,
+            fy: 610.25,
+            cx: 318.0,
+            cy: 243.5,
+            distortion: Distortion::BrownConrady { k1: -0.28, k2: 0.09, p1: 0.001, p2: -0.0005, k3: -0.01 }
+        }
+    }
+
+    /// Two slightly rotated cameras side by side, the right one `BASELINE` to the right.
+    fn rig() -> StereoRig {
+        let rotation = Rotation3::from_euler_angles(0.01, -0.02, 0.005);
+        let right_center = Vector3::new(BASELINE, 0.002, -0.001).normalize() * BASELINE;
+
+        StereoRig {
+            left: intrinsics(),
+            right: CameraIntrinsics { cx: 322.0, cy: 239.0, ..intrinsics() },
+            rotation,
+            translation: -(rotation * right_center)
+        }
+    }
+
+    /// Points in left camera coordinates seen by both cameras.
+    fn scene(count: usize, rng: &mut SmallRng) -> Vec<Point3<f64>> {
+        (0..count)
+            .map(|_| {
+                let depth = rng.gen_range(1.0..6.0);
+                Point3::new(rng.gen_range(-0.3..0.3) * depth, rng.gen_range(-0.25..0.25) * depth, depth)
+            })
+            .collect()
+    }
+
+    /// Pixels of a point in left camera coordinates in the rectified left and right images.
+    fn rectified_pixels(rectification: &Rectification, point: &Point3<f64>) -> (Point2<f64>, Point2<f64>) {
+        let rig = &rectification.rig;
+        let right = rig.rotation * point + rig.translation;
+
+        (
+            rectification.intrinsics.project(&(rectification.left_rotation * point)).unwrap(),
+            rectification.intrinsics.project(&(rectification.right_rotation * right)).unwrap()
+        )
+    }
+
+    fn keypoint(pixel: &Point2<f64>, octave: u32) -> Keypoint {
+        Keypoint { x: pixel.x as f32, y: pixel.y as f32, angle: 0.0, octave }
+    }
+
+    #[test]
+    fn rectified_cameras_only_differ_by_the_baseline() {
+        let rectification = Rectification::new(&rig()).unwrap();
+        let rig = &rectification.rig;
+
+        assert!((rig.baseline() - BASELINE).abs() < 1e-12);
+        assert_eq!(rectification.intrinsics.distortion, Distortion::None);
+        assert_eq!(rectification.intrinsics.fx, rectification.intrinsics.fy);
+
+        // Rectified right coordinates are rectified left ones shifted along -x
+        let shift = rectification.right_rotation * rig.translation;
+        assert!((shift - Vector3::new(-BASELINE, 0.0, 0.0)).norm() < 1e-12);
+        let relative = rectification.right_rotation * rig.rotation * rectification.left_rotation.inverse();
+        assert!((relative.matrix() - Matrix3::identity()).norm() < 1e-12);
+    }
+
+    #[test]
+    fn rectified_points_share_a_row() {
+        let rectification = Rectification::new(&rig()).unwrap();
+        let mut rng = SmallRng::seed_from_u64(1);
+
+        for point in scene(100, &mut rng) {
+            let (left, right) = rectified_pixels(&rectification, &point);
+
+            assert!((left.y - right.y).abs() < 1e-9);
+            assert!(left.x > right.x);
+        }
+    }
+
+    #[test]
+    fn maps_sample_where_points_project() {
+        let rectification = Rectification::new(&rig()).unwrap();
+        let maps = [rectification.left_map(), rectification.right_map()];
+        let rig = &rectification.rig;
+        let mut rng = SmallRng::seed_from_u64(2);
+
+        for point in scene(100, &mut rng) {
+            let (left, right) = rectified_pixels(&rectification, &point);
+            let originals = [
+                (left, rig.left.project(&point).unwrap()),
+                (right, rig.right.project(&(rig.rotation * point + rig.translation)).unwrap())
+            ];
+
+            for (map, (rectified, original)) in maps.iter().zip(originals) {
+                let (x, y) = (rectified.x.round(), rectified.y.round());
+
+                if x < 0.0 || y < 0.0 || x >= map.width as f64 || y >= map.height as f64 {
+                    continue;
+                }
+
+                // The map is sampled at the nearest pixel, less than a pixel from the point
+                let source = map.source[y as usize * map.width as usize + x as usize];
+                let error = (Point2::new(source.x as f64, source.y as f64) - original).norm();
+                assert!(error < 1.0, "sampled {error} px away");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_degenerate_rigs() {
+        let rig = rig();
+
+        assert!(Rectification::new(&StereoRig { right: CameraIntrinsics { width: 320, ..rig.right }, ..rig }).is_none());
+        assert!(Rectification::new(&StereoRig { translation: Vector3::zeros(), ..rig }).is_none());
+        assert!(Rectification::new(&StereoRig { rotation: Rotation3::identity(), translation: Vector3::new(0.0, 0.0, -BASELINE), ..rig }).is_none());
+    }
+
+    #[test]
+    fn triangulates_metric_depth() {
+        let rectification = Rectification::new(&rig()).unwrap();
+        let mut rng = SmallRng::seed_from_u64(3);
+
+        for point in scene(100, &mut rng) {
+            let (left, right) = rectified_pixels(&rectification, &point);
+            let triangulated = rectification.triangulate(&left, &right).unwrap();
+
+            assert!((triangulated - point).norm() < 1e-9 * point.z);
+        }
+
+        // Points at infinity or behind the cameras
+        let left = Point2::new(300.0, 200.0);
+        assert!(rectification.triangulate(&left, &left).is_none());
+        assert!(rectification.triangulate(&left, &Point2::new(310.0, 200.0)).is_none());
+    }
+
+    #[test]
+    fn matches_keypoints_along_rows() {
+        let rectification = Rectification::new(&rig()).unwrap();
+        let mut rng = SmallRng::seed_from_u64(4);
+        let points = scene(200, &mut rng);
+
+        let mut left = FrameFeatures::default();
+        let mut right = FrameFeatures::default();
+
+        for point in &points {
+            let (left_pixel, right_pixel) = rectified_pixels(&rectification, point);
+            let descriptor = Descriptor(rng.gen());
+
+            left.keypoints.push(keypoint(&left_pixel, 0));
+            left.descriptors.push(descriptor);
+            right.keypoints.push(keypoint(&right_pixel, 0));
+            right.descriptors.push(descriptor);
+        }
+
+        // Off the epipolar line, behind the cameras, and two pyramid levels apart, so the
+        // same descriptors are never matched
+        let decoys = 3;
+        right.keypoints[0].y += 10.0;
+        right.keypoints[1].x = left.keypoints[1].x + 5.0;
+        right.keypoints[2].octave = 2;
+
+        let matches = match_stereo(&left, &right, &StereoMatcherConfig::default());
+        assert_eq!(matches.len(), points.len() - decoys);
+
+        for m in &matches {
+            assert_eq!(m.left, m.right);
+            assert_eq!(m.distance, 0);
+
+            let triangulated = rectification.triangulate(&left.keypoints[m.left].pixel(), &right.keypoints[m.right].pixel()).unwrap();
+            assert!((triangulated - points[m.left]).norm() < 1e-3 * points[m.left].z);
+        }
+
+        // Pyramid levels further apart are compared when allowed
+        let config = StereoMatcherConfig { max_octave_difference: 2, ..Default::default() };
+        assert_eq!(match_stereo(&left, &right, &config).len(), points.len() - decoys + 1);
+    }
+}
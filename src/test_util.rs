@@ -0,0 +1,45 @@
+use nalgebra::{Isometry3, Point3, Translation3, UnitQuaternion};
+
+use crate::camera_model::{CameraIntrinsics, Distortion};
+use crate::matching::{Descriptor, FrameFeatures, Keypoint};
+use crate::odometry::Pose;
+
+/// A distortion free 640x480 camera.
+pub fn intrinsics() -> CameraIntrinsics {
+    CameraIntrinsics { width: 640, height: 480, fx: 500.0, fy: 500.0, cx: 320.0, cy: 240.0, distortion: Distortion::None }
+}
+
+/// A camera `x` to the right of the origin, turned by `yaw` about the vertical axis.
+pub fn pose(x: f64, yaw: f64) -> Pose {
+    let world_from_camera = Isometry3::from_parts(Translation3::new(x, 0.0, 0.0), UnitQuaternion::from_euler_angles(0.0, yaw, 0.0));
+    Pose { camera_from_world: world_from_camera.inverse() }
+}
+
+/// Features of the points visible from `pose` through `intrinsics()`, and the index of the point of each keypoint.
+pub fn observe(pose: &Pose, points: &[Point3<f64>], descriptors: &[Descriptor]) -> (FrameFeatures, Vec<usize>) {
+    let intrinsics = intrinsics();
+    let mut features = FrameFeatures::default();
+    let mut indices = Vec::new();
+
+    for (index, point) in points.iter().enumerate() {
+        let Some(pixel) = intrinsics.project(&(pose.camera_from_world * point)) else { continue; };
+
+        if pixel.x < 0.0 || pixel.y < 0.0 || pixel.x >= intrinsics.width as f64 || pixel.y >= intrinsics.height as f64 {
+            continue;
+        }
+
+        features.keypoints.push(Keypoint { x: pixel.x as f32, y: pixel.y as f32, angle: 0.0, octave: 0 });
+        features.descriptors.push(descriptors[index]);
+        indices.push(index);
+    }
+
+    (features, indices)
+}
+
+/// Features of `points` seen from `pose`, which must all be in view, in the order of the points.
+pub fn observe_all(pose: &Pose, points: &[Point3<f64>], descriptors: &[Descriptor]) -> FrameFeatures {
+    let (features, indices) = observe(pose, points, descriptors);
+    assert_eq!(indices.len(), points.len(), "some points are out of view");
+
+    features
+}
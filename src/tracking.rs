@@ -0,0 +1,321 @@
+use std::fmt;
+
+use rand::{rngs::SmallRng, SeedableRng};
+
+use crate::geometry::{ransac_pnp, PnpConfig, PointCorrespondence};
+use crate::loop_closure::KeyFrameDatabase;
+use crate::map::{KeyFrameId, Map, MapPointId};
+use crate::matching::{match_features, FrameFeatures, MatcherConfig};
+use crate::odometry::Pose;
+use crate::vocabulary::Vocabulary;
+
+/// Whether the frames are being tracked against the map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrackingState {
+    /// No frame has been added to the map yet.
+    NotInitialized,
+    /// The map has keyframes but not enough points to track against yet.
+    Initializing,
+    Tracking,
+    /// Too few map points were found in the last frame, or they did not agree on its pose.
+    /// Frames are not added to the map until one is relocalized.
+    Lost
+}
+
+impl fmt::Display for TrackingState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrackingState::NotInitialized => write!(f, "not initialized"),
+            TrackingState::Initializing => write!(f, "initializing"),
+            TrackingState::Tracking => write!(f, "tracking"),
+            TrackingState::Lost => write!(f, "lost")
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TrackingConfig {
+    /// Map points needed before tracking starts.
+    pub min_map_points: usize,
+    /// Frames located from fewer map points than this, PnP inliers only, lose tracking.
+    pub min_tracked_points: usize,
+    /// Keyframes a lost frame is matched against, the most similar first.
+    pub relocalization_candidates: usize,
+    /// PnP inliers needed to relocalize against a keyframe.
+    pub min_relocalization_inliers: usize,
+    pub pnp: PnpConfig,
+    pub matcher: MatcherConfig
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        Self {
+            min_map_points: 50,
+            min_tracked_points: 15,
+            relocalization_candidates: 5,
+            min_relocalization_inliers: 30,
+            pnp: PnpConfig::default(),
+            matcher: MatcherConfig::default()
+        }
+    }
+}
+
+/// A lost frame located in the map.
+#[derive(Clone, Debug)]
+pub struct Relocalization {
+    /// The keyframe the frame was matched with.
+    pub keyframe: KeyFrameId,
+    pub pose: Pose,
+    /// Map point of every keypoint of the frame that is a PnP inlier.
+    pub map_points: Vec<Option<MapPointId>>,
+    pub inliers: usize
+}
+
+/// A frame located in the map from the map points its keypoints were matched with.
+#[derive(Clone, Debug)]
+pub struct TrackedFrame {
+    pub pose: Pose,
+    /// Map point of every keypoint of the frame that is a PnP inlier.
+    pub map_points: Vec<Option<MapPointId>>,
+    pub inliers: usize
+}
+
+/// The tracking state machine, pose estimation of tracked frames, and relocalization of lost
+/// frames against the keyframes of the map.
+pub struct Tracker {
+    pub config: TrackingConfig,
+    state: TrackingState,
+    rng: SmallRng
+}
+
+impl Tracker {
+    pub fn new(config: TrackingConfig) -> Self {
+        Self {
+            config,
+            state: TrackingState::NotInitialized,
+            rng: SmallRng::seed_from_u64(0)
+        }
+    }
+
+    pub fn state(&self) -> TrackingState {
+        self.state
+    }
+
+    /// Advances the state after a frame was located from `tracked_points` map points,
+    /// either by tracking or by relocalization, and returns the new state.
+    pub fn update(&mut self, map: &Map, tracked_points: usize) -> TrackingState {
+        let tracked = tracked_points >= self.config.min_tracked_points;
+
+        self.state = match self.state {
+            TrackingState::NotInitialized | TrackingState::Initializing => {
+                if map.keyframes().next().is_none() {
+                    TrackingState::NotInitialized
+                } else if map.points().count() >= self.config.min_map_points {
+                    TrackingState::Tracking
+                } else {
+                    TrackingState::Initializing
+                }
+            },
+            TrackingState::Tracking | TrackingState::Lost if tracked => TrackingState::Tracking,
+            TrackingState::Tracking | TrackingState::Lost => TrackingState::Lost
+        };
+
+        self.state
+    }
+
+    /// Keyframes to relocalize against: the best bag-of-words matches if a vocabulary and
+    /// its keyframe database are available, otherwise the most recent keyframes.
+    fn candidates(
+        &self,
+        map: &Map,
+        features: &FrameFeatures,
+        database: Option<(&Vocabulary, &KeyFrameDatabase)>
+    ) -> Vec<KeyFrameId> {
+        let count = self.config.relocalization_candidates;
+
+        match database {
+            Some((vocabulary, database)) if !database.is_empty() => {
+                let vector = vocabulary.transform(&features.descriptors);
+
+                // Same common word ratio as loop candidates, but any score will do
+                database.query(&vector, |_| false, 0.8, 0.0)
+                    .into_iter()
+                    .map(|(keyframe, _)| keyframe)
+                    .filter(|keyframe| map.keyframe(*keyframe).is_some())
+                    .take(count)
+                    .collect()
+            },
+            _ => map.keyframes().rev().take(count).map(|keyframe| keyframe.id).collect()
+        }
+    }
+
+    /// Tries to locate a lost frame: its descriptors are matched with those of candidate
+    /// keyframes, and the pose is estimated with PnP-RANSAC from the matched map points.
+    pub fn relocalize(
+        &mut self,
+        map: &Map,
+        features: &FrameFeatures,
+        database: Option<(&Vocabulary, &KeyFrameDatabase)>
+    ) -> Option<Relocalization> {
+        for keyframe in self.candidates(map, features, database) {
+            let Some(frame) = map.keyframe(keyframe) else { continue; };
+
+            // Keypoint of the current frame and map point of every match
+            let matched: Vec<(usize, MapPointId)> = match_features(&frame.features, features, &self.config.matcher)
+                .into_iter()
+                .filter_map(|m| Some((m.current, frame.map_points[m.previous]?)))
+                .filter(|(_, point)| map.point(*point).is_some())
+                .collect();
+
+            if matched.len() < self.config.min_relocalization_inliers {
+                continue;
+            }
+
+            let Some(located) = self.locate(map, features, &matched) else { continue; };
+
+            if located.inliers >= self.config.min_relocalization_inliers {
+                return Some(Relocalization {
+                    keyframe,
+                    pose: located.pose,
+                    map_points: located.map_points,
+                    inliers: located.inliers
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Estimates the pose of a frame whose keypoints were matched with `map_points`, e.g.
+    /// with those of the last keyframe, by PnP-RANSAC from the matched map points.
+    ///
+    /// Returns `None` if fewer than `min_tracked_points` matches are PnP inliers, in which
+    /// case the frame should count as tracking no map points at all.
+    pub fn track(&mut self, map: &Map, features: &FrameFeatures, map_points: &[Option<MapPointId>]) -> Option<TrackedFrame> {
+        let matched: Vec<(usize, MapPointId)> = map_points.iter()
+            .enumerate()
+            .filter_map(|(keypoint, point)| Some((keypoint, (*point)?)))
+            .filter(|(_, point)| map.point(*point).is_some())
+            .collect();
+
+        if matched.len() < self.config.min_tracked_points {
+            return None;
+        }
+
+        self.locate(map, features, &matched)
+            .filter(|located| located.inliers >= self.config.min_tracked_points)
+    }
+
+    /// PnP-RANSAC from the map points matched with keypoints of the frame, keeping the
+    /// inlier matches.
+    fn locate(&mut self, map: &Map, features: &FrameFeatures, matched: &[(usize, MapPointId)]) -> Option<TrackedFrame> {
+        let correspondences: Vec<PointCorrespondence> = matched.iter()
+            .map(|(keypoint, point)| (
+                map.point(*point).unwrap().position,
+                map.intrinsics().unproject(&features.keypoints[*keypoint].pixel())
+            ))
+            .collect();
+
+        let (camera_from_world, inliers) = ransac_pnp(&correspondences, &self.config.pnp, &mut self.rng)?;
+
+        let mut map_points = vec![None; features.len()];
+
+        for ((keypoint, point), inlier) in matched.iter().zip(&inliers) {
+            if *inlier {
+                map_points[*keypoint] = Some(*point);
+            }
+        }
+
+        Some(TrackedFrame {
+            pose: Pose { camera_from_world },
+            map_points,
+            inliers: inliers.iter().filter(|inlier| **inlier).count()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Point3;
+    use rand::{seq::SliceRandom, Rng};
+
+    use crate::map::{MapConfig, Observation};
+    use crate::matching::Descriptor;
+    use crate::test_util::{intrinsics, observe_all, pose};
+
+    /// A map with a keyframe at the origin seeing 100 points, and a tracker that has started
+    /// tracking it.
+    fn tracking_map(rng: &mut SmallRng) -> (Map, Tracker, Vec<Point3<f64>>, Vec<MapPointId>) {
+        let points: Vec<Point3<f64>> = (0..100)
+            .map(|_| Point3::new(rng.gen_range(-1.5..1.5), rng.gen_range(-1.0..1.0), rng.gen_range(4.0..8.0)))
+            .collect();
+
+        let mut map = Map::new(MapConfig::default(), intrinsics());
+        let features = observe_all(&Pose::identity(), &points, &vec![Descriptor::default(); points.len()]);
+        let keyframe = map.insert_keyframe(Pose::identity(), features, vec![None; points.len()]);
+
+        let ids = points.iter()
+            .enumerate()
+            .map(|(keypoint, point)| map.add_point(*point, &[Observation { keyframe, keypoint }]))
+            .collect();
+
+        let mut tracker = Tracker::new(TrackingConfig::default());
+        assert_eq!(tracker.update(&map, 0), TrackingState::Tracking);
+
+        (map, tracker, points, ids)
+    }
+
+    #[test]
+    fn tracks_pose_from_map_points() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let (map, mut tracker, points, ids) = tracking_map(&mut rng);
+
+        let truth = pose(0.3, 0.05);
+        let features = observe_all(&truth, &points, &vec![Descriptor::default(); points.len()]);
+
+        // A fifth of the matches are wrong
+        let mut map_points: Vec<Option<MapPointId>> = ids.iter().copied().map(Some).collect();
+        for keypoint in (0..points.len()).step_by(5) {
+            map_points[keypoint] = Some(ids[(keypoint + 50) % ids.len()]);
+        }
+
+        let tracked = tracker.track(&map, &features, &map_points).unwrap();
+
+        let error = tracked.pose.camera_from_world * truth.camera_from_world.inverse();
+        assert!(error.translation.vector.norm() < 1e-3);
+        assert!(error.rotation.angle() < 1e-3);
+        assert_eq!(tracked.inliers, 80);
+        assert!((0..points.len()).step_by(5).all(|keypoint| tracked.map_points[keypoint].is_none()));
+
+        assert_eq!(tracker.update(&map, tracked.inliers), TrackingState::Tracking);
+    }
+
+    #[test]
+    fn inconsistent_matches_lose_tracking() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        let (map, mut tracker, points, mut ids) = tracking_map(&mut rng);
+
+        // Plenty of matches, but to random map points
+        let features = observe_all(&pose(0.3, 0.05), &points, &vec![Descriptor::default(); points.len()]);
+        ids.shuffle(&mut rng);
+        let map_points: Vec<Option<MapPointId>> = ids.into_iter().map(Some).collect();
+
+        assert!(tracker.track(&map, &features, &map_points).is_none());
+        assert_eq!(tracker.update(&map, 0), TrackingState::Lost);
+    }
+
+    #[test]
+    fn too_few_matches_are_not_tracked() {
+        let mut rng = SmallRng::seed_from_u64(3);
+        let (map, mut tracker, points, ids) = tracking_map(&mut rng);
+
+        let features = observe_all(&pose(0.3, 0.05), &points, &vec![Descriptor::default(); points.len()]);
+        let mut map_points = vec![None; points.len()];
+        for keypoint in 0..10 {
+            map_points[keypoint] = Some(ids[keypoint]);
+        }
+
+        assert!(tracker.track(&map, &features, &map_points).is_none());
+    }
+}
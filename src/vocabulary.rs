@@ -0,0 +1,555 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::path::Path;
+
+use rand::Rng;
+
+use crate::matching::Descriptor;
+
+pub type WordId = usize;
+
+#[derive(Debug)]
+pub enum VocabularyError {
+    Io(std::io::Error),
+    Parse(String)
+}
+
+impl fmt::Display for VocabularyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VocabularyError::Io(why) => write!(f, "could not access vocabulary file: {why}"),
+            VocabularyError::Parse(why) => write!(f, "could not parse vocabulary file: {why}")
+        }
+    }
+}
+
+impl std::error::Error for VocabularyError {}
+
+#[derive(Clone, Copy, Debug)]
+pub struct VocabularyConfig {
+    /// Children of every node of the tree.
+    pub branching: usize,
+    /// Levels below the root, so there are at most `branching^depth` words.
+    pub depth: usize,
+    /// Largest number of k-majority iterations per node.
+    pub iterations: usize
+}
+
+impl Default for VocabularyConfig {
+    fn default() -> Self {
+        Self {
+            branching: 10,
+            depth: 5,
+            iterations: 10
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    descriptor: Descriptor,
+    children: Vec<usize>,
+    /// Inverse document frequency of the word, for leaves.
+    weight: f64,
+    word: Option<WordId>
+}
+
+/// A bag-of-words image representation: the tf-idf weight of every word, L1 normalized.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BowVector(pub BTreeMap<WordId, f64>);
+
+impl BowVector {
+    /// DBoW2's L1 score, from 0 for images without common words to 1 for identical ones.
+    pub fn score(&self, other: &BowVector) -> f64 {
+        let common: f64 = self.0.iter()
+            .filter_map(|(word, a)| other.0.get(word).map(|b| a.abs() + b.abs() - (a - b).abs()))
+            .sum();
+
+        common * 0.5
+    }
+}
+
+/// A vocabulary tree of binary words, as in DBoW2: descriptors are quantized to a word by
+/// descending to the closest child at every level.
+#[derive(Clone, Debug)]
+pub struct Vocabulary {
+    branching: usize,
+    depth: usize,
+    /// The root is the first node.
+    nodes: Vec<Node>,
+    /// Node of every word.
+    words: Vec<usize>
+}
+
+/// Bitwise majority of descriptors, the binary counterpart of the mean.
+fn majority(descriptors: &[&Descriptor]) -> Descriptor {
+    let mut result = Descriptor::default();
+
+    for (word, value) in result.0.iter_mut().enumerate() {
+        for bit in 0..32 {
+            let ones = descriptors.iter().filter(|descriptor| descriptor.0[word] >> bit & 1 == 1).count();
+
+            if ones * 2 > descriptors.len() {
+                *value |= 1 << bit;
+            }
+        }
+    }
+
+    result
+}
+
+/// k-majority clustering: k-means with Hamming distances and bitwise majority centers,
+/// seeded like k-means++. Returns the centers and the members of each cluster.
+fn k_majority(
+    descriptors: &[&Descriptor],
+    k: usize,
+    iterations: usize,
+    rng: &mut impl Rng
+) -> Vec<(Descriptor, Vec<usize>)> {
+    if descriptors.len() <= k {
+        return descriptors.iter()
+            .enumerate()
+            .map(|(index, descriptor)| (**descriptor, vec![index]))
+            .collect();
+    }
+
+    // k-means++: every new center is drawn with probability proportional to the squared
+    // distance to the closest center so far
+    let mut centers = vec![*descriptors[rng.gen_range(0..descriptors.len())]];
+    let mut closest: Vec<f64> = descriptors.iter()
+        .map(|descriptor| descriptor.distance(&centers[0]) as f64)
+        .collect();
+
+    while centers.len() < k {
+        let total: f64 = closest.iter().map(|distance| distance * distance).sum();
+
+        if total == 0.0 {
+            break;
+        }
+
+        let mut target = rng.gen_range(0.0..total);
+        let mut chosen = descriptors.len() - 1;
+
+        for (index, distance) in closest.iter().enumerate() {
+            target -= distance * distance;
+
+            if target < 0.0 {
+                chosen = index;
+                break;
+            }
+        }
+
+        let center = *descriptors[chosen];
+
+        for (distance, descriptor) in closest.iter_mut().zip(descriptors) {
+            *distance = distance.min(descriptor.distance(&center) as f64);
+        }
+
+        centers.push(center);
+    }
+
+    let mut assignment: Vec<usize> = Vec::new();
+
+    for _ in 0..iterations {
+        let new_assignment: Vec<usize> = descriptors.iter()
+            .map(|descriptor| {
+                (0..centers.len())
+                    .min_by_key(|center| descriptor.distance(&centers[*center]))
+                    .unwrap()
+            })
+            .collect();
+
+        if new_assignment == assignment {
+            break;
+        }
+
+        assignment = new_assignment;
+
+        for (index, center) in centers.iter_mut().enumerate() {
+            let members: Vec<&Descriptor> = descriptors.iter()
+                .zip(&assignment)
+                .filter(|(_, cluster)| **cluster == index)
+                .map(|(descriptor, _)| *descriptor)
+                .collect();
+
+            if !members.is_empty() {
+                *center = majority(&members);
+            }
+        }
+    }
+
+    let mut clusters: Vec<(Descriptor, Vec<usize>)> = centers.into_iter().map(|center| (center, Vec::new())).collect();
+
+    for (index, cluster) in assignment.into_iter().enumerate() {
+        clusters[cluster].1.push(index);
+    }
+
+    clusters.retain(|(_, members)| !members.is_empty());
+    clusters
+}
+
+impl Vocabulary {
+    /// Trains a vocabulary on the descriptors of a set of training images, such as the
+    /// `CornerDescriptor`s of `OrbProgram` converted with `Descriptor::from`.
+    ///
+    /// The tree is built top-down by hierarchical k-majority clustering, then every word is
+    /// weighted by its inverse document frequency over the training images.
+    pub fn train(images: &[Vec<Descriptor>], config: &VocabularyConfig, rng: &mut impl Rng) -> Self {
+        let mut vocabulary = Self {
+            branching: config.branching,
+            depth: config.depth,
+            nodes: vec![Node { descriptor: Descriptor::default(), children: Vec::new(), weight: 0.0, word: None }],
+            words: Vec::new()
+        };
+
+        let descriptors: Vec<&Descriptor> = images.iter().flatten().collect();
+
+        // Nodes to split, with their level and descriptors
+        let mut pending = vec![(0, 0, descriptors)];
+
+        while let Some((parent, level, descriptors)) = pending.pop() {
+            for (center, members) in k_majority(&descriptors, config.branching, config.iterations, rng) {
+                let id = vocabulary.nodes.len();
+                vocabulary.nodes.push(Node { descriptor: center, children: Vec::new(), weight: 0.0, word: None });
+                vocabulary.nodes[parent].children.push(id);
+
+                if level + 1 < config.depth && members.len() > 1 {
+                    pending.push((id, level + 1, members.iter().map(|member| descriptors[*member]).collect()));
+                }
+            }
+        }
+
+        vocabulary.number_words();
+
+        let mut document_frequency = vec![0usize; vocabulary.words.len()];
+
+        for image in images {
+            let mut seen: Vec<WordId> = image.iter().map(|descriptor| vocabulary.word(descriptor)).collect();
+            seen.sort_unstable();
+            seen.dedup();
+
+            for word in seen {
+                document_frequency[word] += 1;
+            }
+        }
+
+        for (word, frequency) in document_frequency.into_iter().enumerate() {
+            if frequency > 0 {
+                let node = vocabulary.words[word];
+                vocabulary.nodes[node].weight = (images.len() as f64 / frequency as f64).ln();
+            }
+        }
+
+        vocabulary
+    }
+
+    /// Numbers the leaves in node order.
+    fn number_words(&mut self) {
+        self.words.clear();
+
+        for id in 0..self.nodes.len() {
+            if id != 0 && self.nodes[id].children.is_empty() {
+                self.nodes[id].word = Some(self.words.len());
+                self.words.push(id);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn branching(&self) -> usize {
+        self.branching
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The word a descriptor is quantized to.
+    pub fn word(&self, descriptor: &Descriptor) -> WordId {
+        let mut node = 0;
+
+        while let Some(child) = self.nodes[node].children.iter()
+            .min_by_key(|child| descriptor.distance(&self.nodes[**child].descriptor))
+        {
+            node = *child;
+        }
+
+        self.nodes[node].word.unwrap_or(0)
+    }
+
+    pub fn weight(&self, word: WordId) -> f64 {
+        self.nodes[self.words[word]].weight
+    }
+
+    /// The bag-of-words vector of an image's descriptors.
+    pub fn transform(&self, descriptors: &[Descriptor]) -> BowVector {
+        let mut vector = BTreeMap::new();
+
+        if self.is_empty() {
+            return BowVector(vector);
+        }
+
+        for descriptor in descriptors {
+            let word = self.word(descriptor);
+            let weight = self.weight(word);
+
+            if weight > 0.0 {
+                *vector.entry(word).or_insert(0.0) += weight;
+            }
+        }
+
+        let norm: f64 = vector.values().sum();
+
+        if norm > 0.0 {
+            for value in vector.values_mut() {
+                *value /= norm;
+            }
+        }
+
+        BowVector(vector)
+    }
+
+    /// Writes the vocabulary in the text layout of DBoW2 and ORB-SLAM's `ORBvoc.txt`: a
+    /// `branching depth scoring weighting` header (L1 scoring and tf-idf weighting, both 0),
+    /// then one line per node after the root with its parent, whether it is a word, the 32
+    /// bytes of its descriptor and its weight.
+    pub fn format(&self) -> String {
+        let mut parents = vec![0; self.nodes.len()];
+        for (id, node) in self.nodes.iter().enumerate() {
+            for child in &node.children {
+                parents[*child] = id;
+            }
+        }
+
+        let mut out = format!("{} {} 0 0\n", self.branching, self.depth);
+
+        for (id, node) in self.nodes.iter().enumerate().skip(1) {
+            let _ = write!(out, "{} {} ", parents[id], node.word.is_some() as u8);
+
+            for byte in node.descriptor.0.iter().flat_map(|word| word.to_le_bytes()) {
+                let _ = write!(out, "{} ", byte);
+            }
+
+            let _ = writeln!(out, "{}", node.weight);
+        }
+
+        out
+    }
+
+    /// Reads a vocabulary written by `format`. Nodes must come after their parent.
+    pub fn parse(source: &str) -> Result<Self, VocabularyError> {
+        let invalid = |line: usize, why: &str| VocabularyError::Parse(format!("line {}: {}", line + 1, why));
+
+        let mut lines = source.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+
+        let (_, header) = lines.next().ok_or_else(|| VocabularyError::Parse("empty file".to_string()))?;
+        let header: Vec<usize> = header.split_whitespace()
+            .map(|value| value.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid(0, "invalid header"))?;
+
+        let [branching, depth, scoring, weighting] = header[..] else {
+            return Err(invalid(0, "expected branching, depth, scoring and weighting"));
+        };
+
+        if scoring != 0 || weighting != 0 {
+            return Err(invalid(0, "only L1 scoring and tf-idf weighting are supported"));
+        }
+
+        let mut nodes = vec![Node { descriptor: Descriptor::default(), children: Vec::new(), weight: 0.0, word: None }];
+
+        for (number, line) in lines {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            if fields.len() != 35 {
+                return Err(invalid(number, "expected parent, leaf flag, 32 descriptor bytes and weight"));
+            }
+
+            let parent: usize = fields[0].parse().map_err(|_| invalid(number, "invalid parent"))?;
+
+            if parent >= nodes.len() {
+                return Err(invalid(number, "parent is not defined before its child"));
+            }
+
+            let mut bytes = [0u8; 32];
+            for (byte, field) in bytes.iter_mut().zip(&fields[2..34]) {
+                *byte = field.parse().map_err(|_| invalid(number, "invalid descriptor byte"))?;
+            }
+
+            let mut descriptor = Descriptor::default();
+            for (word, chunk) in descriptor.0.iter_mut().zip(bytes.chunks_exact(4)) {
+                *word = u32::from_le_bytes(chunk.try_into().unwrap());
+            }
+
+            let weight: f64 = fields[34].parse().map_err(|_| invalid(number, "invalid weight"))?;
+
+            let id = nodes.len();
+            nodes.push(Node { descriptor, children: Vec::new(), weight, word: None });
+            nodes[parent].children.push(id);
+        }
+
+        let mut vocabulary = Self { branching, depth, nodes, words: Vec::new() };
+        vocabulary.number_words();
+
+        Ok(vocabulary)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, VocabularyError> {
+        let source = std::fs::read_to_string(path).map_err(VocabularyError::Io)?;
+        Self::parse(&source)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), VocabularyError> {
+        std::fs::write(path, self.format()).map_err(VocabularyError::Io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    /// `count` descriptors around each of `centers` random centers, each differing from its
+    /// center by a few bits. Returns the centers and every descriptor with its cluster.
+    fn clusters(centers: usize, count: usize, rng: &mut SmallRng) -> (Vec<Descriptor>, Vec<(Descriptor, usize)>) {
+        let centers: Vec<Descriptor> = (0..centers).map(|_| Descriptor(rng.gen())).collect();
+
+        let members = centers.iter()
+            .enumerate()
+            .flat_map(|(cluster, center)| (0..count).map(move |_| (cluster, *center)))
+            .map(|(cluster, mut descriptor)| {
+                for _ in 0..rng.gen_range(0..6) {
+                    let bit = rng.gen_range(0..256);
+                    descriptor.0[bit / 32] ^= 1 << (bit % 32);
+                }
+                (descriptor, cluster)
+            })
+            .collect();
+
+        (centers, members)
+    }
+
+    /// A two level vocabulary trained on four images, each showing one cluster and the
+    /// cluster shared by all of them.
+    fn trained() -> (Vocabulary, Vec<Vec<Descriptor>>) {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let (_, members) = clusters(5, 40, &mut rng);
+
+        let images: Vec<Vec<Descriptor>> = (1..5)
+            .map(|image| members.iter().filter(|(_, cluster)| *cluster == 0 || *cluster == image).map(|(descriptor, _)| *descriptor).collect())
+            .collect();
+
+        let config = VocabularyConfig { branching: 5, depth: 2, iterations: 10 };
+        (Vocabulary::train(&images, &config, &mut rng), images)
+    }
+
+    #[test]
+    fn k_majority_separates_clusters() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        let (centers, members) = clusters(4, 25, &mut rng);
+        let descriptors: Vec<&Descriptor> = members.iter().map(|(descriptor, _)| descriptor).collect();
+
+        let result = k_majority(&descriptors, 4, 10, &mut rng);
+        assert_eq!(result.len(), 4);
+
+        for (center, indices) in result {
+            let cluster = members[indices[0]].1;
+
+            assert_eq!(indices.len(), 25);
+            assert!(indices.iter().all(|index| members[*index].1 == cluster));
+            // The bitwise majority of few bit flips is the true center
+            assert_eq!(center, centers[cluster]);
+        }
+    }
+
+    #[test]
+    fn k_majority_keeps_small_sets_as_they_are() {
+        let mut rng = SmallRng::seed_from_u64(3);
+        let (_, members) = clusters(3, 1, &mut rng);
+        let descriptors: Vec<&Descriptor> = members.iter().map(|(descriptor, _)| descriptor).collect();
+
+        let result = k_majority(&descriptors, 5, 10, &mut rng);
+
+        assert_eq!(result.len(), 3);
+        for (index, (center, indices)) in result.iter().enumerate() {
+            assert_eq!(*center, members[index].0);
+            assert_eq!(*indices, vec![index]);
+        }
+    }
+
+    #[test]
+    fn train_gives_each_cluster_its_own_words() {
+        let (vocabulary, images) = trained();
+
+        assert!(vocabulary.len() <= 25);
+
+        let words: Vec<Vec<WordId>> = images.iter()
+            .map(|image| image.iter().map(|descriptor| vocabulary.word(descriptor)).collect())
+            .collect();
+
+        for (image, image_words) in words.iter().enumerate() {
+            // The shared cluster comes first in every image, and is seen by all of them
+            let (shared, own) = image_words.split_at(40);
+
+            assert!(shared.iter().all(|word| vocabulary.weight(*word) == 0.0));
+            assert!(own.iter().all(|word| (vocabulary.weight(*word) - 4f64.ln()).abs() < 1e-12));
+
+            for other in words.iter().skip(image + 1) {
+                assert!(own.iter().all(|word| !other[40..].contains(word)));
+            }
+        }
+
+        // Words seen in every image carry no information, leaving only the image's own
+        let vectors: Vec<BowVector> = images.iter().map(|image| vocabulary.transform(image)).collect();
+
+        for (image, vector) in vectors.iter().enumerate() {
+            assert!((vector.0.values().sum::<f64>() - 1.0).abs() < 1e-12);
+            assert!((vector.score(vector) - 1.0).abs() < 1e-12);
+
+            for other in vectors.iter().skip(image + 1) {
+                assert_eq!(vector.score(other), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn format_parse_round_trip() {
+        let (vocabulary, images) = trained();
+        let text = vocabulary.format();
+        let parsed = Vocabulary::parse(&text).unwrap();
+
+        assert_eq!(parsed.format(), text);
+        assert_eq!(parsed.len(), vocabulary.len());
+        assert_eq!((parsed.branching(), parsed.depth()), (5, 2));
+
+        for descriptor in images.iter().flatten() {
+            let word = vocabulary.word(descriptor);
+            assert_eq!(parsed.word(descriptor), word);
+            assert_eq!(parsed.weight(word), vocabulary.weight(word));
+        }
+    }
+
+    #[test]
+    fn parse_rejects_invalid_files() {
+        let node = |parent: usize| format!("{parent} 1 {}0.5\n", "7 ".repeat(32));
+
+        assert!(Vocabulary::parse(&format!("10 5 0 0\n{}", node(0))).is_ok());
+
+        for source in [
+            String::new(),
+            "10 5\n".to_string(),
+            format!("10 5 1 0\n{}", node(0)),
+            format!("10 5 0 0\n{}", node(1)),
+            "10 5 0 0\n0 1 7 7 0.5\n".to_string()
+        ] {
+            assert!(matches!(Vocabulary::parse(&source), Err(VocabularyError::Parse(_))), "{source:?}");
+        }
+    }
+}